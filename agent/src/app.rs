@@ -1,26 +1,51 @@
-use std::net::IpAddr;
+use std::{
+  fs::File,
+  io::BufReader,
+  net::IpAddr,
+  path::{Path, PathBuf},
+  sync::Arc,
+};
 
 use actix_cors::Cors;
 use actix_web::{
   App, HttpServer, middleware,
   web::{self, ServiceConfig},
 };
+use tracing::{info, warn};
 
 use crate::{
+  backend::CaddyConfig,
+  builtin::{self, AccessLog, BuiltinConfig, SiteRoutes},
   components::{
     base::health_check, deployment::DeploymentComponent, heartbeat::HeartbeatComponent,
   },
-  config::Config,
+  config::{Config, ServeMode},
   error::AppError,
+  middlewares::{NonceCache, SiteLocks, TaskResultCache},
+  openapi::openapi_json,
 };
 
 #[derive(Debug, Clone)]
 pub struct AppState {
   pub storage_path: String,
   pub nginx_config_path: String,
+  pub nginx_brotli_static: bool,
+  pub serve_mode: ServeMode,
+  /// 内置服务模式下的访问日志
+  pub access_log: AccessLog,
+  /// 内置服务模式下的站点路由
+  pub site_routes: SiteRoutes,
+  pub caddy_admin_url: String,
   pub upload_token_key: String,
   pub upload_token_key_expire: i64,
-  pub public_ip: IpAddr,
+  pub public_ips: Vec<IpAddr>,
+  pub agent_token: String,
+  pub signature_tolerance: i64,
+  pub nonce_cache: NonceCache,
+  /// 已完成上传的部署，防止上传 token 被重复使用
+  pub used_upload_tokens: NonceCache,
+  pub task_results: TaskResultCache,
+  pub site_locks: SiteLocks,
 }
 
 pub fn config_app(cfg: &mut ServiceConfig) {
@@ -28,6 +53,7 @@ pub fn config_app(cfg: &mut ServiceConfig) {
     web::scope("/api")
       .configure(HeartbeatComponent::config)
       .configure(DeploymentComponent::config)
+      .service(openapi_json)
       .route("/health", web::get().to(health_check)),
   );
 }
@@ -39,29 +65,110 @@ pub async fn start() -> Result<(), AppError> {
     workers,
     storage_path,
     nginx_config_path,
+    nginx_brotli_static,
+    serve_mode,
+    serve_port,
+    access_log_path,
+    caddy_admin_url,
     upload_token_key,
     upload_token_key_expire,
     public_ip,
+    agent_token,
+    signature_tolerance,
+    tls_cert,
+    tls_key,
     ..
   } = Config::from_env()?;
+  let tls_config = match (tls_cert, tls_key) {
+    (Some(cert), Some(key)) => Some(load_tls_config(&cert, &key)?),
+    (None, None) => None,
+    _ => {
+      return Err(AppError::Tls(
+        "TLS_CERT and TLS_KEY must be set together".to_string(),
+      ));
+    }
+  };
+  let site_routes = SiteRoutes::default();
+  if serve_mode == ServeMode::Builtin {
+    BuiltinConfig::new(&storage_path, site_routes.clone()).load()?;
+  }
+  if serve_mode == ServeMode::Caddy {
+    // Caddy 还没有启动时，在下次发布时重新加载
+    if let Err(err) = CaddyConfig::new(&storage_path, &caddy_admin_url)
+      .restore()
+      .await
+    {
+      warn!("Failed to restore Caddy routes: {}", err);
+    }
+  }
+  let used_upload_tokens = NonceCache::persistent(used_upload_tokens_path(&storage_path));
   let state = AppState {
     storage_path,
     nginx_config_path,
+    nginx_brotli_static,
+    serve_mode,
+    access_log: AccessLog::new(access_log_path),
+    site_routes,
+    caddy_admin_url,
     upload_token_key,
     upload_token_key_expire,
-    public_ip,
+    public_ips: public_ip,
+    agent_token,
+    signature_tolerance,
+    nonce_cache: NonceCache::default(),
+    used_upload_tokens,
+    task_results: TaskResultCache::default(),
+    site_locks: SiteLocks::default(),
+  };
+  let api_state = state.clone();
+  let server = HttpServer::new(move || {
+    App::new()
+      .app_data(web::Data::new(api_state.clone()))
+      .wrap(Cors::permissive())
+      .wrap(middleware::Logger::default())
+      .configure(config_app)
+  });
+  let server = match tls_config {
+    Some(tls_config) => server.bind_rustls_0_23((host.clone(), port), tls_config)?,
+    None => server.bind((host.clone(), port))?,
   };
-  Ok(
-    HttpServer::new(move || {
-      App::new()
-        .app_data(web::Data::new(state.clone()))
-        .wrap(Cors::permissive())
-        .wrap(middleware::Logger::default())
-        .configure(config_app)
-    })
-    .bind((host, port))?
-    .workers(workers)
-    .run()
-    .await?,
-  )
+  let server = server.workers(workers).run();
+  if serve_mode != ServeMode::Builtin {
+    return Ok(server.await?);
+  }
+  info!("Builtin static server listening on port {}", serve_port);
+  let site_server = HttpServer::new(move || {
+    App::new()
+      .app_data(web::Data::new(state.clone()))
+      .default_service(web::to(builtin::serve))
+  })
+  .bind((host, serve_port))?
+  .workers(workers)
+  .run();
+  futures_util::future::try_join(server, site_server).await?;
+  Ok(())
+}
+
+/// 已使用的上传 token 记录，与分片上传的临时文件放在一起
+pub fn used_upload_tokens_path(storage_path: &str) -> PathBuf {
+  Path::new(storage_path)
+    .join(".uploads")
+    .join("used_tokens.json")
+}
+
+/// 读取证书链和私钥，私钥文件中只使用第一个私钥
+fn load_tls_config(cert_path: &str, key_path: &str) -> Result<rustls::ServerConfig, AppError> {
+  let certs = rustls_pemfile::certs(&mut BufReader::new(File::open(cert_path)?))
+    .collect::<Result<Vec<_>, _>>()?;
+  if certs.is_empty() {
+    return Err(AppError::Tls(format!("No certificate in {}", cert_path)));
+  }
+  let key = rustls_pemfile::private_key(&mut BufReader::new(File::open(key_path)?))?
+    .ok_or_else(|| AppError::Tls(format!("No private key in {}", key_path)))?;
+  rustls::ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+    .with_safe_default_protocol_versions()
+    .map_err(|err| AppError::Tls(err.to_string()))?
+    .with_no_client_auth()
+    .with_single_cert(certs, key)
+    .map_err(|err| AppError::Tls(err.to_string()))
 }
@@ -0,0 +1,389 @@
+//! Caddy 后端，通过 Caddy 的 JSON 管理接口为每个站点添加一条路由，证书由 Caddy 自动申请
+
+use std::{
+  fs, io,
+  path::{Path, PathBuf},
+  time::Duration,
+};
+
+use async_trait::async_trait;
+use common::agent::DeploymentStage;
+use reqwest::StatusCode;
+use serde_json::{Value, json};
+use thiserror::Error;
+use tracing::{debug, error};
+
+use super::{SiteSpec, WebServerBackend};
+use crate::helper::TaskLog;
+
+/// Pupup 在 Caddy 中使用的 HTTP 服务名
+const SERVER_NAME: &str = "pupup";
+
+/// 管理接口的错误，`status` 为 `None` 时请求没有得到响应
+#[derive(Debug, Error)]
+#[error("{message}")]
+pub struct AdminError {
+  status: Option<StatusCode>,
+  message: String,
+}
+
+#[derive(Debug)]
+pub struct CaddyConfig {
+  /// 已发布的路由副本，用于盘点站点
+  config_path: PathBuf,
+  admin_url: String,
+  client: reqwest::Client,
+}
+
+impl CaddyConfig {
+  pub fn new(storage_path: &str, admin_url: &str) -> Self {
+    Self {
+      config_path: Path::new(storage_path).join(".caddy"),
+      admin_url: admin_url.trim_end_matches('/').to_string(),
+      client: reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .unwrap_or_default(),
+    }
+  }
+
+  /// 站点路由的 `@id`，Caddy 的 `/id/` 接口通过它直接定位路由
+  fn route_id(site_id: &str) -> String {
+    format!("pupup-{}", site_id)
+  }
+
+  /// 站点的配置文件路径
+  pub fn config_file(&self, site_id: &str) -> PathBuf {
+    self.config_path.join(format!("{}.json", site_id))
+  }
+
+  pub fn generate_config(&self, site_id: &str, server_name: &str, root_path: &str) -> String {
+    let hosts: Vec<&str> = server_name.split_whitespace().collect();
+    let route = json!({
+      "@id": Self::route_id(site_id),
+      "match": [{ "host": hosts }],
+      "handle": [{
+        "handler": "subroute",
+        "routes": [
+          {
+            // 与 Nginx 的 `try_files $uri $uri/ /index.html` 相同
+            "match": [{
+              "file": {
+                "root": root_path,
+                "try_files": [
+                  "{http.request.uri.path}",
+                  "{http.request.uri.path}/index.html",
+                  "/index.html"
+                ]
+              }
+            }],
+            "handle": [{ "handler": "rewrite", "uri": "{http.matchers.file.relative}" }]
+          },
+          {
+            "handle": [{
+              "handler": "file_server",
+              "root": root_path,
+              "precompressed": { "br": {}, "gzip": {} },
+              "precompressed_order": ["br", "gzip"]
+            }]
+          }
+        ]
+      }],
+      "terminal": true
+    });
+    let config = serde_json::to_string_pretty(&route).unwrap_or_default();
+    debug!("Caddy route: {}", config);
+    config
+  }
+
+  async fn request(
+    &self,
+    method: reqwest::Method,
+    path: &str,
+    body: Option<&Value>,
+  ) -> Result<String, AdminError> {
+    let mut request = self
+      .client
+      .request(method, format!("{}{}", self.admin_url, path));
+    if let Some(body) = body {
+      request = request.json(body);
+    }
+    let response = request.send().await.map_err(|err| AdminError {
+      status: None,
+      message: err.to_string(),
+    })?;
+    let status = response.status();
+    let text = response.text().await.unwrap_or_default();
+    if status.is_success() {
+      Ok(text)
+    } else {
+      Err(AdminError {
+        status: Some(status),
+        message: format!("{} {}", status, text.trim()),
+      })
+    }
+  }
+
+  async fn server_exists(&self) -> bool {
+    let existing = self
+      .request(
+        reqwest::Method::GET,
+        &format!("/config/apps/http/servers/{}", SERVER_NAME),
+        None,
+      )
+      .await
+      .unwrap_or_default();
+    existing.trim() != "null" && !existing.trim().is_empty()
+  }
+
+  /// 确保 Caddy 中存在 Pupup 的 HTTP 服务，不存在时逐级向上创建，并重新加载已发布的路由
+  async fn ensure_server(&self) -> Result<(), AdminError> {
+    if self.server_exists().await {
+      return Ok(());
+    }
+    self.create_server().await?;
+    self.restore_routes().await
+  }
+
+  async fn create_server(&self) -> Result<(), AdminError> {
+    let path = ["apps", "http", "servers", SERVER_NAME];
+    let mut value = json!({ "listen": [":80", ":443"], "routes": [] });
+    let mut last_error = None;
+    for depth in (1..=path.len()).rev() {
+      match self
+        .request(
+          reqwest::Method::PUT,
+          &format!("/config/{}", path[..depth].join("/")),
+          Some(&value),
+        )
+        .await
+      {
+        Ok(_) => return Ok(()),
+        Err(err) => last_error = Some(err),
+      }
+      let mut parent = serde_json::Map::new();
+      parent.insert(path[depth - 1].to_string(), value);
+      value = Value::Object(parent);
+    }
+    Err(last_error.unwrap_or_else(|| AdminError {
+      status: None,
+      message: "no server path".to_string(),
+    }))
+  }
+
+  /// 替换站点的路由，站点第一次发布时还没有路由，追加到路由列表中
+  async fn load_route(&self, site_id: &str, route: &Value) -> Result<(), AdminError> {
+    let id_path = format!("/id/{}", Self::route_id(site_id));
+    if self
+      .request(reqwest::Method::PATCH, &id_path, Some(route))
+      .await
+      .is_ok()
+    {
+      return Ok(());
+    }
+    self
+      .request(
+        reqwest::Method::POST,
+        &format!("/config/apps/http/servers/{}/routes", SERVER_NAME),
+        Some(route),
+      )
+      .await
+      .map(|_| ())
+  }
+
+  /// 重新加载 `.caddy` 中保存的路由，Caddy 重启后没有持久化的路由会丢失
+  async fn restore_routes(&self) -> Result<(), AdminError> {
+    let sites = self.managed_sites().map_err(|err| AdminError {
+      status: None,
+      message: err.to_string(),
+    })?;
+    for site_id in sites {
+      let Some(route) = self
+        .read_config(&site_id)
+        .and_then(|config| serde_json::from_str::<Value>(&config).ok())
+      else {
+        continue;
+      };
+      self.load_route(&site_id, &route).await?;
+    }
+    Ok(())
+  }
+
+  /// Agent 启动时同步 Caddy 中的服务和路由
+  pub async fn restore(&self) -> Result<(), AdminError> {
+    if self.server_exists().await {
+      self.restore_routes().await
+    } else {
+      self.ensure_server().await
+    }
+  }
+}
+
+#[async_trait]
+impl WebServerBackend for CaddyConfig {
+  fn render(&self, site: &SiteSpec<'_>) -> String {
+    self.generate_config(site.site_id, site.server_name, site.root_path)
+  }
+
+  async fn validate(&self, site: &SiteSpec<'_>, config: &str, log: &mut TaskLog) -> bool {
+    if serde_json::from_str::<Value>(config).is_err() {
+      log.line("invalid Caddy route");
+      return log.step(DeploymentStage::Validate, false);
+    }
+    // Caddy 没有对应 `limit_rate` 的内置指令
+    if site.bandwidth != "0" {
+      log.line(&format!(
+        "bandwidth limit {} is not supported by Caddy",
+        site.bandwidth
+      ));
+    }
+    let result = self.ensure_server().await;
+    if let Err(err) = &result {
+      error!("Failed to prepare Caddy server: {}", err);
+      log.line(&format!("caddy admin api: {}", err));
+    }
+    log.step(DeploymentStage::Validate, result.is_ok())
+  }
+
+  /// Caddy 在应用新配置前会完整校验，失败时保留原来的配置
+  async fn activate(&self, site: &SiteSpec<'_>, config: &str, log: &mut TaskLog) -> bool {
+    let Ok(route) = serde_json::from_str::<Value>(config) else {
+      return log.step(DeploymentStage::Activate, false);
+    };
+    if let Err(err) = self.load_route(site.site_id, &route).await {
+      error!("Failed to load Caddy route: {}", err);
+      log.line(&format!("caddy admin api: {}", err));
+      return log.step(DeploymentStage::Activate, false);
+    }
+    if let Err(err) = fs::create_dir_all(&self.config_path)
+      .and_then(|_| fs::write(self.config_file(site.site_id), config))
+    {
+      error!("Failed to write Caddy route: {}", err);
+      log.line(&format!("write Caddy route failed: {}", err));
+    }
+    log.line(&format!("caddy is serving {}", site.server_name));
+    log.step(DeploymentStage::Activate, true)
+  }
+
+  async fn remove(&self, site_id: &str) -> io::Result<bool> {
+    let config_file = self.config_file(site_id);
+    if !config_file.exists() {
+      error!("Config file does not exist: {:?}", config_file);
+      return Ok(false);
+    }
+    let result = self
+      .request(
+        reqwest::Method::DELETE,
+        &format!("/id/{}", Self::route_id(site_id)),
+        None,
+      )
+      .await;
+    match result {
+      // Caddy 重启后路由可能已经不存在
+      Err(err) if err.status == Some(StatusCode::NOT_FOUND) => {
+        debug!("Caddy route of {} does not exist", site_id)
+      }
+      result => {
+        result.map_err(io::Error::other)?;
+      }
+    }
+    fs::remove_file(config_file)?;
+    Ok(true)
+  }
+
+  /// 以 Caddy 中生效的路由为准，与保存的路由一致时返回保存的内容，路由不存在时返回 `None`
+  async fn live_config(&self, site_id: &str) -> Option<String> {
+    let live = self
+      .request(
+        reqwest::Method::GET,
+        &format!("/id/{}", Self::route_id(site_id)),
+        None,
+      )
+      .await
+      .ok()
+      .and_then(|text| serde_json::from_str::<Value>(&text).ok())
+      .filter(|route| !route.is_null())?;
+    match self.read_config(site_id) {
+      Some(config) if serde_json::from_str::<Value>(&config).is_ok_and(|route| route == live) => {
+        Some(config)
+      }
+      _ => serde_json::to_string_pretty(&live).ok(),
+    }
+  }
+
+  fn read_config(&self, site_id: &str) -> Option<String> {
+    fs::read_to_string(self.config_file(site_id))
+      .ok()
+      .filter(|config| {
+        serde_json::from_str::<Value>(config)
+          .is_ok_and(|route| route["@id"] == Self::route_id(site_id))
+      })
+  }
+
+  fn managed_sites(&self) -> io::Result<Vec<String>> {
+    if !self.config_path.exists() {
+      return Ok(Vec::new());
+    }
+    let mut sites = Vec::new();
+    for entry in fs::read_dir(&self.config_path)? {
+      let path = entry?.path();
+      let site_id = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .filter(|_| path.extension().is_some_and(|ext| ext == "json"))
+        .filter(|site_id| self.read_config(site_id).is_some());
+      sites.extend(site_id.map(String::from));
+    }
+    Ok(sites)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_generate_config() {
+    let caddy = CaddyConfig::new("../target/sprout", "http://localhost:2019/");
+    let config = caddy.generate_config("abc", "a.example.com b.example.com", "/var/www/abc");
+    let route: Value = serde_json::from_str(&config).unwrap();
+    assert_eq!(route["@id"], "pupup-abc");
+    assert_eq!(
+      route["match"][0]["host"],
+      json!(["a.example.com", "b.example.com"])
+    );
+    assert_eq!(caddy.admin_url, "http://localhost:2019");
+  }
+
+  #[actix_web::test]
+  async fn test_remove_missing_route() {
+    use std::{
+      io::{Read, Write},
+      net::TcpListener,
+    };
+
+    // 模拟重启后已经没有该路由的 Caddy
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let admin_url = format!("http://{}", listener.local_addr().unwrap());
+    let server = std::thread::spawn(move || {
+      let (mut stream, _) = listener.accept().unwrap();
+      let mut buf = [0; 1024];
+      let len = stream.read(&mut buf).unwrap();
+      stream
+        .write_all(b"HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\nconnection: close\r\n\r\n")
+        .unwrap();
+      String::from_utf8_lossy(&buf[..len]).to_string()
+    });
+
+    let storage = std::env::temp_dir().join(format!("pupup-caddy-{}", std::process::id()));
+    let caddy = CaddyConfig::new(storage.to_str().unwrap(), &admin_url);
+    fs::create_dir_all(&caddy.config_path).unwrap();
+    let config = caddy.generate_config("abc", "a.example.com", "/var/www/abc");
+    fs::write(caddy.config_file("abc"), config).unwrap();
+
+    assert!(caddy.remove("abc").await.unwrap());
+    assert!(!caddy.config_file("abc").exists());
+    assert!(server.join().unwrap().starts_with("DELETE /id/pupup-abc "));
+    fs::remove_dir_all(storage).unwrap();
+  }
+}
@@ -0,0 +1,81 @@
+//! 提供站点的 Web 服务后端
+
+use std::io;
+
+use async_trait::async_trait;
+
+use crate::{app::AppState, builtin::BuiltinConfig, config::ServeMode, helper::TaskLog};
+
+mod caddy;
+mod nginx;
+
+pub use caddy::CaddyConfig;
+pub use nginx::NginxConfig;
+
+/// 要发布的站点
+#[derive(Debug, Clone)]
+pub struct SiteSpec<'a> {
+  pub site_id: &'a str,
+  /// 以空格分隔的域名，与 Nginx 的 `server_name` 相同
+  pub server_name: &'a str,
+  pub root_path: &'a str,
+  pub bandwidth: &'a str,
+  /// 绑定域名已解析到本机，可以申请证书
+  pub tls: bool,
+}
+
+/// Web 服务后端，发布时依次执行 `render`、`validate` 和 `activate`
+#[async_trait]
+pub trait WebServerBackend: Send + Sync {
+  /// 生成站点配置
+  fn render(&self, site: &SiteSpec<'_>) -> String;
+
+  /// 检查配置，失败时正在服务的站点不受影响
+  async fn validate(&self, site: &SiteSpec<'_>, config: &str, log: &mut TaskLog) -> bool;
+
+  /// 使配置生效
+  async fn activate(&self, site: &SiteSpec<'_>, config: &str, log: &mut TaskLog) -> bool;
+
+  /// 移除站点配置，配置不存在时返回 `false`
+  async fn remove(&self, site_id: &str) -> io::Result<bool>;
+
+  /// 读取由 Pupup 生成的站点配置
+  fn read_config(&self, site_id: &str) -> Option<String>;
+
+  /// 正在生效的站点配置，用于盘点
+  async fn live_config(&self, site_id: &str) -> Option<String> {
+    self.read_config(site_id)
+  }
+
+  /// 所有由 Pupup 生成配置的站点
+  fn managed_sites(&self) -> io::Result<Vec<String>>;
+
+  /// 是否需要在发布前确认绑定域名的解析，用于决定是否申请证书
+  fn requests_certificate(&self) -> bool {
+    false
+  }
+
+  async fn deploy(&self, site: &SiteSpec<'_>, log: &mut TaskLog) -> bool {
+    let config = self.render(site);
+    self.validate(site, &config, log).await && self.activate(site, &config, log).await
+  }
+}
+
+/// 按 `serve_mode` 选择后端
+pub fn web_server(state: &AppState) -> Box<dyn WebServerBackend> {
+  match state.serve_mode {
+    ServeMode::Nginx => {
+      let mut nginx_config = NginxConfig::new(&state.nginx_config_path);
+      nginx_config.brotli_static = state.nginx_brotli_static;
+      Box::new(nginx_config)
+    }
+    ServeMode::Builtin => Box::new(BuiltinConfig::new(
+      &state.storage_path,
+      state.site_routes.clone(),
+    )),
+    ServeMode::Caddy => Box::new(CaddyConfig::new(
+      &state.storage_path,
+      &state.caddy_admin_url,
+    )),
+  }
+}
@@ -0,0 +1,221 @@
+//! Nginx 后端，每个站点一个配置文件，通过 `nginx -t` 检查、`nginx -s reload` 生效
+
+use std::{
+  fs, io,
+  path::{Path, PathBuf},
+  process::Command,
+};
+
+use async_trait::async_trait;
+use common::agent::DeploymentStage;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error};
+
+use super::{SiteSpec, WebServerBackend};
+use crate::helper::TaskLog;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NginxConfig {
+  config_path: PathBuf,
+  /// 需要 Nginx 安装 ngx_brotli 模块
+  pub brotli_static: bool,
+}
+
+impl NginxConfig {
+  pub fn new(config_path: &str) -> Self {
+    Self {
+      config_path: config_path.into(),
+      brotli_static: false,
+    }
+  }
+
+  pub fn generate_config(
+    &self,
+    site_id: &str,
+    server_name: &str,
+    root_path: &str,
+    bandwidth: &str,
+  ) -> String {
+    let mut config = String::new();
+    config.push_str("server {\n");
+    config.push_str(&format!("    access_log logs/{} pupup;\n", site_id));
+    config.push_str("    listen 80;\n");
+    config.push_str(&format!("    server_name {};\n", server_name));
+    config.push_str("    location / {\n");
+    config.push_str("        try_files $uri $uri/ /index.html;\n");
+    config.push_str(&format!("        root {};\n", root_path));
+    config.push_str("        index index.html;\n");
+    config.push_str(&format!("        limit_rate {};\n", bandwidth));
+    config.push_str("        gzip_static on;\n");
+    if self.brotli_static {
+      config.push_str("        brotli_static on;\n");
+    }
+    config.push_str("    }\n");
+    config.push_str("}\n");
+    debug!("Nginx config: {}", config);
+    config
+  }
+
+  /// 站点的配置文件路径
+  pub fn config_file(&self, site_id: &str) -> PathBuf {
+    self.config_path.join(format!("{}.conf", site_id))
+  }
+
+  /// 通过 certbot 申请证书，certbot 会修改站点配置并重新加载 Nginx
+  pub fn apply_ssl(&self, domain: &str, log: &mut TaskLog) -> bool {
+    log.run(
+      Command::new("certbot")
+        .arg("--nginx")
+        .arg("--non-interactive")
+        .arg("-d")
+        .arg(domain),
+    )
+  }
+
+  /// 先写入临时文件再重命名，Nginx 不会读到写了一半的配置
+  fn write_config(&self, config_file: &Path, config: &str) -> io::Result<()> {
+    // 临时文件不以 `.conf` 结尾，不会被 Nginx 加载
+    let tmp_file = config_file.with_extension("conf.tmp");
+    fs::write(&tmp_file, config)
+      .and_then(|_| fs::rename(&tmp_file, config_file))
+      .inspect_err(|_| {
+        let _ = fs::remove_file(&tmp_file);
+      })
+  }
+}
+
+#[async_trait]
+impl WebServerBackend for NginxConfig {
+  fn render(&self, site: &SiteSpec<'_>) -> String {
+    self.generate_config(
+      site.site_id,
+      site.server_name,
+      site.root_path,
+      site.bandwidth,
+    )
+  }
+
+  /// `nginx -t` 只能检查已加载的配置，新配置替换站点配置后检查，失败时恢复原来的配置。
+  /// 在 `activate` 重新加载之前，正在运行的 Nginx 不受影响
+  async fn validate(&self, site: &SiteSpec<'_>, config: &str, log: &mut TaskLog) -> bool {
+    if let Err(e) = fs::create_dir_all(self.config_path.as_path()) {
+      tracing::error!("Failed to create config directory: {}", e);
+      log.line(&format!("failed to create config directory: {}", e));
+      return false;
+    }
+
+    let config_file = self.config_file(site.site_id);
+    let previous = fs::read_to_string(&config_file).ok();
+    if let Err(err) = self.write_config(&config_file, config) {
+      tracing::error!("Write Nginx configuration failed: {}", err);
+      log.line(&format!("write Nginx configuration failed: {}", err));
+      return false;
+    }
+
+    // 测试配置是否正确
+    let success = log.run(Command::new("nginx").arg("-t"));
+    if !log.step(DeploymentStage::NginxTest, success) {
+      tracing::error!("{}", "Nginx configuration test failed");
+      let restored = match previous {
+        Some(previous) => self.write_config(&config_file, &previous),
+        None => fs::remove_file(&config_file),
+      };
+      if let Err(err) = restored {
+        tracing::error!("Restore Nginx configuration failed: {}", err);
+        log.line(&format!("restore Nginx configuration failed: {}", err));
+      }
+      return false;
+    }
+    true
+  }
+
+  async fn activate(&self, site: &SiteSpec<'_>, _config: &str, log: &mut TaskLog) -> bool {
+    let success = log.run(Command::new("nginx").arg("-s").arg("reload"));
+    if !log.step(DeploymentStage::NginxReload, success) {
+      tracing::error!("{}", "reload Nginx failed");
+      return false;
+    }
+    // 只为绑定域名申请证书，申请失败时站点仍通过 HTTP 提供服务
+    if let Some(domain) = site.server_name.split(' ').next().filter(|_| site.tls) {
+      if !self.apply_ssl(domain, log) {
+        log.line(&format!("apply certificate for {} failed", domain));
+      }
+    }
+    true
+  }
+
+  async fn remove(&self, site_id: &str) -> io::Result<bool> {
+    let domian_config = self.config_file(site_id);
+    if domian_config.exists() {
+      fs::remove_file(domian_config)?;
+      Ok(true)
+    } else {
+      error!("Config file does not exist: {:?}", domian_config);
+      Ok(false)
+    }
+  }
+
+  /// 读取由 Pupup 生成的站点配置，配置目录中其他程序的配置返回 `None`
+  fn read_config(&self, site_id: &str) -> Option<String> {
+    fs::read_to_string(self.config_file(site_id))
+      .ok()
+      .filter(|config| config.contains(&format!("access_log logs/{} pupup;", site_id)))
+  }
+
+  /// 配置目录中所有由 Pupup 生成配置的站点
+  fn managed_sites(&self) -> io::Result<Vec<String>> {
+    if !self.config_path.exists() {
+      return Ok(Vec::new());
+    }
+    let mut sites = Vec::new();
+    for entry in fs::read_dir(&self.config_path)? {
+      let path = entry?.path();
+      if path.extension().is_some_and(|ext| ext == "conf") {
+        if let Some(site_id) = path.file_stem().and_then(|stem| stem.to_str()) {
+          if self.read_config(site_id).is_some() {
+            sites.push(site_id.to_string());
+          }
+        }
+      }
+    }
+    Ok(sites)
+  }
+
+  /// 通过 certbot 申请证书
+  fn requests_certificate(&self) -> bool {
+    true
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::{NginxConfig, SiteSpec, TaskLog, WebServerBackend};
+
+  #[actix_web::test]
+  async fn test_deploy() {
+    let nc = NginxConfig::new("../target/sprout");
+    println!(
+      "{}",
+      nc.generate_config("abcdefghijklmn", "jinqiu.wang", "/var/www/html", "100k")
+    );
+    let mut log = TaskLog::default();
+    nc.deploy(
+      &SiteSpec {
+        site_id: "abcdefghijklmn",
+        server_name: "jinqiu.wang",
+        root_path: "/var/www/html",
+        bandwidth: "100k",
+        tls: true,
+      },
+      &mut log,
+    )
+    .await;
+    println!("{}", log.into_string());
+  }
+
+  #[actix_web::test]
+  async fn test_revoke() {
+    let nc = NginxConfig::new("../target/sprout");
+    nc.remove("abcdefghijklmn").await.unwrap();
+  }
+}
@@ -0,0 +1,531 @@
+//! 内置静态文件服务，不依赖系统的 Nginx，适用于小型主机和容器
+
+use std::{
+  collections::HashMap,
+  fs::{self, OpenOptions},
+  io::{self, Write},
+  path::{Path, PathBuf},
+  sync::{Arc, RwLock, mpsc},
+  thread,
+  time::Duration,
+};
+
+use actix_web::{
+  HttpRequest, HttpResponse,
+  body::SizedStream,
+  http::{
+    Method, StatusCode,
+    header::{ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_TYPE, REFERER, USER_AGENT, VARY},
+  },
+  web::{self, Bytes, BytesMut, Data},
+};
+use async_trait::async_trait;
+use common::agent::DeploymentStage;
+use futures_util::Stream;
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncReadExt;
+use tracing::{debug, error, warn};
+
+/// 不限速时每次读取的字节数
+const CHUNK_SIZE: usize = 64 * 1024;
+
+use crate::{
+  app::AppState,
+  backend::{SiteSpec, WebServerBackend},
+  helper::TaskLog,
+};
+
+/// 内置服务的站点配置，对应 Nginx 配置中的一个 server 块
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BuiltinSite {
+  pub site_id: String,
+  pub server_names: Vec<String>,
+  pub root: PathBuf,
+  /// 每个连接的限速，格式同 Nginx 的 `limit_rate`，如 `100k`，0 表示不限速
+  pub bandwidth: String,
+}
+
+/// 按 Host 查找站点的路由表，发布和撤销后立即生效
+#[derive(Debug, Clone, Default)]
+pub struct SiteRoutes {
+  inner: Arc<RwLock<HashMap<String, Arc<BuiltinSite>>>>,
+}
+
+impl SiteRoutes {
+  /// 添加或替换站点，站点原来的域名会被移除
+  pub fn insert(&self, site: BuiltinSite) {
+    let mut routes = self.inner.write().unwrap_or_else(|e| e.into_inner());
+    routes.retain(|_, route| route.site_id != site.site_id);
+    let site = Arc::new(site);
+    for name in &site.server_names {
+      routes.insert(name.to_ascii_lowercase(), site.clone());
+    }
+  }
+
+  pub fn remove(&self, site_id: &str) {
+    let mut routes = self.inner.write().unwrap_or_else(|e| e.into_inner());
+    routes.retain(|_, route| route.site_id != site_id);
+  }
+
+  pub fn get(&self, host: &str) -> Option<Arc<BuiltinSite>> {
+    let routes = self.inner.read().unwrap_or_else(|e| e.into_inner());
+    routes.get(host).cloned()
+  }
+}
+
+/// 站点配置保存在存储目录的 `.sites` 中，Agent 重启后重新加载
+#[derive(Debug)]
+pub struct BuiltinConfig {
+  config_path: PathBuf,
+  routes: SiteRoutes,
+}
+
+impl BuiltinConfig {
+  pub fn new(storage_path: &str, routes: SiteRoutes) -> Self {
+    Self {
+      config_path: Path::new(storage_path).join(".sites"),
+      routes,
+    }
+  }
+
+  pub fn generate_config(
+    &self,
+    site_id: &str,
+    server_name: &str,
+    root_path: &str,
+    bandwidth: &str,
+  ) -> String {
+    let site = BuiltinSite {
+      site_id: site_id.to_string(),
+      server_names: server_name.split_whitespace().map(String::from).collect(),
+      root: root_path.into(),
+      bandwidth: bandwidth.to_string(),
+    };
+    let config = serde_json::to_string_pretty(&site).unwrap_or_default();
+    debug!("Builtin site config: {}", config);
+    config
+  }
+
+  /// 站点的配置文件路径
+  pub fn config_file(&self, site_id: &str) -> PathBuf {
+    self.config_path.join(format!("{}.json", site_id))
+  }
+
+  /// 启动时加载所有站点的路由
+  pub fn load(&self) -> io::Result<()> {
+    for site_id in self.managed_sites()? {
+      if let Some(site) = self
+        .read_config(&site_id)
+        .and_then(|config| serde_json::from_str(&config).ok())
+      {
+        self.routes.insert(site);
+      }
+    }
+    Ok(())
+  }
+}
+
+#[async_trait]
+impl WebServerBackend for BuiltinConfig {
+  fn render(&self, site: &SiteSpec<'_>) -> String {
+    self.generate_config(
+      site.site_id,
+      site.server_name,
+      site.root_path,
+      site.bandwidth,
+    )
+  }
+
+  /// 站点目录中没有 `index.html` 时视为失败
+  async fn validate(&self, _site: &SiteSpec<'_>, config: &str, log: &mut TaskLog) -> bool {
+    let site: BuiltinSite = match serde_json::from_str(config) {
+      Ok(site) => site,
+      Err(err) => {
+        log.line(&format!("invalid site config: {}", err));
+        return log.step(DeploymentStage::Validate, false);
+      }
+    };
+    let success = site.root.join("index.html").is_file();
+    if !success {
+      log.line(&format!("index.html not found in {}", site.root.display()));
+    }
+    log.step(DeploymentStage::Validate, success)
+  }
+
+  /// 写入站点配置并更新路由表
+  async fn activate(&self, site: &SiteSpec<'_>, config: &str, log: &mut TaskLog) -> bool {
+    let Ok(builtin_site) = serde_json::from_str::<BuiltinSite>(config) else {
+      return log.step(DeploymentStage::Activate, false);
+    };
+    if let Err(err) = fs::create_dir_all(&self.config_path)
+      .and_then(|_| fs::write(self.config_file(site.site_id), config))
+    {
+      error!("Failed to write site config: {}", err);
+      log.line(&format!("write site config failed: {}", err));
+      return log.step(DeploymentStage::Activate, false);
+    }
+    self.routes.insert(builtin_site);
+    log.line(&format!(
+      "serving {} from {}",
+      site.server_name, site.root_path
+    ));
+    log.step(DeploymentStage::Activate, true)
+  }
+
+  async fn remove(&self, site_id: &str) -> io::Result<bool> {
+    self.routes.remove(site_id);
+    let config_file = self.config_file(site_id);
+    if config_file.exists() {
+      fs::remove_file(config_file)?;
+      Ok(true)
+    } else {
+      error!("Config file does not exist: {:?}", config_file);
+      Ok(false)
+    }
+  }
+
+  fn read_config(&self, site_id: &str) -> Option<String> {
+    fs::read_to_string(self.config_file(site_id))
+      .ok()
+      .filter(|config| serde_json::from_str::<BuiltinSite>(config).is_ok())
+  }
+
+  /// 配置目录中所有站点
+  fn managed_sites(&self) -> io::Result<Vec<String>> {
+    if !self.config_path.exists() {
+      return Ok(Vec::new());
+    }
+    let mut sites = Vec::new();
+    for entry in fs::read_dir(&self.config_path)? {
+      let path = entry?.path();
+      let site_id = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .filter(|_| path.extension().is_some_and(|ext| ext == "json"))
+        .filter(|site_id| self.read_config(site_id).is_some());
+      sites.extend(site_id.map(String::from));
+    }
+    Ok(sites)
+  }
+}
+
+/// 解析 Nginx `limit_rate` 格式的限速（字节/秒），为 0 或无法解析时不限速
+pub fn parse_rate(rate: &str) -> Option<u64> {
+  let rate = rate.trim().to_ascii_lowercase();
+  let (number, unit) = if let Some(number) = rate.strip_suffix('k') {
+    (number, 1024)
+  } else if let Some(number) = rate.strip_suffix('m') {
+    (number, 1024 * 1024)
+  } else {
+    (rate.as_str(), 1)
+  };
+  number
+    .parse::<u64>()
+    .ok()
+    .map(|number| number * unit)
+    .filter(|rate| *rate > 0)
+}
+
+/// 去掉 Host 中的端口
+fn host_name(host: &str) -> String {
+  let name = match host.rsplit_once(':') {
+    Some((name, port)) if !port.is_empty() && port.chars().all(|c| c.is_ascii_digit()) => name,
+    _ => host,
+  };
+  name.to_ascii_lowercase()
+}
+
+/// 按 `try_files $uri $uri/ /index.html` 的顺序查找文件，路径越界时返回 `None`
+fn resolve_file(root: &Path, path: &str) -> Option<PathBuf> {
+  let mut file = root.to_path_buf();
+  for segment in path.split('/') {
+    match segment {
+      "" | "." => continue,
+      ".." => return None,
+      segment if segment.contains('\\') => return None,
+      segment => file.push(segment),
+    }
+  }
+  if file.is_file() {
+    return Some(file);
+  }
+  let index = file.join("index.html");
+  if index.is_file() {
+    return Some(index);
+  }
+  let fallback = root.join("index.html");
+  fallback.is_file().then_some(fallback)
+}
+
+/// 客户端支持时使用预压缩的 `.br` 或 `.gz` 文件，对应 `brotli_static` 和 `gzip_static`
+fn precompressed(file: &Path, accept_encoding: &str) -> Option<(PathBuf, &'static str)> {
+  let accepts = |encoding: &str| {
+    accept_encoding.split(',').any(|item| {
+      let mut parts = item.split(';').map(str::trim);
+      parts.next() == Some(encoding) && !parts.any(|param| param == "q=0")
+    })
+  };
+  [("br", "br"), ("gzip", "gz")]
+    .into_iter()
+    .filter(|(encoding, _)| accepts(encoding))
+    .map(|(encoding, ext)| {
+      (
+        PathBuf::from(format!("{}.{}", file.display(), ext)),
+        encoding,
+      )
+    })
+    .find(|(path, _)| path.is_file())
+}
+
+/// 分块读取文件，限速时每 100 毫秒发送 `rate / 10` 字节
+fn file_stream(
+  file: tokio::fs::File,
+  rate: Option<u64>,
+) -> impl Stream<Item = Result<Bytes, actix_web::Error>> {
+  let chunk = rate.map_or(CHUNK_SIZE, |rate| (rate / 10).max(1) as usize);
+  futures_util::stream::unfold((Some(file), true), move |(file, first)| async move {
+    let mut file = file?;
+    if !first && rate.is_some() {
+      actix_web::rt::time::sleep(Duration::from_millis(100)).await;
+    }
+    let mut buf = BytesMut::with_capacity(chunk);
+    match file.read_buf(&mut buf).await {
+      Ok(0) => None,
+      Ok(_) => Some((Ok(buf.freeze()), (Some(file), false))),
+      // 读取失败后结束响应
+      Err(err) => Some((Err(err.into()), (None, false))),
+    }
+  })
+}
+
+/// 访问日志由单独的线程写入，不阻塞处理请求的 worker
+#[derive(Debug, Clone)]
+pub struct AccessLog {
+  sender: mpsc::Sender<(String, String)>,
+}
+
+impl AccessLog {
+  pub fn new(dir: impl Into<PathBuf>) -> Self {
+    let dir = dir.into();
+    let (sender, receiver) = mpsc::channel::<(String, String)>();
+    thread::spawn(move || {
+      // 每次把队列中的日志按站点合并后写入，文件不常驻打开，便于 logrotate 切割
+      while let Ok(first) = receiver.recv() {
+        let mut lines: HashMap<String, String> = HashMap::new();
+        for (site_id, line) in std::iter::once(first).chain(receiver.try_iter()) {
+          lines.entry(site_id).or_default().push_str(&line);
+        }
+        for (site_id, lines) in lines {
+          let result = fs::create_dir_all(&dir).and_then(|_| {
+            OpenOptions::new()
+              .create(true)
+              .append(true)
+              .open(dir.join(format!("{}.log", site_id)))?
+              .write_all(lines.as_bytes())
+          });
+          if let Err(err) = result {
+            warn!("Failed to write access log of {}: {}", site_id, err);
+          }
+        }
+      }
+    });
+    Self { sender }
+  }
+
+  /// 按站点追加访问日志，格式与 Nginx 的 combined 相同
+  pub fn write(&self, site_id: &str, req: &HttpRequest, status: StatusCode, size: u64) {
+    let line = access_log_line(req, status, size);
+    if self.sender.send((site_id.to_string(), line)).is_err() {
+      warn!("Access log writer stopped, dropping log of {}", site_id);
+    }
+  }
+}
+
+fn access_log_line(req: &HttpRequest, status: StatusCode, size: u64) -> String {
+  let header = |name| {
+    req
+      .headers()
+      .get(name)
+      .and_then(|value| value.to_str().ok())
+      .unwrap_or("-")
+      .to_string()
+  };
+  format!(
+    "{} - - [{}] \"{} {} {:?}\" {} {} \"{}\" \"{}\"\n",
+    req
+      .peer_addr()
+      .map(|addr| addr.ip().to_string())
+      .unwrap_or_default(),
+    chrono::Local::now().format("%d/%b/%Y:%H:%M:%S %z"),
+    req.method(),
+    req.uri(),
+    req.version(),
+    status.as_u16(),
+    size,
+    header(REFERER),
+    header(USER_AGENT),
+  )
+}
+
+/// 内置服务的请求入口，按 Host 找到站点后返回文件
+pub async fn serve(req: HttpRequest, state: Data<AppState>) -> HttpResponse {
+  let host = host_name(req.connection_info().host());
+  let Some(site) = state.site_routes.get(&host) else {
+    return HttpResponse::NotFound().finish();
+  };
+  let (response, size) = serve_site(&req, &site).await;
+  state
+    .access_log
+    .write(&site.site_id, &req, response.status(), size);
+  response
+}
+
+async fn serve_site(req: &HttpRequest, site: &BuiltinSite) -> (HttpResponse, u64) {
+  if req.method() != Method::GET && req.method() != Method::HEAD {
+    return (HttpResponse::MethodNotAllowed().finish(), 0);
+  }
+  let path = percent_encoding::percent_decode_str(req.path())
+    .decode_utf8_lossy()
+    .into_owned();
+  let accept_encoding = req
+    .headers()
+    .get(ACCEPT_ENCODING)
+    .and_then(|value| value.to_str().ok())
+    .unwrap_or_default()
+    .to_string();
+  let root = site.root.clone();
+  // 查找文件需要访问磁盘，放到线程池中执行
+  let lookup = web::block(move || {
+    resolve_file(&root, &path).map(|file| {
+      let compressed = precompressed(&file, &accept_encoding);
+      (file, compressed)
+    })
+  })
+  .await;
+  let (file, compressed) = match lookup {
+    Ok(Some(found)) => found,
+    Ok(None) => return (HttpResponse::NotFound().finish(), 0),
+    Err(err) => {
+      error!("Failed to resolve {}: {}", req.path(), err);
+      return (HttpResponse::InternalServerError().finish(), 0);
+    }
+  };
+  let (body_file, encoding) = match compressed {
+    Some((path, encoding)) => (path, Some(encoding)),
+    None => (file.clone(), None),
+  };
+  let opened = async {
+    let content = tokio::fs::File::open(&body_file).await?;
+    let size = content.metadata().await?.len();
+    io::Result::Ok((content, size))
+  };
+  let (content, size) = match opened.await {
+    Ok(opened) => opened,
+    Err(err) => {
+      error!("Failed to read {:?}: {}", body_file, err);
+      return (HttpResponse::InternalServerError().finish(), 0);
+    }
+  };
+  let mut builder = HttpResponse::Ok();
+  builder
+    .insert_header((
+      CONTENT_TYPE,
+      mime_guess::from_path(&file)
+        .first_or_octet_stream()
+        .to_string(),
+    ))
+    .insert_header((VARY, "Accept-Encoding"));
+  if let Some(encoding) = encoding {
+    builder.insert_header((CONTENT_ENCODING, encoding));
+  }
+  if req.method() == Method::HEAD {
+    // HEAD 请求只需要 Content-Length，不读取文件内容
+    let body = SizedStream::new(
+      size,
+      futures_util::stream::empty::<Result<Bytes, actix_web::Error>>(),
+    );
+    return (builder.body(body), 0);
+  }
+  let body = SizedStream::new(size, file_stream(content, parse_rate(&site.bandwidth)));
+  (builder.body(body), size)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse_rate() {
+    assert_eq!(parse_rate("100k"), Some(100 * 1024));
+    assert_eq!(parse_rate("2M"), Some(2 * 1024 * 1024));
+    assert_eq!(parse_rate("512"), Some(512));
+    assert_eq!(parse_rate("0"), None);
+    assert_eq!(parse_rate("fast"), None);
+    assert_eq!(host_name("Example.com:8080"), "example.com");
+    assert_eq!(host_name("[::1]"), "[::1]");
+  }
+
+  #[test]
+  fn test_resolve_file() {
+    let root = std::env::temp_dir().join(format!("pupup-builtin-{}", std::process::id()));
+    fs::create_dir_all(root.join("docs")).unwrap();
+    fs::write(root.join("index.html"), "root").unwrap();
+    fs::write(root.join("docs/index.html"), "docs").unwrap();
+    fs::write(root.join("app.js"), "js").unwrap();
+
+    assert_eq!(resolve_file(&root, "/app.js"), Some(root.join("app.js")));
+    assert_eq!(
+      resolve_file(&root, "/docs/"),
+      Some(root.join("docs/index.html"))
+    );
+    // 单页应用的路由回退到首页
+    assert_eq!(
+      resolve_file(&root, "/users/1"),
+      Some(root.join("index.html"))
+    );
+    assert_eq!(resolve_file(&root, "/../etc/passwd"), None);
+
+    fs::write(root.join("app.js.gz"), "gz").unwrap();
+    assert_eq!(
+      precompressed(&root.join("app.js"), "gzip, br"),
+      Some((root.join("app.js.gz"), "gzip"))
+    );
+    assert_eq!(precompressed(&root.join("app.js"), "gzip;q=0"), None);
+    fs::remove_dir_all(root).unwrap();
+  }
+
+  #[actix_web::test]
+  async fn test_serve_site() {
+    use actix_web::{body::MessageBody, test::TestRequest};
+
+    let root = std::env::temp_dir().join(format!("pupup-builtin-serve-{}", std::process::id()));
+    fs::create_dir_all(&root).unwrap();
+    let content = "x".repeat(CHUNK_SIZE + 10);
+    fs::write(root.join("index.html"), &content).unwrap();
+    let site = BuiltinSite {
+      site_id: "site".into(),
+      server_names: vec!["example.com".into()],
+      root: root.clone(),
+      bandwidth: "0".into(),
+    };
+
+    let req = TestRequest::get().uri("/about").to_http_request();
+    let (response, size) = serve_site(&req, &site).await;
+    assert_eq!(size, content.len() as u64);
+    let body = response.into_body().try_into_bytes().err().unwrap();
+    let body = actix_web::body::to_bytes(body).await.unwrap();
+    assert_eq!(body, content.as_bytes());
+
+    let req = TestRequest::default()
+      .method(Method::HEAD)
+      .uri("/")
+      .to_http_request();
+    let (response, size) = serve_site(&req, &site).await;
+    assert_eq!(size, 0);
+    assert_eq!(
+      response.body().size(),
+      actix_web::body::BodySize::Sized(content.len() as u64)
+    );
+    fs::remove_dir_all(root).unwrap();
+  }
+}
@@ -1,6 +1,12 @@
 use actix_web::HttpResponse;
 use serde_json::json;
 
+#[utoipa::path(
+  get,
+  path = "/health",
+  tag = "base",
+  responses((status = OK, body = serde_json::Value))
+)]
 pub async fn health_check() -> HttpResponse {
   HttpResponse::Ok().json(json!({
     "status": "OK",
@@ -1,27 +1,53 @@
 use actix_multipart::form::MultipartForm;
 use actix_web::{
-  HttpResponse, post,
-  web::{Data, Json},
+  HttpRequest, HttpResponse, get,
+  middleware::from_fn,
+  post, put,
+  web::{Data, Json, Path, Payload, Query},
 };
-use common::agent::{InitUploadRequest, TaskPublishRequest, TaskRevokeRequest};
+use common::{
+  Response,
+  agent::{
+    CompleteManifestRequest, CompleteUploadRequest, InitUploadRequest, InitUploadResponse,
+    InventoryResponse, TaskExportRequest, TaskPublishRequest, TaskPublishResponse,
+    TaskRevokeRequest, UploadManifestRequest, UploadManifestResponse, UploadSessionRequest,
+    UploadSessionResponse,
+  },
+};
+use serde_json::Value;
 
 use crate::{
   app::AppState,
-  components::deployment::{model::UploadForm, service},
+  components::deployment::{
+    model::{MAX_BLOB_SIZE, UPLOAD_CHUNK_SIZE, UploadChunkQuery, UploadForm},
+    service,
+  },
   error::AppError,
+  helper::extract_bearer_token,
+  middlewares::verify_signature,
   traits::IntoHttpResponse,
 };
 
-#[post("/upload/init")]
+#[utoipa::path(
+  tag = "deployment",
+  responses((status = OK, body = Response<InitUploadResponse>)),
+  security(("signature" = []))
+)]
+#[post("/upload/init", wrap = "from_fn(verify_signature)")]
 pub async fn init_upload(
   state: Data<AppState>,
   body: Json<InitUploadRequest>,
 ) -> Result<HttpResponse, AppError> {
-  service::get_upload_token(&state, body.0.site_id)
+  service::get_upload_token(&state, body.into_inner())
     .await
     .into_http_response()
 }
 
+#[utoipa::path(
+  tag = "deployment",
+  request_body(content_type = "multipart/form-data", description = "`dist` 为站点文件，`upload_token` 为上传 token"),
+  responses((status = OK, body = Response<Value>))
+)]
 #[post("/upload/file")]
 pub async fn file_upload(
   state: Data<AppState>,
@@ -32,7 +58,124 @@ pub async fn file_upload(
     .into_http_response()
 }
 
-#[post("/task/publish")]
+#[utoipa::path(
+  tag = "deployment",
+  responses((status = OK, body = Response<UploadSessionResponse>))
+)]
+#[post("/upload/session")]
+pub async fn upload_session(
+  state: Data<AppState>,
+  body: Json<UploadSessionRequest>,
+) -> Result<HttpResponse, AppError> {
+  service::upload_session(&state, body.into_inner().upload_token)
+    .await
+    .into_http_response()
+}
+
+#[utoipa::path(
+  tag = "deployment",
+  params(UploadChunkQuery),
+  request_body(content_type = "application/octet-stream", description = "从 `offset` 开始的分片内容"),
+  responses((status = OK, body = Response<UploadSessionResponse>)),
+  security(("upload_token" = []))
+)]
+#[put("/upload/chunk")]
+pub async fn upload_chunk(
+  req: HttpRequest,
+  state: Data<AppState>,
+  query: Query<UploadChunkQuery>,
+  payload: Payload,
+) -> Result<HttpResponse, AppError> {
+  let upload_token = extract_bearer_token(&req)?;
+  let chunk = payload
+    .to_bytes_limited(UPLOAD_CHUNK_SIZE as usize)
+    .await
+    .map_err(|_| AppError::ChunkTooLarge)?
+    .map_err(|err| AppError::InternalServerError {
+      source: Some(err.to_string().into()),
+    })?;
+  service::upload_chunk(&state, upload_token, query.offset, &chunk)
+    .await
+    .into_http_response()
+}
+
+#[utoipa::path(tag = "deployment", responses((status = OK, body = Response<Value>)))]
+#[post("/upload/complete")]
+pub async fn complete_upload(
+  state: Data<AppState>,
+  body: Json<CompleteUploadRequest>,
+) -> Result<HttpResponse, AppError> {
+  let CompleteUploadRequest {
+    upload_token,
+    sha256,
+    encoding,
+  } = body.into_inner();
+  service::complete_upload(&state, upload_token, sha256, encoding)
+    .await
+    .into_http_response()
+}
+
+#[utoipa::path(
+  tag = "deployment",
+  responses((status = OK, body = Response<UploadManifestResponse>))
+)]
+#[post("/upload/manifest")]
+pub async fn upload_manifest(
+  state: Data<AppState>,
+  body: Json<UploadManifestRequest>,
+) -> Result<HttpResponse, AppError> {
+  let UploadManifestRequest {
+    upload_token,
+    files,
+  } = body.into_inner();
+  service::upload_manifest(&state, upload_token, files)
+    .await
+    .into_http_response()
+}
+
+#[utoipa::path(
+  tag = "deployment",
+  request_body(content_type = "application/octet-stream", description = "文件内容"),
+  responses((status = OK, body = Response<Value>)),
+  security(("upload_token" = []))
+)]
+#[put("/upload/blob/{sha256}")]
+pub async fn upload_blob(
+  req: HttpRequest,
+  state: Data<AppState>,
+  sha256: Path<String>,
+  payload: Payload,
+) -> Result<HttpResponse, AppError> {
+  let upload_token = extract_bearer_token(&req)?;
+  let content = payload
+    .to_bytes_limited(MAX_BLOB_SIZE)
+    .await
+    .map_err(|_| AppError::ChunkTooLarge)?
+    .map_err(|err| AppError::InternalServerError {
+      source: Some(err.to_string().into()),
+    })?;
+  service::upload_blob(&state, upload_token, sha256.into_inner(), &content)
+    .await
+    .into_http_response()
+}
+
+#[utoipa::path(tag = "deployment", responses((status = OK, body = Response<Value>)))]
+#[post("/upload/manifest/complete")]
+pub async fn complete_manifest(
+  state: Data<AppState>,
+  body: Json<CompleteManifestRequest>,
+) -> Result<HttpResponse, AppError> {
+  service::complete_manifest(&state, body.into_inner().upload_token)
+    .await
+    .into_http_response()
+}
+
+#[utoipa::path(
+  tag = "deployment",
+  responses((status = OK, body = Response<TaskPublishResponse>)),
+  security(("signature" = []))
+)]
+#[post("/task/publish", wrap = "from_fn(verify_signature)")]
 pub async fn publish_site(
   state: Data<AppState>,
   body: Json<TaskPublishRequest>,
@@ -40,15 +183,22 @@ pub async fn publish_site(
   service::publish_site(
     &state,
     body.0.site_id,
+    body.0.deployment_id,
     body.0.bandwidth,
     body.0.bind_domain,
     body.0.preview_domain,
+    body.0.idempotency_key,
   )
   .await
   .into_http_response()
 }
 
-#[post("/task/revoke")]
+#[utoipa::path(
+  tag = "deployment",
+  responses((status = OK, body = Response<Value>)),
+  security(("signature" = []))
+)]
+#[post("/task/revoke", wrap = "from_fn(verify_signature)")]
 pub async fn revoke_site(
   state: Data<AppState>,
   body: Json<TaskRevokeRequest>,
@@ -57,3 +207,31 @@ pub async fn revoke_site(
     .await
     .into_http_response()
 }
+
+#[utoipa::path(
+  tag = "deployment",
+  responses((status = OK, body = Response<InventoryResponse>)),
+  security(("signature" = []))
+)]
+#[get("/inventory", wrap = "from_fn(verify_signature)")]
+pub async fn get_inventory(state: Data<AppState>) -> Result<HttpResponse, AppError> {
+  service::get_inventory(&state).await.into_http_response()
+}
+
+#[utoipa::path(
+  tag = "deployment",
+  responses((status = OK, description = "站点文件的 tar.gz", content_type = "application/gzip")),
+  security(("signature" = []))
+)]
+#[post("/task/export", wrap = "from_fn(verify_signature)")]
+pub async fn export_site(
+  state: Data<AppState>,
+  body: Json<TaskExportRequest>,
+) -> Result<HttpResponse, AppError> {
+  let content = service::export_site(&state, body.0.site_id).await?;
+  Ok(
+    HttpResponse::Ok()
+      .content_type("application/gzip")
+      .body(content),
+  )
+}
@@ -1,4 +1,5 @@
 use actix_web::web::ServiceConfig;
+use utoipa::OpenApi;
 
 mod handler;
 pub mod model;
@@ -9,8 +10,34 @@ impl DeploymentComponent {
   pub fn config(cfg: &mut ServiceConfig) {
     cfg.service(handler::init_upload);
     cfg.service(handler::file_upload);
+    cfg.service(handler::upload_session);
+    cfg.service(handler::upload_chunk);
+    cfg.service(handler::complete_upload);
+    cfg.service(handler::upload_manifest);
+    cfg.service(handler::upload_blob);
+    cfg.service(handler::complete_manifest);
     cfg.service(handler::publish_site);
     cfg.service(handler::revoke_site);
+    cfg.service(handler::export_site);
+    cfg.service(handler::get_inventory);
     // cfg.service(handler::disable_site);
   }
 }
+
+/// 部署相关接口的 OpenAPI 文档
+#[derive(OpenApi)]
+#[openapi(paths(
+  handler::init_upload,
+  handler::file_upload,
+  handler::upload_session,
+  handler::upload_chunk,
+  handler::complete_upload,
+  handler::upload_manifest,
+  handler::upload_blob,
+  handler::complete_manifest,
+  handler::publish_site,
+  handler::revoke_site,
+  handler::export_site,
+  handler::get_inventory,
+))]
+pub struct DeploymentApi;
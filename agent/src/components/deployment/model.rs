@@ -1,4 +1,12 @@
 use actix_multipart::form::{MultipartForm, tempfile::TempFile, text::Text};
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+/// 单个分片的最大字节数
+pub const UPLOAD_CHUNK_SIZE: u64 = 4 * 1024 * 1024;
+
+/// 按文件清单上传时单个文件的最大字节数
+pub const MAX_BLOB_SIZE: usize = 50 * 1024 * 1024;
 
 #[derive(Debug, MultipartForm)]
 pub struct UploadForm {
@@ -6,3 +14,9 @@ pub struct UploadForm {
   pub dist: Vec<TempFile>,
   pub upload_token: Text<String>,
 }
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct UploadChunkQuery {
+  pub offset: u64,
+}
@@ -1,114 +1,698 @@
-use std::{fs, path::Path};
+use std::{
+  collections::{BTreeSet, HashSet},
+  fs::{self, OpenOptions},
+  io::Write,
+  path::{Component, Path, PathBuf},
+};
 
-use common::agent::InitUploadResponse;
+use common::{
+  agent::{
+    ArtifactEncoding, DeploymentStage, InitUploadRequest, InitUploadResponse, InventoryResponse,
+    InventorySite, ManifestEntry, TaskPublishResponse, UploadClaims, UploadManifestResponse,
+    UploadSessionResponse,
+  },
+  digest::{manifest_digest, sha256_bytes, sha256_file, sha256_reader},
+  signature,
+};
 use serde_json::Value;
 use tracing::debug;
 
 use crate::{
   app::AppState,
+  backend::{SiteSpec, web_server},
   error::AppError,
-  helper::{NginxConfig, check_dns_record, extract_tar},
+  helper::{TaskLog, check_dns_record, extract_tar, precompress_dir, supported_encodings},
   types::ServiceResult,
 };
 use helpers::{self, jwt};
 
-use super::model::UploadForm;
+use super::model::{UPLOAD_CHUNK_SIZE, UploadForm};
 
 pub async fn get_upload_token(
   state: &AppState,
-  site_id: String,
+  body: InitUploadRequest,
 ) -> ServiceResult<InitUploadResponse> {
   let upload_token = jwt::sign(
-    site_id,
+    UploadClaims {
+      site_id: body.site_id,
+      deployment_id: body.deployment_id,
+      sha256: body.sha256.map(|s| s.to_lowercase()),
+      upload_id: body.upload_id,
+    },
     &state.upload_token_key,
     state.upload_token_key_expire,
   )?;
   Ok(InitUploadResponse { upload_token })
 }
 
+/// 保存上传的站点文件，存放路径由 token 中的 site_id 决定，客户端提供的文件名会被忽略。
+/// 每个部署的 token 只能成功上传一次。
 pub async fn file_upload(state: &AppState, form: UploadForm) -> ServiceResult<Value> {
-  jwt::verify::<String>(&form.upload_token, &state.upload_token_key)?;
-  let base_dir = Path::new(&state.storage_path);
+  let claims = verify_upload_token(state, &form.upload_token)?;
+  let _guard = state.site_locks.lock(&claims.site_id).await;
+  let [tempfile] = form.dist.as_slice() else {
+    return Err(AppError::TempfileNotFound);
+  };
+
+  let used_key = claims.used_key();
+  if !state.used_upload_tokens.insert(
+    &used_key,
+    signature::timestamp(),
+    state.upload_token_key_expire,
+  ) {
+    return Err(AppError::UploadTokenUsed);
+  }
+  let result = save_upload(state, &claims, tempfile.file.path());
+  if result.is_err() {
+    // 上传失败时允许使用同一个 token 重试
+    state.used_upload_tokens.remove(&used_key);
+  }
+  result
+}
 
+fn save_upload(state: &AppState, claims: &UploadClaims, file: &Path) -> ServiceResult<Value> {
+  if let Some(expected) = &claims.sha256 {
+    if sha256_file(file)? != *expected {
+      return Err(AppError::DigestMismatch);
+    }
+  }
+
+  let base_dir = Path::new(&state.storage_path);
   if !base_dir.exists() {
     fs::create_dir_all(base_dir)?;
   }
+  let target = artifact_path(state, &claims.site_id, ArtifactEncoding::Identity);
+  remove_pending(state, &claims.site_id)?;
+  fs::copy(file, target)?;
+  mark_pending(state, &claims)?;
+  Ok(Value::Null)
+}
+
+/// 站点压缩包的存放位置
+fn artifact_path(state: &AppState, site_id: &str, encoding: ArtifactEncoding) -> PathBuf {
+  Path::new(&state.storage_path).join(format!("{}.{}", site_id, encoding.extension()))
+}
+
+const ARTIFACT_ENCODINGS: [ArtifactEncoding; 3] = [
+  ArtifactEncoding::Zstd,
+  ArtifactEncoding::Gzip,
+  ArtifactEncoding::Identity,
+];
+
+/// 查找站点已上传的压缩包
+fn find_artifact(state: &AppState, site_id: &str) -> Option<(PathBuf, ArtifactEncoding)> {
+  ARTIFACT_ENCODINGS
+    .into_iter()
+    .map(|encoding| (artifact_path(state, site_id, encoding), encoding))
+    .find(|(path, _)| path.exists())
+}
+
+fn remove_artifacts(state: &AppState, site_id: &str) -> ServiceResult<()> {
+  for encoding in ARTIFACT_ENCODINGS {
+    let path = artifact_path(state, site_id, encoding);
+    if path.exists() {
+      fs::remove_file(path)?;
+    }
+  }
+  Ok(())
+}
+
+/// 清除站点尚未发布的压缩包和文件清单目录，保证发布时使用的是最后一次完成的上传
+fn remove_pending(state: &AppState, site_id: &str) -> ServiceResult<()> {
+  remove_artifacts(state, site_id)?;
+  let release = release_path(state, site_id);
+  if release.exists() {
+    fs::remove_dir_all(release)?;
+  }
+  let marker = pending_marker(state, site_id);
+  if marker.exists() {
+    fs::remove_file(marker)?;
+  }
+  Ok(())
+}
+
+/// 记录待发布的文件属于哪个部署，与 `{site_id}.release` 同级
+fn pending_marker(state: &AppState, site_id: &str) -> PathBuf {
+  Path::new(&state.storage_path).join(format!("{}.pending", site_id))
+}
+
+fn mark_pending(state: &AppState, claims: &UploadClaims) -> ServiceResult<()> {
+  fs::write(
+    pending_marker(state, &claims.site_id),
+    claims.deployment_id.to_string(),
+  )?;
+  Ok(())
+}
+
+/// 读取记录在标记文件中的部署 ID
+fn read_marker(path: &Path) -> Option<i32> {
+  fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+fn verify_upload_token(state: &AppState, upload_token: &str) -> ServiceResult<UploadClaims> {
+  Ok(
+    jwt::verify::<UploadClaims>(upload_token, &state.upload_token_key)
+      .map_err(|_| AppError::InvalidUploadToken)?
+      .claims
+      .data,
+  )
+}
+
+/// 分片上传过程中的临时文件
+/// 未完成的分片上传，每次上传单独保存
+fn partial_path(state: &AppState, claims: &UploadClaims) -> PathBuf {
+  Path::new(&state.storage_path)
+    .join(".uploads")
+    .join(format!("{}.part", claims.used_key()))
+}
+
+fn received_bytes(path: &Path) -> u64 {
+  fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+}
+
+/// 开始或恢复分片上传，返回 Agent 已收到的字节数
+pub async fn upload_session(
+  state: &AppState,
+  upload_token: String,
+) -> ServiceResult<UploadSessionResponse> {
+  let claims = verify_upload_token(state, &upload_token)?;
+  ensure_upload_unused(state, &claims)?;
+  Ok(UploadSessionResponse {
+    offset: received_bytes(&partial_path(state, &claims)),
+    chunk_size: UPLOAD_CHUNK_SIZE,
+    encodings: supported_encodings(),
+  })
+}
+
+/// 追加一个分片，`offset` 必须等于已收到的字节数
+pub async fn upload_chunk(
+  state: &AppState,
+  upload_token: String,
+  offset: u64,
+  chunk: &[u8],
+) -> ServiceResult<UploadSessionResponse> {
+  let claims = verify_upload_token(state, &upload_token)?;
+  ensure_upload_unused(state, &claims)?;
+  let path = partial_path(state, &claims);
+  if let Some(parent) = path.parent() {
+    fs::create_dir_all(parent)?;
+  }
+  let received = received_bytes(&path);
+  if offset != received {
+    return Err(AppError::UploadOffsetMismatch(received));
+  }
+  let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+  file.write_all(chunk)?;
+  file.sync_data()?;
+  Ok(UploadSessionResponse {
+    offset: received + chunk.len() as u64,
+    chunk_size: UPLOAD_CHUNK_SIZE,
+    encodings: supported_encodings(),
+  })
+}
+
+/// 校验分片上传的文件摘要并保存为站点文件，摘要不一致时丢弃已上传的内容
+pub async fn complete_upload(
+  state: &AppState,
+  upload_token: String,
+  sha256: String,
+  encoding: ArtifactEncoding,
+) -> ServiceResult<Value> {
+  let claims = verify_upload_token(state, &upload_token)?;
+  let _guard = state.site_locks.lock(&claims.site_id).await;
+  if encoding != ArtifactEncoding::Identity && !supported_encodings().contains(&encoding) {
+    return Err(AppError::UnsupportedEncoding);
+  }
+  let used_key = claims.used_key();
+  if !state.used_upload_tokens.insert(
+    &used_key,
+    signature::timestamp(),
+    state.upload_token_key_expire,
+  ) {
+    return Err(AppError::UploadTokenUsed);
+  }
+  let path = partial_path(state, &claims);
+  let digest = match sha256_file(&path) {
+    Ok(digest) => digest,
+    Err(err) => {
+      state.used_upload_tokens.remove(&used_key);
+      return Err(err.into());
+    }
+  };
+  // 客户端提交的摘要用于校验传输的文件，签发 token 时登记的是解压后的 tar 的摘要
+  let bound = match &claims.sha256 {
+    Some(expected) => sha256_decoded(&path, encoding).is_ok_and(|decoded| decoded == *expected),
+    None => true,
+  };
+  if digest != sha256.to_lowercase() || !bound {
+    fs::remove_file(&path)?;
+    state.used_upload_tokens.remove(&used_key);
+    return Err(AppError::DigestMismatch);
+  }
+  remove_pending(state, &claims.site_id)?;
+  fs::rename(&path, artifact_path(state, &claims.site_id, encoding))?;
+  mark_pending(state, &claims)?;
+  Ok(Value::Null)
+}
+
+/// 解压后内容的 SHA-256，压缩包损坏时返回错误
+fn sha256_decoded(path: &Path, encoding: ArtifactEncoding) -> std::io::Result<String> {
+  let file = fs::File::open(path)?;
+  match encoding {
+    ArtifactEncoding::Zstd => sha256_reader(zstd::Decoder::new(file)?),
+    ArtifactEncoding::Gzip => sha256_reader(flate2::read::GzDecoder::new(file)),
+    ArtifactEncoding::Identity => sha256_reader(file),
+  }
+}
+
+fn ensure_upload_unused(state: &AppState, claims: &UploadClaims) -> ServiceResult<()> {
+  if state.used_upload_tokens.contains(&claims.used_key()) {
+    return Err(AppError::UploadTokenUsed);
+  }
+  Ok(())
+}
+
+fn is_sha256(digest: &str) -> bool {
+  digest.len() == 64
+    && digest
+      .bytes()
+      .all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+/// 内容寻址存储中的文件位置，按摘要前两位分目录
+fn blob_path(state: &AppState, sha256: &str) -> PathBuf {
+  Path::new(&state.storage_path)
+    .join(".blobs")
+    .join(&sha256[..2])
+    .join(sha256)
+}
+
+/// 清理不再被任何站点引用的文件。站点目录中的文件是存储中文件的硬链接，
+/// 链接数为 1 说明只剩存储中的这一份；未完成的上传登记过的文件和最近写入的文件会被保留
+fn collect_blobs(state: &AppState) {
+  if let Err(err) = try_collect_blobs(state) {
+    tracing::warn!("Failed to collect blobs: {}", err);
+  }
+}
+
+#[cfg(unix)]
+fn try_collect_blobs(state: &AppState) -> std::io::Result<usize> {
+  use std::os::unix::fs::MetadataExt;
+
+  let blobs = Path::new(&state.storage_path).join(".blobs");
+  if !blobs.exists() {
+    return Ok(0);
+  }
+  let mut pending = HashSet::new();
+  let uploads = Path::new(&state.storage_path).join(".uploads");
+  if uploads.exists() {
+    for entry in fs::read_dir(uploads)? {
+      let path = entry?.path();
+      if path.to_string_lossy().ends_with(".manifest.json") {
+        let files: Vec<ManifestEntry> =
+          serde_json::from_slice(&fs::read(&path)?).unwrap_or_default();
+        pending.extend(files.into_iter().map(|f| f.sha256));
+      }
+    }
+  }
+  let min_age = std::time::Duration::from_secs(state.upload_token_key_expire.max(0) as u64);
+  let mut removed = 0;
+  for dir in fs::read_dir(&blobs)? {
+    let dir = dir?.path();
+    if !dir.is_dir() {
+      continue;
+    }
+    for entry in fs::read_dir(&dir)? {
+      let path = entry?.path();
+      let metadata = fs::metadata(&path)?;
+      let name = path.file_name().unwrap_or_default().to_string_lossy();
+      let recent = metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .is_none_or(|age| age < min_age);
+      if metadata.nlink() == 1 && !recent && !pending.contains(name.as_ref()) {
+        fs::remove_file(&path)?;
+        removed += 1;
+      }
+    }
+    // 目录为空时一并删除
+    let _ = fs::remove_dir(&dir);
+  }
+  debug!("Collected {} blobs", removed);
+  Ok(removed)
+}
+
+#[cfg(not(unix))]
+fn try_collect_blobs(_state: &AppState) -> std::io::Result<usize> {
+  Ok(0)
+}
+
+fn manifest_path(state: &AppState, deployment_id: i32) -> PathBuf {
+  Path::new(&state.storage_path)
+    .join(".uploads")
+    .join(format!("{}.manifest.json", deployment_id))
+}
+
+/// 待发布的站点目录，发布时替换 `{site_id}` 目录
+fn release_path(state: &AppState, site_id: &str) -> PathBuf {
+  Path::new(&state.storage_path)
+    .join(".releases")
+    .join(site_id)
+}
+
+/// 清单中的路径只能是相对路径，且不能包含 `..`
+fn is_safe_path(path: &str) -> bool {
+  !path.is_empty()
+    && Path::new(path)
+      .components()
+      .all(|c| matches!(c, Component::Normal(_)))
+}
+
+/// 登记本次部署的文件清单，返回存储中缺少的文件摘要
+pub async fn upload_manifest(
+  state: &AppState,
+  upload_token: String,
+  files: Vec<ManifestEntry>,
+) -> ServiceResult<UploadManifestResponse> {
+  let claims = verify_upload_token(state, &upload_token)?;
+  ensure_upload_unused(state, &claims)?;
+  if claims
+    .sha256
+    .as_ref()
+    .is_some_and(|expected| *expected != manifest_digest(&files))
+  {
+    return Err(AppError::DigestMismatch);
+  }
+  if files
+    .iter()
+    .any(|f| !is_safe_path(&f.path) || !is_sha256(&f.sha256))
+  {
+    return Err(AppError::InvalidManifest);
+  }
+
+  let mut seen = HashSet::new();
+  let missing = files
+    .iter()
+    .filter(|f| !blob_path(state, &f.sha256).exists())
+    .filter(|f| seen.insert(f.sha256.clone()))
+    .map(|f| f.sha256.clone())
+    .collect();
+
+  let path = manifest_path(state, claims.deployment_id);
+  if let Some(parent) = path.parent() {
+    fs::create_dir_all(parent)?;
+  }
+  fs::write(path, serde_json::to_vec(&files).unwrap_or_default())?;
+  Ok(UploadManifestResponse { missing })
+}
+
+/// 保存一个文件到内容寻址存储，内容必须与摘要一致
+pub async fn upload_blob(
+  state: &AppState,
+  upload_token: String,
+  sha256: String,
+  content: &[u8],
+) -> ServiceResult<Value> {
+  let claims = verify_upload_token(state, &upload_token)?;
+  ensure_upload_unused(state, &claims)?;
+  if !is_sha256(&sha256) || sha256_bytes(content) != sha256 {
+    return Err(AppError::DigestMismatch);
+  }
+  let path = blob_path(state, &sha256);
+  if path.exists() {
+    return Ok(Value::Null);
+  }
+  if let Some(parent) = path.parent() {
+    fs::create_dir_all(parent)?;
+  }
+  // 先写临时文件再改名，避免并发上传时读到不完整的文件
+  let tmp = path.with_extension(format!("{}.tmp", claims.deployment_id));
+  fs::write(&tmp, content)?;
+  fs::rename(tmp, path)?;
+  Ok(Value::Null)
+}
 
-  for tempfile in form.dist.iter() {
-    let filename = tempfile
-      .file_name
-      .clone()
-      .ok_or(AppError::TempfileNotFound)?;
-    let target_path = base_dir.join(filename);
-    fs::copy(tempfile.file.path(), target_path)?;
+/// 用硬链接把清单中的文件组装成待发布的站点目录
+pub async fn complete_manifest(state: &AppState, upload_token: String) -> ServiceResult<Value> {
+  let claims = verify_upload_token(state, &upload_token)?;
+  let _guard = state.site_locks.lock(&claims.site_id).await;
+  ensure_upload_unused(state, &claims)?;
+  let manifest = manifest_path(state, claims.deployment_id);
+  let files: Vec<ManifestEntry> =
+    serde_json::from_slice(&fs::read(&manifest)?).map_err(|_| AppError::InvalidManifest)?;
+  if let Some(entry) = files.iter().find(|f| !blob_path(state, &f.sha256).exists()) {
+    return Err(AppError::BlobMissing(entry.sha256.clone()));
   }
 
+  remove_pending(state, &claims.site_id)?;
+  let release = release_path(state, &claims.site_id);
+  fs::create_dir_all(&release)?;
+  for entry in files.iter() {
+    let target = release.join(&entry.path);
+    if let Some(parent) = target.parent() {
+      fs::create_dir_all(parent)?;
+    }
+    fs::hard_link(blob_path(state, &entry.sha256), target)?;
+  }
+  mark_pending(state, &claims)?;
+  fs::remove_file(manifest)?;
+  state.used_upload_tokens.insert(
+    &claims.used_key(),
+    signature::timestamp(),
+    state.upload_token_key_expire,
+  );
   Ok(Value::Null)
 }
 
+/// 发布站点，带有幂等键的请求成功后会缓存结果，Master 重试时直接返回
 pub async fn publish_site(
   state: &AppState,
   site_id: String,
+  deployment_id: i32,
   bandwidth: String,
   bind_domain: Option<String>,
   preview_domain: String,
-) -> ServiceResult<Value> {
+  idempotency_key: Option<String>,
+) -> ServiceResult<TaskPublishResponse> {
+  // 前一个相同的请求还在处理时等待它结束，再从缓存中取结果
+  let _guard = state.site_locks.lock(&site_id).await;
+  if let Some(result) = idempotency_key
+    .as_deref()
+    .and_then(|key| state.task_results.get(key))
+  {
+    return Ok(result);
+  }
+  let result = deploy_site(
+    state,
+    site_id,
+    deployment_id,
+    bandwidth,
+    bind_domain,
+    preview_domain,
+  )
+  .await?;
+  if let Some(key) = idempotency_key {
+    state
+      .task_results
+      .insert(key, result.clone(), signature::timestamp());
+  }
+  Ok(result)
+}
+
+async fn deploy_site(
+  state: &AppState,
+  site_id: String,
+  deployment_id: i32,
+  bandwidth: String,
+  bind_domain: Option<String>,
+  preview_domain: String,
+) -> ServiceResult<TaskPublishResponse> {
   let base_dir = Path::new(&state.storage_path);
+  let mut log = TaskLog::default();
 
   if !base_dir.exists() {
     fs::create_dir_all(base_dir)?;
   }
 
   let nginx_root_path = format!("{}/{}", base_dir.canonicalize()?.to_string_lossy(), site_id);
-  // 解压 tar
-  if !extract_tar(
-    nginx_root_path.clone() + ".tar",
-    base_dir.canonicalize()?.to_string_lossy().to_string(),
-  ) {
-    return Err(AppError::ExtractTar);
-  };
+  let release = release_path(state, &site_id);
+  // 只发布属于这个部署的上传，上传没有到达时不能用站点原有的文件冒充
+  let pending = read_marker(&pending_marker(state, &site_id)) == Some(deployment_id);
+  let published = read_marker(&release_marker(state, &site_id)) == Some(deployment_id);
+  if pending && release.exists() {
+    // 通过文件清单上传的站点已经组装好，直接替换
+    if Path::new(&nginx_root_path).exists() {
+      fs::remove_dir_all(&nginx_root_path)?;
+    }
+    fs::rename(release, &nginx_root_path)?;
+    log.line(&format!("release moved to {}", nginx_root_path));
+  } else if let Some((artifact, encoding)) = find_artifact(state, &site_id).filter(|_| pending) {
+    if !extract_tar(
+      artifact.to_string_lossy().to_string(),
+      base_dir.canonicalize()?.to_string_lossy().to_string(),
+      encoding,
+      &mut log,
+    ) {
+      return Err(AppError::ExtractTar(log.into_string()));
+    }
+  } else if (pending || published) && Path::new(&nginx_root_path).exists() {
+    // 同一个部署重试发布时，文件已经在上一次尝试中放到了站点目录
+    log.line(&format!("reuse existing files in {}", nginx_root_path));
+  } else {
+    return Err(AppError::ArtifactMissing(deployment_id));
+  }
+  // 预压缩文本资源，配合 Nginx 的 gzip_static 和 brotli_static 使用
+  let precompressed = precompress_dir(Path::new(&nginx_root_path));
+  if let Err(err) = &precompressed {
+    tracing::error!("Failed to precompress {}: {}", nginx_root_path, err);
+    log.line(&format!("precompress failed: {}", err));
+  }
+  log.step(DeploymentStage::Precompress, precompressed.is_ok());
 
   debug!("nginx_root_path: {:?}", nginx_root_path);
-  let mut nginx_config = NginxConfig::new(&state.nginx_config_path, false);
+  let backend = web_server(state);
+  let mut tls = false;
   let server_name = if let Some(bind_domain) = bind_domain {
-    if check_dns_record(&bind_domain, state.public_ip)? {
-      nginx_config.ssl_enabled = true;
+    if backend.requests_certificate() && check_dns_record(&bind_domain, &state.public_ips)? {
+      tls = true;
     }
     [bind_domain, preview_domain].join(" ")
   } else {
     preview_domain
   };
+  let site = SiteSpec {
+    site_id: &site_id,
+    server_name: &server_name,
+    root_path: &nginx_root_path,
+    bandwidth: &bandwidth,
+    tls,
+  };
 
-  if nginx_config.deploy(&server_name, &nginx_root_path, &bandwidth, &site_id) {
-    Ok(Value::Null)
+  if backend.deploy(&site, &mut log).await {
+    fs::write(release_marker(state, &site_id), deployment_id.to_string())?;
+    collect_blobs(state);
+    let mut response = log.into_response();
+    response.config = backend.read_config(&site_id);
+    Ok(response)
   } else {
-    Err(AppError::NginxDeploy)
+    Err(AppError::WebServerDeploy(log.into_string()))
   }
 }
 
+/// 记录站点当前发布的部署 ID，与站点目录同级，不会被 Nginx 访问到
+fn release_marker(state: &AppState, site_id: &str) -> PathBuf {
+  Path::new(&state.storage_path).join(format!("{}.release", site_id))
+}
+
+/// 列出存储目录和 Nginx 配置中的所有站点及其发布的版本和配置摘要
+pub async fn get_inventory(state: &AppState) -> ServiceResult<InventoryResponse> {
+  let base_dir = Path::new(&state.storage_path);
+  let backend = web_server(state);
+  let mut site_ids: BTreeSet<String> = backend.managed_sites()?.into_iter().collect();
+  if base_dir.exists() {
+    for entry in fs::read_dir(base_dir)? {
+      let entry = entry?;
+      let name = entry.file_name().to_string_lossy().to_string();
+      // `.uploads`、`.blobs`、`.releases` 等内部目录不是站点
+      if entry.file_type()?.is_dir() && !name.starts_with('.') {
+        site_ids.insert(name);
+      }
+    }
+  }
+  let mut sites = Vec::with_capacity(site_ids.len());
+  for site_id in site_ids {
+    sites.push(InventorySite {
+      release_id: read_marker(&release_marker(state, &site_id)),
+      config_hash: backend
+        .live_config(&site_id)
+        .await
+        .map(|config| sha256_bytes(config.as_bytes())),
+      has_files: base_dir.join(&site_id).is_dir(),
+      site_id,
+    });
+  }
+  Ok(InventoryResponse { sites })
+}
+
 pub async fn revoke_site(state: &AppState, site_id: String) -> ServiceResult<Value> {
+  let _guard = state.site_locks.lock(&site_id).await;
   let base_dir = Path::new(&state.storage_path);
-  fs::remove_dir_all(base_dir.join(&site_id))?;
-  let nc = NginxConfig::new(&state.nginx_config_path, false);
-  nc.remove_config(&site_id)?;
+  let site_dir = base_dir.join(&site_id);
+  if site_dir.exists() {
+    fs::remove_dir_all(site_dir)?;
+  }
+  remove_pending(state, &site_id)?;
+  let marker = release_marker(state, &site_id);
+  if marker.exists() {
+    fs::remove_file(marker)?;
+  }
+  web_server(state).remove(&site_id).await?;
+  collect_blobs(state);
   Ok(Value::Null)
 }
 
+/// 将站点目录打包为 tar.gz，用于迁移到其他 Agent
+pub async fn export_site(state: &AppState, site_id: String) -> ServiceResult<Vec<u8>> {
+  let base_dir = Path::new(&state.storage_path);
+  let _guard = state.site_locks.lock(&site_id).await;
+  if !is_safe_path(&site_id) || !base_dir.join(&site_id).is_dir() {
+    return Err(AppError::SiteNotFound(site_id));
+  }
+  let archive = base_dir.join(format!("{}.export.tar.gz", site_id));
+  let mut log = TaskLog::default();
+  let mut command = std::process::Command::new("tar");
+  command
+    .arg("-czf")
+    .arg(&archive)
+    .arg("-C")
+    .arg(base_dir)
+    .arg(&site_id);
+  if !log.run(&mut command) {
+    let _ = fs::remove_file(&archive);
+    return Err(AppError::ExportSite(log.into_string()));
+  }
+  let content = fs::read(&archive)?;
+  fs::remove_file(&archive)?;
+  Ok(content)
+}
+
 #[cfg(test)]
 mod tests {
-  use super::get_upload_token;
-  use crate::app::AppState;
+  use super::*;
+  use crate::testing::{temp_storage, test_state};
+  use common::agent::InitUploadRequest;
+
+  async fn upload_token(state: &AppState, deployment_id: i32, sha256: Option<String>) -> String {
+    get_upload_token(
+      state,
+      InitUploadRequest {
+        site_id: "site".to_string(),
+        deployment_id,
+        sha256,
+        upload_id: None,
+      },
+    )
+    .await
+    .unwrap()
+    .upload_token
+  }
+
   #[actix_web::test]
   async fn test_get_upload_token() {
-    let state = AppState {
-      nginx_config_path: "/etc/nginx/sprout".to_string(),
-      storage_path: "./".to_string(),
-      upload_token_key: "efkalwfewalkf".to_string(),
-      upload_token_key_expire: 1000,
-      public_ip: "192.168.5.12".parse().unwrap(),
-    };
+    let state = test_state("./");
 
-    match get_upload_token(&state, "alfjalfafj".to_string()).await {
+    match get_upload_token(
+      &state,
+      InitUploadRequest {
+        site_id: "alfjalfafj".to_string(),
+        deployment_id: 1,
+        sha256: None,
+        upload_id: None,
+      },
+    )
+    .await
+    {
       Ok(res) => {
         println!("Upload token: {}", res.upload_token);
       }
@@ -125,4 +709,266 @@ mod tests {
   //     upload_token_key_expire: 1000,
   //   };
   // }
+
+  #[actix_web::test]
+  async fn test_publish_requires_artifact() {
+    let storage = temp_storage("publish");
+    let state = test_state(&storage);
+    let publish = |deployment_id| {
+      deploy_site(
+        &state,
+        "site".to_string(),
+        deployment_id,
+        "0".to_string(),
+        None,
+        "site.example.com".to_string(),
+      )
+    };
+    // 站点目录中是已发布的部署 1 的文件
+    fs::create_dir_all(Path::new(&storage).join("site")).unwrap();
+    fs::write(release_marker(&state, "site"), "1").unwrap();
+    assert!(matches!(
+      publish(2).await,
+      Err(AppError::ArtifactMissing(2))
+    ));
+
+    // 待发布的上传属于部署 3，不能当作部署 2 发布
+    let token = upload_token(&state, 3, None).await;
+    upload_chunk(&state, token.clone(), 0, b"archive")
+      .await
+      .unwrap();
+    let digest = sha256_bytes(b"archive");
+    complete_upload(&state, token, digest, ArtifactEncoding::Identity)
+      .await
+      .unwrap();
+    assert!(matches!(
+      publish(2).await,
+      Err(AppError::ArtifactMissing(2))
+    ));
+    assert_eq!(read_marker(&pending_marker(&state, "site")), Some(3));
+  }
+
+  #[actix_web::test]
+  async fn test_upload_token_binding() {
+    let storage = temp_storage("binding");
+    let state = test_state(&storage);
+    let content = b"site archive";
+    let digest = sha256_bytes(content);
+    let token = upload_token(&state, 1, Some(digest.clone())).await;
+
+    // 篡改过的 token 和其他部署登记的摘要都会被拒绝
+    assert!(matches!(
+      upload_session(&state, format!("{}x", token)).await,
+      Err(AppError::InvalidUploadToken)
+    ));
+    upload_chunk(&state, token.clone(), 0, b"other archive")
+      .await
+      .unwrap();
+    let other = sha256_bytes(b"other archive");
+    assert!(matches!(
+      complete_upload(&state, token.clone(), other, ArtifactEncoding::Identity).await,
+      Err(AppError::DigestMismatch)
+    ));
+
+    // 摘要不一致时丢弃已上传的内容，可以用同一个 token 重新上传
+    assert_eq!(
+      upload_session(&state, token.clone()).await.unwrap().offset,
+      0
+    );
+    upload_chunk(&state, token.clone(), 0, content)
+      .await
+      .unwrap();
+    complete_upload(
+      &state,
+      token.clone(),
+      digest.clone(),
+      ArtifactEncoding::Identity,
+    )
+    .await
+    .unwrap();
+    assert!(find_artifact(&state, "site").is_some());
+
+    // 上传完成后 token 不能再次使用，Agent 重启后同样如此
+    assert!(matches!(
+      upload_session(&state, token.clone()).await,
+      Err(AppError::UploadTokenUsed)
+    ));
+    let restarted = test_state(&storage);
+    assert!(matches!(
+      upload_chunk(&restarted, token.clone(), 0, content).await,
+      Err(AppError::UploadTokenUsed)
+    ));
+    assert!(matches!(
+      complete_upload(&restarted, token, digest, ArtifactEncoding::Identity).await,
+      Err(AppError::UploadTokenUsed)
+    ));
+    fs::remove_dir_all(storage).unwrap();
+  }
+
+  #[actix_web::test]
+  async fn test_upload_id() {
+    let storage = temp_storage("upload-id");
+    let state = test_state(&storage);
+    let content = b"site archive";
+    let digest = sha256_bytes(content);
+    let upload = |upload_id: Option<&str>| InitUploadRequest {
+      site_id: "site".to_string(),
+      deployment_id: 4,
+      sha256: None,
+      upload_id: upload_id.map(String::from),
+    };
+    for upload_id in [None, Some("migrate:1:1"), Some("migrate:1:2")] {
+      let token = get_upload_token(&state, upload(upload_id))
+        .await
+        .unwrap()
+        .upload_token;
+      upload_chunk(&state, token.clone(), 0, content)
+        .await
+        .unwrap();
+      complete_upload(
+        &state,
+        token.clone(),
+        digest.clone(),
+        ArtifactEncoding::Identity,
+      )
+      .await
+      .unwrap();
+      // 同一次上传的 token 仍然只能使用一次
+      assert!(matches!(
+        upload_session(&state, token).await,
+        Err(AppError::UploadTokenUsed)
+      ));
+    }
+    fs::remove_dir_all(storage).unwrap();
+  }
+
+  #[actix_web::test]
+  async fn test_chunked_upload_resume() {
+    let storage = temp_storage("resume");
+    let state = test_state(&storage);
+    let token = upload_token(&state, 2, None).await;
+
+    let session = upload_session(&state, token.clone()).await.unwrap();
+    assert_eq!(session.offset, 0);
+    assert_eq!(session.chunk_size, UPLOAD_CHUNK_SIZE);
+    let session = upload_chunk(&state, token.clone(), 0, b"hello ")
+      .await
+      .unwrap();
+    assert_eq!(session.offset, 6);
+
+    // 偏移量与已收到的字节数不一致时返回正确的偏移量
+    assert!(matches!(
+      upload_chunk(&state, token.clone(), 0, b"hello ").await,
+      Err(AppError::UploadOffsetMismatch(6))
+    ));
+    assert!(matches!(
+      upload_chunk(&state, token.clone(), 10, b"world").await,
+      Err(AppError::UploadOffsetMismatch(6))
+    ));
+
+    // 中断后重新开始会话，从已收到的位置继续上传
+    let restarted = test_state(&storage);
+    let session = upload_session(&restarted, token.clone()).await.unwrap();
+    assert_eq!(session.offset, 6);
+    upload_chunk(&restarted, token.clone(), session.offset, b"world")
+      .await
+      .unwrap();
+    complete_upload(
+      &restarted,
+      token,
+      sha256_bytes(b"hello world"),
+      ArtifactEncoding::Identity,
+    )
+    .await
+    .unwrap();
+    let (artifact, _) = find_artifact(&restarted, "site").unwrap();
+    assert_eq!(fs::read(artifact).unwrap(), b"hello world");
+    assert!(!Path::new(&storage).join(".uploads").join("2.part").exists());
+    fs::remove_dir_all(storage).unwrap();
+  }
+
+  #[actix_web::test]
+  async fn test_upload_replaces_pending_release() {
+    let storage = temp_storage("pending");
+    let state = test_state(&storage);
+    let stale = release_path(&state, "site");
+    fs::create_dir_all(&stale).unwrap();
+    fs::write(stale.join("index.html"), "old").unwrap();
+
+    let token = upload_token(&state, 3, None).await;
+    upload_chunk(&state, token.clone(), 0, b"archive")
+      .await
+      .unwrap();
+    complete_upload(
+      &state,
+      token,
+      sha256_bytes(b"archive"),
+      ArtifactEncoding::Identity,
+    )
+    .await
+    .unwrap();
+    // 较早的文件清单上传不能覆盖之后完成的压缩包上传
+    assert!(!stale.exists());
+    assert!(find_artifact(&state, "site").is_some());
+    fs::remove_dir_all(storage).unwrap();
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn test_collect_blobs() {
+    let storage = temp_storage("blobs");
+    let mut state = test_state(&storage);
+    state.upload_token_key_expire = 0;
+    let blob = |content: &[u8]| {
+      let path = blob_path(&state, &sha256_bytes(content));
+      fs::create_dir_all(path.parent().unwrap()).unwrap();
+      fs::write(&path, content).unwrap();
+      path
+    };
+    let linked = blob(b"linked");
+    let orphan = blob(b"orphan");
+    let pending = blob(b"pending");
+    let site_dir = Path::new(&storage).join("site");
+    fs::create_dir_all(&site_dir).unwrap();
+    fs::hard_link(&linked, site_dir.join("index.html")).unwrap();
+    let manifest = manifest_path(&state, 4);
+    fs::create_dir_all(manifest.parent().unwrap()).unwrap();
+    let files = vec![ManifestEntry {
+      path: "index.html".to_string(),
+      sha256: sha256_bytes(b"pending"),
+      size: 7,
+    }];
+    fs::write(manifest, serde_json::to_vec(&files).unwrap()).unwrap();
+
+    assert_eq!(try_collect_blobs(&state).unwrap(), 1);
+    assert!(linked.exists());
+    assert!(!orphan.exists());
+    assert!(pending.exists());
+    fs::remove_dir_all(storage).unwrap();
+  }
+
+  #[actix_web::test]
+  async fn test_compressed_upload_binding() {
+    let storage = temp_storage("compressed");
+    let state = test_state(&storage);
+    let tar = b"uncompressed tar".to_vec();
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+    encoder.write_all(&tar).unwrap();
+    let gzip = encoder.finish().unwrap();
+
+    // token 登记的是解压后的摘要，压缩格式可以在之后协商
+    let token = upload_token(&state, 5, Some(sha256_bytes(&tar))).await;
+    upload_chunk(&state, token.clone(), 0, &gzip).await.unwrap();
+    complete_upload(&state, token, sha256_bytes(&gzip), ArtifactEncoding::Gzip)
+      .await
+      .unwrap();
+
+    let token = upload_token(&state, 6, Some(sha256_bytes(b"other tar"))).await;
+    upload_chunk(&state, token.clone(), 0, &gzip).await.unwrap();
+    assert!(matches!(
+      complete_upload(&state, token, sha256_bytes(&gzip), ArtifactEncoding::Gzip).await,
+      Err(AppError::DigestMismatch)
+    ));
+    fs::remove_dir_all(storage).unwrap();
+  }
 }
@@ -1,7 +1,11 @@
-use crate::{components::heartbeat::service, error::AppError, traits::IntoHttpResponse};
-use actix_web::{HttpResponse, get};
+use crate::{
+  app::AppState, components::heartbeat::service, error::AppError, traits::IntoHttpResponse,
+};
+use actix_web::{HttpResponse, get, web::Data};
+use common::{Response, agent::HeartbeatResponse};
 
+#[utoipa::path(tag = "heartbeat", responses((status = OK, body = Response<HeartbeatResponse>)))]
 #[get("/heartbeat")]
-pub async fn heartbeat() -> Result<HttpResponse, AppError> {
-  service::heartbeat().await.into_http_response()
+pub async fn heartbeat(state: Data<AppState>) -> Result<HttpResponse, AppError> {
+  service::heartbeat(&state).await.into_http_response()
 }
@@ -2,6 +2,7 @@ mod handler;
 mod service;
 
 use actix_web::web::ServiceConfig;
+use utoipa::OpenApi;
 
 pub struct HeartbeatComponent;
 
@@ -10,3 +11,8 @@ impl HeartbeatComponent {
     cfg.service(handler::heartbeat);
   }
 }
+
+/// 心跳接口的 OpenAPI 文档
+#[derive(OpenApi)]
+#[openapi(paths(handler::heartbeat))]
+pub struct HeartbeatApi;
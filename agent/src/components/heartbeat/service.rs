@@ -1,9 +1,9 @@
 use common::agent::HeartbeatResponse;
 use sysinfo::{CpuRefreshKind, RefreshKind};
 
-use crate::error::AppError;
+use crate::{app::AppState, error::AppError};
 
-pub async fn heartbeat() -> Result<HeartbeatResponse, AppError> {
+pub async fn heartbeat(state: &AppState) -> Result<HeartbeatResponse, AppError> {
   let mut s = sysinfo::System::new_with_specifics(
     RefreshKind::everything().with_cpu(CpuRefreshKind::everything()),
   );
@@ -18,5 +18,6 @@ pub async fn heartbeat() -> Result<HeartbeatResponse, AppError> {
     total_memory: s.total_memory() / 1024 / 1024,
     free_memory: s.free_memory() / 1024 / 1024,
     memory_usage: ((s.used_memory() as f64 / s.total_memory() as f64) * 100.0).trunc(),
+    public_ips: state.public_ips.clone(),
   })
 }
@@ -2,6 +2,7 @@
 
 use std::net::IpAddr;
 
+use common::agent::DEFAULT_AGENT_PORT;
 use serde::Deserialize;
 
 use crate::error::AppError;
@@ -11,7 +12,7 @@ fn default_host() -> String {
 }
 
 fn default_port() -> u16 {
-  5001
+  DEFAULT_AGENT_PORT
 }
 
 fn default_workers() -> usize {
@@ -22,6 +23,40 @@ fn default_nginx_config_path() -> String {
   "/etc/nginx/agent".to_string()
 }
 
+fn default_signature_tolerance() -> i64 {
+  300
+}
+
+/// 默认的 Nginx 没有 ngx_brotli 模块，开启后 `nginx -t` 会失败
+fn default_nginx_brotli_static() -> bool {
+  false
+}
+
+fn default_serve_port() -> u16 {
+  80
+}
+
+fn default_access_log_path() -> String {
+  "logs".to_string()
+}
+
+fn default_caddy_admin_url() -> String {
+  "http://localhost:2019".to_string()
+}
+
+/// 站点的服务方式
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ServeMode {
+  /// 生成 Nginx 配置并重新加载 Nginx
+  #[default]
+  Nginx,
+  /// 由 Agent 内置的静态文件服务提供站点
+  Builtin,
+  /// 通过管理接口向 Caddy 添加路由，证书由 Caddy 自动申请
+  Caddy,
+}
+
 #[derive(Deserialize, Debug)]
 pub struct Config {
   #[serde(default = "default_workers")]
@@ -32,10 +67,34 @@ pub struct Config {
   pub port: u16,
   #[serde(default = "default_nginx_config_path")]
   pub nginx_config_path: String,
+  /// 是否在 Nginx 配置中启用 brotli_static，需要 Nginx 安装 ngx_brotli 模块
+  #[serde(default = "default_nginx_brotli_static")]
+  pub nginx_brotli_static: bool,
+  #[serde(default)]
+  pub serve_mode: ServeMode,
+  /// 内置服务监听的端口，仅在 `serve_mode` 为 `builtin` 时使用
+  #[serde(default = "default_serve_port")]
+  pub serve_port: u16,
+  /// 内置服务的访问日志目录，每个站点一个文件
+  #[serde(default = "default_access_log_path")]
+  pub access_log_path: String,
+  /// Caddy 管理接口地址，仅在 `serve_mode` 为 `caddy` 时使用
+  #[serde(default = "default_caddy_admin_url")]
+  pub caddy_admin_url: String,
   pub storage_path: String,
   pub upload_token_key: String,
   pub upload_token_key_expire: i64,
-  pub public_ip: IpAddr,
+  /// 站点域名应解析到的公网地址，以逗号分隔，可以同时包含 IPv4 和 IPv6
+  pub public_ip: Vec<IpAddr>,
+  /// Master 注册 Agent 时签发的 token，用于校验 Master 发来的请求签名
+  pub agent_token: String,
+  /// 请求时间戳允许的最大偏差（秒）
+  #[serde(default = "default_signature_tolerance")]
+  pub signature_tolerance: i64,
+  /// PEM 格式的证书链路径，和 `tls_key` 同时设置时使用 HTTPS
+  pub tls_cert: Option<String>,
+  /// PEM 格式的私钥路径，支持 PKCS#8、PKCS#1 和 SEC1
+  pub tls_key: Option<String>,
 }
 
 impl Config {
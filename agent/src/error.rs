@@ -1,5 +1,5 @@
 use actix_web::{HttpResponse, ResponseError, http::StatusCode};
-use common::Response;
+use common::{Response, error::ErrorCode};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -16,10 +16,36 @@ pub enum AppError {
   },
   #[error("Temp file not found error")]
   TempfileNotFound,
-  #[error("Extract tar error")]
-  ExtractTar,
-  #[error("Nginx deploy error")]
-  NginxDeploy,
+  #[error("Extract tar error\n{0}")]
+  ExtractTar(String),
+  #[error("Web server deploy error\n{0}")]
+  WebServerDeploy(String),
+  #[error("Invalid request signature")]
+  InvalidSignature,
+  #[error("Invalid upload token")]
+  InvalidUploadToken,
+  #[error("Upload token has been used")]
+  UploadTokenUsed,
+  #[error("Uploaded file digest mismatch")]
+  DigestMismatch,
+  #[error("Upload offset mismatch, expected {0}")]
+  UploadOffsetMismatch(u64),
+  #[error("Upload chunk too large")]
+  ChunkTooLarge,
+  #[error("Invalid manifest")]
+  InvalidManifest,
+  #[error("Unsupported artifact encoding")]
+  UnsupportedEncoding,
+  #[error("Blob {0} is missing")]
+  BlobMissing(String),
+  #[error("Artifact of deployment {0} is missing")]
+  ArtifactMissing(i32),
+  #[error("Site {0} not found")]
+  SiteNotFound(String),
+  #[error("Export site error\n{0}")]
+  ExportSite(String),
+  #[error("TLS config error: {0}")]
+  Tls(String),
   #[error("Internal server error {source:?}")]
   InternalServerError {
     #[source]
@@ -28,14 +54,26 @@ pub enum AppError {
 }
 
 impl AppError {
-  pub fn code(&self) -> i32 {
+  pub fn code(&self) -> ErrorCode {
     match self {
-      AppError::InternalServerError { .. }
-      | AppError::LoadEnv { .. }
-      | AppError::DeserializeEnv { .. }
-      | AppError::TempfileNotFound
-      | AppError::ExtractTar
-      | AppError::NginxDeploy => 1000,
+      AppError::InternalServerError { .. } | AppError::TempfileNotFound => ErrorCode::Internal,
+      AppError::LoadEnv { .. } | AppError::DeserializeEnv { .. } | AppError::Tls(_) => {
+        ErrorCode::Config
+      }
+      AppError::ExtractTar(_) => ErrorCode::ExtractArchive,
+      AppError::WebServerDeploy(_) => ErrorCode::PublishFailed,
+      AppError::ExportSite(_) => ErrorCode::ExportFailed,
+      AppError::InvalidSignature => ErrorCode::InvalidSignature,
+      AppError::InvalidUploadToken => ErrorCode::InvalidUploadToken,
+      AppError::UploadTokenUsed => ErrorCode::UploadTokenUsed,
+      AppError::DigestMismatch => ErrorCode::DigestMismatch,
+      AppError::UploadOffsetMismatch(_) => ErrorCode::UploadOffsetMismatch,
+      AppError::ChunkTooLarge => ErrorCode::ChunkTooLarge,
+      AppError::InvalidManifest => ErrorCode::InvalidManifest,
+      AppError::BlobMissing(_) => ErrorCode::BlobMissing,
+      AppError::ArtifactMissing(_) => ErrorCode::ArtifactMissing,
+      AppError::UnsupportedEncoding => ErrorCode::UnsupportedEncoding,
+      AppError::SiteNotFound(_) => ErrorCode::SiteNotFound,
     }
   }
 
@@ -44,9 +82,20 @@ impl AppError {
       AppError::InternalServerError { .. }
       | AppError::LoadEnv { .. }
       | AppError::DeserializeEnv { .. }
+      | AppError::Tls(_)
       | AppError::TempfileNotFound
-      | AppError::ExtractTar
-      | AppError::NginxDeploy => StatusCode::INTERNAL_SERVER_ERROR,
+      | AppError::ExtractTar(_)
+      | AppError::WebServerDeploy(_)
+      | AppError::ExportSite(_) => StatusCode::INTERNAL_SERVER_ERROR,
+      AppError::InvalidSignature | AppError::InvalidUploadToken => StatusCode::UNAUTHORIZED,
+      AppError::UploadTokenUsed => StatusCode::CONFLICT,
+      AppError::DigestMismatch => StatusCode::BAD_REQUEST,
+      AppError::UploadOffsetMismatch(_) => StatusCode::CONFLICT,
+      AppError::ChunkTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+      AppError::InvalidManifest => StatusCode::BAD_REQUEST,
+      AppError::BlobMissing(_) | AppError::ArtifactMissing(_) => StatusCode::CONFLICT,
+      AppError::UnsupportedEncoding => StatusCode::BAD_REQUEST,
+      AppError::SiteNotFound(_) => StatusCode::NOT_FOUND,
     }
   }
 }
@@ -56,8 +105,8 @@ impl ResponseError for AppError {
     tracing::error!("{:#?}", self);
     HttpResponse::build(self.status_code()).json(Response::<Option<()>> {
       data: None,
-      code: self.code(),
-      msg: self.to_string(), // 使用 thiserror 格式化的错误消息，或调用 user_message()
+      code: self.code().into(),
+      msg: self.to_string(),
     })
   }
 }
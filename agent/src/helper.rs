@@ -1,200 +1,196 @@
-use serde::{Deserialize, Serialize};
 use std::{
-  fs,
-  io::{BufRead, BufReader},
+  fs::{self, File},
+  io::{self, BufWriter, Write},
   net::IpAddr,
-  path::PathBuf,
+  path::{Path, PathBuf},
   process::{Command, Stdio},
+  sync::OnceLock,
 };
-use tracing::{debug, error, info, trace};
+use tracing::{debug, error, info};
+
+use actix_web::{HttpRequest, http::header::AUTHORIZATION};
+use common::agent::{ArtifactEncoding, DeploymentStage, TaskPublishResponse, TaskStep};
+use flate2::{Compression, write::GzEncoder};
 
 use crate::error::AppError;
 
-pub fn extract_tar(filename: String, output: String) -> bool {
-  let mut child = Command::new("tar")
-    .arg("-xf")
-    .arg(&filename)
-    .arg("-C")
-    .arg(output)
-    .stdout(Stdio::piped())
-    .stderr(Stdio::piped())
-    .spawn()
-    .expect("Build failed");
-  let stdout = child.stdout.take().expect("Failed to capture stdout");
-  let stderr = child.stderr.take().expect("Failed to capture stderr");
-  let stdout_reader = BufReader::new(stdout);
-  for line in stdout_reader.lines() {
-    match line {
-      Ok(line) => trace!("{}", line),
-      Err(e) => error!("Error reading stdout: {}", e),
-    }
-  }
-  let stderr_reader = BufReader::new(stderr);
-  for line in stderr_reader.lines() {
-    match line {
-      Ok(line) => trace!("{}", line),
-      Err(e) => error!("Error reading stderr: {}", e),
-    }
-  }
-  let status = child.wait().expect("Failed to wait for build process");
-  if status.success() {
-    info!("{}: decompressed", filename);
-    true
-  } else {
-    error!("Build failed with status: {}", status);
-    false
-  }
+/// 从 `Authorization: Bearer <token>` 请求头中取出 token
+pub fn extract_bearer_token(req: &HttpRequest) -> Result<String, AppError> {
+  req
+    .headers()
+    .get(AUTHORIZATION)
+    .and_then(|v| v.to_str().ok())
+    .and_then(|v| v.strip_prefix("Bearer "))
+    .map(|v| v.to_string())
+    .ok_or(AppError::InvalidUploadToken)
+}
+
+/// Agent 支持的压缩包格式，zstd 需要系统安装 `zstd` 命令
+pub fn supported_encodings() -> Vec<ArtifactEncoding> {
+  static ENCODINGS: OnceLock<Vec<ArtifactEncoding>> = OnceLock::new();
+  ENCODINGS
+    .get_or_init(|| {
+      let zstd = Command::new("zstd")
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|s| s.success());
+      if zstd {
+        vec![ArtifactEncoding::Zstd, ArtifactEncoding::Gzip]
+      } else {
+        vec![ArtifactEncoding::Gzip]
+      }
+    })
+    .clone()
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct NginxConfig {
-  config_path: PathBuf,
-  pub ssl_enabled: bool,
+/// 部署任务的执行日志，记录每条命令的输出和每个阶段的结果，返回给 Master 保存
+#[derive(Debug, Default)]
+pub struct TaskLog {
+  output: String,
+  steps: Vec<TaskStep>,
 }
 
-impl NginxConfig {
-  pub fn new(config_path: &str, ssl_enabled: bool) -> Self {
-    Self {
-      config_path: config_path.into(),
-      ssl_enabled,
-    }
+impl TaskLog {
+  pub fn line(&mut self, message: &str) {
+    self.output.push_str(message);
+    self.output.push('\n');
   }
 
-  pub fn generate_config(
-    &self,
-    site_id: &str,
-    server_name: &str,
-    root_path: &str,
-    bandwidth: &str,
-  ) -> String {
-    let mut config = String::new();
-    config.push_str("server {\n");
-    config.push_str(&format!("    access_log logs/{} pupup;\n", site_id));
-    config.push_str("    listen 80;\n");
-    config.push_str(&format!("    server_name {};\n", server_name));
-    config.push_str("    location / {\n");
-    config.push_str("        try_files $uri $uri/ /index.html;\n");
-    config.push_str(&format!("        root {};\n", root_path));
-    config.push_str("        index index.html;\n");
-    config.push_str(&format!("        limit_rate {};\n", bandwidth));
-    config.push_str("    }\n");
-    config.push_str("}\n");
-    debug!("Nginx config: {}", config);
-    config
+  /// 记录一个阶段的结果，原样返回 `success`
+  pub fn step(&mut self, stage: DeploymentStage, success: bool) -> bool {
+    self.steps.push(TaskStep { stage, success });
+    success
   }
 
-  pub fn remove_config(&self, site_id: &str) -> Result<bool, std::io::Error> {
-    let domian_config = self.config_path.join(site_id.to_string() + ".conf");
-    if domian_config.exists() {
-      fs::remove_file(domian_config)?;
-      Ok(true)
-    } else {
-      error!("Config file does not exist: {:?}", domian_config);
-      Ok(false)
+  /// 执行命令并记录输出，返回命令是否成功
+  pub fn run(&mut self, command: &mut Command) -> bool {
+    let program = command.get_program().to_string_lossy().to_string();
+    let args = command
+      .get_args()
+      .map(|arg| arg.to_string_lossy())
+      .collect::<Vec<_>>()
+      .join(" ");
+    self.line(&format!("$ {} {}", program, args));
+    match command.output() {
+      Ok(output) => {
+        self
+          .output
+          .push_str(&String::from_utf8_lossy(&output.stdout));
+        self
+          .output
+          .push_str(&String::from_utf8_lossy(&output.stderr));
+        if !output.status.success() {
+          self.line(&format!("{} exited with {}", program, output.status));
+          error!("{} exited with {}", program, output.status);
+        }
+        output.status.success()
+      }
+      Err(err) => {
+        self.line(&format!("failed to run {}: {}", program, err));
+        error!("Failed to run {}: {}", program, err);
+        false
+      }
     }
   }
 
-  pub fn deploy(&self, server_name: &str, root_path: &str, bandwidth: &str, site_id: &str) -> bool {
-    // 生成配置文件内容
-    let config_content = self.generate_config(site_id, server_name, root_path, bandwidth);
-
-    // 暂时简单写入，以后替换为原子写入
-    if let Err(e) = fs::create_dir_all(self.config_path.as_path()) {
-      tracing::error!("Failed to create config directory: {}", e);
-      return false;
-    }
+  pub fn into_string(self) -> String {
+    self.output
+  }
 
-    if fs::write(
-      self.config_path.join(format!("{}.conf", site_id)),
-      config_content,
-    )
-    .map_err(|_| {
-      tracing::error!("{}", "write Nginx configuration failed");
-    })
-    .is_err()
-    {
-      return false;
+  pub fn into_response(self) -> TaskPublishResponse {
+    TaskPublishResponse {
+      logs: self.output,
+      steps: self.steps,
+      config: None,
     }
+  }
+}
 
-    if self.ssl_enabled {
-      let split = server_name
-        .split(" ")
-        .map(|s| s.to_string())
-        .collect::<Vec<String>>();
-      println!("{:?}", split);
-      let domain = if split.len() == 2 {
-        split[0].clone()
-      } else {
-        split[0].clone()
-      };
-      self.apply_ssl(&domain);
-    }
-    // 测试配置是否正确
-    if Command::new("nginx").arg("-t").status().is_err() {
-      tracing::error!("{}", "Nginx configuration test failed");
-      return false;
-    }
-    // 重新加载 Nginx
-    if Command::new("nginx")
-      .arg("-s")
-      .arg("reload")
-      .status()
-      .is_err()
-    {
-      tracing::error!("{}", "reload Nginx failed");
-      return false;
-    }
+pub fn extract_tar(
+  filename: String,
+  output: String,
+  encoding: ArtifactEncoding,
+  log: &mut TaskLog,
+) -> bool {
+  let mut command = Command::new("tar");
+  match encoding {
+    ArtifactEncoding::Zstd => command.arg("--zstd"),
+    ArtifactEncoding::Gzip => command.arg("-z"),
+    ArtifactEncoding::Identity => &mut command,
+  };
+  command.arg("-xf").arg(&filename).arg("-C").arg(output);
+  let success = log.run(&mut command);
+  if log.step(DeploymentStage::Extract, success) {
+    info!("{}: decompressed", filename);
     true
+  } else {
+    false
   }
+}
 
-  pub fn apply_ssl(&self, domain: &str) {
-    println!("Applying SSL configuration...");
-    let c = Command::new("certbot")
-      .arg("--nginx")
-      .arg("-d")
-      .arg(domain)
-      .spawn()
-      .unwrap();
+/// 需要预压缩的文本资源扩展名
+const PRECOMPRESS_EXTENSIONS: [&str; 10] = [
+  "html", "htm", "css", "js", "mjs", "json", "svg", "xml", "txt", "map",
+];
+
+/// 压缩文件不存在或比源文件旧时需要重新生成
+fn is_stale(source: &Path, compressed: &Path) -> bool {
+  let modified = |p: &Path| fs::metadata(p).and_then(|m| m.modified()).ok();
+  match (modified(source), modified(compressed)) {
+    (Some(source), Some(compressed)) => compressed < source,
+    _ => true,
   }
 }
 
-pub fn check_dns_record(domian: &str, ip: IpAddr) -> Result<bool, AppError> {
-  let ips = dns_lookup::lookup_host(domian)?;
-  println!("{:?}", ips);
-  if ips.is_empty() {
-    Ok(false)
-  } else {
-    if ips.contains(&ip) {
-      Ok(true)
-    } else {
-      Ok(false)
+/// 为目录下的文本资源生成 `.gz` 和 `.br` 文件
+pub fn precompress_dir(root: &Path) -> io::Result<()> {
+  for entry in fs::read_dir(root)? {
+    let path = entry?.path();
+    if path.is_dir() {
+      precompress_dir(&path)?;
+      continue;
+    }
+    let Some(ext) = path.extension().and_then(|e| e.to_str()) else {
+      continue;
+    };
+    if !PRECOMPRESS_EXTENSIONS.contains(&ext.to_lowercase().as_str()) {
+      continue;
+    }
+    let gz = PathBuf::from(format!("{}.gz", path.display()));
+    if is_stale(&path, &gz) {
+      let mut encoder = GzEncoder::new(BufWriter::new(File::create(&gz)?), Compression::best());
+      io::copy(&mut File::open(&path)?, &mut encoder)?;
+      encoder.finish()?.flush()?;
+    }
+    let br = PathBuf::from(format!("{}.br", path.display()));
+    if is_stale(&path, &br) {
+      let mut encoder =
+        brotli::CompressorWriter::new(BufWriter::new(File::create(&br)?), 4096, 11, 22);
+      io::copy(&mut File::open(&path)?, &mut encoder)?;
+      encoder.into_inner().flush()?;
     }
   }
+  Ok(())
+}
+
+/// 域名是否解析到了 Agent 的某个公网地址，A 和 AAAA 记录都会检查
+pub fn check_dns_record(domian: &str, public_ips: &[IpAddr]) -> Result<bool, AppError> {
+  let ips = dns_lookup::lookup_host(domian)?;
+  debug!("{:?}", ips);
+  Ok(ips.iter().any(|ip| public_ips.contains(ip)))
 }
 
 #[cfg(test)]
 mod test {
-  use super::{NginxConfig, check_dns_record};
-
-  #[test]
-  fn test_deploy() {
-    let nc = NginxConfig::new("../target/sprout", true);
-    println!(
-      "{}",
-      nc.generate_config("abcdefghijklmn", "jinqiu.wang", "/var/www/html", "100k")
-    );
-    nc.deploy("jinqiu.wang", "/var/www/html", "100k", "abcdefghijklmn");
-  }
-
-  #[test]
-  fn test_revoke() {
-    let nc = NginxConfig::new("../target/sprout", false);
-    nc.remove_config("abcdefghijklmn").unwrap();
-  }
+  use super::check_dns_record;
 
   #[test]
   fn test_check_dns_record() {
-    let res = check_dns_record("localhost", "127.0.0.1".parse().unwrap()).unwrap();
+    let res = check_dns_record("localhost", &["127.0.0.1".parse().unwrap()]).unwrap();
     assert_eq!(res, true);
+    let res = check_dns_record("localhost", &["192.0.2.1".parse().unwrap()]).unwrap();
+    assert_eq!(res, false);
   }
 }
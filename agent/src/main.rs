@@ -2,11 +2,17 @@ use tracing::level_filters::LevelFilter;
 use tracing_subscriber::{EnvFilter, filter, fmt, layer::SubscriberExt, util::SubscriberInitExt};
 
 mod app;
+mod backend;
+mod builtin;
 mod components;
 mod config;
 mod error;
 mod helper;
+mod middlewares;
+mod openapi;
 mod response;
+#[cfg(test)]
+mod testing;
 mod traits;
 mod types;
 
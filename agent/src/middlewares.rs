@@ -0,0 +1,280 @@
+use std::{
+  collections::HashMap,
+  fs,
+  path::PathBuf,
+  sync::{Arc, Mutex},
+};
+
+use actix_web::{
+  Error, HttpMessage,
+  body::MessageBody,
+  dev::{ServiceRequest, ServiceResponse},
+  error::PayloadError,
+  middleware::Next,
+  web::{Bytes, BytesMut, Data},
+};
+use common::{agent::TaskPublishResponse, signature};
+use futures_util::StreamExt;
+use tokio::sync::OwnedMutexGuard;
+
+use tracing::warn;
+
+use crate::{app::AppState, error::AppError};
+
+/// 记录已使用过的 nonce，防止请求或上传 token 被重放
+#[derive(Debug, Clone, Default)]
+pub struct NonceCache {
+  inner: Arc<Mutex<HashMap<String, i64>>>,
+  /// 设置后每次变更都写入该文件，Agent 重启后仍然有效
+  path: Option<Arc<PathBuf>>,
+}
+
+impl NonceCache {
+  /// 从 `path` 加载已有的记录，文件不存在或无法解析时从空记录开始
+  pub fn persistent(path: impl Into<PathBuf>) -> Self {
+    let path = path.into();
+    let nonces = fs::read(&path)
+      .ok()
+      .and_then(|content| serde_json::from_slice(&content).ok())
+      .unwrap_or_default();
+    Self {
+      inner: Arc::new(Mutex::new(nonces)),
+      path: Some(Arc::new(path)),
+    }
+  }
+
+  /// 先写临时文件再改名，避免写到一半时重启留下损坏的文件
+  fn save(&self, nonces: &HashMap<String, i64>) {
+    let Some(path) = &self.path else {
+      return;
+    };
+    let tmp = path.with_extension("tmp");
+    let result = path
+      .parent()
+      .map_or(Ok(()), fs::create_dir_all)
+      .and_then(|_| fs::write(&tmp, serde_json::to_vec(nonces).unwrap_or_default()))
+      .and_then(|_| fs::rename(&tmp, path.as_ref()));
+    if let Err(err) = result {
+      warn!("Failed to save {:?}: {}", path, err);
+    }
+  }
+
+  /// nonce 未出现过时记录并返回 `true`，同时清理超出有效期的记录
+  pub fn insert(&self, nonce: &str, now: i64, tolerance: i64) -> bool {
+    let mut nonces = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+    nonces.retain(|_, ts| now - *ts <= tolerance * 2);
+    if nonces.contains_key(nonce) {
+      return false;
+    }
+    nonces.insert(nonce.to_string(), now);
+    self.save(&nonces);
+    true
+  }
+
+  pub fn contains(&self, nonce: &str) -> bool {
+    let nonces = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+    nonces.contains_key(nonce)
+  }
+
+  pub fn remove(&self, nonce: &str) {
+    let mut nonces = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+    if nonces.remove(nonce).is_some() {
+      self.save(&nonces);
+    }
+  }
+}
+
+/// 已成功执行的发布任务结果，Master 用相同的幂等键重试时直接返回，
+/// 避免已经移走的文件清单目录导致重试失败
+#[derive(Debug, Clone, Default)]
+pub struct TaskResultCache {
+  inner: Arc<Mutex<HashMap<String, (i64, TaskPublishResponse)>>>,
+}
+
+impl TaskResultCache {
+  /// 结果的保留时间（秒）
+  const TTL: i64 = 86400;
+
+  pub fn get(&self, key: &str) -> Option<TaskPublishResponse> {
+    let results = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+    results.get(key).map(|(_, result)| result.clone())
+  }
+
+  pub fn insert(&self, key: String, result: TaskPublishResponse, now: i64) {
+    let mut results = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+    results.retain(|_, (ts, _)| now - *ts <= Self::TTL);
+    results.insert(key, (now, result));
+  }
+}
+
+/// 同一个站点同一时间只处理一个发布、撤销、导出或完成上传的请求，
+/// Master 超时后重新提交的请求会等待前一个结束
+#[derive(Debug, Clone, Default)]
+pub struct SiteLocks {
+  inner: Arc<Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>>,
+}
+
+impl SiteLocks {
+  pub async fn lock(&self, site_id: &str) -> OwnedMutexGuard<()> {
+    let lock = {
+      let mut locks = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+      // 没有请求持有或等待的锁可以清理
+      locks.retain(|_, lock| Arc::strong_count(lock) > 1);
+      locks.entry(site_id.to_string()).or_default().clone()
+    };
+    lock.lock_owned().await
+  }
+}
+
+fn header<'a>(req: &'a ServiceRequest, name: &str) -> Result<&'a str, AppError> {
+  req
+    .headers()
+    .get(name)
+    .and_then(|v| v.to_str().ok())
+    .ok_or(AppError::InvalidSignature)
+}
+
+/// 签名请求的请求体上限，与 Json 提取器的默认上限一致
+pub const SIGNED_BODY_LIMIT: usize = 2 * 1024 * 1024;
+
+/// 读取完整的请求体，超过 `SIGNED_BODY_LIMIT` 时返回 413
+async fn read_body(req: &mut ServiceRequest) -> Result<Bytes, Error> {
+  let mut payload = req.take_payload();
+  let mut body = BytesMut::new();
+  while let Some(chunk) = payload.next().await {
+    let chunk = chunk?;
+    if body.len() + chunk.len() > SIGNED_BODY_LIMIT {
+      return Err(PayloadError::Overflow.into());
+    }
+    body.extend_from_slice(&chunk);
+  }
+  Ok(body.freeze())
+}
+
+/// 校验 Master 发来的请求签名，拒绝未签名、签名错误、过期或重放的请求
+pub async fn verify_signature(
+  mut req: ServiceRequest,
+  next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+  let state = req
+    .app_data::<Data<AppState>>()
+    .cloned()
+    .ok_or(AppError::InvalidSignature)?;
+  let timestamp = header(&req, signature::TIMESTAMP_HEADER)?
+    .parse::<i64>()
+    .map_err(|_| AppError::InvalidSignature)?;
+  let nonce = header(&req, signature::NONCE_HEADER)?.to_string();
+  let sign = header(&req, signature::SIGNATURE_HEADER)?.to_string();
+
+  let now = signature::timestamp();
+  if (now - timestamp).abs() > state.signature_tolerance {
+    return Err(AppError::InvalidSignature.into());
+  }
+
+  let body = read_body(&mut req).await?;
+  // 查询参数同样参与签名
+  let path = req
+    .uri()
+    .path_and_query()
+    .map_or(req.path(), |path| path.as_str());
+  if !signature::verify(
+    &state.agent_token,
+    req.method().as_str(),
+    path,
+    timestamp,
+    &nonce,
+    &body,
+    &sign,
+  ) {
+    return Err(AppError::InvalidSignature.into());
+  }
+  if !state
+    .nonce_cache
+    .insert(&nonce, now, state.signature_tolerance)
+  {
+    return Err(AppError::InvalidSignature.into());
+  }
+  // 请求体已被读取，放回去供后续的提取器使用
+  req.set_payload(body.into());
+  next.call(req).await
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::Duration;
+
+  use actix_web::{
+    App,
+    http::{StatusCode, header::CONTENT_TYPE},
+    middleware::from_fn,
+    test, web,
+  };
+
+  use super::*;
+  use crate::testing::test_state;
+
+  #[actix_web::test]
+  async fn test_verify_signature() {
+    let state = test_state("./");
+    let token = state.agent_token.clone();
+    let app = test::init_service(
+      App::new().app_data(Data::new(state)).service(
+        web::resource("/api/echo")
+          .wrap(from_fn(verify_signature))
+          .route(web::post().to(|body: web::Json<String>| async move { body.len().to_string() })),
+      ),
+    )
+    .await;
+    let request = |uri: &str, signed_path: &str, nonce: &str, body: &[u8]| {
+      let timestamp = signature::timestamp();
+      let sign = signature::sign(&token, "POST", signed_path, timestamp, nonce, body);
+      test::TestRequest::post()
+        .uri(uri)
+        .insert_header((signature::TIMESTAMP_HEADER, timestamp.to_string()))
+        .insert_header((signature::NONCE_HEADER, nonce))
+        .insert_header((signature::SIGNATURE_HEADER, sign))
+        .insert_header((CONTENT_TYPE, "application/json"))
+        .set_payload(body.to_vec())
+        .to_request()
+    };
+
+    // 中间件返回的错误同样转换为响应
+    let status = |req| async {
+      match test::try_call_service(&app, req).await {
+        Ok(resp) => resp.status(),
+        Err(err) => err.error_response().status(),
+      }
+    };
+
+    // 超过默认 256 KB 上限的请求体，查询参数参与签名
+    let body = serde_json::to_vec(&"a".repeat(300 * 1024)).unwrap();
+    let ok = request("/api/echo?a=1", "/api/echo?a=1", "1", &body);
+    assert_eq!(status(ok).await, StatusCode::OK);
+    let tampered = request("/api/echo?a=2", "/api/echo?a=1", "2", &body);
+    assert_eq!(status(tampered).await, StatusCode::UNAUTHORIZED);
+    let unsigned_query = request("/api/echo?a=1", "/api/echo", "3", &body);
+    assert_eq!(status(unsigned_query).await, StatusCode::UNAUTHORIZED);
+
+    let body = vec![b'a'; SIGNED_BODY_LIMIT + 1];
+    let too_large = request("/api/echo", "/api/echo", "4", &body);
+    assert_eq!(status(too_large).await, StatusCode::PAYLOAD_TOO_LARGE);
+  }
+
+  #[actix_web::test]
+  async fn test_site_locks() {
+    let locks = SiteLocks::default();
+    let guard = locks.lock("a").await;
+    // 其他站点不受影响
+    drop(locks.lock("b").await);
+    let waiting = {
+      let locks = locks.clone();
+      actix_web::rt::spawn(async move {
+        drop(locks.lock("a").await);
+      })
+    };
+    actix_web::rt::time::sleep(Duration::from_millis(50)).await;
+    assert!(!waiting.is_finished());
+    drop(guard);
+    waiting.await.unwrap();
+  }
+}
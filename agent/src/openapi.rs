@@ -0,0 +1,83 @@
+//! 由 handler 上的注解生成的 OpenAPI 文档，在 `/api/openapi.json` 提供
+
+use actix_web::{HttpResponse, get};
+use common::signature::{NONCE_HEADER, SIGNATURE_HEADER, TIMESTAMP_HEADER};
+use utoipa::{
+  Modify, OpenApi,
+  openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme},
+};
+
+use crate::components::{base, deployment::DeploymentApi, heartbeat::HeartbeatApi};
+
+/// 补充注解无法表达的部分：Master 请求签名与上传 token 的认证方式，以及去掉 Cargo.toml 未声明时生成的空 license
+struct Addon;
+
+impl Modify for Addon {
+  fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+    openapi.info.license = None;
+    let components = openapi.components.get_or_insert_with(Default::default);
+    components.add_security_scheme(
+      "signature",
+      SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::with_description(
+        SIGNATURE_HEADER,
+        &format!(
+          "Master 用 agent token 对请求签名，同时需携带 `{}` 和 `{}` 请求头",
+          TIMESTAMP_HEADER, NONCE_HEADER
+        ),
+      ))),
+    );
+    components.add_security_scheme(
+      // 由 `/upload/init` 签发的上传 token
+      "upload_token",
+      SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+    );
+  }
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(base::health_check, openapi_json))]
+struct BaseApi;
+
+#[derive(OpenApi)]
+#[openapi(
+  info(title = "Pupup Agent API", description = "Agent 对 Master 和 CLI 提供的接口"),
+  nest(
+    (path = "/api", api = BaseApi),
+    (path = "/api", api = HeartbeatApi),
+    (path = "/api", api = DeploymentApi),
+  ),
+  modifiers(&Addon)
+)]
+pub struct ApiDoc;
+
+#[utoipa::path(
+  tag = "base",
+  responses((status = OK, description = "OpenAPI 3 文档", content_type = "application/json"))
+)]
+#[get("/openapi.json")]
+pub async fn openapi_json() -> HttpResponse {
+  HttpResponse::Ok().json(ApiDoc::openapi())
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{fs, path::Path};
+
+  use super::*;
+
+  /// 接口变更后需用 `UPDATE_OPENAPI=1 cargo test -p agent openapi` 重新生成 `openapi.json`
+  #[test]
+  fn test_openapi_up_to_date() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("openapi.json");
+    let spec = ApiDoc::openapi().to_pretty_json().unwrap() + "\n";
+    if std::env::var_os("UPDATE_OPENAPI").is_some() {
+      fs::write(&path, &spec).unwrap();
+      return;
+    }
+    let saved = fs::read_to_string(&path).unwrap_or_default();
+    assert!(
+      saved == spec,
+      "agent/openapi.json is out of date, regenerate it with `UPDATE_OPENAPI=1 cargo test -p agent openapi`"
+    );
+  }
+}
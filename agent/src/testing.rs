@@ -0,0 +1,38 @@
+//! 测试共用的应用状态和存储目录
+
+use std::fs;
+
+use crate::{
+  app::{AppState, used_upload_tokens_path},
+  builtin::AccessLog,
+  middlewares::NonceCache,
+};
+
+pub fn test_state(storage_path: &str) -> AppState {
+  AppState {
+    nginx_config_path: "/etc/nginx/sprout".to_string(),
+    storage_path: storage_path.to_string(),
+    upload_token_key: "efkalwfewalkf".to_string(),
+    upload_token_key_expire: 1000,
+    public_ips: vec!["192.168.5.12".parse().unwrap()],
+    agent_token: "agent_token".to_string(),
+    signature_tolerance: 300,
+    nginx_brotli_static: false,
+    serve_mode: Default::default(),
+    access_log: AccessLog::new("logs"),
+    caddy_admin_url: "http://localhost:2019".to_string(),
+    site_routes: Default::default(),
+    nonce_cache: Default::default(),
+    used_upload_tokens: NonceCache::persistent(used_upload_tokens_path(storage_path)),
+    task_results: Default::default(),
+    site_locks: Default::default(),
+  }
+}
+
+/// 每个测试使用独立的存储目录
+pub fn temp_storage(name: &str) -> String {
+  let dir = std::env::temp_dir().join(format!("pupup-{}-{}", name, std::process::id()));
+  let _ = fs::remove_dir_all(&dir);
+  fs::create_dir_all(&dir).unwrap();
+  dir.to_string_lossy().to_string()
+}
@@ -21,12 +21,9 @@ where
     }))
   }
 
+  /// 交给 `ResponseError` 处理，使 HTTP 状态码与错误码一致
   fn error(error: AppError) -> Result<HttpResponse, AppError> {
-    Ok(HttpResponse::Ok().json(Response::<Option<T>> {
-      data: None,
-      code: error.code(),
-      msg: error.to_string(),
-    }))
+    Err(error)
   }
 }
 
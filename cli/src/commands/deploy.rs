@@ -1,21 +1,31 @@
 use std::{
   fs,
-  io::{BufRead, BufReader},
+  io::{BufRead, BufReader, Read},
   panic,
   path::Path,
   process::{Command, Stdio},
+  thread,
+  time::Duration,
 };
 
 use clap::Parser;
+use common::{
+  agent::ArtifactEncoding,
+  digest::{manifest_digest, sha256_file},
+  master::{CreateDeploymentResponse, DeploymentStreamEvent, StageState},
+};
 use console::style;
+use entity::task::TaskStatus;
+use rpc::{AgentApi, AgentUrl, AssignTaskData, MasterApi};
+use tokio::sync::mpsc::{UnboundedReceiver, unbounded_channel};
 use tracing::{debug, trace};
 
 use crate::{
   Cli, MASTER_URL,
   error::Error,
   helper::{
-    Process, audit_directory, get_cli_config, get_project_config, load_keywords_from_embedded,
-    set_project_config, tar_directory,
+    Process, audit_directory, build_manifest, choose_encoding, compress_tar, get_cli_config,
+    get_project_config, load_keywords_from_embedded, set_project_config, tar_directory,
   },
 };
 
@@ -57,10 +67,21 @@ pub fn get_project_type(target: Option<String>) -> ProjectType {
   ProjectType::Unknown
 }
 
+/// 上报给 Master 的构建日志上限，超出后不再上报
+const MAX_BUILD_LOG_SIZE: usize = 1024 * 1024;
+
+/// 构建输出攒够这么多字节就立即上报
+const BUILD_LOG_CHUNK_SIZE: usize = 64 * 1024;
+
+/// 构建输出的上报间隔
+const BUILD_LOG_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 构建项目，返回站点目录，构建输出逐行交给 `log`
 pub fn build_project(
   project_type: ProjectType,
   target: Option<String>,
   skip_build: bool,
+  log: impl Fn(&str) + Sync,
 ) -> String {
   match project_type {
     ProjectType::Vuepress => {
@@ -76,25 +97,17 @@ pub fn build_project(
         .expect("Build failed");
       let stdout = child.stdout.take().expect("Failed to capture stdout");
       let stderr = child.stderr.take().expect("Failed to capture stderr");
-      let stdout_reader = BufReader::new(stdout);
-      for line in stdout_reader.lines() {
-        match line {
-          Ok(line) => println!("{}", line),
-          Err(e) => println!("Error reading stdout: {}", e),
-        }
-      }
-      let stderr_reader = BufReader::new(stderr);
-      for line in stderr_reader.lines() {
-        match line {
-          Ok(line) => println!("{}", line),
-          Err(e) => println!("Error reading stderr: {}", e),
-        }
-      }
+      // 同时读取 stdout 和 stderr，输出按产生的顺序上报
+      thread::scope(|scope| {
+        scope.spawn(|| forward_lines(stderr, "stderr", &log));
+        forward_lines(stdout, "stdout", &log);
+      });
       let status = child.wait().expect("Failed to wait for build process");
       if status.success() {
         println!("Build succeeded!");
       } else {
         println!("Build failed with status: {}", status);
+        log(&format!("Build failed with status: {}", status));
       }
       "./docs/.vuepress/dist".to_string()
     }
@@ -109,7 +122,250 @@ pub fn build_project(
   }
 }
 
-async fn deploy_project(path: String) -> Result<(String, Option<String>), Error> {
+/// 打印构建进程的输出并逐行交给 `log`
+fn forward_lines(reader: impl Read, name: &str, log: &(impl Fn(&str) + Sync)) {
+  for line in BufReader::new(reader).lines() {
+    match line {
+      Ok(line) => {
+        println!("{}", line);
+        log(&line);
+      }
+      Err(e) => println!("Error reading {}: {}", name, e),
+    }
+  }
+}
+
+/// 在构建过程中分段上报构建输出，直到 `lines` 关闭，失败不影响部署
+async fn report_build_log(
+  master_rpc: &dyn MasterApi,
+  token: &str,
+  deployment_id: Option<i32>,
+  mut lines: UnboundedReceiver<String>,
+) {
+  let Some(deployment_id) = deployment_id else {
+    return;
+  };
+  let mut chunk = String::new();
+  let mut reported = 0;
+  let mut ticker = tokio::time::interval_at(
+    tokio::time::Instant::now() + BUILD_LOG_FLUSH_INTERVAL,
+    BUILD_LOG_FLUSH_INTERVAL,
+  );
+  loop {
+    let closed = tokio::select! {
+      line = lines.recv() => match line {
+        Some(line) => {
+          chunk.push_str(&line);
+          chunk.push('\n');
+          if chunk.len() < BUILD_LOG_CHUNK_SIZE {
+            continue;
+          }
+          false
+        }
+        None => true,
+      },
+      _ = ticker.tick() => false,
+    };
+    if !chunk.is_empty() && reported < MAX_BUILD_LOG_SIZE {
+      reported += chunk.len();
+      let mut content = std::mem::take(&mut chunk);
+      if reported >= MAX_BUILD_LOG_SIZE {
+        content.push_str("Build log truncated\n");
+      }
+      if let Err(err) = master_rpc
+        .append_deployment_logs(token, deployment_id, content)
+        .await
+      {
+        tracing::warn!("Failed to report build log: {}", err);
+      }
+    }
+    chunk.clear();
+    if closed {
+      break;
+    }
+  }
+}
+
+/// 为构建好的站点开启上传，构建前已创建部署时沿用该部署
+async fn open_upload(
+  master_rpc: &dyn MasterApi,
+  token: &str,
+  site_id: String,
+  deployment_id: Option<i32>,
+  digest: String,
+) -> Result<CreateDeploymentResponse, Error> {
+  let deploy_data = match deployment_id {
+    Some(deployment_id) => {
+      master_rpc
+        .init_deployment_upload(deployment_id, Some(digest), token)
+        .await?
+    }
+    None => {
+      master_rpc
+        .create_deployment(site_id, Some(digest), token)
+        .await?
+    }
+  };
+  Ok(deploy_data)
+}
+
+/// 上传站点文件，返回部署 id。
+/// 默认按文件清单增量上传，`archive` 为 `true` 时打包成 tar 整体上传。
+async fn upload_site(
+  master_rpc: &dyn MasterApi,
+  agent_rpc: &dyn AgentApi,
+  token: &str,
+  site_id: String,
+  deployment_id: Option<i32>,
+  path: String,
+  archive: bool,
+  process: &Process,
+) -> Result<i32, Error> {
+  let progress = |uploaded, total| process.set_progress(uploaded, total);
+  if archive {
+    // 压缩格式要在创建部署后和 Agent 协商，因此登记的是未压缩的 tar 的摘要
+    let tar_path = tar_directory(path, &site_id, ArtifactEncoding::Identity);
+    let digest = sha256_file(&tar_path)?;
+    let deploy_data = open_upload(master_rpc, token, site_id, deployment_id, digest).await?;
+    let agent_url = AgentUrl::new(deploy_data.deploy_url, deploy_data.deploy_tls);
+    let session = agent_rpc
+      .upload_session(&agent_url, &deploy_data.deploy_token)
+      .await?;
+    let encoding = choose_encoding(&session.encodings);
+    let tar_path = compress_tar(&tar_path, encoding)?;
+    rpc::upload_file_chunked(
+      agent_rpc,
+      &agent_url,
+      deploy_data.deploy_token,
+      tar_path,
+      encoding,
+      progress,
+    )
+    .await?;
+    Ok(deploy_data.deployment_id)
+  } else {
+    let root = Path::new(&path);
+    let files = build_manifest(root)?;
+    let digest = manifest_digest(&files);
+    let deploy_data = open_upload(master_rpc, token, site_id, deployment_id, digest).await?;
+    trace!("{:?}", deploy_data);
+    rpc::upload_directory(
+      agent_rpc,
+      &AgentUrl::new(deploy_data.deploy_url, deploy_data.deploy_tls),
+      deploy_data.deploy_token,
+      root,
+      files,
+      progress,
+    )
+    .await?;
+    Ok(deploy_data.deployment_id)
+  }
+}
+
+fn render_event(process: &Process, event: DeploymentStreamEvent) {
+  match event {
+    DeploymentStreamEvent::Status { status, reason } => {
+      let status = format!("{:?}", status).to_lowercase();
+      process.println(match reason {
+        Some(reason) => format!("  {} {}: {}", style("›").dim(), status, reason),
+        None => format!("  {} {}", style("›").dim(), status),
+      });
+    }
+    DeploymentStreamEvent::Stage { stage, state } => match state {
+      StageState::Started => process.set_stage(&stage.to_string()),
+      StageState::Succeeded => process.println(format!("  {} {}", style("✔").green(), stage)),
+      StageState::Failed => process.println(format!("  {} {}", style("✘").red(), stage)),
+    },
+    // 构建输出已经在本地打印过
+    DeploymentStreamEvent::Log { source, line } if source != "build" => {
+      process.println(format!("    {}", style(line).dim()))
+    }
+    DeploymentStreamEvent::Log { .. } => (),
+  }
+}
+
+/// 订阅部署进度并渲染各阶段，订阅失败时只是不显示服务端进度
+async fn watch_deployment(
+  master_rpc: &dyn MasterApi,
+  token: &str,
+  deployment_id: i32,
+  process: &Process,
+) -> Option<tokio::task::JoinHandle<()>> {
+  let mut stream = match master_rpc.stream_deployment(token, deployment_id).await {
+    Ok(stream) => stream,
+    Err(err) => {
+      debug!("Cannot subscribe deployment progress: {}", err);
+      return None;
+    }
+  };
+  let process = process.clone();
+  Some(tokio::spawn(async move {
+    loop {
+      match stream.next_event().await {
+        Ok(Some(event)) => render_event(&process, event),
+        Ok(None) => break,
+        Err(err) => {
+          debug!("Deployment stream closed: {}", err);
+          break;
+        }
+      }
+    }
+  }))
+}
+
+/// 发布请求返回后等待进度流推送完剩余事件
+async fn finish_watching(watcher: Option<tokio::task::JoinHandle<()>>) {
+  if let Some(watcher) = watcher {
+    if tokio::time::timeout(Duration::from_secs(5), watcher)
+      .await
+      .is_err()
+    {
+      debug!("Deployment stream did not finish in time");
+    }
+  }
+}
+
+/// 轮询发布任务的间隔
+const TASK_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 提交发布任务并等待其完成，失败时打印最后一次的错误
+async fn publish_site(
+  master_rpc: &dyn MasterApi,
+  token: &str,
+  site_id: String,
+  deployment_id: i32,
+  bind_domain: Option<String>,
+  process: &Process,
+) -> Result<AssignTaskData, Error> {
+  let mut task = master_rpc
+    .publish_site(token, site_id, deployment_id, bind_domain, None)
+    .await?;
+  while !task.status.is_finished() {
+    tokio::time::sleep(TASK_POLL_INTERVAL).await;
+    task = master_rpc.get_task(token, task.task_id).await?;
+  }
+  match (task.status, task.result) {
+    (TaskStatus::Succeeded, Some(result)) => {
+      serde_json::from_value(result).map_err(|_| Error::RpcCall)
+    }
+    _ => {
+      if let Some(err) = task.last_error {
+        process.println(format!("  {} {}", style("✘").red(), err));
+      }
+      Err(Error::RpcCall)
+    }
+  }
+}
+
+/// 部署使用的令牌和站点
+struct DeployTarget {
+  token: String,
+  site_id: String,
+  bind_domain: Option<String>,
+}
+
+/// 取得部署使用的令牌和站点，未登录时使用临时令牌并创建临时站点
+async fn resolve_target(master_rpc: &dyn MasterApi) -> Result<DeployTarget, Error> {
   let cli = Cli::parse();
   let bind_domain = if let Some(domain) = cli.bind_domain {
     let mut pc = get_project_config();
@@ -119,105 +375,108 @@ async fn deploy_project(path: String) -> Result<(String, Option<String>), Error>
   } else {
     get_project_config().bind_domain
   };
-  let master_rpc = rpc::MasterRpc::new(MASTER_URL.to_string())?;
-  let agent_rpc = rpc::AgentRpc::new()?;
-
   if let Some(token) = get_cli_config().token {
-    if let Some(site_id) = get_project_config().site_id {
-      let path = tar_directory(path.clone(), &site_id);
-      let deploy_data = master_rpc
-        .create_deployment(site_id.clone(), &token)
-        .await?;
-      agent_rpc
-        .upload_file(
-          &deploy_data.deploy_url,
-          deploy_data.deploy_token,
-          // deploy_data.deployment_id,
-          path,
-        )
-        .await?;
-      let assign_task_data = master_rpc
-        .publish_site(&token, site_id, deploy_data.deployment_id, bind_domain)
-        .await?;
-      Ok((
-        assign_task_data.preview_url,
-        get_project_config().bind_domain,
-      ))
+    let site_id = if let Some(site_id) = get_project_config().site_id {
+      site_id
     } else {
       let create_site_data = master_rpc.create_site(&token).await?;
       let mut project_config = get_project_config();
       project_config.site_id = Some(create_site_data.site_id.clone());
       set_project_config(project_config);
-      let path = tar_directory(path.clone(), &create_site_data.site_id);
-      let deploy_data = master_rpc
-        .create_deployment(create_site_data.site_id.clone(), &token)
-        .await?;
-      agent_rpc
-        .upload_file(
-          &deploy_data.deploy_url,
-          deploy_data.deploy_token,
-          // deploy_data.deployment_id,
-          path,
-        )
-        .await?;
-      let assign_task_data = master_rpc
-        .publish_site(
-          &token,
-          create_site_data.site_id,
-          deploy_data.deployment_id,
-          bind_domain,
-        )
-        .await?;
-      Ok((
-        assign_task_data.preview_url,
-        get_project_config().bind_domain,
-      ))
-    }
+      create_site_data.site_id
+    };
+    Ok(DeployTarget {
+      token,
+      site_id,
+      bind_domain,
+    })
   } else {
     let get_casual_token_data = master_rpc.get_casual_token().await?;
     let create_site_data = master_rpc.create_site(&get_casual_token_data.token).await?;
     let mut project_config = get_project_config();
     project_config.site_id = Some(create_site_data.site_id.clone());
     set_project_config(project_config);
-    let path = tar_directory(path.clone(), &create_site_data.site_id);
     trace!("{:?}", create_site_data);
-    let deploy_data = master_rpc
-      .create_deployment(
-        create_site_data.site_id.clone(),
-        &get_casual_token_data.token,
-      )
-      .await?;
-    let agent_rpc = rpc::AgentRpc::new()?;
-    let _ = agent_rpc
-      .upload_file(
-        &deploy_data.deploy_url,
-        deploy_data.deploy_token,
-        // deploy_data.deployment_id,
-        path,
-      )
-      .await;
-    let assign_task_data = master_rpc
-      .publish_site(
-        &get_casual_token_data.token,
-        create_site_data.site_id,
-        deploy_data.deployment_id,
-        None,
-      )
-      .await?;
-    Ok((assign_task_data.preview_url, None))
+    Ok(DeployTarget {
+      token: get_casual_token_data.token,
+      site_id: create_site_data.site_id,
+      bind_domain: None,
+    })
   }
 }
 
-pub async fn deploy(target: Option<String>, skip_build: bool) -> Result<(), Error> {
+async fn deploy_project(
+  master_rpc: &dyn MasterApi,
+  site: DeployTarget,
+  deployment_id: Option<i32>,
+  path: String,
+  archive: bool,
+  process: &Process,
+) -> Result<(i32, String, Option<String>, Option<String>), Error> {
+  let agent_rpc = rpc::AgentRpc::new()?;
+  let deployment_id = upload_site(
+    master_rpc,
+    &agent_rpc,
+    &site.token,
+    site.site_id.clone(),
+    deployment_id,
+    path,
+    archive,
+    process,
+  )
+  .await?;
+  let watcher = watch_deployment(master_rpc, &site.token, deployment_id, process).await;
+  let assign_task_data = publish_site(
+    master_rpc,
+    &site.token,
+    site.site_id,
+    deployment_id,
+    site.bind_domain.clone(),
+    process,
+  )
+  .await;
+  finish_watching(watcher).await;
+  let assign_task_data = assign_task_data?;
+  Ok((
+    deployment_id,
+    assign_task_data.preview_url,
+    site.bind_domain,
+    assign_task_data.expired_at,
+  ))
+}
+
+pub async fn deploy(target: Option<String>, skip_build: bool, archive: bool) -> Result<(), Error> {
   let pb1 = Process::new(&format!(
     "{} Get project type...",
     style("[1/4]").bold().dim()
   ));
   let project_type = get_project_type(target.clone());
+  let master_rpc = rpc::MasterRpc::new(MASTER_URL.to_string())?;
+  let site = resolve_target(&master_rpc).await?;
   pb1.finish(None);
 
   let pb2 = Process::new(&format!("{} Build project...", style("[2/4]").bold().dim()));
-  let path = build_project(project_type, target, skip_build);
+  // 需要构建时先创建部署，构建输出边构建边上报
+  let deployment_id = if matches!(project_type, ProjectType::Vuepress) && !skip_build {
+    Some(
+      master_rpc
+        .prepare_deployment(site.site_id.clone(), &site.token)
+        .await?,
+    )
+  } else {
+    None
+  };
+  let (sender, receiver) = unbounded_channel();
+  let build = tokio::task::spawn_blocking(move || {
+    build_project(project_type, target, skip_build, |line| {
+      let _ = sender.send(line.to_string());
+    })
+  });
+  let (path, ()) = tokio::join!(
+    build,
+    report_build_log(&master_rpc, &site.token, deployment_id, receiver)
+  );
+  let path = path.unwrap_or_else(|err| panic::resume_unwind(err.into_panic()));
   pb2.finish(None);
 
   let pb3 = Process::new(&format!(
@@ -228,7 +487,8 @@ pub async fn deploy(target: Option<String>, skip_build: bool) -> Result<(), Erro
   pb3.finish(None);
 
   let pb4 = Process::new(&format!("{} Deploy site...", style("[4/4]").bold().dim()));
-  let (preview_url, bind_url) = deploy_project(path).await?;
+  let (deployment_id, preview_url, bind_url, expired_at) =
+    deploy_project(&master_rpc, site, deployment_id, path, archive, &pb4).await?;
   pb4.finish(None);
 
   let finish_msg = if let Some(bind_url) = bind_url {
@@ -241,5 +501,71 @@ pub async fn deploy(target: Option<String>, skip_build: bool) -> Result<(), Erro
     format!("Preview url: {}", style(preview_url).cyan())
   };
   println!("{finish_msg}",);
+  println!(
+    "Run {} to view the deployment logs",
+    style(format!("pupup logs {}", deployment_id)).bold()
+  );
+  if let Some(expired_at) = expired_at {
+    println!(
+      "This site will expire at {}, log in to keep it",
+      style(expired_at).yellow()
+    );
+  }
   Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+  use common::master::TaskResponse;
+  use entity::task::TaskType;
+  use rpc::MockMasterApi;
+  use serde_json::json;
+
+  use super::*;
+
+  fn task(status: TaskStatus, result: Option<serde_json::Value>) -> TaskResponse {
+    TaskResponse {
+      task_id: 1,
+      r#type: TaskType::Publish,
+      status,
+      attempts: 0,
+      last_error: None,
+      result,
+    }
+  }
+
+  #[tokio::test]
+  async fn test_publish_site() {
+    let mut master_rpc = MockMasterApi::new();
+    master_rpc
+      .expect_publish_site()
+      .times(1)
+      .returning(|_, _, _, _, _| Ok(task(TaskStatus::Pending, None)));
+    master_rpc.expect_get_task().times(1).returning(|_, _| {
+      Ok(task(
+        TaskStatus::Succeeded,
+        Some(json!({ "preview_url": "https://preview.example.com" })),
+      ))
+    });
+    let process = Process::new("publish");
+    let data = publish_site(&master_rpc, "token", "site".to_string(), 1, None, &process)
+      .await
+      .unwrap();
+    assert_eq!(data.preview_url, "https://preview.example.com");
+  }
+
+  #[tokio::test]
+  async fn test_report_build_log() {
+    let mut master_rpc = MockMasterApi::new();
+    master_rpc
+      .expect_append_deployment_logs()
+      .withf(|_, deployment_id, content| *deployment_id == 1 && content == "a\nb\n")
+      .times(1)
+      .returning(|_, _, _| Ok(()));
+    let (sender, receiver) = unbounded_channel();
+    sender.send("a".to_string()).unwrap();
+    sender.send("b".to_string()).unwrap();
+    drop(sender);
+    report_build_log(&master_rpc, "token", Some(1), receiver).await;
+  }
+}
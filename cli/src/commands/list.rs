@@ -1,15 +1,31 @@
+use rpc::MasterApi;
+
 use crate::{
   MASTER_URL,
   error::Error,
   helper::{draw_table, get_cli_config},
 };
 
-pub async fn list() -> Result<(), Error> {
+/// 每次请求的站点数
+const LIST_PAGE_SIZE: u64 = 50;
+
+/// 列出站点，指定 `page` 时只显示这一页，否则依次获取所有页
+pub async fn list(page: Option<u64>) -> Result<(), Error> {
   let token = get_cli_config()
     .token
     .ok_or(Error::AuthenticationRequired)?;
   let rpc = rpc::MasterRpc::new(MASTER_URL.to_string())?;
-  let sites = rpc.get_sites(&token).await?.sites;
+  let mut sites = vec![];
+  let mut current = page.unwrap_or(1).max(1);
+  let total = loop {
+    let result = rpc.get_sites(&token, current, LIST_PAGE_SIZE).await?;
+    let has_more = result.has_more();
+    sites.extend(result.items);
+    if page.is_some() || !has_more {
+      break result.total;
+    }
+    current += 1;
+  };
   let mut rows: Vec<Vec<String>> = sites
     .iter()
     .map(|site| {
@@ -30,6 +46,14 @@ pub async fn list() -> Result<(), Error> {
     ],
   );
   draw_table(rows);
+  if page.is_some() {
+    println!(
+      "Page {} of {}, {} sites in total",
+      current,
+      total.div_ceil(LIST_PAGE_SIZE).max(1),
+      total
+    );
+  }
   Ok(())
 }
 
@@ -1,4 +1,5 @@
 use console::Color;
+use rpc::MasterApi;
 use tracing::debug;
 
 use crate::{
@@ -0,0 +1,24 @@
+use console::style;
+use rpc::MasterApi;
+
+use crate::{MASTER_URL, error::Error, helper::get_cli_config};
+
+/// 打印部署的构建与发布日志
+pub async fn logs(deployment_id: i32) -> Result<(), Error> {
+  let token = get_cli_config()
+    .token
+    .ok_or(Error::AuthenticationRequired)?;
+  let rpc = rpc::MasterRpc::new(MASTER_URL.to_string())?;
+  let res = rpc.get_deployment_logs(&token, deployment_id).await?;
+  println!(
+    "Deployment {} {}",
+    style(res.deployment_id).bold(),
+    style(format!("{:?}", res.status).to_lowercase()).cyan()
+  );
+  if res.logs.is_empty() {
+    println!("No logs yet");
+  } else {
+    print!("{}", res.logs);
+  }
+  Ok(())
+}
@@ -1,4 +1,5 @@
 pub mod deploy;
 pub mod list;
 pub mod login;
+pub mod logs;
 pub mod signup;
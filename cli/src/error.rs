@@ -1,35 +1,145 @@
+use common::error::ErrorCode;
+
 #[derive(Debug)]
 pub enum Error {
+  /// 服务端返回了无法识别的响应，或任务执行失败
   RpcCall,
+  /// 未登录或 token 已失效，需要重新登录
   AuthenticationRequired,
   CannotConnect,
+  /// 服务端返回的错误，`code` 为 `None` 时是当前版本未知的错误码
+  Api {
+    code: Option<ErrorCode>,
+    msg: String,
+  },
+  /// 读取本地站点文件失败
+  Io(std::io::Error),
+}
+
+impl Error {
+  /// 根据错误码给出下一步该怎么做的提示
+  pub fn guidance(&self) -> String {
+    let (code, msg) = match self {
+      Error::RpcCall => return "Unexpected response from server".to_string(),
+      Error::AuthenticationRequired => {
+        return "Please run `pupup login` to log in first".to_string();
+      }
+      Error::CannotConnect => {
+        return "Cannot connect to server, please check your network and try again".to_string();
+      }
+      Error::Api { code: None, msg } => return msg.clone(),
+      Error::Io(err) => return format!("Failed to read site files: {}", err),
+      Error::Api {
+        code: Some(code),
+        msg,
+      } => (*code, msg),
+    };
+    match code {
+      ErrorCode::PasswordError => "Incorrect email or password, please try again".to_string(),
+      ErrorCode::UserNotFound => {
+        "No account found for this email, run `pupup signup` to create one".to_string()
+      }
+      ErrorCode::UserExists => {
+        "This email is already registered, run `pupup login` instead".to_string()
+      }
+      ErrorCode::UserSuspended => {
+        "Your account has been suspended, please contact the administrator".to_string()
+      }
+      ErrorCode::Forbidden => "You do not have permission to do this".to_string(),
+      ErrorCode::InvalidParams => format!("Invalid input: {}", msg),
+      ErrorCode::TooManyRequests => {
+        "Too many requests, please wait a moment and try again".to_string()
+      }
+      ErrorCode::SiteNotFound => "Site not found, run `pupup list` to see your sites".to_string(),
+      ErrorCode::DeploymentNotFound => "Deployment not found, check the deployment ID".to_string(),
+      ErrorCode::AgentNotFound | ErrorCode::Upstream => {
+        "No server is available to host the site right now, please try again later".to_string()
+      }
+      ErrorCode::InvalidStatusTransition => {
+        "The deployment cannot be published in its current state, please run `pupup deploy` again"
+          .to_string()
+      }
+      ErrorCode::InvalidUploadToken | ErrorCode::UploadTokenUsed => {
+        "The upload session has expired, please run `pupup deploy` again".to_string()
+      }
+      ErrorCode::DigestMismatch
+      | ErrorCode::UploadOffsetMismatch
+      | ErrorCode::ChunkTooLarge
+      | ErrorCode::InvalidManifest
+      | ErrorCode::BlobMissing
+      | ErrorCode::ArtifactMissing => {
+        format!("Upload failed ({}), please run `pupup deploy` again", msg)
+      }
+      ErrorCode::UnsupportedEncoding => {
+        "The server does not support this upload format, try `pupup deploy --archive`".to_string()
+      }
+      ErrorCode::NotImplemented => "This feature is not supported by the server yet".to_string(),
+      ErrorCode::InvalidToken | ErrorCode::TokenExpired | ErrorCode::AuthorizationRequired => {
+        "Please run `pupup login` to log in again".to_string()
+      }
+      ErrorCode::Internal
+      | ErrorCode::Database
+      | ErrorCode::Config
+      | ErrorCode::ExtractArchive
+      | ErrorCode::PublishFailed
+      | ErrorCode::ExportFailed
+      | ErrorCode::AgentExists
+      | ErrorCode::WebhookNotFound
+      | ErrorCode::TaskNotFound
+      | ErrorCode::InvalidSignature => format!("{}, please try again later", msg),
+    }
+  }
+}
+
+impl From<std::io::Error> for Error {
+  fn from(err: std::io::Error) -> Self {
+    Error::Io(err)
+  }
 }
 
 impl From<rpc::error::Error> for Error {
   fn from(err: rpc::error::Error) -> Self {
     tracing::error!("{:#?}", err);
+    let code = err.error_code();
     match err {
-      rpc::error::Error::Internal { .. } => Error::RpcCall,
-      rpc::error::Error::RpcCall { source } => {
-        tracing::error!("{:#?}", source);
-        Error::RpcCall
-      }
-      rpc::error::Error::CloudflareFramework { .. } => Error::RpcCall,
-      rpc::error::Error::CloudflareResponse { .. } => Error::RpcCall,
-      rpc::error::Error::ConnectAgent { .. } => Error::RpcCall,
-      rpc::error::Error::Api(status_code, code, msg) => {
-        if status_code == 401 {
-          return Error::AuthenticationRequired;
-        }
-        if status_code == 403 {
-          return Error::AuthenticationRequired;
-        }
-        if status_code == 500 {
-          return Error::CannotConnect;
-        }
-        return Error::RpcCall;
+      rpc::error::Error::Api(status_code, _, msg) => match code {
+        Some(
+          ErrorCode::InvalidToken | ErrorCode::TokenExpired | ErrorCode::AuthorizationRequired,
+        ) => Error::AuthenticationRequired,
+        None if status_code == 401 => Error::AuthenticationRequired,
+        code => Error::Api { code, msg },
+      },
+      rpc::error::Error::ConnectAgent(_) | rpc::error::Error::ConnectMaster => Error::CannotConnect,
+      rpc::error::Error::RpcCall { source } if source.is_connect() || source.is_timeout() => {
+        Error::CannotConnect
       }
       _ => Error::RpcCall,
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_from_rpc_error() {
+    let api = |status, code: ErrorCode| rpc::error::Error::Api(status, code.code(), String::new());
+    assert!(matches!(
+      Error::from(api(401, ErrorCode::TokenExpired)),
+      Error::AuthenticationRequired
+    ));
+    // 密码错误同样是 401，但不应该再次进入登录流程
+    assert!(matches!(
+      Error::from(api(401, ErrorCode::PasswordError)),
+      Error::Api {
+        code: Some(ErrorCode::PasswordError),
+        ..
+      }
+    ));
+    assert!(matches!(
+      Error::from(rpc::error::Error::Api(500, 1234, "boom".to_string())),
+      Error::Api { code: None, .. }
+    ));
+  }
+}
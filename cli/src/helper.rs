@@ -13,9 +13,14 @@ use comfy_table::{
   modifiers::{UTF8_ROUND_CORNERS, UTF8_SOLID_INNER_BORDERS},
   presets::UTF8_FULL,
 };
+use common::{
+  agent::{ArtifactEncoding, ManifestEntry},
+  digest::sha256_file,
+};
 use console::{Color, Style, Term};
 use dialoguer::{Input, Password, theme::ColorfulTheme};
-use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use flate2::{Compression, write::GzEncoder};
+use indicatif::{HumanBytes, MultiProgress, ProgressBar, ProgressStyle};
 use serde::{Deserialize, Serialize};
 use tar::Builder;
 use tracing::{debug, error, trace};
@@ -186,15 +191,80 @@ pub fn load_keywords_from_embedded(file_paths: &[&str]) -> Vec<String> {
   keywords
 }
 
-pub fn tar_directory(source: String, filename: &str) -> PathBuf {
-  let temp = temp_dir().join(format!("{filename}.tar"));
+pub fn tar_directory(source: String, filename: &str, encoding: ArtifactEncoding) -> PathBuf {
+  let temp = temp_dir().join(format!("{filename}.{}", encoding.extension()));
   trace!(">>> tar dist to {:?}", temp.clone());
-  let mut builder = Builder::new(File::create(temp.clone()).unwrap());
-  builder.append_dir_all(filename, source).unwrap();
-  builder.finish().unwrap();
+  let file = File::create(temp.clone()).unwrap();
+  match encoding {
+    ArtifactEncoding::Zstd => {
+      let mut builder = Builder::new(zstd::Encoder::new(file, 19).unwrap());
+      builder.append_dir_all(filename, source).unwrap();
+      builder.into_inner().unwrap().finish().unwrap();
+    }
+    ArtifactEncoding::Gzip => {
+      let mut builder = Builder::new(GzEncoder::new(file, Compression::best()));
+      builder.append_dir_all(filename, source).unwrap();
+      builder.into_inner().unwrap().finish().unwrap();
+    }
+    ArtifactEncoding::Identity => {
+      let mut builder = Builder::new(file);
+      builder.append_dir_all(filename, source).unwrap();
+      builder.finish().unwrap();
+    }
+  }
   temp
 }
 
+/// 按协商的压缩格式压缩 tar 包，`Identity` 时直接使用原文件
+pub fn compress_tar(tar: &Path, encoding: ArtifactEncoding) -> io::Result<PathBuf> {
+  let target = tar.with_extension(encoding.extension());
+  match encoding {
+    ArtifactEncoding::Zstd => {
+      let mut encoder = zstd::Encoder::new(File::create(&target)?, 19)?;
+      io::copy(&mut File::open(tar)?, &mut encoder)?;
+      encoder.finish()?;
+    }
+    ArtifactEncoding::Gzip => {
+      let mut encoder = GzEncoder::new(File::create(&target)?, Compression::best());
+      io::copy(&mut File::open(tar)?, &mut encoder)?;
+      encoder.finish()?;
+    }
+    ArtifactEncoding::Identity => return Ok(tar.to_path_buf()),
+  }
+  Ok(target)
+}
+
+/// 从 Agent 支持的压缩格式中选择 CLI 优先使用的一种
+pub fn choose_encoding(supported: &[ArtifactEncoding]) -> ArtifactEncoding {
+  [ArtifactEncoding::Zstd, ArtifactEncoding::Gzip]
+    .into_iter()
+    .find(|e| supported.contains(e))
+    .unwrap_or_default()
+}
+
+/// 生成目录下所有文件的清单，按路径排序
+pub fn build_manifest(root: &Path) -> io::Result<Vec<ManifestEntry>> {
+  let mut files = Vec::new();
+  visit_dirs(root, &mut |path| {
+    let relative = path
+      .strip_prefix(root)
+      .map_err(io::Error::other)?
+      .components()
+      .map(|c| c.as_os_str().to_string_lossy())
+      .collect::<Vec<_>>()
+      .join("/");
+    files.push(ManifestEntry {
+      path: relative,
+      sha256: sha256_file(path)?,
+      size: fs::metadata(path)?.len(),
+    });
+    Ok(())
+  })?;
+  files.sort_by(|a, b| a.path.cmp(&b.path));
+  trace!(">>> manifest of {:?}: {} files", root, files.len());
+  Ok(files)
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CliConfig {
   pub token: Option<String>,
@@ -347,6 +417,7 @@ pub fn print_error(text: &str) {
   console_print(text, Some(Color::Magenta), true, true);
 }
 
+#[derive(Clone)]
 pub struct Process {
   pb: ProgressBar,
   msg: String,
@@ -368,6 +439,26 @@ impl Process {
     }
   }
 
+  /// 在消息后显示已完成的字节数
+  pub fn set_progress(&self, done: u64, total: u64) {
+    self.pb.set_message(format!(
+      "{} {}/{}",
+      self.msg,
+      HumanBytes(done),
+      HumanBytes(total)
+    ));
+  }
+
+  /// 在消息后显示正在执行的阶段
+  pub fn set_stage(&self, stage: &str) {
+    self.pb.set_message(format!("{} {}...", self.msg, stage));
+  }
+
+  /// 在进度条上方打印一行
+  pub fn println(&self, line: String) {
+    self.pb.println(line);
+  }
+
   pub fn finish(&self, msg: Option<String>) {
     if let Some(msg) = msg {
       self.pb.finish_with_message(msg);
@@ -386,7 +477,7 @@ mod test {
 
   #[test]
   pub fn test_tar_directory() {
-    tar_directory("./".to_owned(), "cli.tar");
+    tar_directory("./".to_owned(), "cli", ArtifactEncoding::Identity);
   }
 
   // #[test]
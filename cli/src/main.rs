@@ -4,7 +4,7 @@ mod error;
 mod helper;
 
 use clap::{Parser, Subcommand};
-use commands::{deploy::deploy, list::list, login::login, signup::signup};
+use commands::{deploy::deploy, list::list, login::login, logs::logs, signup::signup};
 use error::Error;
 use helper::print_error;
 use tracing_subscriber::{EnvFilter, fmt, layer::SubscriberExt, util::SubscriberInitExt};
@@ -31,9 +31,23 @@ enum Commands {
     target: Option<String>,
     #[arg(long, default_value_t = false, help = "Skip the build step")]
     skip_build: bool,
+    #[arg(
+      long,
+      default_value_t = false,
+      help = "Upload the whole site as one archive instead of only changed files"
+    )]
+    archive: bool,
   },
   /// list all sites
-  List,
+  List {
+    #[arg(long, help = "Only show this page of sites")]
+    page: Option<u64>,
+  },
+  /// show build and publish logs of a deployment
+  Logs {
+    #[arg(help = "Deployment ID")]
+    deployment: i32,
+  },
 }
 
 static MASTER_URL: &str = "http://127.0.0.1:3000";
@@ -44,29 +58,26 @@ async fn main() -> Result<(), Error> {
     .with(tracing_subscriber::fmt::layer().with_timer(fmt::time::LocalTime::rfc_3339()))
     .with(EnvFilter::from_default_env())
     .init();
-  match Cli::parse().command {
-    Commands::Signup => signup().await?,
-    Commands::Login => login().await?,
-    Commands::Deploy { target, skip_build } => {
-      match deploy(target, skip_build).await {
-        Ok(_) => (),
-        Err(err) => match err {
-          Error::AuthenticationRequired => login().await?,
-          Error::RpcCall => print_error("Deploy error"),
-          Error::CannotConnect => print_error("Cannot connect to server"),
-        },
-      };
-    }
-    Commands::List => {
-      match list().await {
-        Ok(_) => (),
-        Err(err) => match err {
-          Error::AuthenticationRequired => login().await?,
-          Error::RpcCall => (),
-          Error::CannotConnect => print_error("Cannot connect to server"),
-        },
-      };
-    }
+  let (result, context) = match Cli::parse().command {
+    Commands::Signup => (signup().await, "Sign up failed"),
+    Commands::Login => (login().await, "Login failed"),
+    Commands::Deploy {
+      target,
+      skip_build,
+      archive,
+    } => (deploy(target, skip_build, archive).await, "Deploy failed"),
+    Commands::Logs { deployment } => (logs(deployment).await, "Cannot get deployment logs"),
+    Commands::List { page } => (list(page).await, "Cannot list sites"),
   };
+  match result {
+    // 登录过期时直接进入登录流程
+    Err(Error::AuthenticationRequired) => {
+      if let Err(err) = login().await {
+        print_error(&format!("Login failed: {}", err.guidance()));
+      }
+    }
+    Err(err) => print_error(&format!("{}: {}", context, err.guidance())),
+    Ok(_) => (),
+  }
   Ok(())
 }
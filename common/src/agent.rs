@@ -1,34 +1,246 @@
+use std::net::IpAddr;
+
 use serde::{Deserialize, Serialize};
 
+/// Agent 默认监听的端口
+pub const DEFAULT_AGENT_PORT: u16 = 5001;
+
+/// Master 和 CLI 校验 Agent 证书的方式，都为空时使用系统内置的根证书。
+/// 同时设置时以证书指纹为准
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AgentTls {
+  /// PEM 格式的 CA 证书，只信任由它签发的证书
+  #[serde(default)]
+  pub ca_cert: Option<String>,
+  /// 证书（DER）的 SHA-256 指纹，十六进制，可以包含冒号
+  #[serde(default)]
+  pub cert_fingerprint: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct InitUploadRequest {
   pub site_id: String,
+  pub deployment_id: i32,
+  /// 上传文件预期的 SHA-256，为空时不校验。压缩包为解压后的 tar 的摘要，与压缩格式无关
+  pub sha256: Option<String>,
+  /// 区分同一个部署的多次上传，如迁移任务的每次执行。为空时每个部署只能上传一次
+  #[serde(default)]
+  pub upload_id: Option<String>,
+}
+
+/// 上传 token 携带的信息，Agent 据此决定文件的存放位置
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UploadClaims {
+  pub site_id: String,
+  pub deployment_id: i32,
+  pub sha256: Option<String>,
+  #[serde(default)]
+  pub upload_id: Option<String>,
+}
+
+impl UploadClaims {
+  /// 记录 token 已使用的键
+  pub fn used_key(&self) -> String {
+    match &self.upload_id {
+      Some(upload_id) => format!("{}:{}", self.deployment_id, upload_id),
+      None => self.deployment_id.to_string(),
+    }
+  }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct TaskPublishRequest {
   pub site_id: String,
-  pub deployment_id: u32,
+  pub deployment_id: i32,
   pub bandwidth: String,
   pub bind_domain: Option<String>,
   pub preview_domain: String,
+  /// 重试同一个任务时 Agent 直接返回上一次成功的结果
+  #[serde(default)]
+  pub idempotency_key: Option<String>,
+}
+
+/// 发布过程中的阶段，DNS 由 Master 创建，其余在 Agent 上执行
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum DeploymentStage {
+  Dns,
+  Extract,
+  Precompress,
+  NginxTest,
+  NginxReload,
+  /// Nginx 以外的后端检查配置
+  Validate,
+  /// Nginx 以外的后端启用站点
+  Activate,
+}
+
+impl std::fmt::Display for DeploymentStage {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let name = match self {
+      DeploymentStage::Dns => "Create DNS record",
+      DeploymentStage::Extract => "Extract files",
+      DeploymentStage::Precompress => "Precompress assets",
+      DeploymentStage::NginxTest => "Test nginx config",
+      DeploymentStage::NginxReload => "Reload nginx",
+      DeploymentStage::Validate => "Validate config",
+      DeploymentStage::Activate => "Activate site",
+    };
+    f.write_str(name)
+  }
+}
+
+/// Agent 执行的一个阶段及其结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TaskStep {
+  pub stage: DeploymentStage,
+  pub success: bool,
+}
+
+/// 发布任务的执行结果，包含解压与 Nginx 命令的输出
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TaskPublishResponse {
+  pub logs: String,
+  #[serde(default)]
+  pub steps: Vec<TaskStep>,
+  /// 发布后的 Nginx 配置内容
+  #[serde(default)]
+  pub config: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct TaskRevokeRequest {
   pub site_id: String,
 }
 
+/// Agent 上一个站点的实际状态
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct InventorySite {
+  pub site_id: String,
+  /// 当前发布的部署 ID，旧版本发布的站点没有记录
+  pub release_id: Option<i32>,
+  /// Nginx 配置文件内容的 SHA-256，配置不存在时为空
+  pub config_hash: Option<String>,
+  /// 站点目录是否存在
+  pub has_files: bool,
+}
+
+/// `GET /api/inventory` 返回 Agent 上所有由 Pupup 管理的站点
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct InventoryResponse {
+  pub sites: Vec<InventorySite>,
+}
+
+/// 将站点目录打包导出，用于迁移到其他 Agent
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TaskExportRequest {
+  pub site_id: String,
+}
+
 #[derive(Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct HeartbeatResponse {
   pub cpu_cores: usize,
   pub cpu_usage: f32,
   pub total_memory: u64,
   pub free_memory: u64,
   pub memory_usage: f64,
+  /// Agent 的公网地址，可以同时包含 IPv4 和 IPv6，旧版本的 Agent 不上报
+  #[serde(default)]
+  #[cfg_attr(feature = "openapi", schema(value_type = Vec<String>))]
+  pub public_ips: Vec<IpAddr>,
 }
 
 #[derive(Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct InitUploadResponse {
   pub upload_token: String,
 }
+
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UploadSessionRequest {
+  pub upload_token: String,
+}
+
+/// 分片上传的进度，`offset` 为 Agent 已收到的字节数
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UploadSessionResponse {
+  pub offset: u64,
+  pub chunk_size: u64,
+  /// Agent 支持的压缩格式，按优先级排序
+  #[serde(default)]
+  pub encodings: Vec<ArtifactEncoding>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CompleteUploadRequest {
+  pub upload_token: String,
+  pub sha256: String,
+  #[serde(default)]
+  pub encoding: ArtifactEncoding,
+}
+
+/// 站点压缩包的压缩格式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum ArtifactEncoding {
+  Zstd,
+  Gzip,
+  #[default]
+  Identity,
+}
+
+impl ArtifactEncoding {
+  /// 压缩包的扩展名
+  pub fn extension(&self) -> &'static str {
+    match self {
+      ArtifactEncoding::Zstd => "tar.zst",
+      ArtifactEncoding::Gzip => "tar.gz",
+      ArtifactEncoding::Identity => "tar",
+    }
+  }
+}
+
+/// 站点文件清单中的一项，`path` 为相对站点根目录、以 `/` 分隔的路径
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ManifestEntry {
+  pub path: String,
+  pub sha256: String,
+  pub size: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UploadManifestRequest {
+  pub upload_token: String,
+  pub files: Vec<ManifestEntry>,
+}
+
+/// Agent 的内容寻址存储中缺少的文件摘要
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UploadManifestResponse {
+  pub missing: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CompleteManifestRequest {
+  pub upload_token: String,
+}
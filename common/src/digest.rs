@@ -0,0 +1,27 @@
+use std::{fs::File, io, path::Path};
+
+use sha2::{Digest, Sha256};
+
+use crate::agent::ManifestEntry;
+
+/// 计算字节内容的 SHA-256，返回十六进制字符串
+pub fn sha256_bytes(bytes: &[u8]) -> String {
+  hex::encode(Sha256::digest(bytes))
+}
+
+/// 计算文件清单的摘要，清单需按 `path` 排序
+pub fn manifest_digest(files: &[ManifestEntry]) -> String {
+  sha256_bytes(&serde_json::to_vec(files).unwrap_or_default())
+}
+
+/// 计算文件的 SHA-256，返回十六进制字符串
+pub fn sha256_file(path: impl AsRef<Path>) -> io::Result<String> {
+  sha256_reader(File::open(path)?)
+}
+
+/// 计算读取到的全部内容的 SHA-256，可用于边解压边计算
+pub fn sha256_reader(mut reader: impl io::Read) -> io::Result<String> {
+  let mut hasher = Sha256::new();
+  io::copy(&mut reader, &mut hasher)?;
+  Ok(hex::encode(hasher.finalize()))
+}
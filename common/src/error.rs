@@ -0,0 +1,112 @@
+//! Master、Agent 和 CLI 共用的错误码，数值一经发布不再修改
+
+/// 定义错误码及其默认提示，同时生成数值与枚举之间的转换
+macro_rules! error_codes {
+  ($($(#[$attr:meta])* $name:ident = $code:literal => $message:literal,)*) => {
+    /// 接口返回的错误码，`Response::code` 为 0 表示成功
+    ///
+    /// 1xxx 为服务端错误，2xxx 为 Master 的业务错误，3xxx 为 Agent 的业务错误
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub enum ErrorCode {
+      $($(#[$attr])* $name,)*
+    }
+
+    impl ErrorCode {
+      /// 所有错误码，按数值排列
+      pub const ALL: &[ErrorCode] = &[$(ErrorCode::$name,)*];
+
+      pub fn code(self) -> i32 {
+        match self {
+          $(ErrorCode::$name => $code,)*
+        }
+      }
+
+      /// 可以直接展示给用户的提示
+      pub fn message(self) -> &'static str {
+        match self {
+          $(ErrorCode::$name => $message,)*
+        }
+      }
+
+      /// 未知的错误码（如更新版本的服务端新增的）返回 `None`
+      pub fn from_code(code: i32) -> Option<Self> {
+        match code {
+          $($code => Some(ErrorCode::$name),)*
+          _ => None,
+        }
+      }
+    }
+  };
+}
+
+error_codes! {
+  Internal = 1000 => "Internal server error",
+  Database = 1001 => "Database error",
+  /// 环境变量缺失或格式错误
+  Config = 1002 => "Invalid server configuration",
+  /// 调用 Agent 或 DNS 服务商失败
+  Upstream = 1003 => "Upstream service unavailable",
+  /// Agent 解压上传的产物失败
+  ExtractArchive = 1004 => "Failed to extract the uploaded archive",
+  /// Agent 写入或重载 Web 服务器配置失败
+  PublishFailed = 1005 => "Failed to publish the site",
+  ExportFailed = 1006 => "Failed to export the site",
+  /// token 签名无效或格式错误
+  InvalidToken = 2000 => "Invalid token",
+  PasswordError = 2001 => "Incorrect email or password",
+  /// 缺少 `Authorization` 请求头
+  AuthorizationRequired = 2002 => "Authorization required",
+  Forbidden = 2003 => "Permission denied",
+  InvalidParams = 2004 => "Invalid parameters",
+  UserNotFound = 2005 => "User not found",
+  UserExists = 2006 => "User already exists",
+  UserSuspended = 2007 => "User is suspended",
+  InvalidStatusTransition = 2008 => "Invalid deployment status transition",
+  /// 请求过于频繁或账号被锁定，`Retry-After` 为需要等待的秒数
+  TooManyRequests = 2009 => "Too many requests",
+  TokenExpired = 2010 => "Token has expired",
+  AgentExists = 2011 => "Agent already exists",
+  AgentNotFound = 2012 => "Agent not found",
+  SiteNotFound = 2013 => "Site not found",
+  DeploymentNotFound = 2014 => "Deployment not found",
+  WebhookNotFound = 2015 => "Webhook not found",
+  TaskNotFound = 2016 => "Task not found",
+  InvalidSignature = 3000 => "Invalid request signature",
+  InvalidUploadToken = 3001 => "Invalid upload token",
+  UploadTokenUsed = 3002 => "Upload token has been used",
+  DigestMismatch = 3003 => "Uploaded file digest mismatch",
+  UploadOffsetMismatch = 3004 => "Upload offset mismatch",
+  ChunkTooLarge = 3005 => "Upload chunk too large",
+  InvalidManifest = 3006 => "Invalid manifest",
+  BlobMissing = 3007 => "Uploaded file is missing",
+  UnsupportedEncoding = 3008 => "Unsupported artifact encoding",
+  /// 发布时找不到该部署上传的文件，需要重新上传
+  ArtifactMissing = 3009 => "Artifact of the deployment is missing",
+  NotImplemented = 9999 => "Not implemented",
+}
+
+impl From<ErrorCode> for i32 {
+  fn from(code: ErrorCode) -> Self {
+    code.code()
+  }
+}
+
+impl std::fmt::Display for ErrorCode {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str(self.message())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_error_codes() {
+    for code in ErrorCode::ALL {
+      assert_eq!(ErrorCode::from_code(code.code()), Some(*code));
+    }
+    assert!(ErrorCode::ALL.windows(2).all(|w| w[0].code() < w[1].code()));
+    assert_eq!(ErrorCode::from_code(0), None);
+  }
+}
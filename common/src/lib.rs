@@ -1,11 +1,60 @@
 use serde::{Deserialize, Serialize};
 
 pub mod agent;
+pub mod digest;
+pub mod error;
 pub mod master;
+pub mod signature;
 
 #[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct Response<T> {
   pub code: i32,
   pub msg: String,
   pub data: T,
 }
+
+/// 列表接口的分页返回格式，`page` 从 1 开始
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Paginated<T> {
+  pub items: Vec<T>,
+  pub total: u64,
+  pub page: u64,
+  pub page_size: u64,
+}
+
+impl<T> Paginated<T> {
+  /// 当前页之后是否还有数据
+  pub fn has_more(&self) -> bool {
+    self.page.saturating_mul(self.page_size) < self.total
+  }
+}
+
+/// 列表的排序方向，默认从新到旧
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+  Asc,
+  #[default]
+  Desc,
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_has_more() {
+    let page = |page, total| Paginated::<()> {
+      items: vec![],
+      total,
+      page,
+      page_size: 20,
+    };
+    assert!(page(1, 21).has_more());
+    assert!(!page(1, 20).has_more());
+    assert!(!page(2, 21).has_more());
+  }
+}
@@ -1,7 +1,10 @@
 use serde::{Deserialize, Serialize};
 use validator::Validate;
 
+use crate::agent::AgentTls;
+
 #[derive(Serialize, Deserialize, Validate, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct UserRegisterRequest {
   #[validate(length(min = 2, max = 12))]
   pub nickname: String,
@@ -12,34 +15,166 @@ pub struct UserRegisterRequest {
 }
 
 #[derive(Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct UserLoginRequest {
   pub email: String,
   pub password: String,
 }
 
+/// 提交任务时携带的幂等键请求头，相同的键只会创建一个任务
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
 #[derive(Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct AssignTaskRequest {
   pub r#type: String,
   pub site_id: String,
-  pub deployment_id: u32,
+  pub deployment_id: i32,
   pub bind_domain: Option<String>,
 }
 
+/// 任务的执行状态，提交任务和 `GET /api/tasks/{id}` 都返回该结构
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TaskResponse {
+  pub task_id: i32,
+  pub r#type: entity::task::TaskType,
+  pub status: entity::task::TaskStatus,
+  pub attempts: i32,
+  pub last_error: Option<String>,
+  /// 任务成功后的结果，发布任务包含 `preview_url`、`bind_url` 和 `expired_at`
+  pub result: Option<serde_json::Value>,
+}
+
+impl From<entity::task::Model> for TaskResponse {
+  fn from(task: entity::task::Model) -> Self {
+    Self {
+      task_id: task.id,
+      r#type: task.r#type,
+      status: task.status,
+      attempts: task.attempts,
+      last_error: task.last_error,
+      result: task
+        .result
+        .and_then(|result| serde_json::from_str(&result).ok()),
+    }
+  }
+}
+
+/// 将站点迁移到其他 Agent，不指定时自动选择一个在线的 Agent
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct MigrateSiteRequest {
+  pub agent_id: Option<i32>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct CreateDeploymentRequest {
   pub site_id: String,
+  /// 待上传文件的 SHA-256
+  #[serde(default)]
+  pub sha256: Option<String>,
+}
+
+/// 先创建部署再开始构建，构建输出可以实时上报到该部署
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct PrepareDeploymentRequest {
+  pub site_id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct PrepareDeploymentResponse {
+  pub deployment_id: i32,
+  pub agent_id: i32,
+}
+
+/// 为预先创建的部署开启上传
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct InitDeploymentUploadRequest {
+  /// 待上传文件的 SHA-256
+  #[serde(default)]
+  pub sha256: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct CreateDeploymentResponse {
+  /// Agent API 的地址，如 `https://10.0.0.1:5001`
   pub deploy_url: String,
+  /// 校验 Agent 证书的方式
+  #[serde(default)]
+  pub deploy_tls: AgentTls,
   pub deploy_token: String,
   pub site_id: String,
-  pub agent_id: u32,
-  pub deployment_id: u32,
+  pub agent_id: i32,
+  pub deployment_id: i32,
+}
+
+/// CLI 上报的构建日志
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AppendDeploymentLogsRequest {
+  pub content: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-pub struct GetSitesResponse {
-  pub sites: Vec<entity::site::Model>,
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DeploymentLogsResponse {
+  pub deployment_id: i32,
+  pub status: entity::deployment::DeploymentStatus,
+  pub logs: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum StageState {
+  Started,
+  Succeeded,
+  Failed,
+}
+
+/// `GET /api/deployment/{id}/stream` 推送的事件，`type` 同时作为 SSE 的 `event` 字段
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum DeploymentStreamEvent {
+  Status {
+    status: entity::deployment::DeploymentStatus,
+    reason: Option<String>,
+  },
+  Stage {
+    stage: crate::agent::DeploymentStage,
+    state: StageState,
+  },
+  Log {
+    source: String,
+    line: String,
+  },
+}
+
+impl DeploymentStreamEvent {
+  pub fn name(&self) -> &'static str {
+    match self {
+      DeploymentStreamEvent::Status { .. } => "status",
+      DeploymentStreamEvent::Stage { .. } => "stage",
+      DeploymentStreamEvent::Log { .. } => "log",
+    }
+  }
+
+  /// 部署进入终态后不会再有新的事件
+  pub fn is_terminal(&self) -> bool {
+    use entity::deployment::DeploymentStatus;
+    matches!(
+      self,
+      DeploymentStreamEvent::Status {
+        status: DeploymentStatus::Published | DeploymentStatus::Failed,
+        ..
+      }
+    )
+  }
 }
@@ -0,0 +1,193 @@
+//! Master 调用 Agent 时的请求签名
+//!
+//! 签名内容为 `METHOD\nPATH\nTIMESTAMP\nNONCE\nhex(sha256(body))`，其中 PATH 包含查询字符串，
+//! 使用 Agent 的 token 作为 HMAC-SHA256 的密钥。
+//!
+//! Webhook 投递的签名内容为 `TIMESTAMP.BODY`，使用 Webhook 的 secret 作为密钥。
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+pub const SIGNATURE_HEADER: &str = "x-pupup-signature";
+pub const TIMESTAMP_HEADER: &str = "x-pupup-timestamp";
+pub const NONCE_HEADER: &str = "x-pupup-nonce";
+pub const WEBHOOK_SIGNATURE_HEADER: &str = "x-pupup-webhook-signature";
+pub const WEBHOOK_EVENT_HEADER: &str = "x-pupup-webhook-event";
+pub const WEBHOOK_DELIVERY_HEADER: &str = "x-pupup-webhook-delivery";
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// 当前 Unix 时间戳（秒）
+pub fn timestamp() -> i64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_secs() as i64)
+    .unwrap_or_default()
+}
+
+fn mac(
+  key: &str,
+  method: &str,
+  path: &str,
+  timestamp: i64,
+  nonce: &str,
+  body: &[u8],
+) -> HmacSha256 {
+  let mut mac = HmacSha256::new_from_slice(key.as_bytes()).expect("HMAC accepts keys of any size");
+  let body_hash = hex::encode(Sha256::digest(body));
+  mac.update(
+    format!(
+      "{}\n{}\n{}\n{}\n{}",
+      method.to_uppercase(),
+      path,
+      timestamp,
+      nonce,
+      body_hash
+    )
+    .as_bytes(),
+  );
+  mac
+}
+
+/// 计算请求签名，返回十六进制字符串
+pub fn sign(
+  key: &str,
+  method: &str,
+  path: &str,
+  timestamp: i64,
+  nonce: &str,
+  body: &[u8],
+) -> String {
+  hex::encode(
+    mac(key, method, path, timestamp, nonce, body)
+      .finalize()
+      .into_bytes(),
+  )
+}
+
+/// 校验请求签名，比较过程为常量时间
+pub fn verify(
+  key: &str,
+  method: &str,
+  path: &str,
+  timestamp: i64,
+  nonce: &str,
+  body: &[u8],
+  signature: &str,
+) -> bool {
+  let Ok(signature) = hex::decode(signature) else {
+    return false;
+  };
+  mac(key, method, path, timestamp, nonce, body)
+    .verify_slice(&signature)
+    .is_ok()
+}
+
+fn webhook_mac(secret: &str, timestamp: i64, body: &[u8]) -> HmacSha256 {
+  let mut mac =
+    HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+  mac.update(format!("{}.", timestamp).as_bytes());
+  mac.update(body);
+  mac
+}
+
+/// 计算 Webhook 投递的签名，格式为 `sha256=<hex>`
+pub fn sign_webhook(secret: &str, timestamp: i64, body: &[u8]) -> String {
+  format!(
+    "sha256={}",
+    hex::encode(webhook_mac(secret, timestamp, body).finalize().into_bytes())
+  )
+}
+
+/// 校验 Webhook 投递的签名，供接收方使用
+pub fn verify_webhook(secret: &str, timestamp: i64, body: &[u8], signature: &str) -> bool {
+  let Some(Ok(signature)) = signature.strip_prefix("sha256=").map(hex::decode) else {
+    return false;
+  };
+  webhook_mac(secret, timestamp, body)
+    .verify_slice(&signature)
+    .is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_sign_and_verify() {
+    let signature = sign("token", "post", "/api/task/revoke", 1, "nonce", b"{}");
+    assert!(verify(
+      "token",
+      "POST",
+      "/api/task/revoke",
+      1,
+      "nonce",
+      b"{}",
+      &signature
+    ));
+    assert!(!verify(
+      "other",
+      "POST",
+      "/api/task/revoke",
+      1,
+      "nonce",
+      b"{}",
+      &signature
+    ));
+    assert!(!verify(
+      "token",
+      "POST",
+      "/api/task/publish",
+      1,
+      "nonce",
+      b"{}",
+      &signature
+    ));
+    assert!(!verify(
+      "token",
+      "POST",
+      "/api/task/revoke",
+      2,
+      "nonce",
+      b"{}",
+      &signature
+    ));
+    assert!(!verify(
+      "token",
+      "POST",
+      "/api/task/revoke",
+      1,
+      "nonce",
+      b"[]",
+      &signature
+    ));
+  }
+
+  #[test]
+  fn test_sign_and_verify_webhook() {
+    let signature = sign_webhook("secret", 1, br#"{"event":"ping"}"#);
+    assert!(signature.starts_with("sha256="));
+    assert!(verify_webhook(
+      "secret",
+      1,
+      br#"{"event":"ping"}"#,
+      &signature
+    ));
+    assert!(!verify_webhook(
+      "other",
+      1,
+      br#"{"event":"ping"}"#,
+      &signature
+    ));
+    assert!(!verify_webhook(
+      "secret",
+      2,
+      br#"{"event":"ping"}"#,
+      &signature
+    ));
+    assert!(!verify_webhook("secret", 1, b"{}", &signature));
+    assert!(!verify_webhook("secret", 1, b"{}", "sha256=zz"));
+  }
+}
@@ -1,5 +1,7 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.4
 
+use std::net::IpAddr;
+
 use derive_more::Display;
 use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
@@ -7,6 +9,7 @@ use serde::{Deserialize, Serialize};
 #[derive(
   Debug, Clone, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize, Display,
 )]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 #[sea_orm(
   rs_type = "String",
   db_type = "String(StringLen::None)",
@@ -22,15 +25,24 @@ pub enum AgentStatus {
   Busy,
 }
 
-#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize)]
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
 #[sea_orm(table_name = "agent")]
 pub struct Model {
   #[sea_orm(primary_key)]
-  pub id: u32,
+  pub id: i32,
   pub hostname: String,
   pub ip_address: String,
+  /// Agent API 的地址，如 `https://10.0.0.1:5001`。早期的备份中没有该字段，恢复时按 `ip_address` 补全
+  #[serde(default)]
+  pub api_url: String,
+  /// 校验 Agent 证书的 CA 证书（PEM）
+  pub ca_cert: Option<String>,
+  /// Agent 证书的 SHA-256 指纹
+  pub cert_fingerprint: Option<String>,
+  /// Agent 心跳上报的公网地址，以逗号分隔，可以同时包含 IPv4 和 IPv6
+  pub public_ips: Option<String>,
   pub storage_path: String,
-  pub available_space: u32,
+  pub available_space: i32,
   pub status: AgentStatus,
   pub tags: Option<String>,
   pub token: String,
@@ -39,6 +51,33 @@ pub struct Model {
   pub updated_at: Option<DateTimeUtc>,
 }
 
+impl Model {
+  /// 站点域名应解析到的地址，没有上报时使用注册的 `ip_address`
+  pub fn public_ips(&self) -> Vec<IpAddr> {
+    let ips: Vec<IpAddr> = self
+      .public_ips
+      .as_deref()
+      .unwrap_or_default()
+      .split(',')
+      .filter_map(|ip| ip.trim().parse().ok())
+      .collect();
+    if ips.is_empty() {
+      self.ip_address.parse().into_iter().collect()
+    } else {
+      ips
+    }
+  }
+}
+
+/// 按 [`Model::public_ips`] 的格式保存地址
+pub fn join_public_ips(ips: &[IpAddr]) -> String {
+  ips
+    .iter()
+    .map(ToString::to_string)
+    .collect::<Vec<_>>()
+    .join(",")
+}
+
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {}
 
@@ -4,6 +4,7 @@ use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 #[sea_orm(
   rs_type = "String",
   db_type = "String(StringLen::None)",
@@ -21,22 +22,25 @@ pub enum DeploymentStatus {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct GetDeploymentBody {
-  pub deployment_id: u32,
+  pub deployment_id: i32,
 }
 
-#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize)]
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema), schema(as = Deployment))]
 #[sea_orm(table_name = "deployment")]
 pub struct Model {
   #[sea_orm(primary_key)]
-  pub id: u32,
+  pub id: i32,
   pub site_id: String,
-  pub agent_id: u32,
+  pub agent_id: i32,
   pub status: DeploymentStatus,
   pub build_logs: Option<String>,
   pub deploy_preview_url: Option<String>,
   pub deploy_url: Option<String>,
   pub deploy_token: Option<String>,
+  #[cfg_attr(feature = "openapi", schema(value_type = String, format = DateTime))]
   pub execution_time: DateTimeUtc,
+  #[cfg_attr(feature = "openapi", schema(value_type = String, format = DateTime))]
   pub created_at: DateTimeUtc,
 }
 
@@ -3,6 +3,12 @@
 pub mod prelude;
 
 pub mod agent;
+pub mod audit_log;
 pub mod deployment;
+pub mod deployment_event;
+pub mod nginx;
 pub mod site;
+pub mod task;
 pub mod user;
+pub mod webhook;
+pub mod webhook_delivery;
@@ -1,6 +1,12 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.4
 
 pub use super::agent::Entity as Agent;
+pub use super::audit_log::Entity as AuditLog;
 pub use super::deployment::Entity as Deployment;
+pub use super::deployment_event::Entity as DeploymentEvent;
+pub use super::nginx::Entity as Nginx;
 pub use super::site::Entity as Site;
+pub use super::task::Entity as Task;
 pub use super::user::Entity as User;
+pub use super::webhook::Entity as Webhook;
+pub use super::webhook_delivery::Entity as WebhookDelivery;
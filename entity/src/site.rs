@@ -4,6 +4,7 @@ use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 #[sea_orm(
   rs_type = "String",
   db_type = "String(StringLen::None)",
@@ -25,7 +26,8 @@ impl SiteStatus {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize)]
-#[sea_orm(rs_type = "u32", db_type = "Integer")]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[sea_orm(rs_type = "i32", db_type = "Integer")]
 #[serde(rename_all = "lowercase")]
 pub enum Bandwidth {
   One = 1,
@@ -48,18 +50,25 @@ impl Bandwidth {
 }
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema), schema(as = Site))]
 #[sea_orm(table_name = "site")]
 pub struct Model {
   #[sea_orm(primary_key)]
-  pub id: u32,
+  pub id: i32,
   pub site_id: String,
   pub user_id: String,
-  pub deployment_id: Option<u32>,
+  pub deployment_id: Option<i32>,
   pub name: String,
   pub domain: Option<String>,
   pub status: SiteStatus,
   pub bandwidth: Bandwidth,
+  #[cfg_attr(feature = "openapi", schema(value_type = Option<String>, format = DateTime))]
+  pub expired_at: Option<DateTimeUtc>,
+  #[cfg_attr(feature = "openapi", schema(value_type = Option<String>, format = DateTime))]
+  pub expire_warned_at: Option<DateTimeUtc>,
+  #[cfg_attr(feature = "openapi", schema(value_type = String, format = DateTime))]
   pub created_at: DateTimeUtc,
+  #[cfg_attr(feature = "openapi", schema(value_type = Option<String>, format = DateTime))]
   pub updated_at: Option<DateTimeUtc>,
 }
 
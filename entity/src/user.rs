@@ -4,23 +4,28 @@ use sea_orm::EnumIter;
 use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Debug, Clone, PartialEq, Eq, EnumIter, DeriveActiveEnum)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, EnumIter, DeriveActiveEnum)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 #[sea_orm(
   rs_type = "String",
   db_type = "String(StringLen::None)",
   rename_all = "lowercase"
 )]
+#[serde(rename_all = "lowercase")]
 pub enum UserStatus {
-  Active,  // 正常
-  Deleted, // 已删除
+  Active,    // 正常
+  Suspended, // 已封禁
+  Deleted,   // 已删除
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, EnumIter, DeriveActiveEnum)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 #[sea_orm(
   rs_type = "String",
   db_type = "String(StringLen::None)",
   rename_all = "lowercase"
 )]
+#[serde(rename_all = "lowercase")]
 pub enum UserType {
   /// 临时用户
   Casual,
@@ -30,11 +35,11 @@ pub enum UserType {
   Administrator,
 }
 
-#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
 #[sea_orm(table_name = "user")]
 pub struct Model {
   #[sea_orm(primary_key)]
-  pub id: u32,
+  pub id: i32,
   #[sea_orm(unique)]
   pub user_id: String,
   pub nickname: String,
@@ -42,8 +47,8 @@ pub struct Model {
   pub email: String,
   pub r#type: UserType,
   pub status: UserStatus,
-  pub is_email_verified: i8,
-  pub is_phone_verified: i8,
+  pub is_email_verified: i16,
+  pub is_phone_verified: i16,
   pub created_at: DateTimeUtc,
   pub updated_at: Option<DateTimeUtc>,
 }
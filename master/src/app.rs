@@ -1,23 +1,35 @@
-use std::{net::Ipv4Addr, time::Duration};
+use std::{net::Ipv4Addr, sync::Arc, time::Duration};
 
 use actix_cors::Cors;
 use actix_web::{
-  App, HttpServer, middleware,
+  App, HttpServer,
+  middleware::{self, from_fn},
   rt::time,
   web::{self, ServiceConfig},
 };
-use rpc::{AgentRpc, CloudflareRpc};
+use rpc::{AgentApi, AgentRpc, CloudflareRpc, WebhookRpc};
 
 use crate::{
+  backup::{BACKUP_CHECK_INTERVAL, scheduled_backup},
   components::{
-    agent::AgentComponent, base, deployment::DeploymentComponent, site::SiteComponent,
+    admin::AdminComponent,
+    agent::AgentComponent,
+    base,
+    deployment::{DeploymentComponent, stream::DeploymentStreams},
+    site::SiteComponent,
+    task::{self, TaskComponent, service::TaskWorkers},
     user::UserComponent,
+    webhook::WebhookComponent,
   },
   config::Config,
   error::AppError,
+  helper::TrustedProxies,
   migration::migrate,
+  openapi::openapi_json,
+  rate_limit::{LoginGuard, RateLimiter, rate_limit},
+  reconcile::{OrphanSites, reconcile_agents},
   repository::RepositoryManager,
-  timing::scheduled_task,
+  timing::{SCHEDULED_TASK_INTERVAL, TASK_DISPATCH_INTERVAL, scheduled_task},
 };
 
 #[derive(Debug, Clone)]
@@ -26,17 +38,42 @@ pub struct AppState {
   pub login_token_key: String,
   pub register_agent_key: String,
   pub register_agent_key_expire: i64,
-  pub agent_rpc: AgentRpc,
+  pub agent_rpc: Arc<dyn AgentApi>,
   pub cloudflare_rpc: rpc::CloudflareRpc,
+  pub casual_site_ttl: i64,
+  pub casual_site_expire_warning: i64,
+  pub webhook_rpc: WebhookRpc,
+  pub webhook_max_attempts: i32,
+  pub webhook_retry_interval: i64,
+  pub deployment_streams: DeploymentStreams,
+  pub task_max_attempts: i32,
+  pub task_retry_interval: i64,
+  pub task_retention: i64,
+  pub task_workers: TaskWorkers,
+  pub reconcile_revoke_orphans: bool,
+  pub reconcile_orphan_grace: u64,
+  pub orphan_sites: OrphanSites,
+  pub backup_dir: String,
+  pub backup_interval: i64,
+  pub backup_retention: usize,
+  pub trust_proxy: bool,
+  pub trusted_proxies: TrustedProxies,
+  pub rate_limiter: RateLimiter,
+  pub login_guard: LoginGuard,
 }
 
 pub fn config_app(cfg: &mut ServiceConfig) {
   cfg.service(
     web::scope("/api")
+      .wrap(from_fn(rate_limit))
       .configure(UserComponent::config)
       .configure(AgentComponent::config)
       .configure(SiteComponent::config)
       .configure(DeploymentComponent::config)
+      .configure(AdminComponent::config)
+      .configure(WebhookComponent::config)
+      .configure(TaskComponent::config)
+      .service(openapi_json)
       .route("/health", web::get().to(base::health_check)),
   );
 }
@@ -53,25 +90,108 @@ pub async fn start() -> Result<(), AppError> {
     cloudflare_zone_id,
     cloudflare_api_key,
     cloudflare_email,
+    casual_site_ttl,
+    casual_site_expire_warning,
+    webhook_max_attempts,
+    webhook_retry_interval,
+    webhook_allow_private_network,
+    task_max_attempts,
+    task_retry_interval,
+    task_retention,
+    reconcile_interval,
+    reconcile_revoke_orphans,
+    reconcile_orphan_grace,
+    backup_dir,
+    backup_interval,
+    backup_retention,
+    rate_limit,
+    rate_limit_routes,
+    rate_limit_login_account,
+    trust_proxy,
+    trusted_proxies,
+    login_lockout_threshold,
+    login_lockout_duration,
   } = Config::from_env()?;
   let db = migrate(&database_url).await?;
   db.ping().await?;
   let repo = RepositoryManager::new(db);
   let state = AppState {
-    repo: repo.clone(),
+    repo,
     login_token_key,
     register_agent_key,
     register_agent_key_expire,
-    agent_rpc: AgentRpc::new()?,
+    agent_rpc: Arc::new(AgentRpc::new()?),
     cloudflare_rpc: CloudflareRpc::new(cloudflare_zone_id, cloudflare_email, cloudflare_api_key)
       .await?,
+    casual_site_ttl,
+    casual_site_expire_warning,
+    webhook_rpc: WebhookRpc::new(webhook_allow_private_network)?,
+    webhook_max_attempts,
+    webhook_retry_interval,
+    deployment_streams: DeploymentStreams::default(),
+    task_max_attempts,
+    task_retry_interval,
+    task_retention,
+    task_workers: TaskWorkers::default(),
+    reconcile_revoke_orphans,
+    reconcile_orphan_grace,
+    orphan_sites: OrphanSites::default(),
+    backup_dir,
+    backup_interval,
+    backup_retention,
+    trust_proxy,
+    trusted_proxies,
+    rate_limiter: RateLimiter::new(rate_limit, rate_limit_routes),
+    login_guard: LoginGuard::new(
+      rate_limit_login_account,
+      login_lockout_threshold,
+      Duration::from_secs(login_lockout_duration),
+    ),
   };
 
+  let task_state = state.clone();
   actix_web::rt::spawn(async move {
-    let mut interval = time::interval(Duration::from_secs(5));
+    let mut interval = time::interval(SCHEDULED_TASK_INTERVAL);
     loop {
       interval.tick().await;
-      scheduled_task(&repo).await;
+      scheduled_task(&task_state).await;
+    }
+  });
+
+  let dispatcher_state = state.clone();
+  actix_web::rt::spawn(async move {
+    if let Err(err) = task::service::requeue_interrupted_tasks(&dispatcher_state).await {
+      tracing::error!("Failed to requeue tasks: {}", err);
+    }
+    let mut interval = time::interval(TASK_DISPATCH_INTERVAL);
+    loop {
+      interval.tick().await;
+      if let Err(err) = task::service::dispatch_due_tasks(&dispatcher_state).await {
+        tracing::error!("Task dispatcher failed: {}", err);
+      }
+    }
+  });
+
+  // 备份需要读取整个数据库并写入文件，不放在共用的定时任务循环中
+  let backup_state = state.clone();
+  actix_web::rt::spawn(async move {
+    let mut interval = time::interval(BACKUP_CHECK_INTERVAL);
+    loop {
+      interval.tick().await;
+      if let Err(err) = scheduled_backup(&backup_state).await {
+        tracing::error!("Scheduled backup failed: {}", err);
+      }
+    }
+  });
+
+  let reconcile_state = state.clone();
+  actix_web::rt::spawn(async move {
+    let mut interval = time::interval(Duration::from_secs(reconcile_interval.max(1)));
+    loop {
+      interval.tick().await;
+      if let Err(err) = reconcile_agents(&reconcile_state).await {
+        tracing::error!("Reconcile failed: {}", err);
+      }
     }
   });
 
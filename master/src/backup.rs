@@ -0,0 +1,565 @@
+//! 数据库备份与恢复
+
+use std::{
+  fs,
+  io::Write,
+  path::{Path, PathBuf},
+  time::Duration,
+};
+
+use chrono::{NaiveDateTime, TimeZone, Utc};
+use common::agent::InventorySite;
+use entity::{agent::AgentStatus, deployment::DeploymentStatus, site::SiteStatus, task::TaskType};
+use helpers::time::utc_now;
+use rpc::{AgentApi, AgentEndpoint, AgentRpc};
+use sea_orm::{ActiveValue::Set, IntoActiveModel, prelude::DateTimeUtc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+  app::AppState,
+  components::{
+    agent::service::default_api_url,
+    task::service::{NewTask, PublishPayload, enqueue_task},
+  },
+  config::Config,
+  error::AppError,
+  migration::migrate,
+  repository::{RepositoryManager, Snapshot},
+};
+
+/// 备份文件格式的版本，格式不兼容时递增
+const BACKUP_VERSION: u32 = 1;
+
+/// 检查是否需要自动备份的间隔
+pub const BACKUP_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// 备份文件名中的时间格式，按文件名排序即按时间排序
+const BACKUP_TIME_FORMAT: &str = "%Y%m%d%H%M%S";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Backup {
+  pub version: u32,
+  pub created_at: DateTimeUtc,
+  pub database: Snapshot,
+  /// 备份时各站点在 Agent 上的文件
+  pub artifacts: Vec<Artifact>,
+}
+
+/// 站点发布在 Agent 上的文件
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Artifact {
+  pub site_id: String,
+  pub agent_id: i32,
+  pub deployment_id: i32,
+  /// Agent 报告的实际状态，Agent 不可达或没有该站点时为空
+  pub inventory: Option<InventorySite>,
+}
+
+impl Artifact {
+  /// 备份时 Agent 上是否有该部署的文件，旧版本的 Agent 不报告发布的部署 ID
+  fn has_files(&self) -> bool {
+    self.inventory.as_ref().is_some_and(|site| {
+      site.has_files && site.release_id.is_none_or(|id| id == self.deployment_id)
+    })
+  }
+}
+
+/// 读取数据库快照，并向在线的 Agent 查询站点文件生成清单
+pub async fn create_backup(
+  repo: &RepositoryManager,
+  agent_rpc: &dyn AgentApi,
+) -> Result<Backup, AppError> {
+  let created_at = utc_now();
+  let database = repo.backup().snapshot().await?;
+  let mut inventories = vec![];
+  for agent in &database.agents {
+    if agent.status != AgentStatus::Online {
+      continue;
+    }
+    match agent_rpc.get_inventory(&AgentEndpoint::from(agent)).await {
+      Ok(inventory) => inventories.push((agent.id, inventory.sites)),
+      Err(err) => tracing::warn!("Failed to get inventory of agent {}: {}", agent.id, err),
+    }
+  }
+  let artifacts = database
+    .nginx
+    .iter()
+    .map(|record| Artifact {
+      site_id: record.site_id.clone(),
+      agent_id: record.agent_id,
+      deployment_id: record.deployment_id,
+      inventory: inventories
+        .iter()
+        .find(|(agent_id, _)| *agent_id == record.agent_id)
+        .and_then(|(_, sites)| sites.iter().find(|site| site.site_id == record.site_id))
+        .cloned(),
+    })
+    .collect();
+  Ok(Backup {
+    version: BACKUP_VERSION,
+    created_at,
+    database,
+    artifacts,
+  })
+}
+
+/// 先写入临时文件再重命名，避免留下不完整的备份
+pub fn write_backup(backup: &Backup, path: &Path) -> Result<(), AppError> {
+  if let Some(parent) = path.parent() {
+    fs::create_dir_all(parent)?;
+  }
+  let content = serde_json::to_vec(backup).map_err(|err| AppError::Other {
+    message: "Failed to serialize backup".to_string(),
+    source: Some(Box::new(err)),
+  })?;
+  let tmp = path.with_extension("json.tmp");
+  // 上次中断留下的临时文件可能权限过宽，删除后重新创建
+  if tmp.exists() {
+    fs::remove_file(&tmp)?;
+  }
+  // 备份中包含密码哈希和 Agent token，只允许当前用户读写
+  let mut options = fs::OpenOptions::new();
+  options.write(true).create_new(true);
+  #[cfg(unix)]
+  std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+  let mut file = options.open(&tmp)?;
+  file.write_all(&content)?;
+  file.sync_all()?;
+  fs::rename(tmp, path)?;
+  Ok(())
+}
+
+pub fn read_backup(path: &Path) -> Result<Backup, AppError> {
+  let content = fs::read(path)?;
+  let mut backup: Backup = serde_json::from_slice(&content).map_err(|err| AppError::Other {
+    message: format!("Invalid backup file {}", path.display()),
+    source: Some(Box::new(err)),
+  })?;
+  if backup.version != BACKUP_VERSION {
+    return Err(AppError::Other {
+      message: format!("Unsupported backup version {}", backup.version),
+      source: None,
+    });
+  }
+  // 早期的备份没有 Agent API 地址，使用注册时的默认地址
+  for agent in &mut backup.database.agents {
+    if agent.api_url.is_empty() {
+      agent.api_url = default_api_url(&agent.ip_address);
+    }
+  }
+  Ok(backup)
+}
+
+pub fn backup_file_name(created_at: DateTimeUtc) -> String {
+  format!("backup-{}.json", created_at.format(BACKUP_TIME_FORMAT))
+}
+
+/// 目录下的备份文件及其创建时间，按时间从旧到新排列
+fn list_backups(dir: &Path) -> Result<Vec<(DateTimeUtc, PathBuf)>, AppError> {
+  if !dir.exists() {
+    return Ok(vec![]);
+  }
+  let mut backups = vec![];
+  for entry in fs::read_dir(dir)? {
+    let path = entry?.path();
+    let Some(time) = path
+      .file_name()
+      .and_then(|name| name.to_str())
+      .and_then(|name| name.strip_prefix("backup-")?.strip_suffix(".json"))
+      .and_then(|time| NaiveDateTime::parse_from_str(time, BACKUP_TIME_FORMAT).ok())
+    else {
+      continue;
+    };
+    backups.push((Utc.from_utc_datetime(&time), path));
+  }
+  backups.sort();
+  Ok(backups)
+}
+
+/// 只保留最新的 `retention` 份备份
+fn prune_backups(dir: &Path, retention: usize) -> Result<(), AppError> {
+  let backups = list_backups(dir)?;
+  let expired = backups.len().saturating_sub(retention.max(1));
+  for (_, path) in &backups[..expired] {
+    fs::remove_file(path)?;
+    tracing::info!("Removed expired backup {}", path.display());
+  }
+  Ok(())
+}
+
+/// 在阻塞线程池中读写备份文件，不占用异步运行时的线程
+async fn blocking<T: Send + 'static>(
+  f: impl FnOnce() -> Result<T, AppError> + Send + 'static,
+) -> Result<T, AppError> {
+  actix_web::rt::task::spawn_blocking(f)
+    .await
+    .map_err(|err| AppError::Other {
+      message: "Backup file task panicked".to_string(),
+      source: Some(Box::new(err)),
+    })?
+}
+
+/// 距上一次备份超过 `backup_interval` 时写入新的备份并清理旧备份，由单独的定时循环调用
+pub async fn scheduled_backup(state: &AppState) -> Result<(), AppError> {
+  if state.backup_interval <= 0 {
+    return Ok(());
+  }
+  let dir = PathBuf::from(&state.backup_dir);
+  let now = utc_now();
+  let last = blocking({
+    let dir = dir.clone();
+    move || list_backups(&dir)
+  })
+  .await?
+  .last()
+  .map(|(created_at, _)| *created_at);
+  if last.is_some_and(|last| now - last < chrono::Duration::seconds(state.backup_interval)) {
+    return Ok(());
+  }
+  let backup = create_backup(&state.repo, state.agent_rpc.as_ref()).await?;
+  let retention = state.backup_retention;
+  blocking(move || {
+    let path = dir.join(backup_file_name(backup.created_at));
+    write_backup(&backup, &path)?;
+    tracing::info!("Backup written to {}", path.display());
+    prune_backups(&dir, retention)
+  })
+  .await
+}
+
+/// 恢复的结果
+#[derive(Debug, Default)]
+pub struct Restored {
+  /// 提交了重新发布任务的站点数
+  pub republished: usize,
+  /// 备份时 Agent 上已没有文件、部署被标记为失败的站点
+  pub lost: Vec<String>,
+}
+
+/// 用备份重建数据库，并为备份中已发布的站点提交重新发布的任务。
+/// 备份只包含站点文件的清单，不包含文件本身，重新发布依赖 Agent 上仍保留的文件；
+/// 备份时 Agent 上已没有文件的站点不再发布，其部署被标记为失败，需要用户重新部署。
+/// 备份中未结束的任务会被标记为失败，新任务在 Master 下次启动后由调度器执行
+pub async fn restore_backup(
+  repo: &RepositoryManager,
+  backup: &Backup,
+  force: bool,
+) -> Result<Restored, AppError> {
+  if !force && !repo.backup().is_empty().await? {
+    return Err(AppError::Other {
+      message: "Database is not empty, use --force to overwrite it".to_string(),
+      source: None,
+    });
+  }
+  repo.backup().restore(&backup.database).await?;
+  let mut restored = Restored::default();
+  for record in &backup.database.nginx {
+    let Some(site) = backup
+      .database
+      .sites
+      .iter()
+      .find(|site| site.site_id == record.site_id && site.status == SiteStatus::Active)
+    else {
+      continue;
+    };
+    let has_files = backup.artifacts.iter().any(|artifact| {
+      artifact.site_id == record.site_id
+        && artifact.agent_id == record.agent_id
+        && artifact.has_files()
+    });
+    if !has_files {
+      mark_lost(repo, &record.site_id, record.deployment_id).await?;
+      restored.lost.push(record.site_id.clone());
+      continue;
+    }
+    enqueue_task(
+      repo,
+      NewTask {
+        r#type: TaskType::Publish,
+        user_id: site.user_id.clone(),
+        site_id: site.site_id.clone(),
+        deployment_id: Some(record.deployment_id),
+        payload: json!(PublishPayload {
+          bind_domain: site.domain.clone(),
+        }),
+        idempotency_key: format!(
+          "restore:{}:{}:{}",
+          site.site_id,
+          record.deployment_id,
+          backup.created_at.timestamp()
+        ),
+      },
+    )
+    .await?;
+    restored.republished += 1;
+  }
+  Ok(restored)
+}
+
+/// 文件已丢失的站点：部署标记为失败，删除发布记录，避免被当作已发布的站点重新推送
+async fn mark_lost(
+  repo: &RepositoryManager,
+  site_id: &str,
+  deployment_id: i32,
+) -> Result<(), AppError> {
+  if let Some(deployment) = repo.deployment().get_deployment(deployment_id).await? {
+    let mut active_deployment = deployment.into_active_model();
+    active_deployment.status = Set(DeploymentStatus::Failed);
+    repo
+      .deployment()
+      .update_deployment(active_deployment)
+      .await?;
+  }
+  repo.nginx().delete_by_site_id(site_id).await?;
+  tracing::warn!(
+    "Files of site {} are missing from the backup, deployment {} is marked as failed",
+    site_id,
+    deployment_id
+  );
+  Ok(())
+}
+
+/// `pupup-master backup`：写入一份备份，未指定路径时写入 `BACKUP_DIR`
+pub async fn backup_command(output: Option<PathBuf>) -> Result<(), AppError> {
+  let config = Config::from_env()?;
+  let repo = RepositoryManager::new(migrate(&config.database_url).await?);
+  let backup = create_backup(&repo, &AgentRpc::new()?).await?;
+  let path = output
+    .unwrap_or_else(|| Path::new(&config.backup_dir).join(backup_file_name(backup.created_at)));
+  write_backup(&backup, &path)?;
+  tracing::info!(
+    "Backup of {} sites and {} artifacts written to {}",
+    backup.database.sites.len(),
+    backup.artifacts.len(),
+    path.display()
+  );
+  Ok(())
+}
+
+/// `pupup-master restore`：从备份重建数据库
+pub async fn restore_command(file: PathBuf, force: bool) -> Result<(), AppError> {
+  let config = Config::from_env()?;
+  let backup = read_backup(&file)?;
+  let repo = RepositoryManager::new(migrate(&config.database_url).await?);
+  let restored = restore_backup(&repo, &backup, force).await?;
+  tracing::info!(
+    "Restored backup created at {}, {} sites will be republished on next start, {} sites need to be redeployed",
+    backup.created_at,
+    restored.republished,
+    restored.lost.len()
+  );
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_prune_backups() {
+    let dir = std::env::temp_dir().join(format!("pupup-backup-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let now = utc_now();
+    for days in 0..5 {
+      fs::write(
+        dir.join(backup_file_name(now - chrono::Duration::days(days))),
+        "{}",
+      )
+      .unwrap();
+    }
+    fs::write(dir.join("other.json"), "{}").unwrap();
+
+    prune_backups(&dir, 2).unwrap();
+    let backups = list_backups(&dir).unwrap();
+    assert_eq!(backups.len(), 2);
+    assert_eq!(
+      backups[1].1.file_name().unwrap().to_str(),
+      Some(backup_file_name(now).as_str())
+    );
+    assert!(dir.join("other.json").exists());
+    fs::remove_dir_all(dir).unwrap();
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn test_write_backup_mode() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = std::env::temp_dir().join(format!("pupup-backup-mode-{}", std::process::id()));
+    let path = dir.join("backup.json");
+    let backup = Backup {
+      version: BACKUP_VERSION,
+      created_at: utc_now(),
+      database: Snapshot::default(),
+      artifacts: vec![],
+    };
+    write_backup(&backup, &path).unwrap();
+    let mode = fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+    assert_eq!(read_backup(&path).unwrap().version, BACKUP_VERSION);
+    fs::remove_dir_all(dir).unwrap();
+  }
+
+  #[test]
+  fn test_read_backup_without_api_url() {
+    let dir = std::env::temp_dir().join(format!("pupup-backup-old-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("backup.json");
+    let mut backup = Backup {
+      version: BACKUP_VERSION,
+      created_at: utc_now(),
+      database: Snapshot::default(),
+      artifacts: vec![],
+    };
+    backup.database.agents.push(entity::agent::Model {
+      id: 1,
+      hostname: "agent".into(),
+      ip_address: "::1".into(),
+      api_url: String::new(),
+      ca_cert: None,
+      cert_fingerprint: None,
+      public_ips: None,
+      storage_path: "/data".into(),
+      available_space: 0,
+      status: entity::agent::AgentStatus::Online,
+      tags: None,
+      token: "token".into(),
+      last_heartbeat: None,
+      created_at: utc_now(),
+      updated_at: None,
+    });
+    let mut value = serde_json::to_value(&backup).unwrap();
+    value["database"]["agents"][0]
+      .as_object_mut()
+      .unwrap()
+      .remove("api_url");
+    fs::write(&path, serde_json::to_vec(&value).unwrap()).unwrap();
+
+    let backup = read_backup(&path).unwrap();
+    assert_eq!(backup.database.agents[0].api_url, "http://[::1]:5001");
+    fs::remove_dir_all(dir).unwrap();
+  }
+
+  #[cfg(feature = "sqlite")]
+  #[actix_web::test]
+  async fn test_create_backup() {
+    use common::agent::InventoryResponse;
+    use entity::agent;
+    use rpc::MockAgentApi;
+    use sea_orm::Set;
+
+    let repo = RepositoryManager::new(migrate("sqlite::memory:").await.unwrap());
+    for (ip, status) in [
+      ("10.0.0.1", AgentStatus::Online),
+      ("10.0.0.2", AgentStatus::Offline),
+    ] {
+      repo
+        .agent()
+        .create_agent(agent::ActiveModel {
+          hostname: Set(ip.to_string()),
+          ip_address: Set(ip.to_string()),
+          api_url: Set(format!("http://{}:5001", ip)),
+          storage_path: Set("/data".to_string()),
+          available_space: Set(0),
+          status: Set(status),
+          token: Set(ip.to_string()),
+          created_at: Set(utc_now()),
+          ..Default::default()
+        })
+        .await
+        .unwrap();
+    }
+    let online = repo.agent().get_agents().await.unwrap()[0].id;
+    repo.nginx().save("a", online, 1, None, None).await.unwrap();
+    repo.nginx().save("b", online, 2, None, None).await.unwrap();
+
+    // 只查询在线的 Agent
+    let mut agent_rpc = MockAgentApi::new();
+    agent_rpc
+      .expect_get_inventory()
+      .withf(|agent| agent.url.base_url == "http://10.0.0.1:5001")
+      .times(1)
+      .returning(|_| {
+        Ok(InventoryResponse {
+          sites: vec![InventorySite {
+            site_id: "a".to_string(),
+            release_id: Some(1),
+            config_hash: None,
+            has_files: true,
+          }],
+        })
+      });
+    let backup = create_backup(&repo, &agent_rpc).await.unwrap();
+    assert_eq!(backup.artifacts.len(), 2);
+    assert!(backup.artifacts[0].inventory.is_some());
+    assert!(backup.artifacts[1].inventory.is_none());
+  }
+
+  #[cfg(feature = "sqlite")]
+  #[actix_web::test]
+  async fn test_restore_backup_lost_files() {
+    use common::agent::InventoryResponse;
+    use entity::{deployment::DeploymentStatus, user::UserType};
+    use rpc::MockAgentApi;
+
+    use crate::testing::{create_agent, create_site, create_user, test_state};
+
+    let state = test_state(MockAgentApi::new()).await;
+    create_user(&state, "owner", UserType::Normal).await;
+    let agent = create_agent(&state, "10.0.0.1").await;
+    let mut deployments = vec![];
+    for site_id in ["kept", "lost"] {
+      let (_, deployment) = create_site(
+        &state,
+        site_id,
+        "owner",
+        agent.id,
+        DeploymentStatus::Published,
+      )
+      .await;
+      state
+        .repo
+        .nginx()
+        .save(site_id, agent.id, deployment.id, None, None)
+        .await
+        .unwrap();
+      deployments.push(deployment.id);
+    }
+    // 备份时 Agent 上只有 kept 的文件
+    let kept_id = deployments[0];
+    let mut agent_rpc = MockAgentApi::new();
+    agent_rpc.expect_get_inventory().returning(move |_| {
+      Ok(InventoryResponse {
+        sites: vec![InventorySite {
+          site_id: "kept".to_string(),
+          release_id: Some(kept_id),
+          config_hash: None,
+          has_files: true,
+        }],
+      })
+    });
+    let backup = create_backup(&state.repo, &agent_rpc).await.unwrap();
+
+    let repo = RepositoryManager::new(migrate("sqlite::memory:").await.unwrap());
+    let restored = restore_backup(&repo, &backup, false).await.unwrap();
+    assert_eq!(restored.republished, 1);
+    assert_eq!(restored.lost, ["lost"]);
+    let status = |id| {
+      let repo = &repo;
+      async move {
+        repo
+          .deployment()
+          .get_deployment(id)
+          .await
+          .unwrap()
+          .unwrap()
+          .status
+      }
+    };
+    assert_eq!(status(deployments[0]).await, DeploymentStatus::Published);
+    assert_eq!(status(deployments[1]).await, DeploymentStatus::Failed);
+    assert!(repo.nginx().get_by_site_id("kept").await.unwrap().is_some());
+    assert!(repo.nginx().get_by_site_id("lost").await.unwrap().is_none());
+  }
+}
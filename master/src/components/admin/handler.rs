@@ -0,0 +1,435 @@
+use actix_web::{
+  HttpRequest, HttpResponse, delete, get, post,
+  web::{Data, Json, Path, Query},
+};
+use common::{
+  Paginated, Response,
+  master::{MigrateSiteRequest, TaskResponse},
+};
+use entity::{
+  audit_log::{self, AuditAction},
+  deployment, site,
+};
+use sea_orm::ActiveEnum;
+use serde_json::Value;
+use validator::Validate;
+
+use crate::{
+  app::AppState,
+  components::{
+    admin::{model::*, service},
+    audit::service::{AuditEntry, record},
+  },
+  error::AppError,
+  helper::{client_ip, extract_idempotency_key, extract_user_id},
+  traits::IntoHttpResponse,
+};
+
+/// 校验请求者为管理员，返回其 user_id
+async fn authorize(req: &HttpRequest, state: &AppState) -> Result<String, AppError> {
+  let user_id = extract_user_id(req, state).await?;
+  service::ensure_admin(state, &user_id).await?;
+  Ok(user_id)
+}
+
+/// 管理员操作的审计日志
+fn audit_entry(
+  req: &HttpRequest,
+  state: &AppState,
+  admin_id: &str,
+  action: AuditAction,
+  target: String,
+) -> AuditEntry {
+  AuditEntry::new(format!("user:{}", admin_id), action)
+    .target(target)
+    .ip(client_ip(req, state))
+}
+
+#[utoipa::path(
+  tag = "admin",
+  params(ListUsersQuery),
+  responses((status = OK, body = Response<Paginated<AdminUser>>)),
+  security(("bearer_auth" = []))
+)]
+#[get("/users")]
+pub async fn list_users(
+  req: HttpRequest,
+  state: Data<AppState>,
+  query: Query<ListUsersQuery>,
+) -> Result<HttpResponse, AppError> {
+  authorize(&req, &state).await?;
+  service::list_users(&state, query.into_inner())
+    .await
+    .into_http_response()
+}
+
+#[utoipa::path(
+  tag = "admin",
+  responses((status = OK, body = Response<AdminUser>)),
+  security(("bearer_auth" = []))
+)]
+#[get("/users/{user_id}")]
+pub async fn get_user(
+  req: HttpRequest,
+  state: Data<AppState>,
+  user_id: Path<String>,
+) -> Result<HttpResponse, AppError> {
+  authorize(&req, &state).await?;
+  service::get_user(&state, user_id.into_inner())
+    .await
+    .into_http_response()
+}
+
+#[utoipa::path(
+  tag = "admin",
+  responses((status = OK, body = Response<AdminUser>)),
+  security(("bearer_auth" = []))
+)]
+#[post("/users/{user_id}/status")]
+pub async fn update_user_status(
+  req: HttpRequest,
+  state: Data<AppState>,
+  user_id: Path<String>,
+  body: Json<UpdateUserStatusBody>,
+) -> Result<HttpResponse, AppError> {
+  let operator_id = authorize(&req, &state).await?;
+  let user_id = user_id.into_inner();
+  let entry = audit_entry(
+    &req,
+    &state,
+    &operator_id,
+    AuditAction::UserStatus,
+    format!("user:{}", user_id),
+  )
+  .detail(body.status.to_value());
+  let result = service::update_user_status(&state, &operator_id, user_id, body.into_inner()).await;
+  record(&state, entry, &result).await;
+  result.into_http_response()
+}
+
+#[utoipa::path(
+  tag = "admin",
+  params(ListSitesQuery),
+  responses((status = OK, body = Response<Paginated<site::Model>>)),
+  security(("bearer_auth" = []))
+)]
+#[get("/sites")]
+pub async fn list_sites(
+  req: HttpRequest,
+  state: Data<AppState>,
+  query: Query<ListSitesQuery>,
+) -> Result<HttpResponse, AppError> {
+  authorize(&req, &state).await?;
+  service::list_sites(&state, query.into_inner())
+    .await
+    .into_http_response()
+}
+
+#[utoipa::path(
+  tag = "admin",
+  responses((status = OK, body = Response<site::Model>)),
+  security(("bearer_auth" = []))
+)]
+#[post("/sites/{site_id}/status")]
+pub async fn update_site_status(
+  req: HttpRequest,
+  state: Data<AppState>,
+  site_id: Path<String>,
+  body: Json<UpdateSiteStatusBody>,
+) -> Result<HttpResponse, AppError> {
+  let admin_id = authorize(&req, &state).await?;
+  let site_id = site_id.into_inner();
+  let entry = audit_entry(
+    &req,
+    &state,
+    &admin_id,
+    AuditAction::SiteStatus,
+    format!("site:{}", site_id),
+  )
+  .detail(body.status.to_value());
+  let result = service::update_site_status(&state, site_id, body.into_inner()).await;
+  record(&state, entry, &result).await;
+  result.into_http_response()
+}
+
+#[utoipa::path(
+  tag = "admin",
+  responses((status = OK, description = "`data` 为 null", body = Response<Value>)),
+  security(("bearer_auth" = []))
+)]
+#[delete("/sites/{site_id}")]
+pub async fn delete_site(
+  req: HttpRequest,
+  state: Data<AppState>,
+  site_id: Path<String>,
+) -> Result<HttpResponse, AppError> {
+  let admin_id = authorize(&req, &state).await?;
+  let site_id = site_id.into_inner();
+  let entry = audit_entry(
+    &req,
+    &state,
+    &admin_id,
+    AuditAction::SiteDelete,
+    format!("site:{}", site_id),
+  );
+  let result = service::delete_site(&state, site_id).await;
+  record(&state, entry, &result).await;
+  result.into_http_response()
+}
+
+#[utoipa::path(
+  tag = "admin",
+  params(("Idempotency-Key" = Option<String>, Header, description = "重复提交时返回同一个任务")),
+  responses((status = OK, body = Response<TaskResponse>)),
+  security(("bearer_auth" = []))
+)]
+#[post("/sites/{site_id}/migrate")]
+pub async fn migrate_site(
+  req: HttpRequest,
+  state: Data<AppState>,
+  site_id: Path<String>,
+  body: Json<MigrateSiteRequest>,
+) -> Result<HttpResponse, AppError> {
+  let admin_id = authorize(&req, &state).await?;
+  let idempotency_key = extract_idempotency_key(&req)?;
+  let site_id = site_id.into_inner();
+  let mut entry = audit_entry(
+    &req,
+    &state,
+    &admin_id,
+    AuditAction::SiteMigrate,
+    format!("site:{}", site_id),
+  );
+  if let Some(agent_id) = body.agent_id {
+    entry = entry.detail(format!("agent:{}", agent_id));
+  }
+  let result = service::migrate_site(
+    &state,
+    admin_id,
+    site_id,
+    body.into_inner(),
+    idempotency_key,
+  )
+  .await;
+  record(&state, entry, &result).await;
+  result.into_http_response()
+}
+
+#[utoipa::path(
+  tag = "admin",
+  params(ListDeploymentsQuery),
+  responses((status = OK, body = Response<Paginated<deployment::Model>>)),
+  security(("bearer_auth" = []))
+)]
+#[get("/deployments")]
+pub async fn list_deployments(
+  req: HttpRequest,
+  state: Data<AppState>,
+  query: Query<ListDeploymentsQuery>,
+) -> Result<HttpResponse, AppError> {
+  authorize(&req, &state).await?;
+  service::list_deployments(&state, query.into_inner())
+    .await
+    .into_http_response()
+}
+
+#[utoipa::path(
+  tag = "admin",
+  responses((status = OK, body = Response<deployment::Model>)),
+  security(("bearer_auth" = []))
+)]
+#[post("/deployments/{deployment_id}/status")]
+pub async fn update_deployment_status(
+  req: HttpRequest,
+  state: Data<AppState>,
+  deployment_id: Path<i32>,
+  body: Json<UpdateDeploymentStatusBody>,
+) -> Result<HttpResponse, AppError> {
+  let admin_id = authorize(&req, &state).await?;
+  let deployment_id = deployment_id.into_inner();
+  let entry = audit_entry(
+    &req,
+    &state,
+    &admin_id,
+    AuditAction::DeploymentStatus,
+    format!("deployment:{}", deployment_id),
+  )
+  .detail(body.status.to_value());
+  let result =
+    service::update_deployment_status(&state, admin_id, deployment_id, body.into_inner()).await;
+  record(&state, entry, &result).await;
+  result.into_http_response()
+}
+
+#[utoipa::path(
+  tag = "admin",
+  params(ListAgentsQuery),
+  responses((status = OK, body = Response<Paginated<AdminAgent>>)),
+  security(("bearer_auth" = []))
+)]
+#[get("/agents")]
+pub async fn list_agents(
+  req: HttpRequest,
+  state: Data<AppState>,
+  query: Query<ListAgentsQuery>,
+) -> Result<HttpResponse, AppError> {
+  authorize(&req, &state).await?;
+  service::list_agents(&state, query.into_inner())
+    .await
+    .into_http_response()
+}
+
+#[utoipa::path(
+  tag = "admin",
+  responses((status = OK, body = Response<AdminAgent>)),
+  security(("bearer_auth" = []))
+)]
+#[post("/agents/{agent_id}/status")]
+pub async fn update_agent_status(
+  req: HttpRequest,
+  state: Data<AppState>,
+  agent_id: Path<i32>,
+  body: Json<UpdateAgentStatusBody>,
+) -> Result<HttpResponse, AppError> {
+  let admin_id = authorize(&req, &state).await?;
+  let agent_id = agent_id.into_inner();
+  let entry = audit_entry(
+    &req,
+    &state,
+    &admin_id,
+    AuditAction::AgentStatus,
+    format!("agent:{}", agent_id),
+  )
+  .detail(body.status.to_value());
+  let result = service::update_agent_status(&state, agent_id, body.into_inner()).await;
+  record(&state, entry, &result).await;
+  result.into_http_response()
+}
+
+#[utoipa::path(
+  tag = "admin",
+  responses((status = OK, body = Response<AdminAgent>)),
+  security(("bearer_auth" = []))
+)]
+#[post("/agents/{agent_id}/endpoint")]
+pub async fn update_agent_endpoint(
+  req: HttpRequest,
+  state: Data<AppState>,
+  agent_id: Path<i32>,
+  body: Json<UpdateAgentEndpointBody>,
+) -> Result<HttpResponse, AppError> {
+  let admin_id = authorize(&req, &state).await?;
+  body.0.validate()?;
+  let agent_id = agent_id.into_inner();
+  let entry = audit_entry(
+    &req,
+    &state,
+    &admin_id,
+    AuditAction::AgentEndpoint,
+    format!("agent:{}", agent_id),
+  )
+  .detail(body.api_url.clone());
+  let result = service::update_agent_endpoint(&state, agent_id, body.into_inner()).await;
+  record(&state, entry, &result).await;
+  result.into_http_response()
+}
+
+#[utoipa::path(
+  tag = "admin",
+  params(ListAuditLogsQuery),
+  responses((status = OK, body = Response<Paginated<audit_log::Model>>)),
+  security(("bearer_auth" = []))
+)]
+#[get("/audit")]
+pub async fn list_audit_logs(
+  req: HttpRequest,
+  state: Data<AppState>,
+  query: Query<ListAuditLogsQuery>,
+) -> Result<HttpResponse, AppError> {
+  authorize(&req, &state).await?;
+  service::list_audit_logs(&state, query.into_inner())
+    .await
+    .into_http_response()
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+  use actix_web::{
+    App,
+    http::StatusCode,
+    test::{self, TestRequest},
+  };
+  use entity::user::{UserStatus, UserType};
+  use rpc::MockAgentApi;
+  use serde_json::json;
+
+  use super::*;
+  use crate::{
+    app::config_app,
+    testing::{create_agent, create_user, login_token, test_state},
+  };
+
+  #[actix_web::test]
+  async fn test_admin_endpoints_forbidden() {
+    let state = test_state(MockAgentApi::new()).await;
+    create_user(&state, "user", UserType::Normal).await;
+    create_user(&state, "other", UserType::Normal).await;
+    let agent = create_agent(&state, "10.0.0.1").await;
+    let token = login_token(&state, "user");
+    let app = test::init_service(
+      App::new()
+        .app_data(Data::new(state.clone()))
+        .configure(config_app),
+    )
+    .await;
+
+    let requests = [
+      TestRequest::get().uri("/api/admin/users"),
+      TestRequest::post()
+        .uri("/api/admin/users/other/status")
+        .set_json(json!({ "status": "suspended" })),
+      TestRequest::post()
+        .uri("/api/admin/users/other/status")
+        .set_json(json!({ "status": "deleted" })),
+      TestRequest::get().uri("/api/admin/agents"),
+      TestRequest::post()
+        .uri(&format!("/api/admin/agents/{}/status", agent.id))
+        .set_json(json!({ "status": "offline" })),
+      TestRequest::post()
+        .uri(&format!("/api/admin/agents/{}/endpoint", agent.id))
+        .set_json(json!({ "api_url": "http://10.0.0.2:5001" })),
+      TestRequest::post().uri("/api/agent").set_json(json!({
+        "hostname": "agent",
+        "ip_address": "10.0.0.2",
+        "storage_path": "/data",
+        "available_space": 1024,
+      })),
+      TestRequest::post().uri(&format!("/api/agent/{}/token", agent.id)),
+    ];
+    for request in requests {
+      let req = request
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+      let uri = req.uri().to_string();
+      let status = match test::try_call_service(&app, req).await {
+        Ok(resp) => resp.status(),
+        Err(err) => err.error_response().status(),
+      };
+      assert_eq!(status, StatusCode::FORBIDDEN, "{}", uri);
+    }
+
+    // 请求没有产生任何修改
+    let other = state
+      .repo
+      .user()
+      .get_user_by_id("other".to_string())
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(other.status, UserStatus::Active);
+    let agents = state.repo.agent().get_agents().await.unwrap();
+    assert_eq!(agents.len(), 1);
+    assert_eq!(agents[0].api_url, agent.api_url);
+    assert_eq!(agents[0].token, agent.token);
+  }
+}
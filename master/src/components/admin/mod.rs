@@ -0,0 +1,48 @@
+mod handler;
+pub mod model;
+mod service;
+
+use actix_web::web::{self, ServiceConfig};
+use utoipa::OpenApi;
+
+pub struct AdminComponent;
+
+impl AdminComponent {
+  pub fn config(cfg: &mut ServiceConfig) {
+    cfg.service(
+      web::scope("/admin")
+        .service(handler::list_users)
+        .service(handler::get_user)
+        .service(handler::update_user_status)
+        .service(handler::list_sites)
+        .service(handler::update_site_status)
+        .service(handler::delete_site)
+        .service(handler::migrate_site)
+        .service(handler::list_deployments)
+        .service(handler::update_deployment_status)
+        .service(handler::list_agents)
+        .service(handler::update_agent_status)
+        .service(handler::update_agent_endpoint)
+        .service(handler::list_audit_logs),
+    );
+  }
+}
+
+/// 管理后台相关接口的 OpenAPI 文档
+#[derive(OpenApi)]
+#[openapi(paths(
+  handler::list_users,
+  handler::get_user,
+  handler::update_user_status,
+  handler::list_sites,
+  handler::update_site_status,
+  handler::delete_site,
+  handler::migrate_site,
+  handler::list_deployments,
+  handler::update_deployment_status,
+  handler::list_agents,
+  handler::update_agent_status,
+  handler::update_agent_endpoint,
+  handler::list_audit_logs,
+))]
+pub struct AdminApi;
@@ -0,0 +1,223 @@
+use common::SortOrder;
+use entity::{
+  agent::{self, AgentStatus},
+  audit_log::{AuditAction, AuditResult},
+  deployment::DeploymentStatus,
+  site::SiteStatus,
+  user::{self, UserStatus, UserType},
+};
+use sea_orm::prelude::DateTimeUtc;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use validator::{Validate, ValidationError};
+
+use crate::{
+  components::agent::model::{
+    validate_api_url, validate_ca_cert, validate_cert_fingerprint, validate_tls_scheme,
+  },
+  helper::{default_page, default_page_size},
+  repository::{AgentSort, DeploymentSort, SiteSort},
+};
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListUsersQuery {
+  /// 页码，从 1 开始
+  #[serde(default = "default_page")]
+  pub page: u64,
+  #[serde(default = "default_page_size")]
+  pub page_size: u64,
+  pub status: Option<UserStatus>,
+  pub r#type: Option<UserType>,
+  pub keyword: Option<String>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListSitesQuery {
+  /// 页码，从 1 开始
+  #[serde(default = "default_page")]
+  pub page: u64,
+  #[serde(default = "default_page_size")]
+  pub page_size: u64,
+  pub user_id: Option<String>,
+  pub status: Option<SiteStatus>,
+  pub domain: Option<String>,
+  #[serde(default)]
+  pub sort: SiteSort,
+  #[serde(default)]
+  pub order: SortOrder,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListDeploymentsQuery {
+  /// 页码，从 1 开始
+  #[serde(default = "default_page")]
+  pub page: u64,
+  #[serde(default = "default_page_size")]
+  pub page_size: u64,
+  pub site_id: Option<String>,
+  pub agent_id: Option<i32>,
+  pub status: Option<DeploymentStatus>,
+  #[serde(default)]
+  pub sort: DeploymentSort,
+  #[serde(default)]
+  pub order: SortOrder,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListAgentsQuery {
+  /// 页码，从 1 开始
+  #[serde(default = "default_page")]
+  pub page: u64,
+  #[serde(default = "default_page_size")]
+  pub page_size: u64,
+  pub status: Option<AgentStatus>,
+  #[serde(default)]
+  pub sort: AgentSort,
+  #[serde(default)]
+  pub order: SortOrder,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListAuditLogsQuery {
+  /// 页码，从 1 开始
+  #[serde(default = "default_page")]
+  pub page: u64,
+  #[serde(default = "default_page_size")]
+  pub page_size: u64,
+  /// 如 `user:{user_id}`、`agent`、`system`、`anonymous`
+  pub actor: Option<String>,
+  pub action: Option<AuditAction>,
+  /// 如 `site:{site_id}`、`deployment:{id}`、`agent:{id}`、`email:{email}`
+  pub target: Option<String>,
+  pub result: Option<AuditResult>,
+  pub ip: Option<String>,
+  /// 只返回该时间及之后的记录
+  #[param(value_type = Option<String>, format = DateTime)]
+  pub from: Option<DateTimeUtc>,
+  /// 只返回该时间之前的记录
+  #[param(value_type = Option<String>, format = DateTime)]
+  pub to: Option<DateTimeUtc>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateUserStatusBody {
+  pub status: UserStatus,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateSiteStatusBody {
+  pub status: SiteStatus,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[schema(as = AdminUpdateDeploymentStatusBody)]
+pub struct UpdateDeploymentStatusBody {
+  pub status: DeploymentStatus,
+  pub reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateAgentStatusBody {
+  pub status: AgentStatus,
+}
+
+/// 修改 Agent 的 API 地址和证书校验方式，证书字段为空时清除
+#[derive(Debug, Deserialize, ToSchema, Validate)]
+#[validate(schema(function = "validate_endpoint_tls"))]
+pub struct UpdateAgentEndpointBody {
+  /// 如 `https://10.0.0.1:5001`
+  #[validate(custom(function = "validate_api_url"))]
+  pub api_url: String,
+  /// PEM 格式的 CA 证书
+  #[validate(custom(function = "validate_ca_cert"))]
+  pub ca_cert: Option<String>,
+  /// Agent 证书的 SHA-256 指纹
+  #[validate(custom(function = "validate_cert_fingerprint"))]
+  pub cert_fingerprint: Option<String>,
+}
+
+fn validate_endpoint_tls(body: &UpdateAgentEndpointBody) -> Result<(), ValidationError> {
+  validate_tls_scheme(
+    Some(&body.api_url),
+    body.ca_cert.as_deref(),
+    body.cert_fingerprint.as_deref(),
+  )
+}
+
+/// 管理员视角的用户信息，不包含密码
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AdminUser {
+  pub user_id: String,
+  pub nickname: String,
+  pub email: String,
+  pub r#type: UserType,
+  pub status: UserStatus,
+  #[schema(value_type = String, format = DateTime)]
+  pub created_at: DateTimeUtc,
+  #[schema(value_type = Option<String>, format = DateTime)]
+  pub updated_at: Option<DateTimeUtc>,
+}
+
+impl From<user::Model> for AdminUser {
+  fn from(user: user::Model) -> Self {
+    Self {
+      user_id: user.user_id,
+      nickname: user.nickname,
+      email: user.email,
+      r#type: user.r#type,
+      status: user.status,
+      created_at: user.created_at,
+      updated_at: user.updated_at,
+    }
+  }
+}
+
+/// 管理员视角的 Agent 信息，不包含通信 token
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AdminAgent {
+  pub id: i32,
+  pub hostname: String,
+  pub ip_address: String,
+  /// 站点域名解析到的地址
+  pub public_ips: Vec<String>,
+  pub api_url: String,
+  pub ca_cert: Option<String>,
+  pub cert_fingerprint: Option<String>,
+  pub storage_path: String,
+  pub available_space: i32,
+  pub status: AgentStatus,
+  pub tags: Option<String>,
+  #[schema(value_type = Option<String>, format = DateTime)]
+  pub last_heartbeat: Option<DateTimeUtc>,
+  #[schema(value_type = String, format = DateTime)]
+  pub created_at: DateTimeUtc,
+  #[schema(value_type = Option<String>, format = DateTime)]
+  pub updated_at: Option<DateTimeUtc>,
+}
+
+impl From<agent::Model> for AdminAgent {
+  fn from(agent: agent::Model) -> Self {
+    let public_ips = agent.public_ips().iter().map(ToString::to_string).collect();
+    Self {
+      id: agent.id,
+      hostname: agent.hostname,
+      ip_address: agent.ip_address,
+      public_ips,
+      api_url: agent.api_url,
+      ca_cert: agent.ca_cert,
+      cert_fingerprint: agent.cert_fingerprint,
+      storage_path: agent.storage_path,
+      available_space: agent.available_space,
+      status: agent.status,
+      tags: agent.tags,
+      last_heartbeat: agent.last_heartbeat,
+      created_at: agent.created_at,
+      updated_at: agent.updated_at,
+    }
+  }
+}
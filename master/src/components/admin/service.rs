@@ -0,0 +1,294 @@
+use common::{
+  Paginated,
+  master::{MigrateSiteRequest, TaskResponse},
+};
+use entity::{
+  agent, audit_log, deployment,
+  site::{self, SiteStatus},
+  task::TaskType,
+};
+use helpers::{
+  time::utc_now,
+  uuid::{Alphabet, nanoid},
+};
+use rpc::tls::normalize_fingerprint;
+use sea_orm::{ActiveValue::Set, IntoActiveModel};
+use serde_json::json;
+
+use crate::{
+  app::AppState,
+  components::{
+    admin::model::*,
+    deployment::service::transition_by_id,
+    site::service::{remove_site, revoke_site},
+    task::service::{MigratePayload, NewTask, enqueue},
+  },
+  error::AppError,
+  helper::{page_args, paginated},
+  repository::{AuditLogFilter, DeploymentFilter, SiteFilter, UserFilter},
+  types::ServiceResult,
+};
+
+/// 校验当前用户是否为管理员
+pub async fn ensure_admin(state: &AppState, user_id: &str) -> ServiceResult<()> {
+  if state.repo.user().is_admin_user(user_id).await? {
+    Ok(())
+  } else {
+    Err(AppError::Forbidden)
+  }
+}
+
+pub async fn list_users(
+  state: &AppState,
+  query: ListUsersQuery,
+) -> ServiceResult<Paginated<AdminUser>> {
+  let (page, page_size) = page_args(query.page, query.page_size);
+  let filter = UserFilter {
+    status: query.status,
+    r#type: query.r#type,
+    keyword: query.keyword,
+  };
+  let (users, total) = state
+    .repo
+    .user()
+    .list_users(filter, page, page_size)
+    .await?;
+  Ok(paginated(users, total, page, page_size))
+}
+
+pub async fn get_user(state: &AppState, user_id: String) -> ServiceResult<AdminUser> {
+  let user = state
+    .repo
+    .user()
+    .get_user_by_id(user_id)
+    .await?
+    .ok_or(AppError::UserNotFound)?;
+  Ok(user.into())
+}
+
+/// 修改用户状态，管理员不能修改自己的状态
+pub async fn update_user_status(
+  state: &AppState,
+  operator_id: &str,
+  user_id: String,
+  body: UpdateUserStatusBody,
+) -> ServiceResult<AdminUser> {
+  if operator_id == user_id {
+    return Err(AppError::Forbidden);
+  }
+  let user = state
+    .repo
+    .user()
+    .get_user_by_id(user_id)
+    .await?
+    .ok_or(AppError::UserNotFound)?;
+  let mut active_user = user.into_active_model();
+  active_user.status = Set(body.status);
+  active_user.updated_at = Set(Some(utc_now()));
+  let user = state.repo.user().update_user(active_user).await?;
+  Ok(user.into())
+}
+
+pub async fn list_sites(
+  state: &AppState,
+  query: ListSitesQuery,
+) -> ServiceResult<Paginated<site::Model>> {
+  let (page, page_size) = page_args(query.page, query.page_size);
+  let filter = SiteFilter {
+    user_id: query.user_id,
+    status: query.status,
+    domain: query.domain,
+  };
+  let (sites, total) = state
+    .repo
+    .site()
+    .list_sites(filter, query.sort, query.order, page, page_size)
+    .await?;
+  Ok(paginated(sites, total, page, page_size))
+}
+
+/// 修改站点状态，禁用站点时会同时撤销 Agent 上已发布的内容
+pub async fn update_site_status(
+  state: &AppState,
+  site_id: String,
+  body: UpdateSiteStatusBody,
+) -> ServiceResult<site::Model> {
+  let site = state
+    .repo
+    .site()
+    .get_site_by_id(&site_id)
+    .await?
+    .ok_or(AppError::SiteNotFound)?;
+  if body.status == SiteStatus::Disabled && site.status != SiteStatus::Disabled {
+    revoke_site(state, &site).await?;
+  }
+  let mut active_site = site.into_active_model();
+  active_site.status = Set(body.status);
+  active_site.updated_at = Set(Some(utc_now()));
+  let site = state.repo.site().update_site(active_site).await?;
+  Ok(site)
+}
+
+pub async fn delete_site(state: &AppState, site_id: String) -> ServiceResult<()> {
+  let site = state
+    .repo
+    .site()
+    .get_site_by_id(&site_id)
+    .await?
+    .ok_or(AppError::SiteNotFound)?;
+  remove_site(state, &site).await
+}
+
+/// 提交迁移任务，将站点当前的部署迁移到其他 Agent
+pub async fn migrate_site(
+  state: &AppState,
+  admin_id: String,
+  site_id: String,
+  body: MigrateSiteRequest,
+  idempotency_key: Option<String>,
+) -> ServiceResult<TaskResponse> {
+  let site = state
+    .repo
+    .site()
+    .get_site_by_id(&site_id)
+    .await?
+    .ok_or(AppError::SiteNotFound)?;
+  let deployment_id = site.deployment_id.ok_or(AppError::DeploymentNotFound)?;
+  if let Some(agent_id) = body.agent_id {
+    if !state.repo.agent().has_agent_by_id(agent_id).await? {
+      return Err(AppError::AgentNotFound);
+    }
+  }
+  // 同一个站点可以多次迁移，未提供幂等键时每次请求都是新的任务
+  let idempotency_key = match idempotency_key {
+    Some(key) => format!("user:{}:{}", admin_id, key),
+    None => format!("migrate:{}:{}", site_id, nanoid(&Alphabet::DEFAULT, 12)),
+  };
+  let task = enqueue(
+    state,
+    NewTask {
+      r#type: TaskType::Migrate,
+      user_id: admin_id,
+      site_id,
+      deployment_id: Some(deployment_id),
+      payload: json!(MigratePayload {
+        agent_id: body.agent_id
+      }),
+      idempotency_key,
+    },
+  )
+  .await?;
+  Ok(task.into())
+}
+
+pub async fn list_deployments(
+  state: &AppState,
+  query: ListDeploymentsQuery,
+) -> ServiceResult<Paginated<deployment::Model>> {
+  let (page, page_size) = page_args(query.page, query.page_size);
+  let filter = DeploymentFilter {
+    site_id: query.site_id,
+    agent_id: query.agent_id,
+    status: query.status,
+  };
+  let (deployments, total) = state
+    .repo
+    .deployment()
+    .list_deployments(filter, query.sort, query.order, page, page_size)
+    .await?;
+  Ok(paginated(deployments, total, page, page_size))
+}
+
+pub async fn update_deployment_status(
+  state: &AppState,
+  admin_id: String,
+  deployment_id: i32,
+  body: UpdateDeploymentStatusBody,
+) -> ServiceResult<deployment::Model> {
+  transition_by_id(
+    state,
+    deployment_id,
+    body.status,
+    &format!("admin:{}", admin_id),
+    body.reason,
+  )
+  .await
+}
+
+pub async fn list_agents(
+  state: &AppState,
+  query: ListAgentsQuery,
+) -> ServiceResult<Paginated<AdminAgent>> {
+  let (page, page_size) = page_args(query.page, query.page_size);
+  let (agents, total) = state
+    .repo
+    .agent()
+    .list_agents(query.status, query.sort, query.order, page, page_size)
+    .await?;
+  Ok(paginated(agents, total, page, page_size))
+}
+
+pub async fn update_agent_status(
+  state: &AppState,
+  agent_id: i32,
+  body: UpdateAgentStatusBody,
+) -> ServiceResult<AdminAgent> {
+  let agent: agent::Model = state
+    .repo
+    .agent()
+    .get_agent(agent_id)
+    .await?
+    .ok_or(AppError::AgentNotFound)?;
+  let mut active_agent = agent.into_active_model();
+  active_agent.status = Set(body.status);
+  active_agent.updated_at = Set(Some(utc_now()));
+  let agent = state.repo.agent().update_agent(active_agent).await?;
+  Ok(agent.into())
+}
+
+pub async fn update_agent_endpoint(
+  state: &AppState,
+  agent_id: i32,
+  body: UpdateAgentEndpointBody,
+) -> ServiceResult<AdminAgent> {
+  let agent: agent::Model = state
+    .repo
+    .agent()
+    .get_agent(agent_id)
+    .await?
+    .ok_or(AppError::AgentNotFound)?;
+  let mut active_agent = agent.into_active_model();
+  active_agent.api_url = Set(body.api_url.trim_end_matches('/').to_string());
+  active_agent.ca_cert = Set(body.ca_cert);
+  active_agent.cert_fingerprint = Set(
+    body
+      .cert_fingerprint
+      .as_deref()
+      .and_then(normalize_fingerprint),
+  );
+  active_agent.updated_at = Set(Some(utc_now()));
+  let agent = state.repo.agent().update_agent(active_agent).await?;
+  Ok(agent.into())
+}
+
+pub async fn list_audit_logs(
+  state: &AppState,
+  query: ListAuditLogsQuery,
+) -> ServiceResult<Paginated<audit_log::Model>> {
+  let (page, page_size) = page_args(query.page, query.page_size);
+  let filter = AuditLogFilter {
+    actor: query.actor,
+    action: query.action,
+    target: query.target,
+    result: query.result,
+    ip: query.ip,
+    from: query.from,
+    to: query.to,
+  };
+  let (logs, total) = state
+    .repo
+    .audit_log()
+    .list_logs(filter, page, page_size)
+    .await?;
+  Ok(paginated(logs, total, page, page_size))
+}
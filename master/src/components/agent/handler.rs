@@ -1,79 +1,117 @@
 use actix_web::{
   HttpRequest, HttpResponse, get, post,
-  web::{Data, Json, Path, ReqData},
+  web::{Data, Json, Path},
 };
-use common::master::AssignTaskRequest;
-use helpers::jwt;
+use common::{
+  Response,
+  master::{AssignTaskRequest, TaskResponse},
+};
+use entity::audit_log::AuditAction;
+use serde_json::Value;
+use validator::Validate;
 
 use crate::{
   app::AppState,
-  components::agent::{model::*, service},
+  components::{
+    agent::{model::*, service},
+    audit::service::{AuditEntry, record},
+  },
   error::AppError,
-  helper::extract_token,
-  middlewares::JwtPayload,
+  helper::{client_ip, extract_idempotency_key, extract_user_id},
   traits::IntoHttpResponse,
 };
 
+#[utoipa::path(
+  tag = "agent",
+  responses((status = OK, body = Response<Value>)),
+  security(("bearer_auth" = []))
+)]
 #[post("/agent")]
 pub async fn register_agent(
   req: HttpRequest,
   state: Data<AppState>,
   body: Json<RegisterAgentBody>,
 ) -> Result<HttpResponse, AppError> {
-  let user_id = jwt::verify(&extract_token(&req)?, &state.login_token_key)?
-    .claims
-    .data;
-  let Json(RegisterAgentBody {
-    hostname,
-    ip_address,
-    storage_path,
-    available_space,
-  }) = body;
-  service::register_agent(
-    &state,
-    user_id,
-    hostname,
-    ip_address,
-    storage_path,
-    available_space,
-  )
-  .await
-  .into_http_response()
+  let user_id = extract_user_id(&req, &state).await?;
+  body.0.validate()?;
+  let mut entry = AuditEntry::new(format!("user:{}", user_id), AuditAction::AgentRegister)
+    .ip(client_ip(&req, &state))
+    .detail(format!("{} ({})", body.hostname, body.ip_address));
+  let result = service::register_agent(&state, user_id, body.into_inner()).await;
+  if let Some(agent_id) = result.as_ref().ok().and_then(|agent| agent["id"].as_i64()) {
+    entry = entry.target(format!("agent:{}", agent_id));
+  }
+  record(&state, entry, &result).await;
+  result.into_http_response()
 }
 
+#[utoipa::path(tag = "agent", responses((status = OK, body = Response<Value>)))]
 #[get("/agent/{agent_id}")]
 pub async fn get_agent_status(
   state: Data<AppState>,
-  agent_id: Path<u32>,
+  agent_id: Path<i32>,
 ) -> Result<HttpResponse, AppError> {
   service::get_agent_status(&state, agent_id.into_inner())
     .await
     .into_http_response()
 }
 
+#[utoipa::path(
+  tag = "agent",
+  responses((status = OK, body = Response<Value>)),
+  security(("bearer_auth" = []))
+)]
 #[post("/agent/{agent_id}/token")]
 pub async fn refresh_agent_token(
+  req: HttpRequest,
   state: Data<AppState>,
-  req_data: ReqData<JwtPayload>,
-  agent_id: Path<u32>,
+  agent_id: Path<i32>,
 ) -> Result<HttpResponse, AppError> {
-  service::refresh_agent_token(&state, req_data.user_id.clone(), agent_id.into_inner())
-    .await
-    .into_http_response()
+  // 暂停或删除的用户不能再刷新 token
+  let user_id = extract_user_id(&req, &state).await?;
+  let agent_id = agent_id.into_inner();
+  let entry = AuditEntry::new(format!("user:{}", user_id), AuditAction::AgentTokenRefresh)
+    .target(format!("agent:{}", agent_id))
+    .ip(client_ip(&req, &state));
+  let result = service::refresh_agent_token(&state, user_id, agent_id).await;
+  record(&state, entry, &result).await;
+  result.into_http_response()
 }
 
+#[utoipa::path(
+  tag = "agent",
+  params(("Idempotency-Key" = Option<String>, Header, description = "重复提交时返回同一个任务")),
+  responses((status = OK, body = Response<TaskResponse>)),
+  security(("bearer_auth" = []))
+)]
 #[post("/agent/task")]
 pub async fn assign_task(
   state: Data<AppState>,
+  req: HttpRequest,
   body: Json<AssignTaskRequest>,
 ) -> Result<HttpResponse, AppError> {
-  service::assign_task(
+  let user_id = extract_user_id(&req, &state).await?;
+  let idempotency_key = extract_idempotency_key(&req)?;
+  let action = match body.0.r#type.as_str() {
+    "revoke" => AuditAction::SiteRevoke,
+    _ => AuditAction::SitePublish,
+  };
+  let mut entry = AuditEntry::new(format!("user:{}", user_id), action)
+    .target(format!("site:{}", body.0.site_id))
+    .ip(client_ip(&req, &state));
+  if let Some(domain) = &body.0.bind_domain {
+    entry = entry.detail(format!("bind_domain: {}", domain));
+  }
+  let result = service::assign_task(
     &state,
+    user_id,
     body.0.r#type,
     body.0.site_id,
     body.0.deployment_id,
     body.0.bind_domain,
+    idempotency_key,
   )
-  .await
-  .into_http_response()
+  .await;
+  record(&state, entry, &result).await;
+  result.into_http_response()
 }
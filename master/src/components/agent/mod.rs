@@ -1,8 +1,9 @@
 mod handler;
 pub mod model;
-mod service;
+pub mod service;
 
 use actix_web::web::ServiceConfig;
+use utoipa::OpenApi;
 
 pub struct AgentComponent;
 
@@ -14,3 +15,13 @@ impl AgentComponent {
     cfg.service(handler::assign_task);
   }
 }
+
+/// Agent相关接口的 OpenAPI 文档
+#[derive(OpenApi)]
+#[openapi(paths(
+  handler::register_agent,
+  handler::get_agent_status,
+  handler::refresh_agent_token,
+  handler::assign_task,
+))]
+pub struct AgentApi;
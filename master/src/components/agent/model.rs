@@ -1,9 +1,73 @@
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::{Validate, ValidateUrl, ValidationError};
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema, Validate)]
+#[validate(schema(function = "validate_register_tls"))]
 pub struct RegisterAgentBody {
   pub hostname: String,
   pub ip_address: String,
   pub storage_path: String,
-  pub available_space: u32,
+  pub available_space: i32,
+  /// Agent API 的地址，默认为 `http://{ip_address}:5001`
+  #[validate(custom(function = "validate_api_url"))]
+  pub api_url: Option<String>,
+  /// PEM 格式的 CA 证书，Master 只信任由它签发的 Agent 证书
+  #[validate(custom(function = "validate_ca_cert"))]
+  pub ca_cert: Option<String>,
+  /// Agent 证书的 SHA-256 指纹，设置后只接受该证书
+  #[validate(custom(function = "validate_cert_fingerprint"))]
+  pub cert_fingerprint: Option<String>,
+}
+
+/// 只支持 http 和 https
+pub fn validate_api_url(api_url: &str) -> Result<(), ValidationError> {
+  let valid = ["http://", "https://"].iter().any(|scheme| {
+    api_url
+      .strip_prefix(scheme)
+      .is_some_and(|rest| !rest.is_empty())
+  }) && api_url.validate_url();
+  if valid {
+    Ok(())
+  } else {
+    Err(ValidationError::new("invalid_api_url"))
+  }
+}
+
+pub fn validate_ca_cert(ca_cert: &str) -> Result<(), ValidationError> {
+  if rpc::tls::is_valid_ca_cert(ca_cert) {
+    Ok(())
+  } else {
+    Err(ValidationError::new("invalid_ca_cert"))
+  }
+}
+
+pub fn validate_cert_fingerprint(fingerprint: &str) -> Result<(), ValidationError> {
+  if rpc::tls::normalize_fingerprint(fingerprint).is_some() {
+    Ok(())
+  } else {
+    Err(ValidationError::new("invalid_cert_fingerprint"))
+  }
+}
+
+/// 校验证书时 Agent 必须使用 https
+pub fn validate_tls_scheme(
+  api_url: Option<&str>,
+  ca_cert: Option<&str>,
+  cert_fingerprint: Option<&str>,
+) -> Result<(), ValidationError> {
+  let has_tls = ca_cert.is_some() || cert_fingerprint.is_some();
+  if has_tls && !api_url.is_some_and(|url| url.starts_with("https://")) {
+    Err(ValidationError::new("tls_requires_https"))
+  } else {
+    Ok(())
+  }
+}
+
+fn validate_register_tls(body: &RegisterAgentBody) -> Result<(), ValidationError> {
+  validate_tls_scheme(
+    body.api_url.as_deref(),
+    body.ca_cert.as_deref(),
+    body.cert_fingerprint.as_deref(),
+  )
 }
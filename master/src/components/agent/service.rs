@@ -1,23 +1,49 @@
-use std::{net::Ipv4Addr, str::FromStr};
+use std::net::{IpAddr, SocketAddr};
 
+use common::{agent::DEFAULT_AGENT_PORT, master::TaskResponse};
 use entity::{
-  agent::{self, AgentStatus},
+  agent::{self, AgentStatus, join_public_ips},
   deployment::DeploymentStatus,
+  task::{TaskStatus, TaskType},
 };
 use helpers::{jwt, time::utc_now};
+use rpc::{AgentEndpoint, tls::normalize_fingerprint};
 use sea_orm::{IntoActiveModel, Set};
 use serde_json::{Value, json};
 
-use crate::{app::AppState, error::AppError, types::ServiceResult};
+use crate::{
+  app::AppState,
+  components::{
+    agent::model::RegisterAgentBody,
+    deployment::service::{can_transition, transition},
+    task::service::{NewTask, PublishPayload, enqueue},
+  },
+  error::AppError,
+  types::ServiceResult,
+};
+
+/// IPv6 地址需要放在方括号中
+pub(crate) fn default_api_url(ip_address: &str) -> String {
+  match ip_address.parse::<IpAddr>() {
+    Ok(ip) => format!("http://{}", SocketAddr::new(ip, DEFAULT_AGENT_PORT)),
+    Err(_) => format!("http://{}:{}", ip_address, DEFAULT_AGENT_PORT),
+  }
+}
 
 pub async fn register_agent(
   state: &AppState,
   user_id: String,
-  hostname: String,
-  ip_address: String,
-  storage_path: String,
-  available_space: u32,
+  body: RegisterAgentBody,
 ) -> ServiceResult<Value> {
+  let RegisterAgentBody {
+    hostname,
+    ip_address,
+    storage_path,
+    available_space,
+    api_url,
+    ca_cert,
+    cert_fingerprint,
+  } = body;
   if !state.repo.user().is_admin_user(&user_id).await? {
     return Err(AppError::Forbidden);
   }
@@ -35,8 +61,12 @@ pub async fn register_agent(
     &state.register_agent_key,
     state.register_agent_key_expire,
   )?;
+  let api_url = api_url.unwrap_or_else(|| default_api_url(&ip_address));
   let active_agent = agent::ActiveModel {
     hostname: Set(hostname),
+    api_url: Set(api_url.trim_end_matches('/').to_string()),
+    ca_cert: Set(ca_cert),
+    cert_fingerprint: Set(cert_fingerprint.as_deref().and_then(normalize_fingerprint)),
     ip_address: Set(ip_address),
     storage_path: Set(storage_path),
     available_space: Set(available_space),
@@ -49,14 +79,17 @@ pub async fn register_agent(
   Ok(json!(result))
 }
 
-pub async fn get_agent_status(state: &AppState, agent_id: u32) -> ServiceResult<Value> {
+pub async fn get_agent_status(state: &AppState, agent_id: i32) -> ServiceResult<Value> {
   if let Some(agent) = state.repo.agent().get_agent(agent_id).await? {
     let data = state
       .agent_rpc
-      .get_agent_heartbeat(&agent.ip_address)
+      .get_agent_heartbeat(&AgentEndpoint::from(&agent))
       .await?;
     let mut active_agent = agent.into_active_model();
     active_agent.last_heartbeat = Set(Some(utc_now()));
+    if !data.public_ips.is_empty() {
+      active_agent.public_ips = Set(Some(join_public_ips(&data.public_ips)));
+    }
     state.repo.agent().update_agent(active_agent).await?;
     Ok(json!({
       "cpu_cores" : data.cpu_cores,
@@ -64,6 +97,7 @@ pub async fn get_agent_status(state: &AppState, agent_id: u32) -> ServiceResult<
       "total_memory": data.total_memory,
       "free_memory": data.free_memory,
       "memory_usage": data.memory_usage,
+      "public_ips": data.public_ips,
     }))
   } else {
     Err(AppError::AgentNotFound)
@@ -73,7 +107,7 @@ pub async fn get_agent_status(state: &AppState, agent_id: u32) -> ServiceResult<
 pub async fn refresh_agent_token(
   state: &AppState,
   user_id: String,
-  agent_id: u32,
+  agent_id: i32,
 ) -> ServiceResult<Value> {
   if !state.repo.user().is_admin_user(&user_id).await? {
     return Err(AppError::Forbidden);
@@ -102,75 +136,150 @@ pub async fn refresh_agent_token(
   }))
 }
 
+/// 提交发布或撤销任务，任务由调度器在后台执行，返回任务信息供调用方轮询。
+/// 未提供幂等键时，同一个部署的同类任务还没结束时返回该任务，结束后可以再次提交
 pub async fn assign_task(
   state: &AppState,
+  user_id: String,
   r#type: String,
   site_id: String,
-  deployment_id: u32,
+  deployment_id: i32,
   bind_domain: Option<String>,
-) -> ServiceResult<Value> {
+  idempotency_key: Option<String>,
+) -> ServiceResult<TaskResponse> {
+  let r#type = match r#type.as_str() {
+    "publish" => TaskType::Publish,
+    "revoke" => TaskType::Revoke,
+    _ => return Err(AppError::NotImplemented),
+  };
   let site = state
     .repo
     .site()
     .get_site_by_id(&site_id)
     .await?
     .ok_or(AppError::SiteNotFound)?;
+  if site.user_id != user_id && !state.repo.user().is_admin_user(&user_id).await? {
+    return Err(AppError::Forbidden);
+  }
   let deployment = state
     .repo
     .deployment()
     .get_deployment(deployment_id)
     .await?
+    .filter(|deployment| deployment.site_id == site_id)
     .ok_or(AppError::DeploymentNotFound)?;
-  let agent = state
-    .repo
-    .agent()
-    .get_agent(deployment.agent_id)
-    .await?
-    .ok_or(AppError::AgentNotFound)?;
-  let preview_domain = format!("preview_{}.jinqiu.wang", site_id);
-  state
-    .cloudflare_rpc
-    .create_a_record(&preview_domain, Ipv4Addr::from_str(&agent.ip_address)?)
-    .await;
-  if r#type == "publish" {
-    state
-      .agent_rpc
-      .task_publish(
-        site_id,
+  let idempotency_key = match idempotency_key {
+    Some(key) => format!("user:{}:{}", user_id, key),
+    None => {
+      let latest = state
+        .repo
+        .task()
+        .get_latest_task(deployment_id, r#type)
+        .await?;
+      if let Some(task) = latest
+        .as_ref()
+        .filter(|task| matches!(task.status, TaskStatus::Pending | TaskStatus::Running))
+      {
+        return Ok(task.clone().into());
+      }
+      // 以最近一个任务区分每次提交，同时提交的请求得到相同的键，由唯一索引去重
+      format!(
+        "{}:{}:{}",
+        r#type.as_str(),
         deployment_id,
-        agent.ip_address,
-        site.bandwidth.to_string(),
-        bind_domain.clone(),
-        preview_domain.clone(),
+        latest.map_or(0, |task| task.id)
       )
-      .await?;
-    let preview_url = "http://".to_string() + &preview_domain;
-    let mut active_deployment = deployment.into_active_model();
-    active_deployment.status = Set(DeploymentStatus::Published);
-    active_deployment.deploy_preview_url = Set(Some(preview_url.clone()));
-    state
-      .repo
-      .deployment()
-      .update_deployment(active_deployment)
-      .await?;
-    if let Some(bind_domain) = bind_domain {
-      let mut active_site = site.into_active_model();
-      active_site.domain = Set(Some(bind_domain.clone()));
-      state.repo.site().update_site(active_site).await?;
-      return Ok(json!({
-        "preview_url": preview_url,
-        "bind_url": "http://".to_string() + &bind_domain,
-      }));
+    }
+  };
+  if let Some(task) = state
+    .repo
+    .task()
+    .get_task_by_idempotency_key(&idempotency_key)
+    .await?
+  {
+    return Ok(task.into());
+  }
+  if r#type == TaskType::Publish {
+    let actor = format!("user:{}", user_id);
+    // 请求发布即视为上传已完成，旧版本客户端不会单独上报 Uploaded
+    let deployment = if deployment.status == DeploymentStatus::Uploading {
+      transition(state, deployment, DeploymentStatus::Uploaded, &actor, None).await?
     } else {
-      return Ok(json!({
-        "preview_url": preview_url,
-      }));
+      deployment
+    };
+    // 已发布的部署可以重新发布，如撤销后再次发布
+    if deployment.status != DeploymentStatus::Published
+      && !can_transition(&deployment.status, &DeploymentStatus::Published)
+    {
+      return Err(AppError::InvalidStatusTransition {
+        from: deployment.status,
+        to: DeploymentStatus::Published,
+      });
     }
-  } else if r#type == "revoke" {
-    state
-      .agent_rpc
-      .task_revoke(site_id, &agent.ip_address)
-      .await?;
   }
-  Ok(Value::Null)
+  let task = enqueue(
+    state,
+    NewTask {
+      r#type,
+      user_id,
+      site_id,
+      deployment_id: Some(deployment_id),
+      payload: json!(PublishPayload { bind_domain }),
+      idempotency_key,
+    },
+  )
+  .await?;
+  Ok(task.into())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[cfg(feature = "sqlite")]
+  #[actix_web::test]
+  async fn test_assign_task_again() {
+    use entity::user::UserType;
+    use rpc::MockAgentApi;
+
+    use crate::testing::{create_agent, create_site, create_user, finish, test_state};
+
+    let state = test_state(MockAgentApi::new()).await;
+    create_user(&state, "owner", UserType::Normal).await;
+    let agent = create_agent(&state, "10.0.0.1").await;
+    let (_, deployment) = create_site(
+      &state,
+      "site",
+      "owner",
+      agent.id,
+      DeploymentStatus::Published,
+    )
+    .await;
+    let assign = |r#type: &str| {
+      assign_task(
+        &state,
+        "owner".to_string(),
+        r#type.to_string(),
+        "site".to_string(),
+        deployment.id,
+        None,
+        None,
+      )
+    };
+
+    // 未结束的任务不会重复提交
+    let first = assign("publish").await.unwrap();
+    assert_eq!(assign("publish").await.unwrap().task_id, first.task_id);
+    finish(&state, first.task_id, TaskStatus::Succeeded).await;
+    let revoke = assign("revoke").await.unwrap();
+    finish(&state, revoke.task_id, TaskStatus::Succeeded).await;
+    // 撤销后再次发布
+    let second = assign("publish").await.unwrap();
+    assert_ne!(second.task_id, first.task_id);
+    // 最终失败后重新提交
+    finish(&state, second.task_id, TaskStatus::Failed).await;
+    let third = assign("publish").await.unwrap();
+    assert_ne!(third.task_id, second.task_id);
+    assert_eq!(third.status, TaskStatus::Pending);
+  }
 }
@@ -0,0 +1,135 @@
+use entity::audit_log::{self, AuditAction, AuditResult};
+use helpers::time::utc_now;
+use sea_orm::Set;
+
+use crate::{app::AppState, types::ServiceResult};
+
+/// 一条待写入的审计日志
+#[derive(Debug, Clone)]
+pub struct AuditEntry {
+  actor: String,
+  action: AuditAction,
+  target: Option<String>,
+  ip: Option<String>,
+  detail: Option<String>,
+}
+
+impl AuditEntry {
+  pub fn new(actor: impl Into<String>, action: AuditAction) -> Self {
+    AuditEntry {
+      actor: actor.into(),
+      action,
+      target: None,
+      ip: None,
+      detail: None,
+    }
+  }
+
+  pub fn target(mut self, target: impl Into<String>) -> Self {
+    self.target = Some(target.into());
+    self
+  }
+
+  pub fn ip(mut self, ip: impl Into<String>) -> Self {
+    self.ip = Some(ip.into());
+    self
+  }
+
+  pub fn detail(mut self, detail: impl Into<String>) -> Self {
+    self.detail = Some(detail.into());
+    self
+  }
+}
+
+/// 按操作结果写入审计日志，失败时把错误信息追加到 `detail`；写入失败只记录日志，不影响请求本身
+pub async fn record<T>(state: &AppState, entry: AuditEntry, result: &ServiceResult<T>) {
+  let (result, detail) = match result {
+    Ok(_) => (AuditResult::Success, entry.detail),
+    Err(err) => (
+      AuditResult::Failure,
+      Some(match entry.detail {
+        Some(detail) => format!("{}: {}", detail, err),
+        None => err.to_string(),
+      }),
+    ),
+  };
+  if let Err(err) = state
+    .repo
+    .audit_log()
+    .create_log(audit_log::ActiveModel {
+      actor: Set(entry.actor),
+      action: Set(entry.action),
+      target: Set(entry.target),
+      ip: Set(entry.ip),
+      result: Set(result),
+      detail: Set(detail),
+      created_at: Set(utc_now()),
+      ..Default::default()
+    })
+    .await
+  {
+    tracing::error!("Failed to write audit log: {}", err);
+  }
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+  use rpc::MockAgentApi;
+
+  use super::*;
+  use crate::{error::AppError, repository::AuditLogFilter, testing::test_state};
+
+  async fn logs(state: &AppState) -> Vec<audit_log::Model> {
+    let (logs, _) = state
+      .repo
+      .audit_log()
+      .list_logs(AuditLogFilter::default(), 0, 10)
+      .await
+      .unwrap();
+    logs
+  }
+
+  #[actix_web::test]
+  async fn test_record() {
+    let state = test_state(MockAgentApi::new()).await;
+    let entry = AuditEntry::new("user:admin", AuditAction::UserStatus)
+      .target("user:user")
+      .ip("127.0.0.1")
+      .detail("suspended");
+    record(&state, entry.clone(), &Ok(())).await;
+    record(
+      &state,
+      entry,
+      &ServiceResult::<()>::Err(AppError::Forbidden),
+    )
+    .await;
+    record(
+      &state,
+      AuditEntry::new("system", AuditAction::SiteDelete),
+      &ServiceResult::<()>::Err(AppError::SiteNotFound),
+    )
+    .await;
+
+    let logs = logs(&state).await;
+    assert_eq!(logs.len(), 3);
+    let (failure_without_detail, failure, success) = (&logs[0], &logs[1], &logs[2]);
+    assert_eq!(success.actor, "user:admin");
+    assert_eq!(success.action, AuditAction::UserStatus);
+    assert_eq!(success.target.as_deref(), Some("user:user"));
+    assert_eq!(success.ip.as_deref(), Some("127.0.0.1"));
+    assert_eq!(success.result, AuditResult::Success);
+    assert_eq!(success.detail.as_deref(), Some("suspended"));
+    // 失败时错误信息追加到 detail
+    assert_eq!(failure.result, AuditResult::Failure);
+    assert_eq!(
+      failure.detail,
+      Some(format!("suspended: {}", AppError::Forbidden))
+    );
+    assert_eq!(failure_without_detail.actor, "system");
+    assert_eq!(failure_without_detail.target, None);
+    assert_eq!(
+      failure_without_detail.detail,
+      Some(AppError::SiteNotFound.to_string())
+    );
+  }
+}
@@ -2,52 +2,201 @@ use actix_web::{
   HttpRequest, HttpResponse, get, post,
   web::{Data, Json, Path},
 };
-use common::master::CreateDeploymentRequest;
+use common::{
+  Response,
+  master::{
+    AppendDeploymentLogsRequest, CreateDeploymentRequest, CreateDeploymentResponse,
+    DeploymentLogsResponse, InitDeploymentUploadRequest, PrepareDeploymentRequest,
+    PrepareDeploymentResponse,
+  },
+};
+use entity::{audit_log::AuditAction, deployment_event};
+use sea_orm::ActiveEnum;
+use serde_json::Value;
 
 use crate::{
   app::AppState,
-  components::deployment::model::{UpdateDeploymentRequest, UpdateDeploymentStatusBody},
+  components::{
+    audit::service::{AuditEntry, record},
+    deployment::{
+      model::{UpdateDeploymentRequest, UpdateDeploymentStatusBody},
+      stream::sse_response,
+    },
+  },
   error::AppError,
-  helper::extract_token,
+  helper::{client_ip, extract_user_id},
   traits::IntoHttpResponse,
 };
 
 use super::service;
 
+#[utoipa::path(
+  tag = "deployment",
+  responses((status = OK, body = Response<CreateDeploymentResponse>)),
+  security(("bearer_auth" = []))
+)]
 #[post("/deployment")]
 pub async fn create_deployment(
   state: Data<AppState>,
+  req: HttpRequest,
   body: Json<CreateDeploymentRequest>,
 ) -> Result<HttpResponse, AppError> {
-  service::create_deployment(&state, body.0.site_id)
+  let user_id = extract_user_id(&req, &state).await?;
+  let mut entry = AuditEntry::new(format!("user:{}", user_id), AuditAction::DeploymentCreate)
+    .target(format!("site:{}", body.0.site_id))
+    .ip(client_ip(&req, &state));
+  let result = service::create_deployment(&state, user_id, body.0.site_id, body.0.sha256).await;
+  if let Ok(response) = &result {
+    entry = entry.target(format!("deployment:{}", response.deployment_id));
+  }
+  record(&state, entry, &result).await;
+  result.into_http_response()
+}
+
+/// 创建待上传的部署，构建完成后通过 `/deployment/{deployment_id}/upload` 开始上传
+#[utoipa::path(
+  tag = "deployment",
+  responses((status = OK, body = Response<PrepareDeploymentResponse>)),
+  security(("bearer_auth" = []))
+)]
+#[post("/deployment/prepare")]
+pub async fn prepare_deployment(
+  state: Data<AppState>,
+  req: HttpRequest,
+  body: Json<PrepareDeploymentRequest>,
+) -> Result<HttpResponse, AppError> {
+  let user_id = extract_user_id(&req, &state).await?;
+  let mut entry = AuditEntry::new(format!("user:{}", user_id), AuditAction::DeploymentCreate)
+    .target(format!("site:{}", body.0.site_id))
+    .ip(client_ip(&req, &state));
+  let result = service::prepare_deployment(&state, user_id, body.0.site_id)
+    .await
+    .map(|deployment| PrepareDeploymentResponse {
+      deployment_id: deployment.id,
+      agent_id: deployment.agent_id,
+    });
+  if let Ok(response) = &result {
+    entry = entry.target(format!("deployment:{}", response.deployment_id));
+  }
+  record(&state, entry, &result).await;
+  result.into_http_response()
+}
+
+#[utoipa::path(
+  tag = "deployment",
+  responses((status = OK, body = Response<CreateDeploymentResponse>)),
+  security(("bearer_auth" = []))
+)]
+#[post("/deployment/{deployment_id}/upload")]
+pub async fn init_deployment_upload(
+  state: Data<AppState>,
+  req: HttpRequest,
+  deployment_id: Path<i32>,
+  body: Json<InitDeploymentUploadRequest>,
+) -> Result<HttpResponse, AppError> {
+  let user_id = extract_user_id(&req, &state).await?;
+  service::init_deployment_upload(&state, user_id, deployment_id.into_inner(), body.0.sha256)
     .await
     .into_http_response()
 }
 
+#[utoipa::path(tag = "deployment", responses((status = OK, body = Response<Value>)))]
 #[get("/deployment/{deployment_id}")]
 pub async fn get_deployment(
   state: Data<AppState>,
-  deployment_id: Path<u32>,
+  deployment_id: Path<i32>,
 ) -> Result<HttpResponse, AppError> {
   service::get_deployment_info(&state, deployment_id.into_inner())
     .await
     .into_http_response()
 }
 
+#[utoipa::path(
+  tag = "deployment",
+  responses((status = OK, body = Response<Vec<deployment_event::Model>>)),
+  security(("bearer_auth" = []))
+)]
+#[get("/deployment/{deployment_id}/events")]
+pub async fn get_deployment_events(
+  state: Data<AppState>,
+  req: HttpRequest,
+  deployment_id: Path<i32>,
+) -> Result<HttpResponse, AppError> {
+  let user_id = extract_user_id(&req, &state).await?;
+  service::get_deployment_events(&state, user_id, deployment_id.into_inner())
+    .await
+    .into_http_response()
+}
+
+#[utoipa::path(
+  tag = "deployment",
+  responses((status = OK, body = Response<DeploymentLogsResponse>)),
+  security(("bearer_auth" = []))
+)]
+#[get("/deployment/{deployment_id}/logs")]
+pub async fn get_deployment_logs(
+  state: Data<AppState>,
+  req: HttpRequest,
+  deployment_id: Path<i32>,
+) -> Result<HttpResponse, AppError> {
+  let user_id = extract_user_id(&req, &state).await?;
+  service::get_deployment_logs(&state, user_id, deployment_id.into_inner())
+    .await
+    .into_http_response()
+}
+
+#[utoipa::path(
+  tag = "deployment",
+  responses((status = OK, body = Response<Value>)),
+  security(("bearer_auth" = []))
+)]
+#[post("/deployment/{deployment_id}/logs")]
+pub async fn append_deployment_logs(
+  state: Data<AppState>,
+  req: HttpRequest,
+  deployment_id: Path<i32>,
+  body: Json<AppendDeploymentLogsRequest>,
+) -> Result<HttpResponse, AppError> {
+  let user_id = extract_user_id(&req, &state).await?;
+  service::append_build_logs(&state, user_id, deployment_id.into_inner(), body.0.content)
+    .await
+    .into_http_response()
+}
+
+/// 以 Server-Sent Events 推送部署的状态变更、阶段和日志
+#[utoipa::path(
+  tag = "deployment",
+  responses((status = OK, description = "部署事件流", content_type = "text/event-stream", body = String)),
+  security(("bearer_auth" = []))
+)]
+#[get("/deployment/{deployment_id}/stream")]
+pub async fn stream_deployment(
+  state: Data<AppState>,
+  req: HttpRequest,
+  deployment_id: Path<i32>,
+) -> Result<HttpResponse, AppError> {
+  let user_id = extract_user_id(&req, &state).await?;
+  let (snapshot, receiver) =
+    service::subscribe_deployment(&state, user_id, deployment_id.into_inner()).await?;
+  Ok(sse_response(snapshot, receiver))
+}
+
 #[post("/deployment")]
 pub async fn update_deployment(
   state: Data<AppState>,
   req: HttpRequest,
   body: Json<UpdateDeploymentRequest>,
 ) -> Result<HttpResponse, AppError> {
-  let token = extract_token(&req)?;
-  service::update_deployment(&state, token, body.0.deployment_id, body.0.status)
+  let user_id = extract_user_id(&req, &state).await?;
+  service::update_deployment(&state, user_id, body.0.deployment_id, body.0.status)
     .await
     .into_http_response()
 }
 
+#[utoipa::path(tag = "deployment", responses((status = OK, body = Response<Value>)))]
 #[post("/deployment/status")]
 pub async fn update_deployment_status(
+  req: HttpRequest,
   state: Data<AppState>,
   body: Json<UpdateDeploymentStatusBody>,
 ) -> Result<HttpResponse, AppError> {
@@ -56,7 +205,11 @@ pub async fn update_deployment_status(
     deployment_id,
     status,
   }) = body;
-  service::update_deployment_status(&state, agent_token, deployment_id, status)
-    .await
-    .into_http_response()
+  let entry = AuditEntry::new("agent", AuditAction::DeploymentStatus)
+    .target(format!("deployment:{}", deployment_id))
+    .ip(client_ip(&req, &state))
+    .detail(status.to_value());
+  let result = service::update_deployment_status(&state, agent_token, deployment_id, status).await;
+  record(&state, entry, &result).await;
+  result.into_http_response()
 }
@@ -1,16 +1,39 @@
 mod handler;
 pub mod model;
-mod service;
+pub mod service;
+pub mod stream;
 
 use actix_web::web::ServiceConfig;
+use utoipa::OpenApi;
 
 pub struct DeploymentComponent;
 
 impl DeploymentComponent {
   pub fn config(cfg: &mut ServiceConfig) {
     cfg.service(handler::create_deployment);
+    cfg.service(handler::prepare_deployment);
+    cfg.service(handler::init_deployment_upload);
     cfg.service(handler::get_deployment);
+    cfg.service(handler::get_deployment_events);
+    cfg.service(handler::get_deployment_logs);
+    cfg.service(handler::stream_deployment);
+    cfg.service(handler::append_deployment_logs);
     cfg.service(handler::update_deployment);
     cfg.service(handler::update_deployment_status);
   }
 }
+
+/// 部署相关接口的 OpenAPI 文档
+#[derive(OpenApi)]
+#[openapi(paths(
+  handler::create_deployment,
+  handler::prepare_deployment,
+  handler::init_deployment_upload,
+  handler::get_deployment,
+  handler::get_deployment_events,
+  handler::get_deployment_logs,
+  handler::append_deployment_logs,
+  handler::stream_deployment,
+  handler::update_deployment_status,
+))]
+pub struct DeploymentApi;
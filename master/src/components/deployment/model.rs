@@ -1,15 +1,16 @@
 use entity::deployment::DeploymentStatus;
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct UpdateDeploymentRequest {
-  pub deployment_id: u32,
+  pub deployment_id: i32,
   pub status: DeploymentStatus,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct UpdateDeploymentStatusBody {
   pub agent_token: String,
-  pub deployment_id: u32,
+  pub deployment_id: i32,
   pub status: DeploymentStatus,
 }
@@ -1,63 +1,398 @@
-use common::master::CreateDeploymentResponse;
-use entity::deployment::{self, DeploymentStatus};
+use common::master::{CreateDeploymentResponse, DeploymentLogsResponse, DeploymentStreamEvent};
+use entity::{
+  deployment::{self, DeploymentStatus},
+  deployment_event,
+  webhook::WebhookEvent,
+};
 use helpers::{jwt, time::utc_now};
+use rpc::{AgentEndpoint, AgentUrl};
 use sea_orm::{IntoActiveModel, Set};
 use serde_json::{Value, json};
+use tokio::sync::broadcast::Receiver;
 
-use crate::{app::AppState, error::AppError, types::ServiceResult};
+use crate::{
+  app::AppState, components::webhook::service::emit_deployment_event, error::AppError,
+  types::ServiceResult,
+};
+
+/// 部署状态的合法迁移表，`Published` 与 `Failed` 为终态
+pub fn can_transition(from: &DeploymentStatus, to: &DeploymentStatus) -> bool {
+  use DeploymentStatus::*;
+  matches!(
+    (from, to),
+    (Pending, Uploading)
+      | (Pending, Failed)
+      | (Uploading, Uploaded)
+      | (Uploading, Failed)
+      | (Uploaded, Reviewing)
+      | (Uploaded, Published)
+      | (Uploaded, Failed)
+      | (Reviewing, Published)
+      | (Reviewing, Failed)
+  )
+}
+
+/// 校验并执行一次状态迁移，同时记录迁移事件
+pub async fn transition(
+  state: &AppState,
+  deployment: deployment::Model,
+  to: DeploymentStatus,
+  actor: &str,
+  reason: Option<String>,
+) -> ServiceResult<deployment::Model> {
+  if !can_transition(&deployment.status, &to) {
+    return Err(AppError::InvalidStatusTransition {
+      from: deployment.status,
+      to,
+    });
+  }
+  let from = deployment.status.clone();
+  let mut active_deployment = deployment.into_active_model();
+  active_deployment.status = Set(to.clone());
+  active_deployment.execution_time = Set(utc_now());
+  let deployment = state
+    .repo
+    .deployment()
+    .update_deployment(active_deployment)
+    .await?;
+  record_event(
+    state,
+    deployment.id,
+    Some(from),
+    to.clone(),
+    actor,
+    reason.clone(),
+  )
+  .await?;
+  match to {
+    DeploymentStatus::Published => {
+      emit_deployment_event(state, WebhookEvent::DeploymentPublished, &deployment, None).await
+    }
+    DeploymentStatus::Failed => {
+      emit_deployment_event(
+        state,
+        WebhookEvent::DeploymentFailed,
+        &deployment,
+        reason.as_deref(),
+      )
+      .await
+    }
+    _ => (),
+  }
+  Ok(deployment)
+}
+
+/// 按 ID 查找部署后执行状态迁移
+pub async fn transition_by_id(
+  state: &AppState,
+  deployment_id: i32,
+  to: DeploymentStatus,
+  actor: &str,
+  reason: Option<String>,
+) -> ServiceResult<deployment::Model> {
+  let deployment = state
+    .repo
+    .deployment()
+    .get_deployment(deployment_id)
+    .await?
+    .ok_or(AppError::DeploymentNotFound)?;
+  transition(state, deployment, to, actor, reason).await
+}
+
+async fn record_event(
+  state: &AppState,
+  deployment_id: i32,
+  from_status: Option<DeploymentStatus>,
+  to_status: DeploymentStatus,
+  actor: &str,
+  reason: Option<String>,
+) -> ServiceResult<()> {
+  state
+    .repo
+    .deployment_event()
+    .create_event(deployment_event::ActiveModel {
+      deployment_id: Set(deployment_id),
+      from_status: Set(from_status),
+      to_status: Set(to_status.clone()),
+      actor: Set(actor.to_string()),
+      reason: Set(reason.clone()),
+      created_at: Set(utc_now()),
+      ..Default::default()
+    })
+    .await?;
+  state.deployment_streams.publish(
+    deployment_id,
+    DeploymentStreamEvent::Status {
+      status: to_status,
+      reason,
+    },
+  );
+  Ok(())
+}
+
+/// 在部署日志末尾追加一段带来源和时间的输出
+pub async fn append_logs(
+  state: &AppState,
+  deployment: deployment::Model,
+  source: &str,
+  content: &str,
+) -> ServiceResult<deployment::Model> {
+  push_logs(state, deployment, source, content, true).await
+}
+
+async fn push_logs(
+  state: &AppState,
+  deployment: deployment::Model,
+  source: &str,
+  content: &str,
+  header: bool,
+) -> ServiceResult<deployment::Model> {
+  let mut logs = deployment.build_logs.clone().unwrap_or_default();
+  if header {
+    logs.push_str(&format!("==> [{}] {}\n", source, utc_now().to_rfc3339()));
+  }
+  logs.push_str(content);
+  if !content.ends_with('\n') {
+    logs.push('\n');
+  }
+  let deployment_id = deployment.id;
+  let mut active_deployment = deployment.into_active_model();
+  active_deployment.build_logs = Set(Some(logs));
+  let deployment = state
+    .repo
+    .deployment()
+    .update_deployment(active_deployment)
+    .await?;
+  for line in content.lines() {
+    state.deployment_streams.publish(
+      deployment_id,
+      DeploymentStreamEvent::Log {
+        source: source.to_string(),
+        line: line.to_string(),
+      },
+    );
+  }
+  Ok(deployment)
+}
+
+/// 把保存的日志还原为日志事件，`==> [source]` 行标记之后内容的来源
+fn log_events(logs: &str) -> Vec<DeploymentStreamEvent> {
+  let mut source = String::new();
+  let mut events = Vec::new();
+  for line in logs.lines() {
+    if let Some(header) = line.strip_prefix("==> [") {
+      if let Some((name, _)) = header.split_once(']') {
+        source = name.to_string();
+        continue;
+      }
+    }
+    events.push(DeploymentStreamEvent::Log {
+      source: source.clone(),
+      line: line.to_string(),
+    });
+  }
+  events
+}
+
+/// 订阅部署进度，返回已发生的事件和实时事件的接收端，部署已结束时接收端为空
+pub async fn subscribe_deployment(
+  state: &AppState,
+  user_id: String,
+  deployment_id: i32,
+) -> ServiceResult<(
+  Vec<DeploymentStreamEvent>,
+  Option<Receiver<DeploymentStreamEvent>>,
+)> {
+  get_owned_deployment(state, &user_id, deployment_id).await?;
+  // 先订阅再读取历史，避免两者之间发生的事件丢失
+  let receiver = state.deployment_streams.subscribe(deployment_id);
+  let deployment = state
+    .repo
+    .deployment()
+    .get_deployment(deployment_id)
+    .await?
+    .ok_or(AppError::DeploymentNotFound)?;
+  let mut snapshot: Vec<DeploymentStreamEvent> = state
+    .repo
+    .deployment_event()
+    .get_events_by_deployment_id(deployment_id)
+    .await?
+    .into_iter()
+    .map(|event| DeploymentStreamEvent::Status {
+      status: event.to_status,
+      reason: event.reason,
+    })
+    .collect();
+  snapshot.extend(log_events(
+    deployment.build_logs.as_deref().unwrap_or_default(),
+  ));
+  let finished = matches!(
+    deployment.status,
+    DeploymentStatus::Published | DeploymentStatus::Failed
+  );
+  Ok((snapshot, (!finished).then_some(receiver)))
+}
+
+/// 查找部署并校验请求者为站点所有者或管理员
+async fn get_owned_deployment(
+  state: &AppState,
+  user_id: &str,
+  deployment_id: i32,
+) -> ServiceResult<deployment::Model> {
+  let deployment = state
+    .repo
+    .deployment()
+    .get_deployment(deployment_id)
+    .await?
+    .ok_or(AppError::DeploymentNotFound)?;
+  let site = state
+    .repo
+    .site()
+    .get_site_by_id(&deployment.site_id)
+    .await?
+    .ok_or(AppError::SiteNotFound)?;
+  if site.user_id != user_id && !state.repo.user().is_admin_user(user_id).await? {
+    return Err(AppError::Forbidden);
+  }
+  Ok(deployment)
+}
 
 pub async fn create_deployment(
   state: &AppState,
+  user_id: String,
   site_id: String,
+  sha256: Option<String>,
 ) -> ServiceResult<CreateDeploymentResponse> {
-  let mut active_site = if let Some(site) = state.repo.site().get_site_by_id(&site_id).await? {
-    site
-  } else {
+  let actor = format!("user:{}", user_id);
+  let deployment = prepare_deployment(state, user_id, site_id).await?;
+  start_upload(state, deployment, sha256, &actor).await
+}
+
+/// 选择 Agent 并创建待上传的部署，CLI 在构建前调用以便实时上报构建输出
+pub async fn prepare_deployment(
+  state: &AppState,
+  user_id: String,
+  site_id: String,
+) -> ServiceResult<deployment::Model> {
+  if state.repo.site().get_site_by_id(&site_id).await?.is_none() {
     return Err(AppError::SiteNotFound);
   }
-  .into_active_model();
+  let agent = state
+    .repo
+    .agent()
+    .get_avaliable_agent()
+    .await?
+    .ok_or(AppError::AgentNotFound)?;
+  let deployment = state
+    .repo
+    .deployment()
+    .create_deployment(deployment::ActiveModel {
+      status: Set(DeploymentStatus::Pending),
+      agent_id: Set(agent.id),
+      site_id: Set(site_id),
+      execution_time: Set(utc_now()),
+      created_at: Set(utc_now()),
+      ..Default::default()
+    })
+    .await?;
+  record_event(
+    state,
+    deployment.id,
+    None,
+    DeploymentStatus::Pending,
+    &format!("user:{}", user_id),
+    None,
+  )
+  .await?;
+  Ok(deployment)
+}
 
-  if let Some(agent) = state.repo.agent().get_avaliable_agent().await? {
-    let deployment = state
-      .repo
-      .deployment()
-      .create_deployment(deployment::ActiveModel {
-        status: Set(DeploymentStatus::Pending),
-        agent_id: Set(agent.id),
-        site_id: Set(site_id.clone()),
-        execution_time: Set(utc_now()),
-        created_at: Set(utc_now()),
-        ..Default::default()
-      })
-      .await?;
-    let init_response = state
-      .agent_rpc
-      .init_upload_session(&agent.ip_address, deployment.site_id.clone())
-      .await?;
-    let mut active_deployment = deployment.into_active_model();
-    active_deployment.status = Set(DeploymentStatus::Uploading);
-    active_deployment.deploy_token = Set(Some(init_response.upload_token.clone()));
-    active_deployment.deploy_url = Set(Some(agent.ip_address.clone()));
-    let deployment = state
-      .repo
-      .deployment()
-      .update_deployment(active_deployment)
+/// 为预先创建的部署开启上传会话，只有 `Pending` 状态的部署可以开启
+pub async fn init_deployment_upload(
+  state: &AppState,
+  user_id: String,
+  deployment_id: i32,
+  sha256: Option<String>,
+) -> ServiceResult<CreateDeploymentResponse> {
+  let deployment = get_owned_deployment(state, &user_id, deployment_id).await?;
+  if deployment.status != DeploymentStatus::Pending {
+    return Err(AppError::InvalidStatusTransition {
+      from: deployment.status,
+      to: DeploymentStatus::Uploading,
+    });
+  }
+  start_upload(state, deployment, sha256, &format!("user:{}", user_id)).await
+}
+
+/// 向部署所在的 Agent 申请上传令牌，并把部署设为站点当前的部署
+async fn start_upload(
+  state: &AppState,
+  deployment: deployment::Model,
+  sha256: Option<String>,
+  actor: &str,
+) -> ServiceResult<CreateDeploymentResponse> {
+  let agent = state
+    .repo
+    .agent()
+    .get_agent(deployment.agent_id)
+    .await?
+    .ok_or(AppError::AgentNotFound)?;
+  let init_response = match state
+    .agent_rpc
+    .init_upload_session(
+      &AgentEndpoint::from(&agent),
+      deployment.site_id.clone(),
+      deployment.id,
+      sha256,
+      None,
+    )
+    .await
+  {
+    Ok(init_response) => init_response,
+    Err(err) => {
+      transition(
+        state,
+        deployment,
+        DeploymentStatus::Failed,
+        "system",
+        Some(err.to_string()),
+      )
       .await?;
+      return Err(err.into());
+    }
+  };
+  let deployment = transition(state, deployment, DeploymentStatus::Uploading, actor, None).await?;
+  let mut active_deployment = deployment.into_active_model();
+  active_deployment.deploy_token = Set(Some(init_response.upload_token.clone()));
+  active_deployment.deploy_url = Set(Some(agent.api_url.clone()));
+  let deployment = state
+    .repo
+    .deployment()
+    .update_deployment(active_deployment)
+    .await?;
+  if let Some(site) = state
+    .repo
+    .site()
+    .get_site_by_id(&deployment.site_id)
+    .await?
+  {
+    let mut active_site = site.into_active_model();
     active_site.deployment_id = Set(Some(deployment.id));
     state.repo.site().update_site(active_site).await?;
-    Ok(CreateDeploymentResponse {
-      deploy_url: agent.ip_address,
-      deploy_token: init_response.upload_token,
-      site_id: deployment.site_id,
-      agent_id: deployment.agent_id,
-      deployment_id: deployment.id,
-    })
-  } else {
-    Err(AppError::AgentNotFound)
   }
+  emit_deployment_event(state, WebhookEvent::DeploymentCreated, &deployment, None).await;
+  let AgentUrl { base_url, tls } = AgentUrl::from(&agent);
+  Ok(CreateDeploymentResponse {
+    deploy_url: base_url,
+    deploy_tls: tls,
+    deploy_token: init_response.upload_token,
+    site_id: deployment.site_id,
+    agent_id: deployment.agent_id,
+    deployment_id: deployment.id,
+  })
 }
 
-pub async fn get_deployment_info(state: &AppState, deployment_id: u32) -> ServiceResult<Value> {
+pub async fn get_deployment_info(state: &AppState, deployment_id: i32) -> ServiceResult<Value> {
   if let Some(deployment) = state
     .repo
     .deployment()
@@ -70,42 +405,221 @@ pub async fn get_deployment_info(state: &AppState, deployment_id: u32) -> Servic
   }
 }
 
+/// 查询部署的状态迁移记录，仅站点所有者与管理员可见
+pub async fn get_deployment_events(
+  state: &AppState,
+  user_id: String,
+  deployment_id: i32,
+) -> ServiceResult<Vec<deployment_event::Model>> {
+  get_owned_deployment(state, &user_id, deployment_id).await?;
+  Ok(
+    state
+      .repo
+      .deployment_event()
+      .get_events_by_deployment_id(deployment_id)
+      .await?,
+  )
+}
+
+/// 保存 CLI 在构建过程中分段上报的输出，连续的分段合并在同一个标题下
+pub async fn append_build_logs(
+  state: &AppState,
+  user_id: String,
+  deployment_id: i32,
+  content: String,
+) -> ServiceResult<Value> {
+  let deployment = get_owned_deployment(state, &user_id, deployment_id).await?;
+  let continued = deployment
+    .build_logs
+    .as_deref()
+    .and_then(|logs| logs.lines().rev().find(|line| line.starts_with("==> [")))
+    .is_some_and(|line| line.starts_with("==> [build]"));
+  push_logs(state, deployment, "build", &content, !continued).await?;
+  Ok(json!(()))
+}
+
+/// 查询部署的构建与发布日志
+pub async fn get_deployment_logs(
+  state: &AppState,
+  user_id: String,
+  deployment_id: i32,
+) -> ServiceResult<DeploymentLogsResponse> {
+  let deployment = get_owned_deployment(state, &user_id, deployment_id).await?;
+  Ok(DeploymentLogsResponse {
+    deployment_id: deployment.id,
+    status: deployment.status,
+    logs: deployment.build_logs.unwrap_or_default(),
+  })
+}
+
+/// 站点所有者与管理员修改部署状态
 pub async fn update_deployment(
   state: &AppState,
-  token: String,
-  deployment_id: u32,
+  user_id: String,
+  deployment_id: i32,
   status: DeploymentStatus,
 ) -> ServiceResult<Value> {
-  jwt::verify::<String>(&token, &state.login_token_key)?;
-  state
-    .repo
-    .deployment()
-    .update_deployment(deployment::ActiveModel {
-      id: Set(deployment_id),
-      status: Set(status),
-      execution_time: Set(utc_now()),
-      ..Default::default()
-    })
-    .await?;
+  let deployment = get_owned_deployment(state, &user_id, deployment_id).await?;
+  transition(
+    state,
+    deployment,
+    status,
+    &format!("user:{}", user_id),
+    None,
+  )
+  .await?;
   Ok(json!(()))
 }
 
+/// Agent 上报部署状态，只能修改在自己上运行的部署
 pub async fn update_deployment_status(
   state: &AppState,
   agent_token: String,
-  deployment_id: u32,
+  deployment_id: i32,
   status: DeploymentStatus,
 ) -> ServiceResult<Value> {
   jwt::verify::<String>(&agent_token, &state.register_agent_key)?;
-  state
+  let agent = state
+    .repo
+    .agent()
+    .get_agent_by_token(&agent_token)
+    .await?
+    .ok_or(AppError::Authorization)?;
+  let deployment = state
     .repo
     .deployment()
-    .update_deployment(deployment::ActiveModel {
-      id: Set(deployment_id),
-      status: Set(status),
-      execution_time: Set(utc_now()),
-      ..Default::default()
-    })
-    .await?;
+    .get_deployment(deployment_id)
+    .await?
+    .ok_or(AppError::DeploymentNotFound)?;
+  if deployment.agent_id != agent.id {
+    return Err(AppError::Forbidden);
+  }
+  transition(
+    state,
+    deployment,
+    status,
+    &format!("agent:{}", agent.id),
+    None,
+  )
+  .await?;
   Ok(json!(()))
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_log_events() {
+    let events = log_events("==> [build] 2025-01-01T00:00:00Z\nok\n==> [publish] x\n$ nginx -t\n");
+    let lines: Vec<(String, String)> = events
+      .into_iter()
+      .map(|event| match event {
+        DeploymentStreamEvent::Log { source, line } => (source, line),
+        _ => unreachable!(),
+      })
+      .collect();
+    assert_eq!(
+      lines,
+      vec![
+        ("build".to_string(), "ok".to_string()),
+        ("publish".to_string(), "$ nginx -t".to_string()),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_can_transition() {
+    use DeploymentStatus::*;
+    assert!(can_transition(&Pending, &Uploading));
+    assert!(can_transition(&Uploading, &Uploaded));
+    assert!(can_transition(&Uploaded, &Published));
+    assert!(can_transition(&Reviewing, &Failed));
+    assert!(!can_transition(&Pending, &Published));
+    assert!(!can_transition(&Published, &Uploading));
+    assert!(!can_transition(&Failed, &Pending));
+    assert!(!can_transition(&Uploading, &Uploading));
+  }
+
+  #[cfg(feature = "sqlite")]
+  #[actix_web::test]
+  async fn test_update_deployment_ownership() {
+    use entity::{agent, user::UserType};
+    use rpc::MockAgentApi;
+
+    use crate::testing::{create_agent, create_site, create_user, test_state};
+
+    let state = test_state(MockAgentApi::new()).await;
+    create_user(&state, "owner", UserType::Normal).await;
+    create_user(&state, "other", UserType::Normal).await;
+    let mut tokens = vec![];
+    for ip in ["10.0.0.1", "10.0.0.2"] {
+      let agent = create_agent(&state, ip).await;
+      let token = jwt::sign::<String>(agent.id.to_string(), &state.register_agent_key, 60).unwrap();
+      state
+        .repo
+        .agent()
+        .update_agent(agent::ActiveModel {
+          id: Set(agent.id),
+          token: Set(token.clone()),
+          ..Default::default()
+        })
+        .await
+        .unwrap();
+      tokens.push((agent.id, token));
+    }
+    let (_, deployment) = create_site(
+      &state,
+      "site",
+      "owner",
+      tokens[0].0,
+      DeploymentStatus::Uploaded,
+    )
+    .await;
+
+    let result = update_deployment(
+      &state,
+      "other".to_string(),
+      deployment.id,
+      DeploymentStatus::Failed,
+    )
+    .await;
+    assert!(matches!(result, Err(AppError::Forbidden)));
+    let result = update_deployment(&state, "other".to_string(), 0, DeploymentStatus::Failed).await;
+    assert!(matches!(result, Err(AppError::DeploymentNotFound)));
+    // 部署不在该 Agent 上
+    let result = update_deployment_status(
+      &state,
+      tokens[1].1.clone(),
+      deployment.id,
+      DeploymentStatus::Published,
+    )
+    .await;
+    assert!(matches!(result, Err(AppError::Forbidden)));
+    let deployment = state
+      .repo
+      .deployment()
+      .get_deployment(deployment.id)
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(deployment.status, DeploymentStatus::Uploaded);
+
+    update_deployment_status(
+      &state,
+      tokens[0].1.clone(),
+      deployment.id,
+      DeploymentStatus::Published,
+    )
+    .await
+    .unwrap();
+    let deployment = state
+      .repo
+      .deployment()
+      .get_deployment(deployment.id)
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(deployment.status, DeploymentStatus::Published);
+  }
+}
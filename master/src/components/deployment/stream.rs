@@ -0,0 +1,89 @@
+use std::{
+  collections::HashMap,
+  sync::{Arc, Mutex},
+  time::Duration,
+};
+
+use actix_web::{HttpResponse, http::header, rt::time::timeout, web::Bytes};
+use common::master::DeploymentStreamEvent;
+use futures_util::stream::{self, StreamExt};
+use tokio::sync::broadcast::{self, Receiver, error::RecvError};
+
+use crate::error::AppError;
+
+/// 每个部署缓冲的事件数，订阅者落后太多时会跳过旧事件
+const STREAM_CAPACITY: usize = 256;
+
+/// 没有事件时发送注释行的间隔，避免代理断开空闲连接
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// 部署进度的内存广播，只转发给已连接的订阅者，不做持久化
+#[derive(Debug, Clone, Default)]
+pub struct DeploymentStreams {
+  senders: Arc<Mutex<HashMap<i32, broadcast::Sender<DeploymentStreamEvent>>>>,
+}
+
+impl DeploymentStreams {
+  pub fn subscribe(&self, deployment_id: i32) -> Receiver<DeploymentStreamEvent> {
+    let mut senders = self.senders.lock().unwrap_or_else(|e| e.into_inner());
+    senders
+      .entry(deployment_id)
+      .or_insert_with(|| broadcast::channel(STREAM_CAPACITY).0)
+      .subscribe()
+  }
+
+  /// 推送事件，部署进入终态或已没有订阅者时释放通道
+  pub fn publish(&self, deployment_id: i32, event: DeploymentStreamEvent) {
+    let mut senders = self.senders.lock().unwrap_or_else(|e| e.into_inner());
+    let terminal = event.is_terminal();
+    if let Some(sender) = senders.get(&deployment_id) {
+      if sender.send(event).is_err() || terminal {
+        senders.remove(&deployment_id);
+      }
+    }
+  }
+}
+
+fn sse_frame(event: &DeploymentStreamEvent) -> Bytes {
+  Bytes::from(format!(
+    "event: {}\ndata: {}\n\n",
+    event.name(),
+    serde_json::to_string(event).unwrap_or_default()
+  ))
+}
+
+/// 先发送已有的事件，再转发实时事件，直到部署进入终态
+pub fn sse_response(
+  snapshot: Vec<DeploymentStreamEvent>,
+  receiver: Option<Receiver<DeploymentStreamEvent>>,
+) -> HttpResponse {
+  let snapshot = stream::iter(
+    snapshot
+      .into_iter()
+      .map(|event| Ok::<_, AppError>(sse_frame(&event))),
+  );
+  let live = stream::unfold(receiver, |receiver| async move {
+    let mut receiver = receiver?;
+    loop {
+      match timeout(KEEP_ALIVE_INTERVAL, receiver.recv()).await {
+        Err(_) => return Some((Ok(Bytes::from_static(b": keep-alive\n\n")), Some(receiver))),
+        Ok(Ok(event)) => {
+          let next = if event.is_terminal() {
+            None
+          } else {
+            Some(receiver)
+          };
+          return Some((Ok(sse_frame(&event)), next));
+        }
+        Ok(Err(RecvError::Lagged(skipped))) => {
+          tracing::warn!("Deployment stream lagged, {} events skipped", skipped);
+        }
+        Ok(Err(RecvError::Closed)) => return None,
+      }
+    }
+  });
+  HttpResponse::Ok()
+    .content_type("text/event-stream")
+    .insert_header((header::CACHE_CONTROL, "no-cache"))
+    .streaming(snapshot.chain(live))
+}
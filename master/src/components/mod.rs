@@ -1,5 +1,9 @@
+pub mod admin;
 pub mod agent;
+pub mod audit;
 pub mod base;
 pub mod deployment;
 pub mod site;
+pub mod task;
 pub mod user;
+pub mod webhook;
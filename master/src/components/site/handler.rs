@@ -1,39 +1,81 @@
 use actix_web::{
   HttpRequest, HttpResponse, get, post,
-  web::{Data, Json},
+  web::{Data, Json, Path, Query},
 };
-use helpers::jwt;
+use common::{Paginated, Response};
+use entity::{audit_log::AuditAction, deployment, site};
+use serde_json::Value;
 
 use crate::{
   app::AppState,
-  components::site::{model::*, service},
+  components::{
+    audit::service::{AuditEntry, record},
+    site::{model::*, service},
+  },
   error::AppError,
-  helper::extract_token,
+  helper::{client_ip, extract_user_id},
   traits::IntoHttpResponse,
 };
 
+#[utoipa::path(
+  tag = "site",
+  responses((status = OK, body = Response<Value>)),
+  security(("bearer_auth" = []))
+)]
 #[post("/site")]
 pub async fn create_site(
   req: HttpRequest,
   state: Data<AppState>,
   body: Json<CreateSiteBody>,
 ) -> Result<HttpResponse, AppError> {
-  let token = extract_token(&req)?;
-  let user_id = jwt::verify::<String>(&token, &state.login_token_key)?
-    .claims
-    .data;
-  service::create_site(&state, user_id, body.0.site_name)
+  let user_id = extract_user_id(&req, &state).await?;
+  let mut entry = AuditEntry::new(format!("user:{}", user_id), AuditAction::SiteCreate)
+    .ip(client_ip(&req, &state));
+  let result = service::create_site(&state, user_id, body.0.site_name).await;
+  if let Some(site_id) = result
+    .as_ref()
+    .ok()
+    .and_then(|site| site["site_id"].as_str())
+  {
+    entry = entry.target(format!("site:{}", site_id));
+  }
+  record(&state, entry, &result).await;
+  result.into_http_response()
+}
+
+#[utoipa::path(
+  tag = "site",
+  params(GetSitesQuery),
+  responses((status = OK, body = Response<Paginated<site::Model>>)),
+  security(("bearer_auth" = []))
+)]
+#[get("/sites")]
+pub async fn get_sites(
+  req: HttpRequest,
+  state: Data<AppState>,
+  query: Query<GetSitesQuery>,
+) -> Result<HttpResponse, AppError> {
+  let user_id = extract_user_id(&req, &state).await?;
+  service::get_sites(&state, user_id, query.into_inner())
     .await
     .into_http_response()
 }
 
-#[get("/sites")]
-pub async fn get_sites(req: HttpRequest, state: Data<AppState>) -> Result<HttpResponse, AppError> {
-  let token = extract_token(&req)?;
-  let user_id = jwt::verify::<String>(&token, &state.login_token_key)?
-    .claims
-    .data;
-  service::get_sites(&state, user_id)
+#[utoipa::path(
+  tag = "site",
+  params(GetSiteDeploymentsQuery),
+  responses((status = OK, body = Response<Paginated<deployment::Model>>)),
+  security(("bearer_auth" = []))
+)]
+#[get("/sites/{site_id}/deployments")]
+pub async fn get_site_deployments(
+  req: HttpRequest,
+  state: Data<AppState>,
+  site_id: Path<String>,
+  query: Query<GetSiteDeploymentsQuery>,
+) -> Result<HttpResponse, AppError> {
+  let user_id = extract_user_id(&req, &state).await?;
+  service::get_site_deployments(&state, user_id, site_id.into_inner(), query.into_inner())
     .await
     .into_http_response()
 }
@@ -1,8 +1,9 @@
 mod handler;
 pub mod model;
-mod service;
+pub mod service;
 
 use actix_web::web::ServiceConfig;
+use utoipa::OpenApi;
 
 pub struct SiteComponent;
 
@@ -10,5 +11,15 @@ impl SiteComponent {
   pub fn config(cfg: &mut ServiceConfig) {
     cfg.service(handler::create_site);
     cfg.service(handler::get_sites);
+    cfg.service(handler::get_site_deployments);
   }
 }
+
+/// 站点相关接口的 OpenAPI 文档
+#[derive(OpenApi)]
+#[openapi(paths(
+  handler::create_site,
+  handler::get_sites,
+  handler::get_site_deployments
+))]
+pub struct SiteApi;
@@ -1,6 +1,45 @@
+use common::SortOrder;
+use entity::{deployment::DeploymentStatus, site::SiteStatus};
 use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
 
-#[derive(Deserialize)]
+use crate::{
+  helper::{default_page, default_page_size},
+  repository::{DeploymentSort, SiteSort},
+};
+
+#[derive(Deserialize, ToSchema)]
 pub struct CreateSiteBody {
   pub site_name: String,
 }
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GetSitesQuery {
+  /// 页码，从 1 开始
+  #[serde(default = "default_page")]
+  pub page: u64,
+  #[serde(default = "default_page_size")]
+  pub page_size: u64,
+  pub status: Option<SiteStatus>,
+  pub domain: Option<String>,
+  #[serde(default)]
+  pub sort: SiteSort,
+  #[serde(default)]
+  pub order: SortOrder,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GetSiteDeploymentsQuery {
+  /// 页码，从 1 开始
+  #[serde(default = "default_page")]
+  pub page: u64,
+  #[serde(default = "default_page_size")]
+  pub page_size: u64,
+  pub status: Option<DeploymentStatus>,
+  #[serde(default)]
+  pub sort: DeploymentSort,
+  #[serde(default)]
+  pub order: SortOrder,
+}
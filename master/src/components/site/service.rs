@@ -1,10 +1,21 @@
-use crate::{app::AppState, types::ServiceResult};
-use common::master::GetSitesResponse;
-use entity::site;
+use crate::{
+  app::AppState,
+  components::{
+    site::model::*,
+    webhook::service::{delete_site_webhooks, emit},
+  },
+  error::AppError,
+  helper::{page_args, paginated, preview_domain},
+  repository::{DeploymentFilter, SiteFilter},
+  types::ServiceResult,
+};
+use common::Paginated;
+use entity::{deployment, site, user::UserType, webhook::WebhookEvent};
 use helpers::{
   time::utc_now,
   uuid::{Alphabet, nanoid},
 };
+use rpc::AgentEndpoint;
 use sea_orm::Set;
 use serde_json::{Value, json};
 
@@ -27,6 +38,18 @@ pub async fn create_site(
   user_id: String,
   site_name: String,
 ) -> ServiceResult<Value> {
+  let user = state
+    .repo
+    .user()
+    .get_user_by_id(user_id.clone())
+    .await?
+    .ok_or(AppError::UserNotFound)?;
+  // 临时用户的站点只保留一段时间，到期后由定时任务回收
+  let expired_at = if user.r#type == UserType::Casual {
+    Some(utc_now() + chrono::Duration::seconds(state.casual_site_ttl))
+  } else {
+    None
+  };
   let site = state
     .repo
     .site()
@@ -35,6 +58,7 @@ pub async fn create_site(
       name: Set(site_name),
       user_id: Set(user_id),
       bandwidth: Set(site::Bandwidth::One),
+      expired_at: Set(expired_at),
       created_at: Set(utc_now()),
       ..Default::default()
     })
@@ -42,10 +66,122 @@ pub async fn create_site(
   Ok(json!({
     "site_id": site.site_id,
     "name": site.name,
+    "expired_at": site.expired_at,
   }))
 }
 
-pub async fn get_sites(state: &AppState, user_id: String) -> ServiceResult<GetSitesResponse> {
-  let sites = state.repo.site().get_sites_by_user_id(user_id).await?;
-  Ok(GetSitesResponse { sites })
+/// 分页查询用户自己的站点
+pub async fn get_sites(
+  state: &AppState,
+  user_id: String,
+  query: GetSitesQuery,
+) -> ServiceResult<Paginated<site::Model>> {
+  let (page, page_size) = page_args(query.page, query.page_size);
+  let filter = SiteFilter {
+    user_id: Some(user_id),
+    status: query.status,
+    domain: query.domain,
+  };
+  let (sites, total) = state
+    .repo
+    .site()
+    .list_sites(filter, query.sort, query.order, page, page_size)
+    .await?;
+  Ok(paginated(sites, total, page, page_size))
+}
+
+/// 分页查询站点的部署记录，只有站点所有者可以查看
+pub async fn get_site_deployments(
+  state: &AppState,
+  user_id: String,
+  site_id: String,
+  query: GetSiteDeploymentsQuery,
+) -> ServiceResult<Paginated<deployment::Model>> {
+  match state.repo.site().get_site_by_id(&site_id).await? {
+    Some(site) if site.user_id == user_id => {}
+    _ => return Err(AppError::SiteNotFound),
+  }
+  let (page, page_size) = page_args(query.page, query.page_size);
+  let filter = DeploymentFilter {
+    site_id: Some(site_id),
+    agent_id: None,
+    status: query.status,
+  };
+  let (deployments, total) = state
+    .repo
+    .deployment()
+    .list_deployments(filter, query.sort, query.order, page, page_size)
+    .await?;
+  Ok(paginated(deployments, total, page, page_size))
+}
+
+/// 下线并删除站点：撤销 Agent 上的文件与 Nginx 配置，删除预览域名和绑定域名的 DNS 记录，
+/// 最后删除站点、部署记录和站点级的 Webhook。Agent 撤销失败时不会删除任何记录。
+pub async fn remove_site(state: &AppState, site: &site::Model) -> ServiceResult<()> {
+  revoke_site(state, site).await?;
+  let domains = std::iter::once(preview_domain(&site.site_id)).chain(site.domain.clone());
+  for domain in domains {
+    if let Err(err) = state.cloudflare_rpc.delete_dns_records(&domain).await {
+      tracing::error!("Failed to delete DNS record {}: {}", domain, err);
+    }
+  }
+  let deployment_ids = state
+    .repo
+    .deployment()
+    .get_deployments_by_site_id(&site.site_id)
+    .await?
+    .into_iter()
+    .map(|deployment| deployment.id)
+    .collect();
+  state
+    .repo
+    .deployment_event()
+    .delete_events_by_deployment_ids(deployment_ids)
+    .await?;
+  state
+    .repo
+    .deployment()
+    .delete_deployments_by_site_id(&site.site_id)
+    .await?;
+  state
+    .repo
+    .task()
+    .delete_tasks_by_site_id(&site.site_id)
+    .await?;
+  // 站点级的订阅随站点删除，`site.deleted` 只投递给用户级的订阅
+  delete_site_webhooks(state, &site.site_id).await?;
+  state.repo.site().delete_site(&site.site_id).await?;
+  emit(
+    state,
+    WebhookEvent::SiteDeleted,
+    Some(&site.user_id),
+    Some(&site.site_id),
+    json!({
+      "site_id": site.site_id,
+      "name": site.name,
+    }),
+  )
+  .await;
+  Ok(())
+}
+
+/// 撤销站点当前部署所在 Agent 上的文件与 Nginx 配置，站点未部署时什么也不做
+pub async fn revoke_site(state: &AppState, site: &site::Model) -> ServiceResult<()> {
+  if let Some(deployment_id) = site.deployment_id {
+    if let Some(deployment) = state
+      .repo
+      .deployment()
+      .get_deployment(deployment_id)
+      .await?
+    {
+      if let Some(agent) = state.repo.agent().get_agent(deployment.agent_id).await? {
+        state
+          .agent_rpc
+          .task_revoke(site.site_id.clone(), &AgentEndpoint::from(&agent))
+          .await?;
+      }
+    }
+  }
+  state.repo.nginx().delete_by_site_id(&site.site_id).await?;
+  Ok(())
 }
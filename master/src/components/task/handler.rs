@@ -0,0 +1,27 @@
+use actix_web::{
+  HttpRequest, HttpResponse, get,
+  web::{Data, Path},
+};
+use common::{Response, master::TaskResponse};
+
+use crate::{
+  app::AppState, components::task::service, error::AppError, helper::extract_user_id,
+  traits::IntoHttpResponse,
+};
+
+#[utoipa::path(
+  tag = "task",
+  responses((status = OK, body = Response<TaskResponse>)),
+  security(("bearer_auth" = []))
+)]
+#[get("/tasks/{task_id}")]
+pub async fn get_task(
+  req: HttpRequest,
+  state: Data<AppState>,
+  task_id: Path<i32>,
+) -> Result<HttpResponse, AppError> {
+  let user_id = extract_user_id(&req, &state).await?;
+  service::get_task(&state, &user_id, task_id.into_inner())
+    .await
+    .into_http_response()
+}
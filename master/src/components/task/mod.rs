@@ -0,0 +1,18 @@
+mod handler;
+pub mod service;
+
+use actix_web::web::ServiceConfig;
+use utoipa::OpenApi;
+
+pub struct TaskComponent;
+
+impl TaskComponent {
+  pub fn config(cfg: &mut ServiceConfig) {
+    cfg.service(handler::get_task);
+  }
+}
+
+/// 任务相关接口的 OpenAPI 文档
+#[derive(OpenApi)]
+#[openapi(paths(handler::get_task))]
+pub struct TaskApi;
@@ -0,0 +1,781 @@
+use std::{
+  collections::{BTreeMap, HashSet},
+  net::IpAddr,
+  sync::{Arc, Mutex},
+};
+
+use common::{
+  agent::{ArtifactEncoding, DeploymentStage},
+  master::{DeploymentStreamEvent, StageState, TaskResponse},
+};
+use entity::{
+  agent::{self, AgentStatus},
+  deployment::{self, DeploymentStatus},
+  task::{self, TaskStatus, TaskType},
+};
+use helpers::time::utc_now;
+use rpc::AgentEndpoint;
+use sea_orm::{IntoActiveModel, Set};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use crate::{
+  app::AppState,
+  components::deployment::service::{append_logs, can_transition, transition},
+  error::AppError,
+  helper::{preview_domain, retry_delay},
+  reconcile::config_hash,
+  repository::RepositoryManager,
+  types::ServiceResult,
+};
+
+/// 每轮调度最多取出的任务数
+const DISPATCH_BATCH_SIZE: u64 = 100;
+
+/// 正在执行任务的 Agent。同一个 Agent 上的任务依次执行，不同 Agent 的任务互不阻塞
+#[derive(Debug, Clone, Default)]
+pub struct TaskWorkers {
+  busy: Arc<Mutex<HashSet<Option<i32>>>>,
+}
+
+impl TaskWorkers {
+  /// 占用 Agent，已被占用时返回 `None`，返回的守卫释放时解除占用
+  fn claim(&self, agent_id: Option<i32>) -> Option<WorkerGuard> {
+    let mut busy = self.busy.lock().unwrap_or_else(|e| e.into_inner());
+    busy.insert(agent_id).then(|| WorkerGuard {
+      workers: self.clone(),
+      agent_id,
+    })
+  }
+}
+
+struct WorkerGuard {
+  workers: TaskWorkers,
+  agent_id: Option<i32>,
+}
+
+impl Drop for WorkerGuard {
+  fn drop(&mut self) {
+    let mut busy = self.workers.busy.lock().unwrap_or_else(|e| e.into_inner());
+    busy.remove(&self.agent_id);
+  }
+}
+
+/// 发布任务的参数
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PublishPayload {
+  pub bind_domain: Option<String>,
+}
+
+/// 迁移任务的参数，`agent_id` 为空时自动选择一个在线的 Agent
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct MigratePayload {
+  pub agent_id: Option<i32>,
+}
+
+/// 待提交的任务
+pub struct NewTask {
+  pub r#type: TaskType,
+  pub user_id: String,
+  pub site_id: String,
+  pub deployment_id: Option<i32>,
+  pub payload: Value,
+  pub idempotency_key: String,
+}
+
+/// 提交任务，幂等键相同的任务已存在时直接返回已有的任务
+pub async fn enqueue(state: &AppState, new_task: NewTask) -> ServiceResult<task::Model> {
+  enqueue_task(&state.repo, new_task).await
+}
+
+/// 同 [`enqueue`]，供没有 `AppState` 的命令行子命令使用
+pub async fn enqueue_task(
+  repo: &RepositoryManager,
+  new_task: NewTask,
+) -> ServiceResult<task::Model> {
+  let repo = repo.task();
+  if let Some(task) = repo
+    .get_task_by_idempotency_key(&new_task.idempotency_key)
+    .await?
+  {
+    return Ok(task);
+  }
+  let created = repo
+    .create_task(task::ActiveModel {
+      r#type: Set(new_task.r#type),
+      status: Set(TaskStatus::Pending),
+      user_id: Set(new_task.user_id),
+      site_id: Set(new_task.site_id),
+      deployment_id: Set(new_task.deployment_id),
+      payload: Set(new_task.payload.to_string()),
+      idempotency_key: Set(new_task.idempotency_key.clone()),
+      attempts: Set(0),
+      next_run_at: Set(Some(utc_now())),
+      created_at: Set(utc_now()),
+      ..Default::default()
+    })
+    .await;
+  match created {
+    Ok(task) => Ok(task),
+    // 并发提交时唯一索引冲突，返回先提交的任务
+    Err(err) => repo
+      .get_task_by_idempotency_key(&new_task.idempotency_key)
+      .await?
+      .ok_or(err.into()),
+  }
+}
+
+/// 查询任务，只有提交者和管理员可以查看
+pub async fn get_task(
+  state: &AppState,
+  user_id: &str,
+  task_id: i32,
+) -> ServiceResult<TaskResponse> {
+  let task = state
+    .repo
+    .task()
+    .get_task(task_id)
+    .await?
+    .ok_or(AppError::TaskNotFound)?;
+  if task.user_id != user_id && !state.repo.user().is_admin_user(user_id).await? {
+    return Err(AppError::TaskNotFound);
+  }
+  Ok(task.into())
+}
+
+/// 将上次退出时仍在执行的任务放回队列，在调度器启动时调用
+pub async fn requeue_interrupted_tasks(state: &AppState) -> ServiceResult<()> {
+  let count = state.repo.task().requeue_running_tasks(utc_now()).await?;
+  if count > 0 {
+    tracing::warn!("Requeued {} interrupted tasks", count);
+  }
+  Ok(())
+}
+
+/// 执行到期的任务，由调度器循环调用。任务按所在的 Agent 分组并发执行，
+/// 同一个 Agent 上一批任务还没执行完时，新的任务留在队列中等下一轮
+pub async fn dispatch_due_tasks(state: &AppState) -> ServiceResult<()> {
+  let tasks = state
+    .repo
+    .task()
+    .get_due_tasks(utc_now(), DISPATCH_BATCH_SIZE)
+    .await?;
+  let mut queues: BTreeMap<Option<i32>, Vec<task::Model>> = BTreeMap::new();
+  for task in tasks {
+    let agent_id = task_agent_id(state, &task).await;
+    queues.entry(agent_id).or_default().push(task);
+  }
+  for (agent_id, tasks) in queues {
+    let Some(guard) = state.task_workers.claim(agent_id) else {
+      continue;
+    };
+    let state = state.clone();
+    actix_web::rt::spawn(async move {
+      let _guard = guard;
+      for task in tasks {
+        let id = task.id;
+        // 取出后任务可能已被其他请求处理，执行前重新确认状态
+        match state.repo.task().get_task(id).await {
+          Ok(Some(task)) if task.status == TaskStatus::Pending => {
+            if let Err(err) = run_task(&state, task).await {
+              tracing::error!("Failed to run task {}: {}", id, err);
+            }
+          }
+          Ok(_) => (),
+          Err(err) => tracing::error!("Failed to load task {}: {}", id, err),
+        }
+      }
+    });
+  }
+  Ok(())
+}
+
+/// 任务操作的 Agent，即任务部署所在的 Agent，找不到部署时为 `None`
+async fn task_agent_id(state: &AppState, task: &task::Model) -> Option<i32> {
+  let deployment_id = task.deployment_id?;
+  match state.repo.deployment().get_deployment(deployment_id).await {
+    Ok(deployment) => deployment.map(|deployment| deployment.agent_id),
+    Err(err) => {
+      tracing::error!("Failed to find deployment {}: {}", deployment_id, err);
+      None
+    }
+  }
+}
+
+/// 删除结束超过 `task_retention` 秒的任务，由定时任务调用
+pub async fn prune_finished_tasks(state: &AppState) -> ServiceResult<()> {
+  let before = utc_now() - chrono::Duration::seconds(state.task_retention);
+  let count = state.repo.task().delete_finished_tasks(before).await?;
+  if count > 0 {
+    tracing::info!("Pruned {} finished tasks", count);
+  }
+  Ok(())
+}
+
+/// 执行一次任务并记录结果，可重试的错误按指数退避重新排队
+async fn run_task(state: &AppState, task: task::Model) -> ServiceResult<task::Model> {
+  let attempts = task.attempts + 1;
+  let mut active_task = task.into_active_model();
+  active_task.status = Set(TaskStatus::Running);
+  active_task.attempts = Set(attempts);
+  active_task.updated_at = Set(Some(utc_now()));
+  let task = state.repo.task().update_task(active_task).await?;
+
+  let outcome = match task.r#type {
+    TaskType::Publish => execute_publish(state, &task).await,
+    TaskType::Revoke => execute_revoke(state, &task).await,
+    TaskType::Migrate => execute_migrate(state, &task).await,
+  };
+  let mut active_task = task.clone().into_active_model();
+  match outcome {
+    Ok(result) => {
+      active_task.status = Set(TaskStatus::Succeeded);
+      active_task.result = Set(Some(result.to_string()));
+      active_task.last_error = Set(None);
+      active_task.next_run_at = Set(None);
+    }
+    Err(err) if err.is_retryable() && attempts < state.task_max_attempts => {
+      tracing::warn!("Task {} attempt {} failed: {}", task.id, attempts, err);
+      active_task.status = Set(TaskStatus::Pending);
+      active_task.last_error = Set(Some(err.to_string()));
+      active_task.next_run_at = Set(Some(
+        utc_now() + retry_delay(state.task_retry_interval, attempts),
+      ));
+    }
+    Err(err) => {
+      tracing::error!("Task {} failed: {}", task.id, err);
+      active_task.status = Set(TaskStatus::Failed);
+      active_task.last_error = Set(Some(err.to_string()));
+      active_task.next_run_at = Set(None);
+      on_task_failed(state, &task, &err).await;
+    }
+  }
+  active_task.updated_at = Set(Some(utc_now()));
+  Ok(state.repo.task().update_task(active_task).await?)
+}
+
+/// 任务最终失败后的清理，发布失败时将部署标记为失败
+async fn on_task_failed(state: &AppState, task: &task::Model, err: &AppError) {
+  if task.r#type != TaskType::Publish {
+    return;
+  }
+  let Some(deployment_id) = task.deployment_id else {
+    return;
+  };
+  let deployment = match state.repo.deployment().get_deployment(deployment_id).await {
+    Ok(Some(deployment)) => deployment,
+    Ok(None) => return,
+    Err(err) => {
+      tracing::error!("Failed to find deployment {}: {}", deployment_id, err);
+      return;
+    }
+  };
+  if !can_transition(&deployment.status, &DeploymentStatus::Failed) {
+    return;
+  }
+  if let Err(err) = transition(
+    state,
+    deployment,
+    DeploymentStatus::Failed,
+    &actor(task),
+    Some(err.to_string()),
+  )
+  .await
+  {
+    tracing::error!(
+      "Failed to mark deployment {} failed: {}",
+      deployment_id,
+      err
+    );
+  }
+}
+
+/// 任务触发的状态变更记录的操作方
+fn actor(task: &task::Model) -> String {
+  format!("task:{}", task.id)
+}
+
+async fn get_task_deployment(
+  state: &AppState,
+  task: &task::Model,
+) -> ServiceResult<deployment::Model> {
+  let deployment_id = task.deployment_id.ok_or(AppError::DeploymentNotFound)?;
+  state
+    .repo
+    .deployment()
+    .get_deployment(deployment_id)
+    .await?
+    .ok_or(AppError::DeploymentNotFound)
+}
+
+async fn get_agent(state: &AppState, agent_id: i32) -> ServiceResult<agent::Model> {
+  state
+    .repo
+    .agent()
+    .get_agent(agent_id)
+    .await?
+    .ok_or(AppError::AgentNotFound)
+}
+
+/// 预览域名需要解析到的地址
+fn public_ips(agent: &agent::Model) -> ServiceResult<Vec<IpAddr>> {
+  let ips = agent.public_ips();
+  if ips.is_empty() {
+    return Err(AppError::Other {
+      message: format!("Agent {} has no valid public address", agent.id),
+      source: None,
+    });
+  }
+  Ok(ips)
+}
+
+/// 在部署所在的 Agent 上发布站点，成功后返回预览地址和绑定的域名
+async fn execute_publish(state: &AppState, task: &task::Model) -> ServiceResult<Value> {
+  let payload: PublishPayload = serde_json::from_str(&task.payload).unwrap_or_default();
+  let site = state
+    .repo
+    .site()
+    .get_site_by_id(&task.site_id)
+    .await?
+    .ok_or(AppError::SiteNotFound)?;
+  let deployment = get_task_deployment(state, task).await?;
+  let deployment_id = deployment.id;
+  // 上一次执行已经发布成功但没来得及记录结果时，重新发布不再变更状态
+  let published = deployment.status == DeploymentStatus::Published;
+  if !published && !can_transition(&deployment.status, &DeploymentStatus::Published) {
+    return Err(AppError::InvalidStatusTransition {
+      from: deployment.status,
+      to: DeploymentStatus::Published,
+    });
+  }
+  let agent = get_agent(state, deployment.agent_id).await?;
+  let preview_domain = preview_domain(&site.site_id);
+  let stage = |stage, state| DeploymentStreamEvent::Stage { stage, state };
+  state.deployment_streams.publish(
+    deployment_id,
+    stage(DeploymentStage::Dns, StageState::Started),
+  );
+  // DNS 创建失败不影响发布，记录结果后继续
+  let dns_state = match state
+    .cloudflare_rpc
+    .create_address_records(&preview_domain, &public_ips(&agent)?)
+    .await
+  {
+    Ok(_) => StageState::Succeeded,
+    Err(err) => {
+      tracing::error!("Failed to create DNS record {}: {}", preview_domain, err);
+      StageState::Failed
+    }
+  };
+  state
+    .deployment_streams
+    .publish(deployment_id, stage(DeploymentStage::Dns, dns_state));
+  let publish_result = state
+    .agent_rpc
+    .task_publish(
+      site.site_id.clone(),
+      deployment_id,
+      &AgentEndpoint::from(&agent),
+      site.bandwidth.to_string(),
+      payload.bind_domain.clone(),
+      preview_domain.clone(),
+      Some(task.idempotency_key.clone()),
+    )
+    .await;
+  let deployment = match &publish_result {
+    Ok(res) => {
+      for step in res.steps.iter() {
+        let result = if step.success {
+          StageState::Succeeded
+        } else {
+          StageState::Failed
+        };
+        state
+          .deployment_streams
+          .publish(deployment_id, stage(step.stage, result));
+      }
+      append_logs(state, deployment, "publish", &res.logs).await?
+    }
+    Err(err) => {
+      let message = format!("attempt {} failed: {}", task.attempts, err);
+      append_logs(state, deployment, "publish", &message).await?
+    }
+  };
+  let res = publish_result?;
+  state
+    .repo
+    .nginx()
+    .save(
+      &site.site_id,
+      agent.id,
+      deployment_id,
+      config_hash(res.config.as_deref()),
+      res.config,
+    )
+    .await?;
+
+  let preview_url = "http://".to_string() + &preview_domain;
+  let deployment = if published {
+    deployment
+  } else {
+    transition(
+      state,
+      deployment,
+      DeploymentStatus::Published,
+      &actor(task),
+      None,
+    )
+    .await?
+  };
+  let mut active_deployment = deployment.into_active_model();
+  active_deployment.deploy_preview_url = Set(Some(preview_url.clone()));
+  state
+    .repo
+    .deployment()
+    .update_deployment(active_deployment)
+    .await?;
+  let expired_at = site.expired_at;
+  if let Some(bind_domain) = payload.bind_domain {
+    let mut active_site = site.into_active_model();
+    active_site.domain = Set(Some(bind_domain.clone()));
+    state.repo.site().update_site(active_site).await?;
+    Ok(json!({
+      "preview_url": preview_url,
+      "bind_url": "http://".to_string() + &bind_domain,
+      "expired_at": expired_at,
+    }))
+  } else {
+    Ok(json!({
+      "preview_url": preview_url,
+      "expired_at": expired_at,
+    }))
+  }
+}
+
+async fn execute_revoke(state: &AppState, task: &task::Model) -> ServiceResult<Value> {
+  let deployment = get_task_deployment(state, task).await?;
+  let agent = get_agent(state, deployment.agent_id).await?;
+  state
+    .agent_rpc
+    .task_revoke(task.site_id.clone(), &AgentEndpoint::from(&agent))
+    .await?;
+  state.repo.nginx().delete_by_site_id(&task.site_id).await?;
+  Ok(Value::Null)
+}
+
+/// 将站点的文件从当前 Agent 导出后上传到目标 Agent 并发布，
+/// 随后把预览域名指向新的 Agent 并撤销旧 Agent 上的站点
+async fn execute_migrate(state: &AppState, task: &task::Model) -> ServiceResult<Value> {
+  let payload: MigratePayload = serde_json::from_str(&task.payload).unwrap_or_default();
+  let site = state
+    .repo
+    .site()
+    .get_site_by_id(&task.site_id)
+    .await?
+    .ok_or(AppError::SiteNotFound)?;
+  let deployment = get_task_deployment(state, task).await?;
+  let source = get_agent(state, deployment.agent_id).await?;
+  let target = match payload.agent_id {
+    Some(agent_id) => get_agent(state, agent_id).await?,
+    None => state
+      .repo
+      .agent()
+      .get_agents_by_status(AgentStatus::Online)
+      .await?
+      .into_iter()
+      .find(|agent| agent.id != source.id)
+      .ok_or(AppError::AgentNotFound)?,
+  };
+  // 上一次执行已经完成迁移
+  if source.id == target.id {
+    return Ok(json!({ "site_id": site.site_id, "agent_id": target.id }));
+  }
+
+  let archive = std::env::temp_dir().join(format!("pupup-migrate-{}.tar.gz", task.id));
+  let target_endpoint = AgentEndpoint::from(&target);
+  let transfer = async {
+    state
+      .agent_rpc
+      .task_export(
+        site.site_id.clone(),
+        &AgentEndpoint::from(&source),
+        &archive,
+      )
+      .await?;
+    // 每次执行使用新的上传，上一次执行已完成的上传不会导致 token 被拒绝
+    let upload_token = state
+      .agent_rpc
+      .init_upload_session(
+        &target_endpoint,
+        site.site_id.clone(),
+        deployment.id,
+        None,
+        Some(format!("migrate:{}:{}", task.id, task.attempts)),
+      )
+      .await?
+      .upload_token;
+    rpc::upload_file_chunked(
+      state.agent_rpc.as_ref(),
+      &target_endpoint.url,
+      upload_token,
+      archive.clone(),
+      ArtifactEncoding::Gzip,
+      |_, _| {},
+    )
+    .await
+  }
+  .await;
+  if let Err(err) = tokio::fs::remove_file(&archive).await {
+    tracing::warn!("Failed to remove {}: {}", archive.display(), err);
+  }
+  transfer?;
+
+  let preview_domain = preview_domain(&site.site_id);
+  let res = state
+    .agent_rpc
+    .task_publish(
+      site.site_id.clone(),
+      deployment.id,
+      &target_endpoint,
+      site.bandwidth.to_string(),
+      site.domain.clone(),
+      preview_domain.clone(),
+      Some(task.idempotency_key.clone()),
+    )
+    .await?;
+  if let Err(err) = state
+    .cloudflare_rpc
+    .delete_dns_records(&preview_domain)
+    .await
+  {
+    tracing::error!("Failed to delete DNS record {}: {}", preview_domain, err);
+  }
+  if let Err(err) = state
+    .cloudflare_rpc
+    .create_address_records(&preview_domain, &public_ips(&target)?)
+    .await
+  {
+    tracing::error!("Failed to create DNS record {}: {}", preview_domain, err);
+  }
+
+  state
+    .repo
+    .nginx()
+    .save(
+      &site.site_id,
+      target.id,
+      deployment.id,
+      config_hash(res.config.as_deref()),
+      res.config.clone(),
+    )
+    .await?;
+  let mut active_deployment = deployment.into_active_model();
+  active_deployment.agent_id = Set(target.id);
+  let deployment = state
+    .repo
+    .deployment()
+    .update_deployment(active_deployment)
+    .await?;
+  append_logs(
+    state,
+    deployment,
+    "migrate",
+    &format!(
+      "migrated from agent {} to agent {}\n{}",
+      source.id, target.id, res.logs
+    ),
+  )
+  .await?;
+  // 新 Agent 已经在提供服务，旧 Agent 撤销失败只记录日志
+  if let Err(err) = state
+    .agent_rpc
+    .task_revoke(site.site_id.clone(), &AgentEndpoint::from(&source))
+    .await
+  {
+    tracing::error!(
+      "Failed to revoke site {} on agent {}: {}",
+      site.site_id,
+      source.id,
+      err
+    );
+  }
+  Ok(json!({
+    "site_id": site.site_id,
+    "from_agent_id": source.id,
+    "agent_id": target.id,
+  }))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_task_workers() {
+    let workers = TaskWorkers::default();
+    let guard = workers.claim(Some(1)).unwrap();
+    assert!(workers.claim(Some(1)).is_none());
+    assert!(workers.claim(Some(2)).is_some());
+    drop(guard);
+    assert!(workers.claim(Some(1)).is_some());
+  }
+
+  #[test]
+  fn test_public_ips() {
+    use entity::agent::{AgentStatus, join_public_ips};
+    use helpers::time::utc_now;
+
+    let v4: IpAddr = "203.0.113.1".parse().unwrap();
+    let v6: IpAddr = "2001:db8::1".parse().unwrap();
+    let mut agent = agent::Model {
+      id: 1,
+      hostname: "agent".to_string(),
+      ip_address: "10.0.0.1".to_string(),
+      api_url: "http://10.0.0.1:5001".to_string(),
+      ca_cert: None,
+      cert_fingerprint: None,
+      public_ips: Some(join_public_ips(&[v4, v6])),
+      storage_path: "/data".to_string(),
+      available_space: 0,
+      status: AgentStatus::Online,
+      tags: None,
+      token: "token".to_string(),
+      last_heartbeat: None,
+      created_at: utc_now(),
+      updated_at: None,
+    };
+    assert_eq!(
+      agent.public_ips,
+      Some("203.0.113.1,2001:db8::1".to_string())
+    );
+    assert_eq!(public_ips(&agent).unwrap(), [v4, v6]);
+    // 无法解析的地址被忽略
+    agent.public_ips = Some(" 2001:db8::1 ,invalid, 203.0.113.1".to_string());
+    assert_eq!(public_ips(&agent).unwrap(), [v6, v4]);
+    // 没有上报时使用注册的地址
+    agent.public_ips = None;
+    assert_eq!(
+      public_ips(&agent).unwrap(),
+      ["10.0.0.1".parse::<IpAddr>().unwrap()]
+    );
+    agent.ip_address = "invalid".to_string();
+    assert!(public_ips(&agent).is_err());
+  }
+
+  #[cfg(feature = "sqlite")]
+  #[actix_web::test]
+  async fn test_retry_migrate() {
+    use common::agent::{InitUploadResponse, TaskPublishResponse, UploadSessionResponse};
+    use entity::user::UserType;
+    use rpc::MockAgentApi;
+
+    use crate::testing::{create_agent, create_site, create_user, test_state};
+
+    let upload_ids = Arc::new(Mutex::new(vec![]));
+    let mut agent_rpc = MockAgentApi::new();
+    agent_rpc.expect_task_export().returning(|_, _, dest| {
+      std::fs::write(dest, b"archive")?;
+      Ok(())
+    });
+    let ids = upload_ids.clone();
+    agent_rpc
+      .expect_init_upload_session()
+      .returning(move |_, _, _, _, upload_id| {
+        let upload_id = upload_id.unwrap();
+        ids.lock().unwrap().push(upload_id.clone());
+        Ok(InitUploadResponse {
+          upload_token: upload_id,
+        })
+      });
+    agent_rpc.expect_upload_session().returning(|_, _| {
+      Ok(UploadSessionResponse {
+        offset: 0,
+        chunk_size: 1024,
+        encodings: vec![],
+      })
+    });
+    agent_rpc
+      .expect_upload_chunk()
+      .returning(|_, _, offset, chunk| {
+        Ok(UploadSessionResponse {
+          offset: offset + chunk.len() as u64,
+          chunk_size: 1024,
+          encodings: vec![],
+        })
+      });
+    // 与 Agent 一样，完成过的上传 token 不能再次使用
+    let used = Arc::new(Mutex::new(HashSet::new()));
+    agent_rpc
+      .expect_complete_upload()
+      .returning(move |_, token, _, _| {
+        if used.lock().unwrap().insert(token.to_string()) {
+          Ok(())
+        } else {
+          Err(rpc::error::Error::Api(
+            400,
+            0,
+            "Upload token used".to_string(),
+          ))
+        }
+      });
+    // 第一次执行上传完成后发布失败
+    let mut published = 0;
+    agent_rpc
+      .expect_task_publish()
+      .returning(move |_, _, _, _, _, _, _| {
+        published += 1;
+        if published == 1 {
+          Err(rpc::error::Error::ConnectAgent("timeout".to_string()))
+        } else {
+          Ok(TaskPublishResponse {
+            logs: "ok".to_string(),
+            steps: vec![],
+            config: None,
+          })
+        }
+      });
+    agent_rpc.expect_task_revoke().returning(|_, _| Ok(true));
+
+    let state = test_state(agent_rpc).await;
+    create_user(&state, "owner", UserType::Normal).await;
+    let source = create_agent(&state, "10.0.0.1").await;
+    let target = create_agent(&state, "10.0.0.2").await;
+    let (_, deployment) = create_site(
+      &state,
+      "site",
+      "owner",
+      source.id,
+      DeploymentStatus::Published,
+    )
+    .await;
+    let task = enqueue(
+      &state,
+      NewTask {
+        r#type: TaskType::Migrate,
+        user_id: "owner".to_string(),
+        site_id: "site".to_string(),
+        deployment_id: Some(deployment.id),
+        payload: json!(MigratePayload {
+          agent_id: Some(target.id)
+        }),
+        idempotency_key: "migrate".to_string(),
+      },
+    )
+    .await
+    .unwrap();
+
+    let task = run_task(&state, task).await.unwrap();
+    assert_eq!(task.status, TaskStatus::Pending);
+    let task = run_task(&state, task).await.unwrap();
+    assert_eq!(task.status, TaskStatus::Succeeded, "{:?}", task.last_error);
+    assert_eq!(
+      *upload_ids.lock().unwrap(),
+      vec![
+        format!("migrate:{}:1", task.id),
+        format!("migrate:{}:2", task.id)
+      ]
+    );
+    let deployment = get_task_deployment(&state, &task).await.unwrap();
+    assert_eq!(deployment.agent_id, target.id);
+  }
+}
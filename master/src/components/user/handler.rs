@@ -1,73 +1,137 @@
 use crate::{
   app::AppState,
-  components::user::{model::*, service},
+  components::{
+    audit::service::{AuditEntry, record},
+    user::{model::*, service},
+  },
   error::AppError,
-  middlewares::JwtPayload,
+  helper::{client_ip, extract_user_id},
   traits::IntoHttpResponse,
 };
 use actix_web::{
-  HttpResponse, get, post,
-  web::{Data, Json, ReqData},
+  HttpRequest, HttpResponse, get, post,
+  web::{Data, Json},
 };
-use common::master::{UserLoginRequest, UserRegisterRequest};
+use common::{
+  Response,
+  master::{UserLoginRequest, UserRegisterRequest},
+};
+use entity::audit_log::AuditAction;
+use serde_json::Value;
 use validator::Validate;
 
+#[utoipa::path(
+  tag = "user",
+  responses(
+    (status = OK, body = Response<Value>),
+    (status = TOO_MANY_REQUESTS, description = "请求过于频繁，`Retry-After` 为需要等待的秒数")
+  )
+)]
 #[get("/user/casual")]
-pub async fn generate_casual_user(state: Data<AppState>) -> Result<HttpResponse, AppError> {
-  service::generate_casual_user(&state)
-    .await
-    .into_http_response()
+pub async fn generate_casual_user(
+  req: HttpRequest,
+  state: Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+  let result = service::generate_casual_user(&state).await;
+  let entry = AuditEntry::new("anonymous", AuditAction::UserRegister)
+    .ip(client_ip(&req, &state))
+    .detail("casual");
+  record(&state, entry, &result).await;
+  result.into_http_response()
 }
 
+#[utoipa::path(
+  tag = "user",
+  responses(
+    (status = OK, body = Response<Value>),
+    (status = TOO_MANY_REQUESTS, description = "请求过于频繁，`Retry-After` 为需要等待的秒数")
+  )
+)]
 #[post("/user")]
 pub async fn user_register(
+  req: HttpRequest,
   state: Data<AppState>,
   body: Json<UserRegisterRequest>,
 ) -> Result<HttpResponse, AppError> {
   body.0.validate()?;
-  service::user_register(&state, body.0.nickname, body.0.email, body.0.password)
-    .await
-    .into_http_response()
+  let entry = AuditEntry::new("anonymous", AuditAction::UserRegister)
+    .target(format!("email:{}", body.0.email))
+    .ip(client_ip(&req, &state));
+  let result = service::user_register(&state, body.0.nickname, body.0.email, body.0.password).await;
+  record(&state, entry, &result).await;
+  result.into_http_response()
 }
 
+#[utoipa::path(
+  tag = "user",
+  responses(
+    (status = OK, body = Response<Value>),
+    (status = TOO_MANY_REQUESTS, description = "请求过于频繁或账号因多次登录失败被锁定，`Retry-After` 为需要等待的秒数")
+  )
+)]
 #[post("/user/token")]
 pub async fn user_login(
+  req: HttpRequest,
   state: Data<AppState>,
   body: Json<UserLoginRequest>,
 ) -> Result<HttpResponse, AppError> {
-  service::user_login(&state, body.0.email, body.0.password)
-    .await
-    .into_http_response()
+  let ip = client_ip(&req, &state);
+  let entry = AuditEntry::new("anonymous", AuditAction::UserLogin)
+    .target(format!("email:{}", body.0.email))
+    .ip(ip.clone());
+  let result = service::user_login(&state, body.0.email, body.0.password, &ip).await;
+  record(&state, entry, &result).await;
+  result.into_http_response()
 }
 
+#[utoipa::path(
+  tag = "user",
+  responses((status = OK, body = Response<Value>)),
+  security(("bearer_auth" = []))
+)]
 #[get("/user/info")]
 pub async fn get_user_info(
+  req: HttpRequest,
   state: Data<AppState>,
-  req_data: ReqData<JwtPayload>,
 ) -> Result<HttpResponse, AppError> {
-  service::get_user_info(&state, req_data.user_id.clone())
+  let user_id = extract_user_id(&req, &state).await?;
+  service::get_user_info(&state, user_id)
     .await
     .into_http_response()
 }
 
+#[utoipa::path(
+  tag = "user",
+  responses((status = OK, body = Response<Value>)),
+  security(("bearer_auth" = []))
+)]
 #[post("/user/password")]
 pub async fn set_user_password(
+  req: HttpRequest,
   state: Data<AppState>,
   body: Json<SetUserPasswordBody>,
-  req_data: ReqData<JwtPayload>,
 ) -> Result<HttpResponse, AppError> {
+  let user_id = extract_user_id(&req, &state).await?;
   let Json(SetUserPasswordBody { password }) = body;
-  service::set_user_password(&state, req_data.user_id.clone(), password)
+  service::set_user_password(&state, user_id, password)
     .await
     .into_http_response()
 }
 
+#[utoipa::path(
+  tag = "user",
+  responses((status = OK, body = Response<Value>)),
+  security(("bearer_auth" = []))
+)]
 #[post("/token/refresh")]
 pub async fn refresh_user_token(
+  req: HttpRequest,
   state: Data<AppState>,
-  req_data: ReqData<JwtPayload>,
 ) -> Result<HttpResponse, AppError> {
-  service::refresh_user_token(&state, req_data.user_id.clone())
-    .await
-    .into_http_response()
+  let user_id = extract_user_id(&req, &state).await?;
+  let entry = AuditEntry::new(format!("user:{}", user_id), AuditAction::UserTokenRefresh)
+    .ip(client_ip(&req, &state));
+  let result = service::refresh_user_token(&state, user_id).await;
+  record(&state, entry, &result).await;
+  result.into_http_response()
 }
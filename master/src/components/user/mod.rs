@@ -3,6 +3,7 @@ pub mod model;
 mod service;
 
 use actix_web::web::ServiceConfig;
+use utoipa::OpenApi;
 
 pub struct UserComponent;
 
@@ -16,3 +17,15 @@ impl UserComponent {
     cfg.service(handler::refresh_user_token);
   }
 }
+
+/// 用户相关接口的 OpenAPI 文档
+#[derive(OpenApi)]
+#[openapi(paths(
+  handler::generate_casual_user,
+  handler::user_register,
+  handler::user_login,
+  handler::get_user_info,
+  handler::set_user_password,
+  handler::refresh_user_token,
+))]
+pub struct UserApi;
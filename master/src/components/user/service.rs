@@ -1,3 +1,5 @@
+use std::time::Instant;
+
 use entity::user::{self, UserStatus, UserType};
 use helpers::{
   hash::{argon2, verify_argon2},
@@ -90,6 +92,7 @@ pub async fn user_register(
 /// * `state` - A reference to the application state, which includes the database connection.
 /// * `email` - The email address of the user attempting to log in.
 /// * `password` - The password provided by the user for authentication.
+/// * `ip` - The client IP, failed attempts lock the account only for this IP.
 ///
 /// # Returns
 ///
@@ -99,21 +102,35 @@ pub async fn user_register(
 ///   - `UserNotExist` if the email is not registered
 ///   - `DbError` if there's an issue with the database operation
 ///   - `PasswordError` if the provided password is incorrect
+///   - `TooManyRequests` if the account is locked after repeated failures
 pub async fn user_login(
   state: &AppState,
   email: String,
   password: String,
+  ip: &str,
 ) -> Result<Value, AppError> {
+  let account = email.to_lowercase();
+  let now = Instant::now();
+  state.login_guard.check(&account, ip, now)?;
   if let Some(user) = state.repo.user().get_user_by_email(&email).await? {
+    match user.status {
+      UserStatus::Active => {}
+      UserStatus::Suspended => return Err(AppError::UserSuspended),
+      UserStatus::Deleted => return Err(AppError::UserNotFound),
+    }
     if verify_argon2(&user.password, &password)? {
+      state.login_guard.record_success(&account, ip);
       let token = jwt::sign(user.user_id, &state.login_token_key, 86400)?;
       Ok(json!({
         "token": token
       }))
     } else {
+      state.login_guard.record_failure(&account, ip, now);
       Err(AppError::PasswordError)
     }
   } else {
+    // 不存在的账号同样计入失败次数，避免借此枚举邮箱
+    state.login_guard.record_failure(&account, ip, now);
     Err(AppError::UserNotFound)
   }
 }
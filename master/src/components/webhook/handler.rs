@@ -0,0 +1,101 @@
+use actix_web::{
+  HttpRequest, HttpResponse, delete, get, post,
+  web::{Data, Json, Path},
+};
+use common::Response;
+use entity::webhook_delivery;
+use serde_json::Value;
+use validator::Validate;
+
+use crate::{
+  app::AppState,
+  components::webhook::{model::*, service},
+  error::AppError,
+  helper::extract_user_id,
+  traits::IntoHttpResponse,
+};
+
+#[utoipa::path(
+  tag = "webhook",
+  responses((status = OK, body = Response<CreateWebhookResponse>)),
+  security(("bearer_auth" = []))
+)]
+#[post("/webhooks")]
+pub async fn create_webhook(
+  req: HttpRequest,
+  state: Data<AppState>,
+  body: Json<CreateWebhookRequest>,
+) -> Result<HttpResponse, AppError> {
+  let user_id = extract_user_id(&req, &state).await?;
+  body.0.validate()?;
+  service::create_webhook(&state, user_id, body.into_inner())
+    .await
+    .into_http_response()
+}
+
+#[utoipa::path(
+  tag = "webhook",
+  responses((status = OK, body = Response<Vec<WebhookInfo>>)),
+  security(("bearer_auth" = []))
+)]
+#[get("/webhooks")]
+pub async fn list_webhooks(
+  req: HttpRequest,
+  state: Data<AppState>,
+) -> Result<HttpResponse, AppError> {
+  let user_id = extract_user_id(&req, &state).await?;
+  service::list_webhooks(&state, user_id)
+    .await
+    .into_http_response()
+}
+
+#[utoipa::path(
+  tag = "webhook",
+  responses((status = OK, body = Response<Value>)),
+  security(("bearer_auth" = []))
+)]
+#[delete("/webhooks/{webhook_id}")]
+pub async fn delete_webhook(
+  req: HttpRequest,
+  state: Data<AppState>,
+  webhook_id: Path<i32>,
+) -> Result<HttpResponse, AppError> {
+  let user_id = extract_user_id(&req, &state).await?;
+  service::delete_webhook(&state, user_id, webhook_id.into_inner())
+    .await
+    .into_http_response()
+}
+
+#[utoipa::path(
+  tag = "webhook",
+  responses((status = OK, body = Response<Vec<webhook_delivery::Model>>)),
+  security(("bearer_auth" = []))
+)]
+#[get("/webhooks/{webhook_id}/deliveries")]
+pub async fn list_deliveries(
+  req: HttpRequest,
+  state: Data<AppState>,
+  webhook_id: Path<i32>,
+) -> Result<HttpResponse, AppError> {
+  let user_id = extract_user_id(&req, &state).await?;
+  service::list_deliveries(&state, user_id, webhook_id.into_inner())
+    .await
+    .into_http_response()
+}
+
+#[utoipa::path(
+  tag = "webhook",
+  responses((status = OK, body = Response<webhook_delivery::Model>)),
+  security(("bearer_auth" = []))
+)]
+#[post("/webhooks/{webhook_id}/test")]
+pub async fn test_webhook(
+  req: HttpRequest,
+  state: Data<AppState>,
+  webhook_id: Path<i32>,
+) -> Result<HttpResponse, AppError> {
+  let user_id = extract_user_id(&req, &state).await?;
+  service::test_webhook(&state, user_id, webhook_id.into_inner())
+    .await
+    .into_http_response()
+}
@@ -0,0 +1,29 @@
+mod handler;
+pub mod model;
+pub mod service;
+
+use actix_web::web::ServiceConfig;
+use utoipa::OpenApi;
+
+pub struct WebhookComponent;
+
+impl WebhookComponent {
+  pub fn config(cfg: &mut ServiceConfig) {
+    cfg.service(handler::create_webhook);
+    cfg.service(handler::list_webhooks);
+    cfg.service(handler::delete_webhook);
+    cfg.service(handler::list_deliveries);
+    cfg.service(handler::test_webhook);
+  }
+}
+
+/// Webhook相关接口的 OpenAPI 文档
+#[derive(OpenApi)]
+#[openapi(paths(
+  handler::create_webhook,
+  handler::list_webhooks,
+  handler::delete_webhook,
+  handler::list_deliveries,
+  handler::test_webhook,
+))]
+pub struct WebhookApi;
@@ -0,0 +1,46 @@
+use entity::webhook::{self, WebhookEvent};
+use sea_orm::prelude::DateTimeUtc;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct CreateWebhookRequest {
+  #[validate(url)]
+  pub url: String,
+  /// 为空时订阅用户名下所有站点
+  pub site_id: Option<String>,
+  #[validate(length(min = 1))]
+  pub events: Vec<WebhookEvent>,
+}
+
+/// 不包含 secret 的 Webhook 信息
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WebhookInfo {
+  pub id: i32,
+  pub site_id: Option<String>,
+  pub url: String,
+  pub events: Vec<String>,
+  #[schema(value_type = String, format = DateTime)]
+  pub created_at: DateTimeUtc,
+}
+
+impl From<webhook::Model> for WebhookInfo {
+  fn from(webhook: webhook::Model) -> Self {
+    Self {
+      id: webhook.id,
+      site_id: webhook.site_id,
+      url: webhook.url,
+      events: webhook.events.split(',').map(String::from).collect(),
+      created_at: webhook.created_at,
+    }
+  }
+}
+
+/// 创建 Webhook 的响应，secret 只在创建时返回一次
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CreateWebhookResponse {
+  #[serde(flatten)]
+  pub webhook: WebhookInfo,
+  pub secret: String,
+}
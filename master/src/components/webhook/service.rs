@@ -0,0 +1,334 @@
+use entity::{
+  deployment,
+  webhook::{self, WebhookEvent},
+  webhook_delivery::{self, DeliveryStatus},
+};
+use helpers::{
+  time::utc_now,
+  uuid::{Alphabet, nanoid},
+};
+use sea_orm::{IntoActiveModel, Set};
+use serde_json::{Value, json};
+
+use crate::{
+  app::AppState,
+  components::webhook::model::{CreateWebhookRequest, CreateWebhookResponse, WebhookInfo},
+  error::AppError,
+  helper::retry_delay,
+  types::ServiceResult,
+};
+
+/// 查询投递记录时最多返回的条数
+const DELIVERY_LOG_LIMIT: u64 = 50;
+
+pub async fn create_webhook(
+  state: &AppState,
+  user_id: String,
+  body: CreateWebhookRequest,
+) -> ServiceResult<CreateWebhookResponse> {
+  if let Some(site_id) = &body.site_id {
+    let site = state
+      .repo
+      .site()
+      .get_site_by_id(site_id)
+      .await?
+      .ok_or(AppError::SiteNotFound)?;
+    if site.user_id != user_id {
+      return Err(AppError::Forbidden);
+    }
+  }
+  state
+    .webhook_rpc
+    .check_url(&body.url)
+    .await
+    .map_err(|_| AppError::InvalidWebhookUrl(body.url.clone()))?;
+  // Agent 不属于任何用户，只有管理员可以订阅其状态
+  if body.events.contains(&WebhookEvent::AgentOffline)
+    && (body.site_id.is_some() || !state.repo.user().is_admin_user(&user_id).await?)
+  {
+    return Err(AppError::Forbidden);
+  }
+  let mut events: Vec<&str> = Vec::new();
+  for event in body.events.iter().map(WebhookEvent::as_str) {
+    if !events.contains(&event) {
+      events.push(event);
+    }
+  }
+  let secret = nanoid(&Alphabet::DEFAULT, 32);
+  let webhook = state
+    .repo
+    .webhook()
+    .create_webhook(webhook::ActiveModel {
+      user_id: Set(user_id),
+      site_id: Set(body.site_id),
+      url: Set(body.url),
+      secret: Set(secret.clone()),
+      events: Set(events.join(",")),
+      created_at: Set(utc_now()),
+      ..Default::default()
+    })
+    .await?;
+  Ok(CreateWebhookResponse {
+    webhook: webhook.into(),
+    secret,
+  })
+}
+
+pub async fn list_webhooks(state: &AppState, user_id: String) -> ServiceResult<Vec<WebhookInfo>> {
+  let webhooks = state
+    .repo
+    .webhook()
+    .get_webhooks_by_user_id(&user_id)
+    .await?;
+  Ok(webhooks.into_iter().map(Into::into).collect())
+}
+
+/// 查找用户自己的 Webhook，不属于该用户时按不存在处理
+async fn get_owned_webhook(
+  state: &AppState,
+  user_id: &str,
+  webhook_id: i32,
+) -> ServiceResult<webhook::Model> {
+  state
+    .repo
+    .webhook()
+    .get_webhook(webhook_id)
+    .await?
+    .filter(|webhook| webhook.user_id == user_id)
+    .ok_or(AppError::WebhookNotFound)
+}
+
+pub async fn delete_webhook(
+  state: &AppState,
+  user_id: String,
+  webhook_id: i32,
+) -> ServiceResult<Value> {
+  let webhook = get_owned_webhook(state, &user_id, webhook_id).await?;
+  state
+    .repo
+    .webhook_delivery()
+    .delete_deliveries_by_webhook_id(webhook.id)
+    .await?;
+  state.repo.webhook().delete_webhook(webhook.id).await?;
+  Ok(Value::Null)
+}
+
+/// 删除站点级的 Webhook 及其投递记录，站点删除时调用
+pub async fn delete_site_webhooks(state: &AppState, site_id: &str) -> ServiceResult<()> {
+  for webhook in state
+    .repo
+    .webhook()
+    .get_webhooks_by_site_id(site_id)
+    .await?
+  {
+    state
+      .repo
+      .webhook_delivery()
+      .delete_deliveries_by_webhook_id(webhook.id)
+      .await?;
+    state.repo.webhook().delete_webhook(webhook.id).await?;
+  }
+  Ok(())
+}
+
+pub async fn list_deliveries(
+  state: &AppState,
+  user_id: String,
+  webhook_id: i32,
+) -> ServiceResult<Vec<webhook_delivery::Model>> {
+  let webhook = get_owned_webhook(state, &user_id, webhook_id).await?;
+  Ok(
+    state
+      .repo
+      .webhook_delivery()
+      .get_deliveries_by_webhook_id(webhook.id, DELIVERY_LOG_LIMIT)
+      .await?,
+  )
+}
+
+/// 立即投递一个 `ping` 事件，返回投递结果
+pub async fn test_webhook(
+  state: &AppState,
+  user_id: String,
+  webhook_id: i32,
+) -> ServiceResult<webhook_delivery::Model> {
+  let webhook = get_owned_webhook(state, &user_id, webhook_id).await?;
+  let delivery = enqueue(
+    state,
+    &webhook,
+    WebhookEvent::Ping,
+    json!({ "webhook_id": webhook.id }),
+  )
+  .await?;
+  deliver(state, delivery).await
+}
+
+/// 向订阅了事件的 Webhook 投递消息，投递在后台进行，失败只记录日志
+pub async fn emit(
+  state: &AppState,
+  event: WebhookEvent,
+  user_id: Option<&str>,
+  site_id: Option<&str>,
+  data: Value,
+) {
+  let webhooks = match state
+    .repo
+    .webhook()
+    .get_subscribed_webhooks(event, user_id, site_id)
+    .await
+  {
+    Ok(webhooks) => webhooks,
+    Err(err) => {
+      tracing::error!("Failed to find webhooks for {}: {}", event.as_str(), err);
+      return;
+    }
+  };
+  for webhook in webhooks {
+    match enqueue(state, &webhook, event, data.clone()).await {
+      Ok(delivery) => {
+        let state = state.clone();
+        actix_web::rt::spawn(async move {
+          if let Err(err) = deliver(&state, delivery).await {
+            tracing::error!("Failed to deliver webhook: {}", err);
+          }
+        });
+      }
+      Err(err) => tracing::error!("Failed to enqueue webhook {}: {}", webhook.id, err),
+    }
+  }
+}
+
+/// 投递部署相关的事件，订阅者为站点和站点所有者的 Webhook
+pub async fn emit_deployment_event(
+  state: &AppState,
+  event: WebhookEvent,
+  deployment: &deployment::Model,
+  reason: Option<&str>,
+) {
+  let user_id = match state.repo.site().get_site_by_id(&deployment.site_id).await {
+    Ok(site) => site.map(|site| site.user_id),
+    Err(err) => {
+      tracing::error!("Failed to find site {}: {}", deployment.site_id, err);
+      return;
+    }
+  };
+  emit(
+    state,
+    event,
+    user_id.as_deref(),
+    Some(&deployment.site_id),
+    json!({
+      "deployment_id": deployment.id,
+      "site_id": deployment.site_id,
+      "status": deployment.status,
+      "reason": reason,
+    }),
+  )
+  .await;
+}
+
+/// 记录一次待投递的消息，首次投递失败时由定时任务按时重试
+async fn enqueue(
+  state: &AppState,
+  webhook: &webhook::Model,
+  event: WebhookEvent,
+  data: Value,
+) -> ServiceResult<webhook_delivery::Model> {
+  let payload = json!({
+    "event": event.as_str(),
+    "created_at": utc_now(),
+    "data": data,
+  });
+  Ok(
+    state
+      .repo
+      .webhook_delivery()
+      .create_delivery(webhook_delivery::ActiveModel {
+        webhook_id: Set(webhook.id),
+        event: Set(event.as_str().to_string()),
+        payload: Set(payload.to_string()),
+        status: Set(DeliveryStatus::Pending),
+        attempts: Set(0),
+        next_attempt_at: Set(Some(
+          utc_now() + retry_delay(state.webhook_retry_interval, 1),
+        )),
+        created_at: Set(utc_now()),
+        ..Default::default()
+      })
+      .await?,
+  )
+}
+
+/// 投递一次并记录结果，接收方返回 2xx 视为成功
+async fn deliver(
+  state: &AppState,
+  delivery: webhook_delivery::Model,
+) -> ServiceResult<webhook_delivery::Model> {
+  let attempts = delivery.attempts + 1;
+  let webhook = state
+    .repo
+    .webhook()
+    .get_webhook(delivery.webhook_id)
+    .await?;
+  let (response_status, error) = match &webhook {
+    Some(webhook) => match state
+      .webhook_rpc
+      .deliver(
+        &webhook.url,
+        &webhook.secret,
+        &delivery.event,
+        delivery.id,
+        delivery.payload.clone().into_bytes(),
+      )
+      .await
+    {
+      Ok(code) if (200..300).contains(&code) => (Some(code as i32), None),
+      Ok(code) => (
+        Some(code as i32),
+        Some(format!("Receiver responded with {}", code)),
+      ),
+      Err(err) => (None, Some(err.to_string())),
+    },
+    None => (None, Some("Webhook has been deleted".to_string())),
+  };
+  let (status, next_attempt_at) = if error.is_none() {
+    (DeliveryStatus::Succeeded, None)
+  } else if webhook.is_none() || attempts >= state.webhook_max_attempts {
+    (DeliveryStatus::Failed, None)
+  } else {
+    (
+      DeliveryStatus::Pending,
+      Some(utc_now() + retry_delay(state.webhook_retry_interval, attempts)),
+    )
+  };
+  let mut active_delivery = delivery.into_active_model();
+  active_delivery.attempts = Set(attempts);
+  active_delivery.status = Set(status);
+  active_delivery.response_status = Set(response_status);
+  active_delivery.error = Set(error);
+  active_delivery.next_attempt_at = Set(next_attempt_at);
+  active_delivery.updated_at = Set(Some(utc_now()));
+  Ok(
+    state
+      .repo
+      .webhook_delivery()
+      .update_delivery(active_delivery)
+      .await?,
+  )
+}
+
+/// 重试到期的投递，由定时任务调用
+pub async fn retry_due_deliveries(state: &AppState) -> ServiceResult<()> {
+  let deliveries = state
+    .repo
+    .webhook_delivery()
+    .get_due_deliveries(utc_now())
+    .await?;
+  for delivery in deliveries {
+    let id = delivery.id;
+    if let Err(err) = deliver(state, delivery).await {
+      tracing::error!("Failed to retry webhook delivery {}: {}", id, err);
+    }
+  }
+  Ok(())
+}
@@ -3,7 +3,11 @@
 use helpers::uuid::{Alphabet, nanoid};
 use serde::Deserialize;
 
-use crate::error::AppError;
+use crate::{
+  error::AppError,
+  helper::TrustedProxies,
+  rate_limit::{RateLimit, RouteLimits},
+};
 
 fn default_workers() -> usize {
   1
@@ -25,6 +29,82 @@ fn default_key_expire() -> i64 {
   100000
 }
 
+fn default_casual_site_ttl() -> i64 {
+  86400
+}
+
+fn default_casual_site_expire_warning() -> i64 {
+  3600
+}
+
+fn default_webhook_max_attempts() -> i32 {
+  6
+}
+
+fn default_webhook_retry_interval() -> i64 {
+  30
+}
+
+fn default_task_max_attempts() -> i32 {
+  5
+}
+
+fn default_task_retry_interval() -> i64 {
+  5
+}
+
+fn default_task_retention() -> i64 {
+  7 * 24 * 60 * 60
+}
+
+fn default_reconcile_interval() -> u64 {
+  300
+}
+
+fn default_reconcile_orphan_grace() -> u64 {
+  24 * 60 * 60
+}
+
+fn default_backup_dir() -> String {
+  "backups".to_string()
+}
+
+fn default_backup_interval() -> i64 {
+  86400
+}
+
+fn default_backup_retention() -> usize {
+  7
+}
+
+fn default_rate_limit() -> RateLimit {
+  RateLimit {
+    burst: 300,
+    period: 60,
+  }
+}
+
+fn default_rate_limit_routes() -> RouteLimits {
+  "GET /api/user/casual=5/3600,POST /api/user=10/3600,POST /api/user/token=20/60"
+    .parse()
+    .unwrap_or_default()
+}
+
+fn default_rate_limit_login_account() -> RateLimit {
+  RateLimit {
+    burst: 10,
+    period: 300,
+  }
+}
+
+fn default_login_lockout_threshold() -> u32 {
+  5
+}
+
+fn default_login_lockout_duration() -> u64 {
+  60
+}
+
 #[derive(Deserialize, Debug)]
 pub struct Config {
   #[serde(default = "default_workers")]
@@ -43,6 +123,69 @@ pub struct Config {
   pub cloudflare_api_key: String,
   pub cloudflare_email: String,
   pub cloudflare_zone_id: String,
+  /// 临时用户站点的存活时间（秒）
+  #[serde(default = "default_casual_site_ttl")]
+  pub casual_site_ttl: i64,
+  /// 临时站点过期前多久发出警告（秒）
+  #[serde(default = "default_casual_site_expire_warning")]
+  pub casual_site_expire_warning: i64,
+  /// Webhook 最多投递次数，包括第一次投递
+  #[serde(default = "default_webhook_max_attempts")]
+  pub webhook_max_attempts: i32,
+  /// Webhook 第一次重试的间隔（秒），之后每次翻倍
+  #[serde(default = "default_webhook_retry_interval")]
+  pub webhook_retry_interval: i64,
+  /// 允许 Webhook 投递到回环和内网地址，仅用于自建环境
+  #[serde(default)]
+  pub webhook_allow_private_network: bool,
+  /// 任务最多执行次数，包括第一次执行
+  #[serde(default = "default_task_max_attempts")]
+  pub task_max_attempts: i32,
+  /// 任务第一次重试的间隔（秒），之后每次翻倍
+  #[serde(default = "default_task_retry_interval")]
+  pub task_retry_interval: i64,
+  /// 已结束的任务保留多久（秒），之后从 `task` 表中删除
+  #[serde(default = "default_task_retention")]
+  pub task_retention: i64,
+  /// 对比 Agent 实际状态与数据库记录的间隔（秒）
+  #[serde(default = "default_reconcile_interval")]
+  pub reconcile_interval: u64,
+  /// 撤销 Agent 上不在记录中的站点，默认只报告
+  #[serde(default)]
+  pub reconcile_revoke_orphans: bool,
+  /// 不在记录中的站点持续多久（秒）后才撤销
+  #[serde(default = "default_reconcile_orphan_grace")]
+  pub reconcile_orphan_grace: u64,
+  /// 备份文件的保存目录
+  #[serde(default = "default_backup_dir")]
+  pub backup_dir: String,
+  /// 定时备份的间隔（秒），为 0 时不自动备份
+  #[serde(default = "default_backup_interval")]
+  pub backup_interval: i64,
+  /// 自动备份保留的份数
+  #[serde(default = "default_backup_retention")]
+  pub backup_retention: usize,
+  /// 没有单独配置的路由按客户端 IP 共用的限流规则，格式为 `N/S`
+  #[serde(default = "default_rate_limit")]
+  pub rate_limit: RateLimit,
+  /// 按路由和客户端 IP 的限流规则，格式为 `METHOD PATH=N/S`，以逗号分隔
+  #[serde(default = "default_rate_limit_routes")]
+  pub rate_limit_routes: RouteLimits,
+  /// 每个账号的登录尝试频率
+  #[serde(default = "default_rate_limit_login_account")]
+  pub rate_limit_login_account: RateLimit,
+  /// 是否从 `X-Forwarded-For` 等请求头读取客户端 IP，仅在反向代理后开启
+  #[serde(default)]
+  pub trust_proxy: bool,
+  /// 直连的反向代理之前还有其他代理时，这些代理的 IP，以逗号分隔
+  #[serde(default)]
+  pub trusted_proxies: TrustedProxies,
+  /// 连续登录失败多少次后锁定账号，为 0 时不锁定
+  #[serde(default = "default_login_lockout_threshold")]
+  pub login_lockout_threshold: u32,
+  /// 第一次锁定的时长（秒），之后每次失败翻倍，最长一天
+  #[serde(default = "default_login_lockout_duration")]
+  pub login_lockout_duration: u64,
 }
 
 impl Config {
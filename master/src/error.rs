@@ -1,5 +1,8 @@
-use actix_web::{HttpResponse, ResponseError, http::StatusCode};
-use common::Response;
+use actix_web::{
+  HttpResponse, ResponseError,
+  http::{StatusCode, header},
+};
+use common::{Response, error::ErrorCode};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -28,6 +31,8 @@ pub enum AppError {
   Forbidden,
   #[error("User already exists")]
   UserExists,
+  #[error("User is suspended")]
+  UserSuspended,
   #[error("Password error")]
   PasswordError,
   #[error("Agent already exists")]
@@ -36,6 +41,15 @@ pub enum AppError {
   AgentNotFound,
   #[error("Deployment not found")]
   DeploymentNotFound,
+  #[error("Webhook not found")]
+  WebhookNotFound,
+  #[error("Task not found")]
+  TaskNotFound,
+  #[error("Invalid deployment status transition from {from:?} to {to:?}")]
+  InvalidStatusTransition {
+    from: entity::deployment::DeploymentStatus,
+    to: entity::deployment::DeploymentStatus,
+  },
   #[error("RPC call error: {source}")]
   RpcCallError {
     #[from]
@@ -74,6 +88,10 @@ pub enum AppError {
     #[from]
     source: std::net::AddrParseError,
   },
+  #[error("Webhook url is not allowed: {0}")]
+  InvalidWebhookUrl(String),
+  #[error("Too many requests, retry after {retry_after} seconds")]
+  TooManyRequests { retry_after: u64 },
   #[error("Other error: {message}")]
   Other {
     message: String,
@@ -83,28 +101,35 @@ pub enum AppError {
 }
 
 impl AppError {
-  pub fn code(&self) -> i32 {
+  pub fn code(&self) -> ErrorCode {
     match self {
       AppError::InternalServerError { .. }
-      | AppError::Database { .. }
-      | AppError::Env { .. }
-      | AppError::RpcCallError { .. }
       | AppError::JwtError { .. }
       | AppError::Other { .. }
       | AppError::ToStrError { .. }
       | AppError::AddrParseError { .. }
-      | AppError::HashError { .. } => 1000,
-      AppError::ExpiredSignature | AppError::InvalidJwtSignature => 2000,
-      AppError::PasswordError => 2001,
-      AppError::Authorization => 2002,
-      AppError::Forbidden => 2003,
-      AppError::Params { .. } => 2004,
-      AppError::AgentNotFound
-      | AppError::DeploymentNotFound
-      | AppError::SiteNotFound
-      | AppError::UserNotFound => 2005,
-      AppError::UserExists | AppError::AgentExists => 2006,
-      AppError::NotImplemented => 9999,
+      | AppError::HashError { .. } => ErrorCode::Internal,
+      AppError::Database { .. } => ErrorCode::Database,
+      AppError::Env { .. } => ErrorCode::Config,
+      AppError::RpcCallError { .. } => ErrorCode::Upstream,
+      AppError::InvalidJwtSignature => ErrorCode::InvalidToken,
+      AppError::ExpiredSignature => ErrorCode::TokenExpired,
+      AppError::PasswordError => ErrorCode::PasswordError,
+      AppError::Authorization => ErrorCode::AuthorizationRequired,
+      AppError::Forbidden => ErrorCode::Forbidden,
+      AppError::Params { .. } | AppError::InvalidWebhookUrl(_) => ErrorCode::InvalidParams,
+      AppError::UserNotFound => ErrorCode::UserNotFound,
+      AppError::UserExists => ErrorCode::UserExists,
+      AppError::UserSuspended => ErrorCode::UserSuspended,
+      AppError::AgentExists => ErrorCode::AgentExists,
+      AppError::AgentNotFound => ErrorCode::AgentNotFound,
+      AppError::SiteNotFound => ErrorCode::SiteNotFound,
+      AppError::DeploymentNotFound => ErrorCode::DeploymentNotFound,
+      AppError::WebhookNotFound => ErrorCode::WebhookNotFound,
+      AppError::TaskNotFound => ErrorCode::TaskNotFound,
+      AppError::InvalidStatusTransition { .. } => ErrorCode::InvalidStatusTransition,
+      AppError::TooManyRequests { .. } => ErrorCode::TooManyRequests,
+      AppError::NotImplemented => ErrorCode::NotImplemented,
     }
   }
 
@@ -123,29 +148,52 @@ impl AppError {
       | AppError::PasswordError
       | AppError::InvalidJwtSignature
       | AppError::ExpiredSignature => StatusCode::UNAUTHORIZED,
-      AppError::Forbidden => StatusCode::FORBIDDEN,
+      AppError::Forbidden | AppError::UserSuspended => StatusCode::FORBIDDEN,
       AppError::UserNotFound
       | AppError::SiteNotFound
       | AppError::AgentNotFound
-      | AppError::DeploymentNotFound => StatusCode::NOT_FOUND,
-      AppError::UserExists | AppError::AgentExists => StatusCode::CONFLICT,
-      AppError::Params { .. } => StatusCode::BAD_REQUEST,
+      | AppError::DeploymentNotFound
+      | AppError::WebhookNotFound
+      | AppError::TaskNotFound => StatusCode::NOT_FOUND,
+      AppError::UserExists | AppError::AgentExists | AppError::InvalidStatusTransition { .. } => {
+        StatusCode::CONFLICT
+      }
+      AppError::Params { .. } | AppError::InvalidWebhookUrl(_) => StatusCode::BAD_REQUEST,
+      AppError::TooManyRequests { .. } => StatusCode::TOO_MANY_REQUESTS,
       AppError::NotImplemented => StatusCode::NOT_IMPLEMENTED,
     }
   }
 
+  /// 任务执行失败后是否值得重试，Agent 暂时不可达或数据库出错时可以重试
+  pub fn is_retryable(&self) -> bool {
+    match self {
+      AppError::RpcCallError { source } => source.is_retryable(),
+      AppError::InternalServerError { .. } | AppError::Database { .. } => true,
+      _ => false,
+    }
+  }
+
+  /// 返回给客户端的提示，服务端错误只使用错误码的默认提示，避免泄露内部信息
   pub fn user_message(&self) -> String {
-    "user message".to_string()
+    if self.status_code().is_server_error() {
+      self.code().message().to_string()
+    } else {
+      self.to_string()
+    }
   }
 }
 
 impl ResponseError for AppError {
   fn error_response(&self) -> HttpResponse {
     tracing::error!("{}", self.to_string());
-    HttpResponse::build(self.status_code()).json(Response::<Option<()>> {
+    let mut response = HttpResponse::build(self.status_code());
+    if let AppError::TooManyRequests { retry_after } = self {
+      response.insert_header((header::RETRY_AFTER, retry_after.to_string()));
+    }
+    response.json(Response::<Option<()>> {
       data: None,
-      code: self.code(),
-      msg: self.to_string(), // 使用 thiserror 格式化的错误消息，或调用 user_message()
+      code: self.code().into(),
+      msg: self.user_message(),
     })
   }
 }
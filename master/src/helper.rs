@@ -1,6 +1,13 @@
+use std::{net::IpAddr, str::FromStr};
+
 use actix_web::HttpRequest;
+use common::{Paginated, master::IDEMPOTENCY_KEY_HEADER};
+use helpers::jwt;
+use serde::Deserialize;
+
+use entity::user::UserStatus;
 
-use crate::error::AppError;
+use crate::{app::AppState, error::AppError};
 
 pub fn extract_token(req: &HttpRequest) -> Result<String, AppError> {
   let auth_header = req
@@ -14,6 +21,133 @@ pub fn extract_token(req: &HttpRequest) -> Result<String, AppError> {
   Ok(auth_header[7..].to_string()) // Skip "Bearer " prefix
 }
 
+/// 从请求的登录 token 中解析出用户 ID，并确认用户仍处于正常状态，
+/// 暂停或删除的用户签发过的 token 随即失效
+pub async fn extract_user_id(req: &HttpRequest, state: &AppState) -> Result<String, AppError> {
+  let token = extract_token(req)?;
+  let user_id = jwt::verify::<String>(&token, &state.login_token_key)?
+    .claims
+    .data;
+  let user = state
+    .repo
+    .user()
+    .get_user_by_id(user_id.clone())
+    .await?
+    .ok_or(AppError::UserNotFound)?;
+  match user.status {
+    UserStatus::Active => Ok(user_id),
+    UserStatus::Suspended => Err(AppError::UserSuspended),
+    UserStatus::Deleted => Err(AppError::UserNotFound),
+  }
+}
+
+/// 直连的反向代理之外，还需要跳过的代理地址，以逗号分隔
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct TrustedProxies(Vec<IpAddr>);
+
+impl FromStr for TrustedProxies {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    s.split(',')
+      .map(str::trim)
+      .filter(|ip| !ip.is_empty())
+      .map(|ip| {
+        ip.parse()
+          .map_err(|_| format!("Invalid trusted proxy `{}`, expected an IP address", ip))
+      })
+      .collect::<Result<_, _>>()
+      .map(TrustedProxies)
+  }
+}
+
+impl TryFrom<String> for TrustedProxies {
+  type Error = String;
+
+  fn try_from(s: String) -> Result<Self, Self::Error> {
+    s.parse()
+  }
+}
+
+/// 获取客户端 IP。`trust_proxy` 为 true 时对端是反向代理，从右往左取 `X-Forwarded-For`
+/// 中第一个不属于 `trusted_proxies` 的地址，左侧的部分可以由客户端任意伪造
+pub fn client_ip(req: &HttpRequest, state: &AppState) -> String {
+  let peer = req.peer_addr().map(|addr| addr.ip());
+  let ip = if state.trust_proxy {
+    let forwarded_for = req
+      .headers()
+      .get_all("X-Forwarded-For")
+      .filter_map(|value| value.to_str().ok())
+      .collect::<Vec<_>>()
+      .join(",");
+    forwarded_client_ip(&forwarded_for, &state.trusted_proxies).or(peer)
+  } else {
+    peer
+  };
+  ip.map(|ip| ip.to_string()).unwrap_or_default()
+}
+
+/// `X-Forwarded-For` 中最右侧的不受信任的地址，无法解析时返回 `None`
+fn forwarded_client_ip(forwarded_for: &str, trusted: &TrustedProxies) -> Option<IpAddr> {
+  forwarded_for
+    .rsplit(',')
+    .map(str::trim)
+    .filter(|hop| !hop.is_empty())
+    .map(|hop| hop.parse::<IpAddr>().ok())
+    .find(|ip| ip.is_none_or(|ip| !trusted.0.contains(&ip)))
+    .flatten()
+}
+
+/// 读取请求的 `Idempotency-Key` 请求头
+pub fn extract_idempotency_key(req: &HttpRequest) -> Result<Option<String>, AppError> {
+  Ok(
+    req
+      .headers()
+      .get(IDEMPOTENCY_KEY_HEADER)
+      .map(|key| key.to_str())
+      .transpose()?
+      .map(|key| key.to_string()),
+  )
+}
+
+pub fn default_page() -> u64 {
+  1
+}
+
+pub fn default_page_size() -> u64 {
+  20
+}
+
+/// 把从 1 开始的页码转换为仓储层从 0 开始的页码，并限制每页条数
+pub fn page_args(page: u64, page_size: u64) -> (u64, u64) {
+  (page.max(1) - 1, page_size.clamp(1, 100))
+}
+
+pub fn paginated<T, M: Into<T>>(
+  items: Vec<M>,
+  total: u64,
+  page: u64,
+  page_size: u64,
+) -> Paginated<T> {
+  Paginated {
+    items: items.into_iter().map(Into::into).collect(),
+    total,
+    page: page + 1,
+    page_size,
+  }
+}
+
+/// 站点的预览域名
+pub fn preview_domain(site_id: &str) -> String {
+  format!("preview_{}.jinqiu.wang", site_id)
+}
+
+/// 第 `attempts` 次失败后的重试间隔，从 `interval` 秒开始每次翻倍
+pub fn retry_delay(interval: i64, attempts: i32) -> chrono::Duration {
+  chrono::Duration::seconds(interval.saturating_mul(1 << attempts.clamp(1, 11).saturating_sub(1)))
+}
+
 // pub fn extract_ip(req: &HttpRequest) -> String {
 //   if let Some(h) = req.headers().get("X-Forwarded-For") {
 //     let s = h.to_str().unwrap_or("0.0.0.0").to_string();
@@ -45,3 +179,73 @@ pub fn extract_token(req: &HttpRequest) -> Result<String, AppError> {
 //     .unwrap_or_default()
 //     .to_string()
 // }
+
+#[cfg(test)]
+mod tests {
+  use super::{TrustedProxies, forwarded_client_ip, retry_delay};
+
+  #[test]
+  fn test_retry_delay() {
+    assert_eq!(retry_delay(30, 1).num_seconds(), 30);
+    assert_eq!(retry_delay(30, 2).num_seconds(), 60);
+    assert_eq!(retry_delay(30, 4).num_seconds(), 240);
+    assert_eq!(retry_delay(30, 100), retry_delay(30, 11));
+  }
+
+  #[test]
+  fn test_forwarded_client_ip() {
+    let none = TrustedProxies::default();
+    let trusted: TrustedProxies = "10.0.0.2, 10.0.0.3".parse().unwrap();
+    let ip = |s: &str| Some(s.parse().unwrap());
+    // 客户端自带的 X-Forwarded-For 会出现在最左侧，不能采用
+    assert_eq!(
+      forwarded_client_ip("6.6.6.6, 1.2.3.4", &none),
+      ip("1.2.3.4")
+    );
+    assert_eq!(
+      forwarded_client_ip("6.6.6.6, 1.2.3.4, 10.0.0.3,10.0.0.2", &trusted),
+      ip("1.2.3.4")
+    );
+    assert_eq!(forwarded_client_ip("6.6.6.6, garbage", &none), None);
+    assert_eq!(forwarded_client_ip("10.0.0.2", &trusted), None);
+    assert_eq!(forwarded_client_ip("", &none), None);
+    assert!("10.0.0.1,not-an-ip".parse::<TrustedProxies>().is_err());
+  }
+
+  #[cfg(feature = "sqlite")]
+  #[actix_web::test]
+  async fn test_extract_user_id() {
+    use actix_web::test::TestRequest;
+    use entity::user::{UserStatus, UserType};
+    use rpc::MockAgentApi;
+    use sea_orm::{IntoActiveModel, Set};
+
+    use super::extract_user_id;
+    use crate::{
+      error::AppError,
+      testing::{create_user, login_token, test_state},
+    };
+
+    let state = test_state(MockAgentApi::new()).await;
+    let user = create_user(&state, "user", UserType::Normal).await;
+    let req = TestRequest::default()
+      .insert_header((
+        "Authorization",
+        format!("Bearer {}", login_token(&state, "user")),
+      ))
+      .to_http_request();
+    assert_eq!(extract_user_id(&req, &state).await.unwrap(), "user");
+
+    // 暂停或删除后，之前签发的 token 随即失效
+    for status in [UserStatus::Suspended, UserStatus::Deleted] {
+      let mut active_user = user.clone().into_active_model();
+      active_user.status = Set(status.clone());
+      state.repo.user().update_user(active_user).await.unwrap();
+      let result = extract_user_id(&req, &state).await;
+      match status {
+        UserStatus::Suspended => assert!(matches!(result, Err(AppError::UserSuspended))),
+        _ => assert!(matches!(result, Err(AppError::UserNotFound))),
+      }
+    }
+  }
+}
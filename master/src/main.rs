@@ -1,18 +1,54 @@
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand};
 use tracing::level_filters::LevelFilter;
 use tracing_subscriber::{EnvFilter, filter, fmt, layer::SubscriberExt, util::SubscriberInitExt};
 
 mod app;
+mod backup;
 mod components;
 mod config;
 mod error;
 mod helper;
-mod middlewares;
 mod migration;
+mod openapi;
+mod rate_limit;
+mod reconcile;
 mod repository;
+#[cfg(all(test, feature = "sqlite"))]
+mod testing;
 mod timing;
 mod traits;
 mod types;
 
+#[derive(Parser)]
+#[command(name = "pupup-master", bin_name = "pupup-master", version, about, long_about = None)]
+struct Cli {
+  /// Start the server when no subcommand is given
+  #[command(subcommand)]
+  command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+  /// back up the database and the manifest of site artifacts
+  Backup {
+    #[arg(long, help = "Backup file path, defaults to a new file in BACKUP_DIR")]
+    output: Option<PathBuf>,
+  },
+  /// rebuild the database from a backup and republish its sites
+  Restore {
+    #[arg(help = "Backup file path")]
+    file: PathBuf,
+    #[arg(
+      long,
+      default_value_t = false,
+      help = "Overwrite a database that is not empty"
+    )]
+    force: bool,
+  },
+}
+
 #[actix_web::main]
 async fn main() -> Result<(), error::AppError> {
   let target_filter = filter::Targets::new()
@@ -28,5 +64,9 @@ async fn main() -> Result<(), error::AppError> {
     .with(target_filter)
     .with(env_filter)
     .init();
-  app::start().await
+  match Cli::parse().command {
+    None => app::start().await,
+    Some(Commands::Backup { output }) => backup::backup_command(output).await,
+    Some(Commands::Restore { file, force }) => backup::restore_command(file, force).await,
+  }
 }
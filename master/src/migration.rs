@@ -2,7 +2,54 @@ use migration::{Migrator, MigratorTrait};
 use sea_orm::{DatabaseConnection, DbErr};
 
 pub async fn migrate(database_url: &str) -> Result<DatabaseConnection, DbErr> {
+  check_backend(database_url)?;
   let connection = sea_orm::Database::connect(database_url).await?;
   Migrator::up(&connection, None).await?;
   Ok(connection)
 }
+
+/// 根据 `DATABASE_URL` 的 scheme 检查对应的数据库后端是否已通过 feature 编译进来
+fn check_backend(database_url: &str) -> Result<(), DbErr> {
+  let (scheme, _) = database_url.split_once(':').unwrap_or_default();
+  let (feature, enabled) = match scheme {
+    "sqlite" => ("sqlite", cfg!(feature = "sqlite")),
+    "postgres" | "postgresql" => ("postgres", cfg!(feature = "postgres")),
+    "mysql" => ("mysql", cfg!(feature = "mysql")),
+    _ => {
+      return Err(DbErr::Custom(format!(
+        "Unsupported DATABASE_URL scheme `{}`, expected sqlite, postgres or mysql",
+        scheme
+      )));
+    }
+  };
+  if !enabled {
+    return Err(DbErr::Custom(format!(
+      "DATABASE_URL uses {}, but master was built without the `{}` feature",
+      scheme, feature
+    )));
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_check_backend() {
+    assert!(check_backend("unknown://localhost").is_err());
+    assert!(check_backend("localhost").is_err());
+    assert_eq!(
+      check_backend("sqlite::memory:").is_ok(),
+      cfg!(feature = "sqlite")
+    );
+    assert_eq!(
+      check_backend("postgres://pupup@localhost/pupup").is_ok(),
+      cfg!(feature = "postgres")
+    );
+    assert_eq!(
+      check_backend("mysql://pupup@localhost/pupup").is_ok(),
+      cfg!(feature = "mysql")
+    );
+  }
+}
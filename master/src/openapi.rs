@@ -0,0 +1,87 @@
+//! 由 handler 上的注解生成的 OpenAPI 文档，在 `/api/openapi.json` 提供
+
+use actix_web::{HttpResponse, get};
+use utoipa::{
+  Modify, OpenApi,
+  openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+};
+
+use crate::components::{
+  admin::AdminApi, agent::AgentApi, base, deployment::DeploymentApi, site::SiteApi, task::TaskApi,
+  user::UserApi, webhook::WebhookApi,
+};
+
+/// 补充注解无法表达的部分：登录 token 的认证方式，以及去掉 Cargo.toml 未声明时生成的空 license
+struct Addon;
+
+impl Modify for Addon {
+  fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+    openapi.info.license = None;
+    openapi
+      .components
+      .get_or_insert_with(Default::default)
+      .add_security_scheme(
+        // 以 `Authorization: Bearer <token>` 传递的登录 token
+        "bearer_auth",
+        SecurityScheme::Http(
+          HttpBuilder::new()
+            .scheme(HttpAuthScheme::Bearer)
+            .bearer_format("JWT")
+            .build(),
+        ),
+      );
+  }
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(base::health_check, openapi_json))]
+struct BaseApi;
+
+#[derive(OpenApi)]
+#[openapi(
+  info(title = "Pupup Master API", description = "Master 对 CLI、Agent 和管理后台提供的接口"),
+  nest(
+    (path = "/api", api = BaseApi),
+    (path = "/api", api = UserApi),
+    (path = "/api", api = AgentApi),
+    (path = "/api", api = SiteApi),
+    (path = "/api", api = DeploymentApi),
+    (path = "/api", api = TaskApi),
+    (path = "/api", api = WebhookApi),
+    (path = "/api/admin", api = AdminApi),
+  ),
+  modifiers(&Addon)
+)]
+pub struct ApiDoc;
+
+#[utoipa::path(
+  tag = "base",
+  responses((status = OK, description = "OpenAPI 3 文档", content_type = "application/json"))
+)]
+#[get("/openapi.json")]
+pub async fn openapi_json() -> HttpResponse {
+  HttpResponse::Ok().json(ApiDoc::openapi())
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{fs, path::Path};
+
+  use super::*;
+
+  /// 接口变更后需用 `UPDATE_OPENAPI=1 cargo test -p master openapi` 重新生成 `openapi.json`
+  #[test]
+  fn test_openapi_up_to_date() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("openapi.json");
+    let spec = ApiDoc::openapi().to_pretty_json().unwrap() + "\n";
+    if std::env::var_os("UPDATE_OPENAPI").is_some() {
+      fs::write(&path, &spec).unwrap();
+      return;
+    }
+    let saved = fs::read_to_string(&path).unwrap_or_default();
+    assert!(
+      saved == spec,
+      "master/openapi.json is out of date, regenerate it with `UPDATE_OPENAPI=1 cargo test -p master openapi`"
+    );
+  }
+}
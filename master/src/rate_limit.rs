@@ -0,0 +1,397 @@
+//! 基于令牌桶的限流与登录失败锁定
+
+use std::{
+  collections::HashMap,
+  str::FromStr,
+  sync::{Arc, Mutex},
+  time::{Duration, Instant},
+};
+
+use actix_web::{
+  Error,
+  body::MessageBody,
+  dev::{ServiceRequest, ServiceResponse},
+  middleware::Next,
+  web::Data,
+};
+use serde::Deserialize;
+
+use crate::{app::AppState, error::AppError, helper::client_ip};
+
+/// 连续登录失败后最长的锁定时间
+const MAX_LOCKOUT: Duration = Duration::from_secs(86400);
+
+/// 限流规则 `N/S`：每 S 秒最多 N 个请求，令牌匀速恢复。N 为 0 时不限流
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct RateLimit {
+  pub burst: u32,
+  pub period: u64,
+}
+
+impl RateLimit {
+  fn is_disabled(&self) -> bool {
+    self.burst == 0 || self.period == 0
+  }
+
+  /// 每秒恢复的令牌数
+  fn refill_rate(&self) -> f64 {
+    self.burst as f64 / self.period as f64
+  }
+}
+
+impl FromStr for RateLimit {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let invalid = || {
+      format!(
+        "Invalid rate limit `{}`, expected `<requests>/<seconds>`",
+        s
+      )
+    };
+    let (burst, period) = s.trim().split_once('/').ok_or_else(invalid)?;
+    Ok(RateLimit {
+      burst: burst.trim().parse().map_err(|_| invalid())?,
+      period: period.trim().parse().map_err(|_| invalid())?,
+    })
+  }
+}
+
+impl TryFrom<String> for RateLimit {
+  type Error = String;
+
+  fn try_from(s: String) -> Result<Self, Self::Error> {
+    s.parse()
+  }
+}
+
+/// 按路由配置的限流规则，格式为 `METHOD PATH=N/S`，多条规则以逗号分隔，
+/// PATH 为路由定义中的路径，如 `/api/agent/{agent_id}`
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct RouteLimits(HashMap<String, RateLimit>);
+
+impl RouteLimits {
+  pub fn get(&self, method: &str, pattern: &str) -> Option<RateLimit> {
+    self.0.get(&format!("{} {}", method, pattern)).copied()
+  }
+}
+
+impl FromStr for RouteLimits {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let mut routes = HashMap::new();
+    for rule in s.split(',').map(str::trim).filter(|rule| !rule.is_empty()) {
+      let (route, limit) = rule.rsplit_once('=').ok_or_else(|| {
+        format!(
+          "Invalid route rate limit `{}`, expected `METHOD PATH=N/S`",
+          rule
+        )
+      })?;
+      let (method, path) = route
+        .trim()
+        .split_once(' ')
+        .ok_or_else(|| format!("Invalid route `{}`, expected `METHOD PATH`", route))?;
+      routes.insert(
+        format!("{} {}", method.to_uppercase(), path.trim()),
+        limit.parse()?,
+      );
+    }
+    Ok(RouteLimits(routes))
+  }
+}
+
+impl TryFrom<String> for RouteLimits {
+  type Error = String;
+
+  fn try_from(s: String) -> Result<Self, Self::Error> {
+    s.parse()
+  }
+}
+
+#[derive(Debug)]
+struct Bucket {
+  tokens: f64,
+  limit: RateLimit,
+  updated_at: Instant,
+}
+
+impl Bucket {
+  fn refill(&mut self, now: Instant) {
+    let elapsed = now.saturating_duration_since(self.updated_at).as_secs_f64();
+    self.tokens = (self.tokens + elapsed * self.limit.refill_rate()).min(self.limit.burst as f64);
+    self.updated_at = now;
+  }
+}
+
+/// 以 key 区分的令牌桶
+#[derive(Debug, Clone, Default)]
+struct Buckets {
+  inner: Arc<Mutex<HashMap<String, Bucket>>>,
+}
+
+impl Buckets {
+  /// 取出一个令牌，桶已空时返回需要等待的时间
+  fn acquire(&self, key: &str, limit: RateLimit, now: Instant) -> Result<(), Duration> {
+    if limit.is_disabled() {
+      return Ok(());
+    }
+    let mut buckets = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+    let bucket = buckets.entry(key.to_string()).or_insert(Bucket {
+      tokens: limit.burst as f64,
+      limit,
+      updated_at: now,
+    });
+    bucket.limit = limit;
+    bucket.refill(now);
+    if bucket.tokens >= 1.0 {
+      bucket.tokens -= 1.0;
+      Ok(())
+    } else {
+      Err(Duration::from_secs_f64(
+        (1.0 - bucket.tokens) / limit.refill_rate(),
+      ))
+    }
+  }
+
+  /// 清理已经回满的桶
+  fn prune(&self, now: Instant) {
+    let mut buckets = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+    buckets.retain(|_, bucket| {
+      bucket.refill(now);
+      bucket.tokens < bucket.limit.burst as f64
+    });
+  }
+}
+
+/// 按客户端 IP 和路由限流
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+  buckets: Buckets,
+  default: RateLimit,
+  routes: RouteLimits,
+}
+
+impl RateLimiter {
+  pub fn new(default: RateLimit, routes: RouteLimits) -> Self {
+    RateLimiter {
+      buckets: Buckets::default(),
+      default,
+      routes,
+    }
+  }
+
+  /// 路由有单独的规则时按路由计数，否则所有路由共用默认规则
+  pub fn acquire(
+    &self,
+    method: &str,
+    pattern: Option<&str>,
+    ip: &str,
+    now: Instant,
+  ) -> Result<(), AppError> {
+    let (route, limit) = pattern
+      .and_then(|pattern| {
+        self
+          .routes
+          .get(method, pattern)
+          .map(|limit| (format!("{} {}", method, pattern), limit))
+      })
+      .unwrap_or_else(|| ("*".to_string(), self.default));
+    self
+      .buckets
+      .acquire(&format!("{}|{}", route, ip), limit, now)
+      .map_err(too_many_requests)
+  }
+
+  pub fn prune(&self, now: Instant) {
+    self.buckets.prune(now);
+  }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Failures {
+  count: u32,
+  locked_until: Option<Instant>,
+  last_failure: Instant,
+}
+
+/// 按账号限制登录尝试的频率；同一账号在同一 IP 上连续失败达到阈值后锁定，之后每次失败锁定时间翻倍。
+/// 锁定按账号和 IP 区分，他人无法通过故意输错密码锁住账号
+#[derive(Debug, Clone)]
+pub struct LoginGuard {
+  buckets: Buckets,
+  failures: Arc<Mutex<HashMap<String, Failures>>>,
+  limit: RateLimit,
+  threshold: u32,
+  lockout: Duration,
+}
+
+impl LoginGuard {
+  pub fn new(limit: RateLimit, threshold: u32, lockout: Duration) -> Self {
+    LoginGuard {
+      buckets: Buckets::default(),
+      failures: Arc::default(),
+      limit,
+      threshold,
+      lockout,
+    }
+  }
+
+  /// 登录前调用，账号在该 IP 上被锁定或尝试过于频繁时返回错误
+  pub fn check(&self, account: &str, ip: &str, now: Instant) -> Result<(), AppError> {
+    let failures = self.failures.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(locked_until) = failures
+      .get(&failure_key(account, ip))
+      .and_then(|f| f.locked_until)
+      .filter(|until| *until > now)
+    {
+      return Err(too_many_requests(locked_until - now));
+    }
+    drop(failures);
+    self
+      .buckets
+      .acquire(account, self.limit, now)
+      .map_err(too_many_requests)
+  }
+
+  pub fn record_failure(&self, account: &str, ip: &str, now: Instant) {
+    if self.threshold == 0 {
+      return;
+    }
+    let mut failures = self.failures.lock().unwrap_or_else(|e| e.into_inner());
+    let entry = failures
+      .entry(failure_key(account, ip))
+      .or_insert(Failures {
+        count: 0,
+        locked_until: None,
+        last_failure: now,
+      });
+    entry.count += 1;
+    entry.last_failure = now;
+    if entry.count >= self.threshold {
+      let lockout = self
+        .lockout
+        .saturating_mul(1 << (entry.count - self.threshold).min(16))
+        .min(MAX_LOCKOUT);
+      entry.locked_until = Some(now + lockout);
+      tracing::warn!(
+        "Account {} locked on {} for {:?} after {} failed logins",
+        account,
+        ip,
+        lockout,
+        entry.count
+      );
+    }
+  }
+
+  pub fn record_success(&self, account: &str, ip: &str) {
+    let mut failures = self.failures.lock().unwrap_or_else(|e| e.into_inner());
+    failures.remove(&failure_key(account, ip));
+  }
+
+  /// 清理已回满的令牌桶，以及锁定已结束且超过一天没有再失败的账号
+  pub fn prune(&self, now: Instant) {
+    self.buckets.prune(now);
+    let mut failures = self.failures.lock().unwrap_or_else(|e| e.into_inner());
+    failures.retain(|_, f| {
+      f.locked_until.is_some_and(|until| until > now)
+        || now.saturating_duration_since(f.last_failure) < MAX_LOCKOUT
+    });
+  }
+}
+
+fn failure_key(account: &str, ip: &str) -> String {
+  format!("{}|{}", account, ip)
+}
+
+fn too_many_requests(wait: Duration) -> AppError {
+  AppError::TooManyRequests {
+    retry_after: (wait.as_secs() + u64::from(wait.subsec_nanos() > 0)).max(1),
+  }
+}
+
+/// 按客户端 IP 限流的中间件，超出限制时返回 429 和 `Retry-After`
+pub async fn rate_limit(
+  req: ServiceRequest,
+  next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+  if let Some(state) = req.app_data::<Data<AppState>>() {
+    state.rate_limiter.acquire(
+      req.method().as_str(),
+      req.match_pattern().as_deref(),
+      &client_ip(req.request(), state),
+      Instant::now(),
+    )?;
+  }
+  next.call(req).await
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn retry_after(result: Result<(), AppError>) -> u64 {
+    match result {
+      Err(AppError::TooManyRequests { retry_after }) => retry_after,
+      other => panic!("expected TooManyRequests, got {:?}", other),
+    }
+  }
+
+  #[test]
+  fn test_route_limits() {
+    let routes: RouteLimits = "GET /api/user/casual=5/3600, post /api/user/token=20/60"
+      .parse()
+      .unwrap();
+    assert_eq!(
+      routes.get("POST", "/api/user/token"),
+      Some(RateLimit {
+        burst: 20,
+        period: 60
+      })
+    );
+    assert_eq!(routes.get("GET", "/api/user/token"), None);
+    assert!("GET /api/user/casual".parse::<RouteLimits>().is_err());
+    assert!("GET /api/user/casual=5".parse::<RouteLimits>().is_err());
+    assert_eq!("".parse::<RouteLimits>(), Ok(RouteLimits::default()));
+  }
+
+  #[test]
+  fn test_rate_limiter() {
+    let limiter = RateLimiter::new(
+      "100/60".parse().unwrap(),
+      "GET /api/user/casual=2/60".parse().unwrap(),
+    );
+    let now = Instant::now();
+    let casual = |ip, now| limiter.acquire("GET", Some("/api/user/casual"), ip, now);
+    assert!(casual("1.1.1.1", now).is_ok());
+    assert!(casual("1.1.1.1", now).is_ok());
+    assert_eq!(retry_after(casual("1.1.1.1", now)), 30);
+    // 不同 IP、其他路由使用各自的令牌桶
+    assert!(casual("2.2.2.2", now).is_ok());
+    assert!(limiter.acquire("GET", None, "1.1.1.1", now).is_ok());
+    assert!(casual("1.1.1.1", now + Duration::from_secs(30)).is_ok());
+  }
+
+  #[test]
+  fn test_login_lockout() {
+    let guard = LoginGuard::new("0/60".parse().unwrap(), 3, Duration::from_secs(60));
+    let now = Instant::now();
+    let ip = "1.1.1.1";
+    for _ in 0..2 {
+      guard.record_failure("a@b.c", ip, now);
+    }
+    assert!(guard.check("a@b.c", ip, now).is_ok());
+    guard.record_failure("a@b.c", ip, now);
+    assert_eq!(retry_after(guard.check("a@b.c", ip, now)), 60);
+    // 其他 IP 上的登录不受影响
+    assert!(guard.check("a@b.c", "2.2.2.2", now).is_ok());
+    let later = now + Duration::from_secs(60);
+    assert!(guard.check("a@b.c", ip, later).is_ok());
+    guard.record_failure("a@b.c", ip, later);
+    assert_eq!(retry_after(guard.check("a@b.c", ip, later)), 120);
+    guard.record_success("a@b.c", ip);
+    assert!(guard.check("a@b.c", ip, later).is_ok());
+  }
+}
@@ -0,0 +1,467 @@
+//! 对比 Agent 上实际提供服务的站点与 `nginx` 表中记录的期望状态，
+//! 重新发布不一致的站点，报告不应由该 Agent 提供服务的站点
+
+use std::{
+  collections::HashMap,
+  sync::{Arc, Mutex},
+  time::{Duration, Instant},
+};
+
+use common::{agent::InventorySite, digest::sha256_bytes};
+use entity::{
+  agent::{self, AgentStatus},
+  deployment::DeploymentStatus,
+  nginx,
+  site::SiteStatus,
+  task::TaskType,
+};
+use rpc::AgentEndpoint;
+use sea_orm::{IntoActiveModel, Set};
+use serde_json::json;
+
+use crate::{
+  app::AppState,
+  components::task::service::{NewTask, PublishPayload, enqueue},
+  error::AppError,
+};
+
+/// 同一个不一致原因最多重新发布的次数，之后只记录错误，等待人工处理
+const MAX_REPAIR_ATTEMPTS: u64 = 3;
+
+/// 需要重新发布的站点：文件或配置丢失，或者发布的版本、配置与记录不一致
+fn is_stale(record: &nginx::Model, site: Option<&InventorySite>) -> bool {
+  let Some(site) = site else {
+    return true;
+  };
+  // 旧版本 Agent 不返回配置内容，此时不比较配置
+  let config_drifted = record.config_hash.is_some() && site.config_hash != record.config_hash;
+  !site.has_files || site.release_id != Some(record.deployment_id) || config_drifted
+}
+
+/// Agent 上的文件就是记录中的部署，只是配置不一致，重新发布即可恢复。
+/// Master 不保存上传的文件，文件丢失或版本不一致时无法重新发布
+fn can_republish(record: &nginx::Model, site: Option<&InventorySite>) -> bool {
+  site.is_some_and(|site| site.has_files && site.release_id == Some(record.deployment_id))
+}
+
+/// 可以重新发布时不一致的原因，即 Agent 上实际的配置摘要
+fn drift_cause(site: Option<&InventorySite>) -> String {
+  let config_hash = site.and_then(|site| site.config_hash.as_deref());
+  format!("config:{}", config_hash.unwrap_or("missing"))
+}
+
+/// 各 Agent 上不在记录中的站点第一次被发现的时间
+#[derive(Debug, Clone, Default)]
+pub struct OrphanSites {
+  first_seen: Arc<Mutex<HashMap<(i32, String), Instant>>>,
+}
+
+impl OrphanSites {
+  /// 更新 Agent 上的孤儿站点，返回新发现的站点和已持续超过 `grace` 的站点
+  fn update(
+    &self,
+    agent_id: i32,
+    site_ids: &[String],
+    grace: Duration,
+  ) -> (Vec<String>, Vec<String>) {
+    let now = Instant::now();
+    let mut first_seen = self.first_seen.lock().unwrap_or_else(|e| e.into_inner());
+    first_seen.retain(|(agent, site_id), _| *agent != agent_id || site_ids.contains(site_id));
+    let mut found = Vec::new();
+    let mut expired = Vec::new();
+    for site_id in site_ids {
+      let seen = *first_seen
+        .entry((agent_id, site_id.clone()))
+        .or_insert_with(|| {
+          found.push(site_id.clone());
+          now
+        });
+      if now.duration_since(seen) >= grace {
+        expired.push(site_id.clone());
+      }
+    }
+    (found, expired)
+  }
+
+  fn forget(&self, agent_id: i32, site_id: &str) {
+    let mut first_seen = self.first_seen.lock().unwrap_or_else(|e| e.into_inner());
+    first_seen.remove(&(agent_id, site_id.to_string()));
+  }
+}
+
+/// 对比一个 Agent 的期望状态与实际状态，返回需要重新发布的记录和不在记录中的站点
+fn diff_inventory<'a>(
+  desired: &'a [nginx::Model],
+  inventory: &'a [InventorySite],
+) -> (Vec<&'a nginx::Model>, Vec<&'a InventorySite>) {
+  let stale = desired
+    .iter()
+    .filter(|record| {
+      is_stale(
+        record,
+        inventory.iter().find(|site| site.site_id == record.site_id),
+      )
+    })
+    .collect();
+  let unexpected = inventory
+    .iter()
+    .filter(|site| !desired.iter().any(|record| record.site_id == site.site_id))
+    .collect();
+  (stale, unexpected)
+}
+
+/// 对比所有在线 Agent，由 Master 定时调用
+pub async fn reconcile_agents(state: &AppState) -> Result<(), AppError> {
+  let agents = state
+    .repo
+    .agent()
+    .get_agents_by_status(AgentStatus::Online)
+    .await?;
+  for agent in agents {
+    if let Err(err) = reconcile_agent(state, &agent).await {
+      tracing::error!("Failed to reconcile agent {}: {}", agent.id, err);
+    }
+  }
+  Ok(())
+}
+
+async fn reconcile_agent(state: &AppState, agent: &agent::Model) -> Result<(), AppError> {
+  let inventory = state
+    .agent_rpc
+    .get_inventory(&AgentEndpoint::from(agent))
+    .await?
+    .sites;
+  let desired = state.repo.nginx().get_by_agent_id(agent.id).await?;
+  let (stale, unexpected) = diff_inventory(&desired, &inventory);
+  for record in stale {
+    let inventory_site = inventory.iter().find(|site| site.site_id == record.site_id);
+    if let Err(err) = repair(state, record, inventory_site).await {
+      tracing::error!(
+        "Failed to repair site {} on agent {}: {}",
+        record.site_id,
+        agent.id,
+        err
+      );
+    }
+  }
+  let mut orphans = Vec::new();
+  for site in unexpected {
+    if is_orphan(state, agent, site).await? {
+      orphans.push(site.site_id.clone());
+    }
+  }
+  let grace = Duration::from_secs(state.reconcile_orphan_grace);
+  let (found, expired) = state.orphan_sites.update(agent.id, &orphans, grace);
+  for site_id in found {
+    tracing::warn!("Found orphan site {} on agent {}", site_id, agent.id);
+  }
+  // 数据库记录有误时撤销会删除仍在服务的站点，默认只报告
+  if state.reconcile_revoke_orphans {
+    for site_id in expired {
+      tracing::warn!("Revoke orphan site {} on agent {}", site_id, agent.id);
+      state
+        .agent_rpc
+        .task_revoke(site_id.clone(), &AgentEndpoint::from(agent))
+        .await?;
+      state.orphan_sites.forget(agent.id, &site_id);
+    }
+  }
+  Ok(())
+}
+
+/// 恢复不一致的站点。可以重新发布时提交发布任务，同一个不一致原因最多重新发布
+/// `MAX_REPAIR_ATTEMPTS` 次，仍不一致时返回错误；文件已丢失时等待用户重新上传
+async fn repair(
+  state: &AppState,
+  record: &nginx::Model,
+  inventory_site: Option<&InventorySite>,
+) -> Result<(), AppError> {
+  let site = match state.repo.site().get_site_by_id(&record.site_id).await? {
+    Some(site) if site.status == SiteStatus::Active => site,
+    // 站点已删除或被禁用，记录不再有效
+    _ => {
+      state
+        .repo
+        .nginx()
+        .delete_by_site_id(&record.site_id)
+        .await?;
+      return Ok(());
+    }
+  };
+  if state.repo.task().has_unfinished_task(&site.site_id).await? {
+    return Ok(());
+  }
+  if !can_republish(record, inventory_site) {
+    // 删除记录并清除站点当前的部署，站点需要重新上传后发布
+    state.repo.nginx().delete_by_site_id(&site.site_id).await?;
+    if site.deployment_id == Some(record.deployment_id) {
+      let mut active_site = site.clone().into_active_model();
+      active_site.deployment_id = Set(None);
+      state.repo.site().update_site(active_site).await?;
+    }
+    tracing::warn!(
+      "Files of site {} are lost on agent {}, a new upload is required",
+      site.site_id,
+      record.agent_id
+    );
+    return Ok(());
+  }
+  // 发布成功但仍不一致时原因不变，按次数重新提交，而不是随记录的更新无限重试
+  let prefix = format!(
+    "reconcile:{}:{}:{}:",
+    site.site_id,
+    record.deployment_id,
+    drift_cause(inventory_site)
+  );
+  let attempts = state
+    .repo
+    .task()
+    .count_tasks_by_key_prefix(&site.site_id, &prefix)
+    .await?;
+  if attempts >= MAX_REPAIR_ATTEMPTS {
+    return Err(AppError::Other {
+      message: format!(
+        "Site {} is still drifted after {} republishes",
+        site.site_id, attempts
+      ),
+      source: None,
+    });
+  }
+  let task = enqueue(
+    state,
+    NewTask {
+      r#type: TaskType::Publish,
+      user_id: site.user_id.clone(),
+      site_id: site.site_id.clone(),
+      deployment_id: Some(record.deployment_id),
+      payload: json!(PublishPayload {
+        bind_domain: site.domain.clone(),
+      }),
+      idempotency_key: format!("{}{}", prefix, attempts + 1),
+    },
+  )
+  .await?;
+  tracing::warn!(
+    "Site {} drifted on agent {}, republish in task {}",
+    site.site_id,
+    record.agent_id,
+    task.id
+  );
+  Ok(())
+}
+
+/// 判断不在记录中的站点是否是孤儿：站点已删除、被禁用或已由其他 Agent 提供服务。
+/// 升级前发布、尚未记录的站点则补录记录
+async fn is_orphan(
+  state: &AppState,
+  agent: &agent::Model,
+  inventory_site: &InventorySite,
+) -> Result<bool, AppError> {
+  let site_id = &inventory_site.site_id;
+  // 正在发布或迁移的站点稍后会写入记录
+  if state.repo.task().has_unfinished_task(site_id).await? {
+    return Ok(false);
+  }
+  let site = state.repo.site().get_site_by_id(site_id).await?;
+  let record = state.repo.nginx().get_by_site_id(site_id).await?;
+  let orphan = match (&site, &record) {
+    (None, _) => true,
+    (Some(site), _) if site.status == SiteStatus::Disabled => true,
+    (Some(_), Some(record)) => record.agent_id != agent.id,
+    (Some(site), None) => {
+      adopt(state, agent, site.deployment_id, inventory_site).await?;
+      false
+    }
+  };
+  Ok(orphan)
+}
+
+/// 为升级前已发布的站点补录记录，只接受站点当前部署且已发布在该 Agent 上的情况
+async fn adopt(
+  state: &AppState,
+  agent: &agent::Model,
+  deployment_id: Option<i32>,
+  inventory_site: &InventorySite,
+) -> Result<(), AppError> {
+  let Some(deployment_id) = deployment_id else {
+    return Ok(());
+  };
+  let Some(deployment) = state
+    .repo
+    .deployment()
+    .get_deployment(deployment_id)
+    .await?
+  else {
+    return Ok(());
+  };
+  if deployment.status != DeploymentStatus::Published
+    || deployment.agent_id != agent.id
+    || !inventory_site.has_files
+  {
+    return Ok(());
+  }
+  state
+    .repo
+    .nginx()
+    .save(
+      &inventory_site.site_id,
+      agent.id,
+      deployment.id,
+      inventory_site.config_hash.clone(),
+      None,
+    )
+    .await?;
+  Ok(())
+}
+
+/// 发布结果中配置内容的摘要，与 Agent 清单中的 `config_hash` 对应
+pub fn config_hash(config: Option<&str>) -> Option<String> {
+  config.map(|config| sha256_bytes(config.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+  use common::agent::InventorySite;
+  use entity::nginx;
+  use helpers::time::utc_now;
+
+  use super::{OrphanSites, can_republish, diff_inventory};
+  use std::time::Duration;
+
+  fn record(site_id: &str, deployment_id: i32, config_hash: Option<&str>) -> nginx::Model {
+    nginx::Model {
+      id: 1,
+      site_id: site_id.to_string(),
+      agent_id: 1,
+      deployment_id,
+      config_hash: config_hash.map(str::to_string),
+      config_content: None,
+      created_at: utc_now(),
+      updated_at: None,
+    }
+  }
+
+  fn site(site_id: &str, release_id: i32, config_hash: &str, has_files: bool) -> InventorySite {
+    InventorySite {
+      site_id: site_id.to_string(),
+      release_id: Some(release_id),
+      config_hash: Some(config_hash.to_string()),
+      has_files,
+    }
+  }
+
+  #[test]
+  fn test_diff_inventory() {
+    let desired = vec![
+      record("ok", 1, Some("a")),
+      record("missing", 2, Some("b")),
+      record("old_release", 3, Some("c")),
+      record("config_changed", 4, Some("d")),
+      record("no_files", 5, Some("e")),
+      record("legacy", 6, None),
+    ];
+    let inventory = vec![
+      site("ok", 1, "a", true),
+      site("old_release", 2, "c", true),
+      site("config_changed", 4, "x", true),
+      site("no_files", 5, "e", false),
+      site("legacy", 6, "y", true),
+      site("orphan", 7, "z", true),
+    ];
+    let (stale, unexpected) = diff_inventory(&desired, &inventory);
+    let stale: Vec<&str> = stale.iter().map(|r| r.site_id.as_str()).collect();
+    let unexpected: Vec<&str> = unexpected.iter().map(|s| s.site_id.as_str()).collect();
+    assert_eq!(
+      stale,
+      vec!["missing", "old_release", "config_changed", "no_files"]
+    );
+    assert_eq!(unexpected, vec!["orphan"]);
+
+    // 只有配置不一致时才能重新发布，文件丢失或版本不一致时需要重新上传
+    assert!(can_republish(&desired[3], Some(&inventory[2])));
+    assert!(!can_republish(&desired[1], None));
+    assert!(!can_republish(&desired[2], Some(&inventory[1])));
+    assert!(!can_republish(&desired[4], Some(&inventory[3])));
+  }
+
+  #[test]
+  fn test_orphan_sites() {
+    let orphans = OrphanSites::default();
+    let sites = vec!["a".to_string(), "b".to_string()];
+    let (found, expired) = orphans.update(1, &sites, Duration::ZERO);
+    assert_eq!(found, sites);
+    assert_eq!(expired, sites);
+    let (found, expired) = orphans.update(1, &sites[..1], Duration::from_secs(3600));
+    assert!(found.is_empty());
+    assert!(expired.is_empty());
+    // 不再是孤儿的站点重新计时
+    let (found, _) = orphans.update(1, &sites, Duration::from_secs(3600));
+    assert_eq!(found, vec!["b".to_string()]);
+  }
+
+  #[cfg(feature = "sqlite")]
+  #[actix_web::test]
+  async fn test_repair_attempts() {
+    use common::agent::InventoryResponse;
+    use entity::{deployment::DeploymentStatus, task::TaskStatus, user::UserType};
+    use rpc::MockAgentApi;
+
+    use super::{MAX_REPAIR_ATTEMPTS, reconcile_agents};
+    use crate::testing::{create_agent, create_site, create_user, finish, test_state};
+
+    // Agent 上的配置始终与记录不一致
+    let mut agent_rpc = MockAgentApi::new();
+    agent_rpc.expect_get_inventory().returning(|_| {
+      Ok(InventoryResponse {
+        sites: vec![site("site", 1, "drifted", true)],
+      })
+    });
+    let state = test_state(agent_rpc).await;
+    create_user(&state, "owner", UserType::Normal).await;
+    let agent = create_agent(&state, "10.0.0.1").await;
+    let (_, deployment) = create_site(
+      &state,
+      "site",
+      "owner",
+      agent.id,
+      DeploymentStatus::Published,
+    )
+    .await;
+    assert_eq!(deployment.id, 1);
+    state
+      .repo
+      .nginx()
+      .save("site", agent.id, deployment.id, Some("a".to_string()), None)
+      .await
+      .unwrap();
+
+    let mut keys = vec![];
+    for _ in 0..MAX_REPAIR_ATTEMPTS + 2 {
+      reconcile_agents(&state).await.unwrap();
+      let task = state
+        .repo
+        .task()
+        .get_latest_task(deployment.id, entity::task::TaskType::Publish)
+        .await
+        .unwrap()
+        .unwrap();
+      if !keys.contains(&task.idempotency_key) {
+        keys.push(task.idempotency_key.clone());
+      }
+      // 发布成功后记录被更新，但配置仍不一致
+      finish(&state, task.id, TaskStatus::Succeeded).await;
+      state
+        .repo
+        .nginx()
+        .save("site", agent.id, deployment.id, Some("a".to_string()), None)
+        .await
+        .unwrap();
+    }
+    assert_eq!(
+      keys,
+      (1..=MAX_REPAIR_ATTEMPTS)
+        .map(|attempt| format!("reconcile:site:1:config:drifted:{}", attempt))
+        .collect::<Vec<_>>()
+    );
+  }
+}
@@ -1,13 +1,43 @@
+use common::SortOrder;
 use entity::agent::AgentStatus;
-use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter};
+use sea_orm::{
+  ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, PaginatorTrait,
+  QueryFilter, QueryOrder,
+};
+use serde::Deserialize;
+use utoipa::ToSchema;
 
 use entity::agent;
 
+use super::order;
+
 pub enum AgentQueryBy {
-  Id(u32),
+  Id(i32),
   IpAddress(String),
 }
 
+/// Agent 列表的排序字段
+#[derive(Debug, Clone, Copy, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AgentSort {
+  #[default]
+  CreatedAt,
+  Hostname,
+  AvailableSpace,
+  LastHeartbeat,
+}
+
+impl AgentSort {
+  fn column(self) -> agent::Column {
+    match self {
+      AgentSort::CreatedAt => agent::Column::CreatedAt,
+      AgentSort::Hostname => agent::Column::Hostname,
+      AgentSort::AvailableSpace => agent::Column::AvailableSpace,
+      AgentSort::LastHeartbeat => agent::Column::LastHeartbeat,
+    }
+  }
+}
+
 #[derive(Debug, Clone)]
 pub struct AgentRepository<'a> {
   pub db: &'a DatabaseConnection,
@@ -29,14 +59,54 @@ impl AgentRepository<'_> {
     agent::Entity::find().all(self.db).await
   }
 
-  pub async fn get_agent(&self, id: u32) -> Result<Option<agent::Model>, DbErr> {
+  pub async fn get_agents_by_status(
+    &self,
+    status: AgentStatus,
+  ) -> Result<Vec<agent::Model>, DbErr> {
+    agent::Entity::find()
+      .filter(agent::Column::Status.eq(status))
+      .all(self.db)
+      .await
+  }
+
+  /// 分页查询 Agent，`page` 从 0 开始，返回当前页数据和总数
+  pub async fn list_agents(
+    &self,
+    status: Option<AgentStatus>,
+    sort: AgentSort,
+    sort_order: SortOrder,
+    page: u64,
+    page_size: u64,
+  ) -> Result<(Vec<agent::Model>, u64), DbErr> {
+    let mut select = agent::Entity::find();
+    if let Some(status) = status {
+      select = select.filter(agent::Column::Status.eq(status));
+    }
+    let paginator = select
+      .order_by(sort.column(), order(sort_order))
+      .order_by(agent::Column::Id, order(sort_order))
+      .paginate(self.db, page_size);
+    let total = paginator.num_items().await?;
+    let agents = paginator.fetch_page(page).await?;
+    Ok((agents, total))
+  }
+
+  pub async fn get_agent(&self, id: i32) -> Result<Option<agent::Model>, DbErr> {
     agent::Entity::find()
       .filter(agent::Column::Id.eq(id))
       .one(self.db)
       .await
   }
 
-  pub async fn has_agent_by_id(&self, id: u32) -> Result<bool, DbErr> {
+  /// 按注册或刷新时签发的 token 查找 Agent
+  pub async fn get_agent_by_token(&self, token: &str) -> Result<Option<agent::Model>, DbErr> {
+    agent::Entity::find()
+      .filter(agent::Column::Token.eq(token))
+      .one(self.db)
+      .await
+  }
+
+  pub async fn has_agent_by_id(&self, id: i32) -> Result<bool, DbErr> {
     self.has_agent(AgentQueryBy::Id(id)).await
   }
 
@@ -0,0 +1,67 @@
+use sea_orm::{
+  ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, PaginatorTrait,
+  QueryFilter, QueryOrder, prelude::DateTimeUtc,
+};
+
+use entity::audit_log::{self, AuditAction, AuditResult};
+
+#[derive(Debug, Default)]
+pub struct AuditLogFilter {
+  pub actor: Option<String>,
+  pub action: Option<AuditAction>,
+  pub target: Option<String>,
+  pub result: Option<AuditResult>,
+  pub ip: Option<String>,
+  /// 只包含该时间及之后的记录
+  pub from: Option<DateTimeUtc>,
+  /// 只包含该时间之前的记录
+  pub to: Option<DateTimeUtc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AuditLogRepository<'a> {
+  pub db: &'a DatabaseConnection,
+}
+
+impl AuditLogRepository<'_> {
+  pub async fn create_log(&self, log: audit_log::ActiveModel) -> Result<audit_log::Model, DbErr> {
+    log.insert(self.db).await
+  }
+
+  /// 分页查询审计日志，按时间倒序，`page` 从 0 开始，返回当前页数据和总数
+  pub async fn list_logs(
+    &self,
+    filter: AuditLogFilter,
+    page: u64,
+    page_size: u64,
+  ) -> Result<(Vec<audit_log::Model>, u64), DbErr> {
+    let mut select = audit_log::Entity::find();
+    if let Some(actor) = filter.actor {
+      select = select.filter(audit_log::Column::Actor.eq(actor));
+    }
+    if let Some(action) = filter.action {
+      select = select.filter(audit_log::Column::Action.eq(action));
+    }
+    if let Some(target) = filter.target {
+      select = select.filter(audit_log::Column::Target.eq(target));
+    }
+    if let Some(result) = filter.result {
+      select = select.filter(audit_log::Column::Result.eq(result));
+    }
+    if let Some(ip) = filter.ip {
+      select = select.filter(audit_log::Column::Ip.eq(ip));
+    }
+    if let Some(from) = filter.from {
+      select = select.filter(audit_log::Column::CreatedAt.gte(from));
+    }
+    if let Some(to) = filter.to {
+      select = select.filter(audit_log::Column::CreatedAt.lt(to));
+    }
+    let paginator = select
+      .order_by_desc(audit_log::Column::Id)
+      .paginate(self.db, page_size);
+    let total = paginator.num_items().await?;
+    let logs = paginator.fetch_page(page).await?;
+    Ok((logs, total))
+  }
+}
@@ -0,0 +1,138 @@
+use sea_orm::{
+  AccessMode, ConnectionTrait, DatabaseConnection, DatabaseTransaction, DbBackend, DbErr,
+  EntityTrait, IntoActiveModel, IsolationLevel, PaginatorTrait, TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+
+use entity::{
+  agent, audit_log, deployment, deployment_event, nginx, site,
+  task::{self, TaskStatus},
+  user, webhook, webhook_delivery,
+};
+use helpers::time::utc_now;
+
+/// 批量写入时每条语句的行数，避免超出 SQLite 的参数上限
+const INSERT_CHUNK_SIZE: usize = 50;
+
+/// 所有表在同一时刻的数据
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Snapshot {
+  pub users: Vec<user::Model>,
+  pub agents: Vec<agent::Model>,
+  pub sites: Vec<site::Model>,
+  pub deployments: Vec<deployment::Model>,
+  pub deployment_events: Vec<deployment_event::Model>,
+  pub nginx: Vec<nginx::Model>,
+  pub tasks: Vec<task::Model>,
+  pub webhooks: Vec<webhook::Model>,
+  pub webhook_deliveries: Vec<webhook_delivery::Model>,
+  /// 早期的备份没有审计日志
+  #[serde(default)]
+  pub audit_logs: Vec<audit_log::Model>,
+}
+
+#[derive(Debug, Clone)]
+pub struct BackupRepository<'a> {
+  pub db: &'a DatabaseConnection,
+}
+
+impl BackupRepository<'_> {
+  /// 在同一个事务中读取所有表，得到一致的快照
+  pub async fn snapshot(&self) -> Result<Snapshot, DbErr> {
+    let txn = match self.db.get_database_backend() {
+      // SQLite 的事务本身就是串行化的，不支持设置隔离级别
+      DbBackend::Sqlite => self.db.begin().await?,
+      _ => {
+        self
+          .db
+          .begin_with_config(
+            Some(IsolationLevel::RepeatableRead),
+            Some(AccessMode::ReadOnly),
+          )
+          .await?
+      }
+    };
+    let snapshot = Snapshot {
+      users: user::Entity::find().all(&txn).await?,
+      agents: agent::Entity::find().all(&txn).await?,
+      sites: site::Entity::find().all(&txn).await?,
+      deployments: deployment::Entity::find().all(&txn).await?,
+      deployment_events: deployment_event::Entity::find().all(&txn).await?,
+      nginx: nginx::Entity::find().all(&txn).await?,
+      tasks: task::Entity::find().all(&txn).await?,
+      webhooks: webhook::Entity::find().all(&txn).await?,
+      webhook_deliveries: webhook_delivery::Entity::find().all(&txn).await?,
+      audit_logs: audit_log::Entity::find().all(&txn).await?,
+    };
+    txn.commit().await?;
+    Ok(snapshot)
+  }
+
+  /// 数据库中是否还没有任何用户、Agent 和站点
+  pub async fn is_empty(&self) -> Result<bool, DbErr> {
+    Ok(
+      user::Entity::find().count(self.db).await? == 0
+        && agent::Entity::find().count(self.db).await? == 0
+        && site::Entity::find().count(self.db).await? == 0,
+    )
+  }
+
+  /// 清空所有表后写入快照，保留原来的主键。
+  /// 快照中未结束的任务标记为失败，避免恢复后按过时的状态撤销或迁移站点
+  pub async fn restore(&self, snapshot: &Snapshot) -> Result<(), DbErr> {
+    let tasks: Vec<task::Model> = snapshot
+      .tasks
+      .iter()
+      .cloned()
+      .map(|mut task| {
+        if !task.status.is_finished() {
+          task.status = TaskStatus::Failed;
+          task.last_error = Some("Abandoned by backup restore".to_string());
+          task.next_run_at = None;
+          task.updated_at = Some(utc_now());
+        }
+        task
+      })
+      .collect();
+    let txn = self.db.begin().await?;
+    replace_all::<user::Entity>(&txn, &snapshot.users).await?;
+    replace_all::<agent::Entity>(&txn, &snapshot.agents).await?;
+    replace_all::<site::Entity>(&txn, &snapshot.sites).await?;
+    replace_all::<deployment::Entity>(&txn, &snapshot.deployments).await?;
+    replace_all::<deployment_event::Entity>(&txn, &snapshot.deployment_events).await?;
+    replace_all::<nginx::Entity>(&txn, &snapshot.nginx).await?;
+    replace_all::<task::Entity>(&txn, &tasks).await?;
+    replace_all::<webhook::Entity>(&txn, &snapshot.webhooks).await?;
+    replace_all::<webhook_delivery::Entity>(&txn, &snapshot.webhook_deliveries).await?;
+    replace_all::<audit_log::Entity>(&txn, &snapshot.audit_logs).await?;
+    txn.commit().await
+  }
+}
+
+async fn replace_all<E>(txn: &DatabaseTransaction, models: &[E::Model]) -> Result<(), DbErr>
+where
+  E: EntityTrait,
+  E::Model: IntoActiveModel<E::ActiveModel> + Clone,
+{
+  E::delete_many().exec(txn).await?;
+  for chunk in models.chunks(INSERT_CHUNK_SIZE) {
+    E::insert_many(
+      chunk
+        .iter()
+        .cloned()
+        .map(IntoActiveModel::into_active_model),
+    )
+    .exec_without_returning(txn)
+    .await?;
+  }
+  // 显式写入主键不会推进 Postgres 的序列，需要手动同步
+  if txn.get_database_backend() == DbBackend::Postgres {
+    let table = E::default().table_name().to_string();
+    txn
+      .execute_unprepared(&format!(
+        r#"SELECT setval(pg_get_serial_sequence('"{table}"', 'id'), COALESCE((SELECT MAX(id) FROM "{table}"), 0) + 1, false)"#
+      ))
+      .await?;
+  }
+  Ok(())
+}
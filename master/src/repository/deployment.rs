@@ -1,6 +1,41 @@
-use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter};
+use common::SortOrder;
+use sea_orm::{
+  ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, PaginatorTrait,
+  QueryFilter, QueryOrder,
+};
+use serde::Deserialize;
+use utoipa::ToSchema;
 
-use entity::deployment;
+use entity::deployment::{self, DeploymentStatus};
+
+use super::order;
+
+#[derive(Debug, Default)]
+pub struct DeploymentFilter {
+  pub site_id: Option<String>,
+  pub agent_id: Option<i32>,
+  pub status: Option<DeploymentStatus>,
+}
+
+/// 部署列表的排序字段
+#[derive(Debug, Clone, Copy, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DeploymentSort {
+  #[default]
+  CreatedAt,
+  ExecutionTime,
+  Status,
+}
+
+impl DeploymentSort {
+  fn column(self) -> deployment::Column {
+    match self {
+      DeploymentSort::CreatedAt => deployment::Column::CreatedAt,
+      DeploymentSort::ExecutionTime => deployment::Column::ExecutionTime,
+      DeploymentSort::Status => deployment::Column::Status,
+    }
+  }
+}
 
 #[derive(Debug, Clone)]
 pub struct DeploymentRepository<'a> {
@@ -8,7 +43,7 @@ pub struct DeploymentRepository<'a> {
 }
 
 impl DeploymentRepository<'_> {
-  // pub async fn has_deployment(&self, id: u32) -> Result<bool, DbErr> {
+  // pub async fn has_deployment(&self, id: i32) -> Result<bool, DbErr> {
   //   Ok(
   //     deployment::Entity::find()
   //       .filter(deployment::Column::Id.eq(id))
@@ -24,7 +59,7 @@ impl DeploymentRepository<'_> {
 
   pub async fn get_deployment(
     &self,
-    deployment_id: u32,
+    deployment_id: i32,
   ) -> Result<Option<deployment::Model>, DbErr> {
     deployment::Entity::find()
       .filter(deployment::Column::Id.eq(deployment_id))
@@ -32,6 +67,44 @@ impl DeploymentRepository<'_> {
       .await
   }
 
+  pub async fn get_deployments_by_site_id(
+    &self,
+    site_id: &str,
+  ) -> Result<Vec<deployment::Model>, DbErr> {
+    deployment::Entity::find()
+      .filter(deployment::Column::SiteId.eq(site_id))
+      .all(self.db)
+      .await
+  }
+
+  /// 分页查询部署记录，`page` 从 0 开始，返回当前页数据和总数
+  pub async fn list_deployments(
+    &self,
+    filter: DeploymentFilter,
+    sort: DeploymentSort,
+    sort_order: SortOrder,
+    page: u64,
+    page_size: u64,
+  ) -> Result<(Vec<deployment::Model>, u64), DbErr> {
+    let mut select = deployment::Entity::find();
+    if let Some(site_id) = filter.site_id {
+      select = select.filter(deployment::Column::SiteId.eq(site_id));
+    }
+    if let Some(agent_id) = filter.agent_id {
+      select = select.filter(deployment::Column::AgentId.eq(agent_id));
+    }
+    if let Some(status) = filter.status {
+      select = select.filter(deployment::Column::Status.eq(status));
+    }
+    let paginator = select
+      .order_by(sort.column(), order(sort_order))
+      .order_by(deployment::Column::Id, order(sort_order))
+      .paginate(self.db, page_size);
+    let total = paginator.num_items().await?;
+    let deployments = paginator.fetch_page(page).await?;
+    Ok((deployments, total))
+  }
+
   pub async fn create_deployment(
     &self,
     deployment: deployment::ActiveModel,
@@ -45,4 +118,12 @@ impl DeploymentRepository<'_> {
   ) -> Result<deployment::Model, DbErr> {
     deployment.update(self.db).await
   }
+
+  pub async fn delete_deployments_by_site_id(&self, site_id: &str) -> Result<u64, DbErr> {
+    let res = deployment::Entity::delete_many()
+      .filter(deployment::Column::SiteId.eq(site_id))
+      .exec(self.db)
+      .await?;
+    Ok(res.rows_affected)
+  }
 }
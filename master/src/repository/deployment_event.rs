@@ -0,0 +1,41 @@
+use sea_orm::{
+  ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, QueryOrder,
+};
+
+use entity::deployment_event;
+
+#[derive(Debug, Clone)]
+pub struct DeploymentEventRepository<'a> {
+  pub db: &'a DatabaseConnection,
+}
+
+impl DeploymentEventRepository<'_> {
+  pub async fn create_event(
+    &self,
+    event: deployment_event::ActiveModel,
+  ) -> Result<deployment_event::Model, DbErr> {
+    event.insert(self.db).await
+  }
+
+  pub async fn get_events_by_deployment_id(
+    &self,
+    deployment_id: i32,
+  ) -> Result<Vec<deployment_event::Model>, DbErr> {
+    deployment_event::Entity::find()
+      .filter(deployment_event::Column::DeploymentId.eq(deployment_id))
+      .order_by_asc(deployment_event::Column::Id)
+      .all(self.db)
+      .await
+  }
+
+  pub async fn delete_events_by_deployment_ids(
+    &self,
+    deployment_ids: Vec<i32>,
+  ) -> Result<u64, DbErr> {
+    let res = deployment_event::Entity::delete_many()
+      .filter(deployment_event::Column::DeploymentId.is_in(deployment_ids))
+      .exec(self.db)
+      .await?;
+    Ok(res.rows_affected)
+  }
+}
@@ -1,14 +1,32 @@
 mod agent;
+mod audit_log;
+mod backup;
 mod deployment;
+mod deployment_event;
+mod nginx;
 mod site;
+mod task;
 mod user;
+mod webhook;
+mod webhook_delivery;
 
+use audit_log::AuditLogRepository;
+use backup::BackupRepository;
+use common::SortOrder;
 use deployment::DeploymentRepository;
-use sea_orm::DatabaseConnection;
+use deployment_event::DeploymentEventRepository;
+use nginx::NginxRepository;
+use sea_orm::{DatabaseConnection, Order};
+use task::TaskRepository;
+use webhook::WebhookRepository;
+use webhook_delivery::WebhookDeliveryRepository;
 
-pub use agent::AgentRepository;
-pub use site::SiteRepository;
-pub use user::UserRepository;
+pub use agent::{AgentRepository, AgentSort};
+pub use audit_log::AuditLogFilter;
+pub use backup::Snapshot;
+pub use deployment::{DeploymentFilter, DeploymentSort};
+pub use site::{SiteFilter, SiteRepository, SiteSort};
+pub use user::{UserFilter, UserRepository};
 
 #[derive(Debug, Clone)]
 pub struct RepositoryManager {
@@ -33,4 +51,689 @@ impl RepositoryManager {
   pub fn deployment(&self) -> DeploymentRepository {
     DeploymentRepository { db: &self.db }
   }
+
+  pub fn deployment_event(&self) -> DeploymentEventRepository {
+    DeploymentEventRepository { db: &self.db }
+  }
+
+  pub fn nginx(&self) -> NginxRepository {
+    NginxRepository { db: &self.db }
+  }
+
+  pub fn task(&self) -> TaskRepository {
+    TaskRepository { db: &self.db }
+  }
+
+  pub fn webhook(&self) -> WebhookRepository {
+    WebhookRepository { db: &self.db }
+  }
+
+  pub fn webhook_delivery(&self) -> WebhookDeliveryRepository {
+    WebhookDeliveryRepository { db: &self.db }
+  }
+
+  pub fn audit_log(&self) -> AuditLogRepository {
+    AuditLogRepository { db: &self.db }
+  }
+
+  pub fn backup(&self) -> BackupRepository {
+    BackupRepository { db: &self.db }
+  }
+}
+
+/// 列表排序方向对应的 SQL 排序
+fn order(order: SortOrder) -> Order {
+  match order {
+    SortOrder::Asc => Order::Asc,
+    SortOrder::Desc => Order::Desc,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use chrono::Duration;
+  use helpers::{
+    time::utc_now,
+    uuid::{Alphabet, nanoid},
+  };
+  use sea_orm::{ActiveValue::Set, IntoActiveModel, prelude::DateTimeUtc};
+
+  use super::*;
+  use crate::migration::migrate;
+  use entity::{
+    agent::{self, AgentStatus},
+    audit_log::{self, AuditAction, AuditResult},
+    deployment::{self, DeploymentStatus},
+    deployment_event,
+    site::{self, Bandwidth, SiteStatus},
+    task::{self, TaskStatus, TaskType},
+    user::{self, UserStatus, UserType},
+    webhook::{self, WebhookEvent},
+    webhook_delivery::{self, DeliveryStatus},
+  };
+
+  /// 需要测试的数据库，Postgres 和 MySQL 通过 `TEST_POSTGRES_URL` 和 `TEST_MYSQL_URL` 指定。
+  /// CI 中启用了对应的 feature 却没有指定地址时直接失败，避免悄悄跳过
+  fn database_urls() -> Vec<String> {
+    let mut urls = vec![];
+    if cfg!(feature = "sqlite") {
+      urls.push("sqlite::memory:".to_string());
+    }
+    for (enabled, var) in [
+      (cfg!(feature = "postgres"), "TEST_POSTGRES_URL"),
+      (cfg!(feature = "mysql"), "TEST_MYSQL_URL"),
+    ] {
+      if !enabled {
+        continue;
+      }
+      match std::env::var(var) {
+        Ok(url) => urls.push(url),
+        Err(_) if std::env::var_os("CI").is_some() => panic!("{} is required in CI", var),
+        Err(_) => eprintln!("{} is not set, skipping the backend", var),
+      }
+    }
+    urls
+  }
+
+  /// 在每个后端上跑一遍所有的仓库方法，`key` 用于隔离多次运行写入的数据
+  #[actix_web::test]
+  async fn test_repository_backends() {
+    for url in database_urls() {
+      eprintln!("testing repositories on {}", url);
+      let repo = RepositoryManager::new(migrate(&url).await.unwrap());
+      let key = nanoid(&Alphabet::UPPER, 12);
+      check_user(&repo, &key).await;
+      check_agent(&repo, &key).await;
+      check_site(&repo, &key).await;
+      check_deployment(&repo, &key).await;
+      check_task(&repo, &key).await;
+      check_nginx(&repo, &key).await;
+      check_webhook(&repo, &key).await;
+      check_audit_log(&repo, &key).await;
+      check_backup(&repo, &key).await;
+    }
+  }
+
+  /// MySQL 的 timestamp 按秒取整，写入的时间提前一些，避免按当前时间查询时被进位到未来
+  fn past() -> DateTimeUtc {
+    utc_now() - Duration::minutes(1)
+  }
+
+  async fn check_user(repo: &RepositoryManager, key: &str) {
+    let email = format!("{}@example.com", key.to_lowercase());
+    let created = repo
+      .user()
+      .create_user(user::ActiveModel {
+        user_id: Set(key.to_string()),
+        nickname: Set(key.to_string()),
+        password: Set("password".to_string()),
+        email: Set(email.clone()),
+        r#type: Set(UserType::Normal),
+        status: Set(UserStatus::Active),
+        is_email_verified: Set(1),
+        is_phone_verified: Set(0),
+        created_at: Set(utc_now()),
+        ..Default::default()
+      })
+      .await
+      .unwrap();
+    assert!(!repo.user().is_first_user().await.unwrap());
+    assert!(repo.user().has_user_by_email(&email).await.unwrap());
+    let found = repo
+      .user()
+      .get_user_by_email(&email)
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(found.id, created.id);
+    assert_eq!(found.is_email_verified, 1);
+    assert!(!repo.user().is_admin_user(key).await.unwrap());
+
+    let mut active = found.into_active_model();
+    active.r#type = Set(UserType::Administrator);
+    active.updated_at = Set(Some(utc_now()));
+    repo.user().update_user(active).await.unwrap();
+    assert!(repo.user().is_admin_user(key).await.unwrap());
+    let found = repo.user().get_user_by_id(key.to_string()).await.unwrap();
+    assert!(found.unwrap().updated_at.is_some());
+
+    let (users, total) = repo
+      .user()
+      .list_users(
+        UserFilter {
+          r#type: Some(UserType::Administrator),
+          keyword: Some(key.to_string()),
+          ..Default::default()
+        },
+        0,
+        10,
+      )
+      .await
+      .unwrap();
+    assert_eq!(total, 1);
+    assert_eq!(users[0].id, created.id);
+  }
+
+  async fn check_agent(repo: &RepositoryManager, key: &str) {
+    let created = repo
+      .agent()
+      .create_agent(agent::ActiveModel {
+        hostname: Set(key.to_string()),
+        ip_address: Set(key.to_string()),
+        api_url: Set("https://10.0.0.1:5001".to_string()),
+        storage_path: Set("/data".to_string()),
+        available_space: Set(1024),
+        status: Set(AgentStatus::Online),
+        token: Set(key.to_string()),
+        last_heartbeat: Set(Some(utc_now())),
+        created_at: Set(utc_now()),
+        ..Default::default()
+      })
+      .await
+      .unwrap();
+    assert!(repo.agent().has_agent_by_id(created.id).await.unwrap());
+    assert!(repo.agent().has_agent_by_ip(key.to_string()).await.unwrap());
+    assert!(repo.agent().get_avaliable_agent().await.unwrap().is_some());
+    let online = repo
+      .agent()
+      .get_agents_by_status(AgentStatus::Online)
+      .await
+      .unwrap();
+    assert!(online.iter().any(|a| a.id == created.id));
+    let (agents, total) = repo
+      .agent()
+      .list_agents(
+        Some(AgentStatus::Online),
+        AgentSort::AvailableSpace,
+        SortOrder::Desc,
+        0,
+        1000,
+      )
+      .await
+      .unwrap();
+    assert!(total >= 1);
+    assert!(agents.iter().any(|a| a.id == created.id));
+
+    let mut active = created.clone().into_active_model();
+    active.status = Set(AgentStatus::Offline);
+    active.updated_at = Set(Some(utc_now()));
+    repo.agent().update_agent(active).await.unwrap();
+    let found = repo.agent().get_agent(created.id).await.unwrap().unwrap();
+    assert_eq!(found.status, AgentStatus::Offline);
+    assert!(
+      repo
+        .agent()
+        .get_agents()
+        .await
+        .unwrap()
+        .iter()
+        .any(|a| a.id == created.id)
+    );
+  }
+
+  async fn check_site(repo: &RepositoryManager, key: &str) {
+    let created = repo
+      .site()
+      .create_site(site::ActiveModel {
+        site_id: Set(key.to_string()),
+        user_id: Set(key.to_string()),
+        deployment_id: Set(Some(1)),
+        name: Set(key.to_string()),
+        domain: Set(Some(format!("{}.example.com", key.to_lowercase()))),
+        status: Set(SiteStatus::Active),
+        bandwidth: Set(Bandwidth::One),
+        expired_at: Set(Some(past())),
+        created_at: Set(utc_now()),
+        ..Default::default()
+      })
+      .await
+      .unwrap();
+    let found = repo.site().get_site_by_id(key).await.unwrap().unwrap();
+    assert_eq!(found.id, created.id);
+    assert_eq!(found.bandwidth, Bandwidth::One);
+    let (sites, total) = repo
+      .site()
+      .list_sites(
+        SiteFilter {
+          user_id: Some(key.to_string()),
+          ..Default::default()
+        },
+        SiteSort::ExpiredAt,
+        SortOrder::Asc,
+        0,
+        10,
+      )
+      .await
+      .unwrap();
+    assert_eq!(total, 1);
+    assert_eq!(sites[0].id, created.id);
+    let expired = repo.site().get_expired_sites(utc_now()).await.unwrap();
+    assert!(expired.iter().any(|s| s.id == created.id));
+    let from = utc_now() - Duration::days(1);
+    let expiring = repo
+      .site()
+      .get_sites_to_warn(from, utc_now())
+      .await
+      .unwrap();
+    assert!(expiring.iter().any(|s| s.id == created.id));
+    repo
+      .site()
+      .mark_sites_warned(vec![created.id], utc_now())
+      .await
+      .unwrap();
+    let expiring = repo
+      .site()
+      .get_sites_to_warn(from, utc_now())
+      .await
+      .unwrap();
+    assert!(!expiring.iter().any(|s| s.id == created.id));
+
+    let mut active = found.into_active_model();
+    active.status = Set(SiteStatus::Disabled);
+    active.deployment_id = Set(None);
+    repo.site().update_site(active).await.unwrap();
+    let found = repo.site().get_site_by_id(key).await.unwrap().unwrap();
+    assert_eq!(found.status, SiteStatus::Disabled);
+    assert_eq!(found.deployment_id, None);
+    assert_eq!(repo.site().delete_site(key).await.unwrap(), 1);
+  }
+
+  async fn check_deployment(repo: &RepositoryManager, key: &str) {
+    // 超过 varchar(255) 的构建日志
+    let build_logs = "==> [build]\n".repeat(100);
+    let created = repo
+      .deployment()
+      .create_deployment(deployment::ActiveModel {
+        site_id: Set(key.to_string()),
+        agent_id: Set(1),
+        status: Set(DeploymentStatus::Pending),
+        build_logs: Set(Some(build_logs.clone())),
+        execution_time: Set(utc_now()),
+        created_at: Set(utc_now()),
+        ..Default::default()
+      })
+      .await
+      .unwrap();
+    let found = repo
+      .deployment()
+      .get_deployment(created.id)
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(found.build_logs, Some(build_logs));
+    let (deployments, total) = repo
+      .deployment()
+      .list_deployments(
+        DeploymentFilter {
+          site_id: Some(key.to_string()),
+          agent_id: Some(1),
+          status: Some(DeploymentStatus::Pending),
+        },
+        DeploymentSort::ExecutionTime,
+        SortOrder::Desc,
+        0,
+        10,
+      )
+      .await
+      .unwrap();
+    assert_eq!(total, 1);
+    assert_eq!(deployments[0].id, created.id);
+
+    let mut active = found.into_active_model();
+    active.status = Set(DeploymentStatus::Published);
+    repo.deployment().update_deployment(active).await.unwrap();
+    repo
+      .deployment_event()
+      .create_event(deployment_event::ActiveModel {
+        deployment_id: Set(created.id),
+        from_status: Set(Some(DeploymentStatus::Pending)),
+        to_status: Set(DeploymentStatus::Published),
+        actor: Set(format!("user:{}", key)),
+        created_at: Set(utc_now()),
+        ..Default::default()
+      })
+      .await
+      .unwrap();
+    let events = repo
+      .deployment_event()
+      .get_events_by_deployment_id(created.id)
+      .await
+      .unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].to_status, DeploymentStatus::Published);
+    let deployments = repo
+      .deployment()
+      .get_deployments_by_site_id(key)
+      .await
+      .unwrap();
+    assert_eq!(deployments[0].status, DeploymentStatus::Published);
+
+    assert_eq!(
+      repo
+        .deployment_event()
+        .delete_events_by_deployment_ids(vec![created.id])
+        .await
+        .unwrap(),
+      1
+    );
+    assert_eq!(
+      repo
+        .deployment()
+        .delete_deployments_by_site_id(key)
+        .await
+        .unwrap(),
+      1
+    );
+  }
+
+  async fn check_task(repo: &RepositoryManager, key: &str) {
+    let created = repo
+      .task()
+      .create_task(task::ActiveModel {
+        r#type: Set(TaskType::Publish),
+        status: Set(TaskStatus::Pending),
+        user_id: Set("user".to_string()),
+        site_id: Set(key.to_string()),
+        deployment_id: Set(Some(1)),
+        payload: Set("{}".to_string()),
+        idempotency_key: Set(format!("test:{}", key)),
+        attempts: Set(0),
+        next_run_at: Set(Some(past())),
+        created_at: Set(utc_now()),
+        ..Default::default()
+      })
+      .await
+      .unwrap();
+    let found = repo
+      .task()
+      .get_task_by_idempotency_key(&format!("test:{}", key))
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(found.id, created.id);
+    assert_eq!(found.deployment_id, Some(1));
+    let due = repo.task().get_due_tasks(utc_now(), 1000).await.unwrap();
+    assert!(due.iter().any(|t| t.id == created.id));
+    assert!(repo.task().has_unfinished_task(key).await.unwrap());
+
+    let mut active = found.into_active_model();
+    active.status = Set(TaskStatus::Running);
+    active.attempts = Set(1);
+    repo.task().update_task(active).await.unwrap();
+    assert!(repo.task().requeue_running_tasks(past()).await.unwrap() >= 1);
+    let found = repo.task().get_task(created.id).await.unwrap().unwrap();
+    assert_eq!(
+      (found.status.clone(), found.attempts),
+      (TaskStatus::Pending, 1)
+    );
+
+    let mut active = found.into_active_model();
+    active.status = Set(TaskStatus::Succeeded);
+    active.updated_at = Set(Some(past()));
+    repo.task().update_task(active).await.unwrap();
+    assert!(!repo.task().has_unfinished_task(key).await.unwrap());
+    repo.task().delete_finished_tasks(utc_now()).await.unwrap();
+    assert!(repo.task().get_task(created.id).await.unwrap().is_none());
+    assert_eq!(repo.task().delete_tasks_by_site_id(key).await.unwrap(), 0);
+  }
+
+  async fn check_nginx(repo: &RepositoryManager, key: &str) {
+    let record = repo.nginx().save(key, 1, 2, None, None).await.unwrap();
+    let updated = repo
+      .nginx()
+      .save(key, 3, 4, Some("hash".to_string()), None)
+      .await
+      .unwrap();
+    assert_eq!(updated.id, record.id);
+    assert_eq!((updated.agent_id, updated.deployment_id), (3, 4));
+    let found = repo.nginx().get_by_site_id(key).await.unwrap().unwrap();
+    assert_eq!(found.id, record.id);
+    let records = repo.nginx().get_by_agent_id(3).await.unwrap();
+    assert!(records.iter().any(|r| r.id == record.id));
+    assert_eq!(repo.nginx().delete_by_site_id(key).await.unwrap(), 1);
+  }
+
+  async fn check_webhook(repo: &RepositoryManager, key: &str) {
+    let webhook = repo
+      .webhook()
+      .create_webhook(webhook::ActiveModel {
+        user_id: Set(key.to_string()),
+        site_id: Set(Some(key.to_string())),
+        url: Set("https://example.com/hook".to_string()),
+        secret: Set("secret".to_string()),
+        events: Set("deployment.published,ping".to_string()),
+        created_at: Set(utc_now()),
+        ..Default::default()
+      })
+      .await
+      .unwrap();
+    assert!(
+      repo
+        .webhook()
+        .get_webhook(webhook.id)
+        .await
+        .unwrap()
+        .is_some()
+    );
+    assert_eq!(
+      repo
+        .webhook()
+        .get_webhooks_by_user_id(key)
+        .await
+        .unwrap()
+        .len(),
+      1
+    );
+    assert_eq!(
+      repo
+        .webhook()
+        .get_webhooks_by_site_id(key)
+        .await
+        .unwrap()
+        .len(),
+      1
+    );
+    let subscribed = repo
+      .webhook()
+      .get_subscribed_webhooks(WebhookEvent::DeploymentPublished, Some(key), Some(key))
+      .await
+      .unwrap();
+    assert_eq!(subscribed.len(), 1);
+    let subscribed = repo
+      .webhook()
+      .get_subscribed_webhooks(WebhookEvent::SiteDeleted, Some(key), Some(key))
+      .await
+      .unwrap();
+    assert!(subscribed.is_empty());
+
+    let delivery = repo
+      .webhook_delivery()
+      .create_delivery(webhook_delivery::ActiveModel {
+        webhook_id: Set(webhook.id),
+        event: Set(WebhookEvent::Ping.as_str().to_string()),
+        payload: Set("{}".to_string()),
+        status: Set(DeliveryStatus::Pending),
+        attempts: Set(0),
+        next_attempt_at: Set(Some(past())),
+        created_at: Set(utc_now()),
+        ..Default::default()
+      })
+      .await
+      .unwrap();
+    let due = repo
+      .webhook_delivery()
+      .get_due_deliveries(utc_now())
+      .await
+      .unwrap();
+    assert!(due.iter().any(|d| d.id == delivery.id));
+    let mut active = delivery.into_active_model();
+    active.status = Set(DeliveryStatus::Succeeded);
+    active.attempts = Set(1);
+    active.response_status = Set(Some(200));
+    active.updated_at = Set(Some(utc_now()));
+    repo
+      .webhook_delivery()
+      .update_delivery(active)
+      .await
+      .unwrap();
+    let deliveries = repo
+      .webhook_delivery()
+      .get_deliveries_by_webhook_id(webhook.id, 10)
+      .await
+      .unwrap();
+    assert_eq!(deliveries[0].response_status, Some(200));
+
+    assert_eq!(
+      repo
+        .webhook_delivery()
+        .delete_deliveries_by_webhook_id(webhook.id)
+        .await
+        .unwrap(),
+      1
+    );
+    assert_eq!(repo.webhook().delete_webhook(webhook.id).await.unwrap(), 1);
+  }
+
+  async fn check_audit_log(repo: &RepositoryManager, key: &str) {
+    repo
+      .audit_log()
+      .create_log(audit_log::ActiveModel {
+        actor: Set("user:user".to_string()),
+        action: Set(AuditAction::SitePublish),
+        target: Set(Some(format!("site:{}", key))),
+        ip: Set(Some("127.0.0.1".to_string())),
+        result: Set(AuditResult::Success),
+        created_at: Set(utc_now()),
+        ..Default::default()
+      })
+      .await
+      .unwrap();
+    let (logs, total) = repo
+      .audit_log()
+      .list_logs(
+        AuditLogFilter {
+          action: Some(AuditAction::SitePublish),
+          target: Some(format!("site:{}", key)),
+          ..Default::default()
+        },
+        0,
+        10,
+      )
+      .await
+      .unwrap();
+    assert_eq!(total, 1);
+    assert_eq!(logs[0].result, AuditResult::Success);
+    assert_eq!(logs[0].actor, "user:user");
+    assert_eq!(logs[0].ip.as_deref(), Some("127.0.0.1"));
+
+    let before = utc_now() - Duration::minutes(1);
+    repo
+      .audit_log()
+      .create_log(audit_log::ActiveModel {
+        actor: Set("system".to_string()),
+        action: Set(AuditAction::SitePublish),
+        target: Set(Some(format!("site:{}", key))),
+        result: Set(AuditResult::Failure),
+        detail: Set(Some("failed".to_string())),
+        created_at: Set(utc_now()),
+        ..Default::default()
+      })
+      .await
+      .unwrap();
+    let (logs, total) = repo
+      .audit_log()
+      .list_logs(
+        AuditLogFilter {
+          target: Some(format!("site:{}", key)),
+          from: Some(before),
+          ..Default::default()
+        },
+        0,
+        10,
+      )
+      .await
+      .unwrap();
+    // 按时间倒序
+    assert_eq!(total, 2);
+    assert_eq!(logs[0].result, AuditResult::Failure);
+    assert_eq!(logs[0].actor, "system");
+    assert_eq!(logs[0].detail.as_deref(), Some("failed"));
+    let (_, total) = repo
+      .audit_log()
+      .list_logs(
+        AuditLogFilter {
+          target: Some(format!("site:{}", key)),
+          result: Some(AuditResult::Failure),
+          actor: Some("system".to_string()),
+          to: Some(before),
+          ..Default::default()
+        },
+        0,
+        10,
+      )
+      .await
+      .unwrap();
+    assert_eq!(total, 0);
+  }
+
+  /// 用快照覆盖当前的数据，恢复后新写入的记录不会和已有主键冲突
+  async fn check_backup(repo: &RepositoryManager, key: &str) {
+    let record = repo.nginx().save(key, 1, 2, None, None).await.unwrap();
+    let snapshot = repo.backup().snapshot().await.unwrap();
+    assert!(!repo.backup().is_empty().await.unwrap());
+    repo.backup().restore(&snapshot).await.unwrap();
+    let found = repo.nginx().get_by_site_id(key).await.unwrap().unwrap();
+    assert_eq!(found.id, record.id);
+    let created = repo
+      .nginx()
+      .save(&format!("{}-other", key), 1, 3, None, None)
+      .await
+      .unwrap();
+    assert!(created.id > record.id);
+  }
+
+  #[cfg(feature = "sqlite")]
+  #[actix_web::test]
+  async fn test_backup_restore() {
+    let source = RepositoryManager::new(migrate("sqlite::memory:").await.unwrap());
+    source.nginx().save("site", 1, 2, None, None).await.unwrap();
+    let pending = source
+      .task()
+      .create_task(task::ActiveModel {
+        r#type: Set(TaskType::Revoke),
+        status: Set(TaskStatus::Pending),
+        user_id: Set("user".to_string()),
+        site_id: Set("site".to_string()),
+        payload: Set("{}".to_string()),
+        idempotency_key: Set("revoke:site".to_string()),
+        attempts: Set(0),
+        next_run_at: Set(Some(utc_now())),
+        created_at: Set(utc_now()),
+        ..Default::default()
+      })
+      .await
+      .unwrap();
+    let snapshot = source.backup().snapshot().await.unwrap();
+
+    let target = RepositoryManager::new(migrate("sqlite::memory:").await.unwrap());
+    target.backup().restore(&snapshot).await.unwrap();
+    let record = target
+      .nginx()
+      .get_by_site_id("site")
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(record.id, snapshot.nginx[0].id);
+    // 备份中未结束的任务不会再被执行
+    let task = target.task().get_task(pending.id).await.unwrap().unwrap();
+    assert_eq!(task.status, TaskStatus::Failed);
+    assert!(!target.task().has_unfinished_task("site").await.unwrap());
+    // 恢复后新写入的记录不会和已有主键冲突
+    let created = target
+      .nginx()
+      .save("other", 1, 3, None, None)
+      .await
+      .unwrap();
+    assert!(created.id > record.id);
+  }
 }
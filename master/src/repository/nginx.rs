@@ -0,0 +1,71 @@
+use helpers::time::utc_now;
+use sea_orm::{
+  ActiveModelTrait, ActiveValue::Set, ColumnTrait, DatabaseConnection, DbErr, EntityTrait,
+  IntoActiveModel, QueryFilter,
+};
+
+use entity::nginx;
+
+#[derive(Debug, Clone)]
+pub struct NginxRepository<'a> {
+  pub db: &'a DatabaseConnection,
+}
+
+impl NginxRepository<'_> {
+  pub async fn get_by_site_id(&self, site_id: &str) -> Result<Option<nginx::Model>, DbErr> {
+    nginx::Entity::find()
+      .filter(nginx::Column::SiteId.eq(site_id))
+      .one(self.db)
+      .await
+  }
+
+  pub async fn get_by_agent_id(&self, agent_id: i32) -> Result<Vec<nginx::Model>, DbErr> {
+    nginx::Entity::find()
+      .filter(nginx::Column::AgentId.eq(agent_id))
+      .all(self.db)
+      .await
+  }
+
+  /// 记录站点发布后的状态，已有记录时覆盖
+  pub async fn save(
+    &self,
+    site_id: &str,
+    agent_id: i32,
+    deployment_id: i32,
+    config_hash: Option<String>,
+    config_content: Option<String>,
+  ) -> Result<nginx::Model, DbErr> {
+    match self.get_by_site_id(site_id).await? {
+      Some(record) => {
+        let mut active_record = record.into_active_model();
+        active_record.agent_id = Set(agent_id);
+        active_record.deployment_id = Set(deployment_id);
+        active_record.config_hash = Set(config_hash);
+        active_record.config_content = Set(config_content);
+        active_record.updated_at = Set(Some(utc_now()));
+        active_record.update(self.db).await
+      }
+      None => {
+        nginx::ActiveModel {
+          site_id: Set(site_id.to_string()),
+          agent_id: Set(agent_id),
+          deployment_id: Set(deployment_id),
+          config_hash: Set(config_hash),
+          config_content: Set(config_content),
+          created_at: Set(utc_now()),
+          ..Default::default()
+        }
+        .insert(self.db)
+        .await
+      }
+    }
+  }
+
+  pub async fn delete_by_site_id(&self, site_id: &str) -> Result<u64, DbErr> {
+    let res = nginx::Entity::delete_many()
+      .filter(nginx::Column::SiteId.eq(site_id))
+      .exec(self.db)
+      .await?;
+    Ok(res.rows_affected)
+  }
+}
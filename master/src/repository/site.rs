@@ -1,6 +1,44 @@
-use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter};
+use common::SortOrder;
+use sea_orm::{
+  ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, PaginatorTrait,
+  QueryFilter, QueryOrder, prelude::DateTimeUtc, sea_query::Expr,
+};
+use serde::Deserialize;
+use utoipa::ToSchema;
 
-use entity::site;
+use entity::site::{self, SiteStatus};
+
+use super::order;
+
+#[derive(Debug, Default)]
+pub struct SiteFilter {
+  pub user_id: Option<String>,
+  pub status: Option<SiteStatus>,
+  /// 按绑定域名模糊匹配
+  pub domain: Option<String>,
+}
+
+/// 站点列表的排序字段
+#[derive(Debug, Clone, Copy, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SiteSort {
+  #[default]
+  CreatedAt,
+  Name,
+  Domain,
+  ExpiredAt,
+}
+
+impl SiteSort {
+  fn column(self) -> site::Column {
+    match self {
+      SiteSort::CreatedAt => site::Column::CreatedAt,
+      SiteSort::Name => site::Column::Name,
+      SiteSort::Domain => site::Column::Domain,
+      SiteSort::ExpiredAt => site::Column::ExpiredAt,
+    }
+  }
+}
 
 #[derive(Debug, Clone)]
 pub struct SiteRepository<'a> {
@@ -33,10 +71,71 @@ impl SiteRepository<'_> {
       .await
   }
 
-  pub async fn get_sites_by_user_id(&self, user_id: String) -> Result<Vec<site::Model>, DbErr> {
+  /// 分页查询站点，`page` 从 0 开始，返回当前页数据和总数
+  pub async fn list_sites(
+    &self,
+    filter: SiteFilter,
+    sort: SiteSort,
+    sort_order: SortOrder,
+    page: u64,
+    page_size: u64,
+  ) -> Result<(Vec<site::Model>, u64), DbErr> {
+    let mut select = site::Entity::find();
+    if let Some(user_id) = filter.user_id {
+      select = select.filter(site::Column::UserId.eq(user_id));
+    }
+    if let Some(status) = filter.status {
+      select = select.filter(site::Column::Status.eq(status));
+    }
+    if let Some(domain) = filter.domain {
+      select = select.filter(site::Column::Domain.contains(&domain));
+    }
+    let paginator = select
+      .order_by(sort.column(), order(sort_order))
+      .order_by(site::Column::Id, order(sort_order))
+      .paginate(self.db, page_size);
+    let total = paginator.num_items().await?;
+    let sites = paginator.fetch_page(page).await?;
+    Ok((sites, total))
+  }
+
+  /// 获取在 `now` 之前已过期的站点
+  pub async fn get_expired_sites(&self, now: DateTimeUtc) -> Result<Vec<site::Model>, DbErr> {
+    site::Entity::find()
+      .filter(site::Column::ExpiredAt.lte(now))
+      .all(self.db)
+      .await
+  }
+
+  /// 获取过期时间落在 `(now, to]` 区间内且尚未提醒过的站点
+  pub async fn get_sites_to_warn(
+    &self,
+    now: DateTimeUtc,
+    to: DateTimeUtc,
+  ) -> Result<Vec<site::Model>, DbErr> {
     site::Entity::find()
-      .filter(site::Column::UserId.eq(user_id))
+      .filter(site::Column::ExpiredAt.gt(now))
+      .filter(site::Column::ExpiredAt.lte(to))
+      .filter(site::Column::ExpireWarnedAt.is_null())
       .all(self.db)
       .await
   }
+
+  /// 标记站点已发出过期提醒
+  pub async fn mark_sites_warned(&self, ids: Vec<i32>, now: DateTimeUtc) -> Result<u64, DbErr> {
+    let res = site::Entity::update_many()
+      .col_expr(site::Column::ExpireWarnedAt, Expr::value(now))
+      .filter(site::Column::Id.is_in(ids))
+      .exec(self.db)
+      .await?;
+    Ok(res.rows_affected)
+  }
+
+  pub async fn delete_site(&self, site_id: &str) -> Result<u64, DbErr> {
+    let res = site::Entity::delete_many()
+      .filter(site::Column::SiteId.eq(site_id))
+      .exec(self.db)
+      .await?;
+    Ok(res.rows_affected)
+  }
 }
@@ -0,0 +1,112 @@
+use sea_orm::{
+  ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, PaginatorTrait,
+  QueryFilter, QueryOrder, QuerySelect, prelude::DateTimeUtc, sea_query::Expr,
+};
+
+use entity::task::{self, TaskStatus, TaskType};
+
+#[derive(Debug, Clone)]
+pub struct TaskRepository<'a> {
+  pub db: &'a DatabaseConnection,
+}
+
+impl TaskRepository<'_> {
+  pub async fn create_task(&self, task: task::ActiveModel) -> Result<task::Model, DbErr> {
+    task.insert(self.db).await
+  }
+
+  pub async fn update_task(&self, task: task::ActiveModel) -> Result<task::Model, DbErr> {
+    task.update(self.db).await
+  }
+
+  pub async fn get_task(&self, id: i32) -> Result<Option<task::Model>, DbErr> {
+    task::Entity::find_by_id(id).one(self.db).await
+  }
+
+  pub async fn get_task_by_idempotency_key(
+    &self,
+    idempotency_key: &str,
+  ) -> Result<Option<task::Model>, DbErr> {
+    task::Entity::find()
+      .filter(task::Column::IdempotencyKey.eq(idempotency_key))
+      .one(self.db)
+      .await
+  }
+
+  /// 站点中幂等键以 `prefix` 开头的任务数
+  pub async fn count_tasks_by_key_prefix(&self, site_id: &str, prefix: &str) -> Result<u64, DbErr> {
+    task::Entity::find()
+      .filter(task::Column::SiteId.eq(site_id))
+      .filter(task::Column::IdempotencyKey.starts_with(prefix))
+      .count(self.db)
+      .await
+  }
+
+  /// 部署最近提交的某类任务
+  pub async fn get_latest_task(
+    &self,
+    deployment_id: i32,
+    r#type: TaskType,
+  ) -> Result<Option<task::Model>, DbErr> {
+    task::Entity::find()
+      .filter(task::Column::DeploymentId.eq(deployment_id))
+      .filter(task::Column::Type.eq(r#type))
+      .order_by_desc(task::Column::Id)
+      .one(self.db)
+      .await
+  }
+
+  /// 到了执行时间的待执行任务，按提交顺序返回
+  pub async fn get_due_tasks(
+    &self,
+    now: DateTimeUtc,
+    limit: u64,
+  ) -> Result<Vec<task::Model>, DbErr> {
+    task::Entity::find()
+      .filter(task::Column::Status.eq(TaskStatus::Pending))
+      .filter(task::Column::NextRunAt.lte(now))
+      .order_by_asc(task::Column::Id)
+      .limit(limit)
+      .all(self.db)
+      .await
+  }
+
+  /// 将执行中的任务放回队列，用于 Master 重启后恢复被中断的任务
+  pub async fn requeue_running_tasks(&self, now: DateTimeUtc) -> Result<u64, DbErr> {
+    let res = task::Entity::update_many()
+      .col_expr(task::Column::Status, Expr::value(TaskStatus::Pending))
+      .col_expr(task::Column::NextRunAt, Expr::value(now))
+      .filter(task::Column::Status.eq(TaskStatus::Running))
+      .exec(self.db)
+      .await?;
+    Ok(res.rows_affected)
+  }
+
+  /// 站点是否有尚未结束的任务
+  pub async fn has_unfinished_task(&self, site_id: &str) -> Result<bool, DbErr> {
+    let count = task::Entity::find()
+      .filter(task::Column::SiteId.eq(site_id))
+      .filter(task::Column::Status.is_in([TaskStatus::Pending, TaskStatus::Running]))
+      .count(self.db)
+      .await?;
+    Ok(count > 0)
+  }
+
+  /// 删除在 `before` 之前结束的任务
+  pub async fn delete_finished_tasks(&self, before: DateTimeUtc) -> Result<u64, DbErr> {
+    let res = task::Entity::delete_many()
+      .filter(task::Column::Status.is_in([TaskStatus::Succeeded, TaskStatus::Failed]))
+      .filter(task::Column::UpdatedAt.lt(before))
+      .exec(self.db)
+      .await?;
+    Ok(res.rows_affected)
+  }
+
+  pub async fn delete_tasks_by_site_id(&self, site_id: &str) -> Result<u64, DbErr> {
+    let res = task::Entity::delete_many()
+      .filter(task::Column::SiteId.eq(site_id))
+      .exec(self.db)
+      .await?;
+    Ok(res.rows_affected)
+  }
+}
@@ -1,12 +1,23 @@
-use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter};
+use sea_orm::{
+  ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, PaginatorTrait,
+  QueryFilter, QueryOrder,
+};
 
-use entity::user::{self, UserType};
+use entity::user::{self, UserStatus, UserType};
 
 pub enum UserQueryBy<'a> {
   UserId(String),
   Email(&'a str),
 }
 
+#[derive(Debug, Default)]
+pub struct UserFilter {
+  pub status: Option<UserStatus>,
+  pub r#type: Option<UserType>,
+  /// 按邮箱或昵称模糊匹配
+  pub keyword: Option<String>,
+}
+
 #[derive(Debug, Clone)]
 pub struct UserRepository<'a> {
   pub db: &'a DatabaseConnection,
@@ -49,9 +60,38 @@ impl<'a> UserRepository<'a> {
     user.insert(self.db).await
   }
 
-  // pub async fn update_user(&self, user: user::ActiveModel) -> Result<user::Model, DbErr> {
-  //   user.update(self.db).await
-  // }
+  pub async fn update_user(&self, user: user::ActiveModel) -> Result<user::Model, DbErr> {
+    user.update(self.db).await
+  }
+
+  /// 分页查询用户，`page` 从 0 开始，返回当前页数据和总数
+  pub async fn list_users(
+    &self,
+    filter: UserFilter,
+    page: u64,
+    page_size: u64,
+  ) -> Result<(Vec<user::Model>, u64), DbErr> {
+    let mut select = user::Entity::find();
+    if let Some(status) = filter.status {
+      select = select.filter(user::Column::Status.eq(status));
+    }
+    if let Some(r#type) = filter.r#type {
+      select = select.filter(user::Column::Type.eq(r#type));
+    }
+    if let Some(keyword) = filter.keyword {
+      select = select.filter(
+        user::Column::Email
+          .contains(&keyword)
+          .or(user::Column::Nickname.contains(&keyword)),
+      );
+    }
+    let paginator = select
+      .order_by_desc(user::Column::Id)
+      .paginate(self.db, page_size);
+    let total = paginator.num_items().await?;
+    let users = paginator.fetch_page(page).await?;
+    Ok((users, total))
+  }
 
   // pub async fn has_user_by_id(&self, id: String) -> Result<bool, DbErr> {
   //   self.has_user(UserQueryBy::UserId(id)).await
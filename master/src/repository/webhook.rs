@@ -0,0 +1,74 @@
+use sea_orm::{
+  ActiveModelTrait, ColumnTrait, Condition, DatabaseConnection, DbErr, EntityTrait, QueryFilter,
+  QueryOrder,
+};
+
+use entity::webhook::{self, WebhookEvent};
+
+#[derive(Debug, Clone)]
+pub struct WebhookRepository<'a> {
+  pub db: &'a DatabaseConnection,
+}
+
+impl WebhookRepository<'_> {
+  pub async fn get_webhook(&self, id: i32) -> Result<Option<webhook::Model>, DbErr> {
+    webhook::Entity::find_by_id(id).one(self.db).await
+  }
+
+  pub async fn get_webhooks_by_user_id(&self, user_id: &str) -> Result<Vec<webhook::Model>, DbErr> {
+    webhook::Entity::find()
+      .filter(webhook::Column::UserId.eq(user_id))
+      .order_by_asc(webhook::Column::Id)
+      .all(self.db)
+      .await
+  }
+
+  pub async fn get_webhooks_by_site_id(&self, site_id: &str) -> Result<Vec<webhook::Model>, DbErr> {
+    webhook::Entity::find()
+      .filter(webhook::Column::SiteId.eq(site_id))
+      .all(self.db)
+      .await
+  }
+
+  /// 查找订阅了某个事件的 Webhook：站点级订阅，以及站点所有者的用户级订阅。
+  /// `site_id` 和 `user_id` 都为空时只匹配用户级订阅。
+  pub async fn get_subscribed_webhooks(
+    &self,
+    event: WebhookEvent,
+    user_id: Option<&str>,
+    site_id: Option<&str>,
+  ) -> Result<Vec<webhook::Model>, DbErr> {
+    let mut condition = Condition::any();
+    if let Some(site_id) = site_id {
+      condition = condition.add(webhook::Column::SiteId.eq(site_id));
+    }
+    let mut user_level = Condition::all().add(webhook::Column::SiteId.is_null());
+    if let Some(user_id) = user_id {
+      user_level = user_level.add(webhook::Column::UserId.eq(user_id));
+    }
+    condition = condition.add(user_level);
+    let webhooks = webhook::Entity::find()
+      .filter(condition)
+      .filter(webhook::Column::Events.contains(event.as_str()))
+      .all(self.db)
+      .await?;
+    Ok(
+      webhooks
+        .into_iter()
+        .filter(|webhook| webhook.subscribes(event))
+        .collect(),
+    )
+  }
+
+  pub async fn create_webhook(
+    &self,
+    webhook: webhook::ActiveModel,
+  ) -> Result<webhook::Model, DbErr> {
+    webhook.insert(self.db).await
+  }
+
+  pub async fn delete_webhook(&self, id: i32) -> Result<u64, DbErr> {
+    let res = webhook::Entity::delete_by_id(id).exec(self.db).await?;
+    Ok(res.rows_affected)
+  }
+}
@@ -0,0 +1,62 @@
+use sea_orm::{
+  ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, QueryOrder,
+  QuerySelect, prelude::DateTimeUtc,
+};
+
+use entity::webhook_delivery::{self, DeliveryStatus};
+
+#[derive(Debug, Clone)]
+pub struct WebhookDeliveryRepository<'a> {
+  pub db: &'a DatabaseConnection,
+}
+
+impl WebhookDeliveryRepository<'_> {
+  pub async fn create_delivery(
+    &self,
+    delivery: webhook_delivery::ActiveModel,
+  ) -> Result<webhook_delivery::Model, DbErr> {
+    delivery.insert(self.db).await
+  }
+
+  pub async fn update_delivery(
+    &self,
+    delivery: webhook_delivery::ActiveModel,
+  ) -> Result<webhook_delivery::Model, DbErr> {
+    delivery.update(self.db).await
+  }
+
+  /// 最近的投递记录，按时间倒序
+  pub async fn get_deliveries_by_webhook_id(
+    &self,
+    webhook_id: i32,
+    limit: u64,
+  ) -> Result<Vec<webhook_delivery::Model>, DbErr> {
+    webhook_delivery::Entity::find()
+      .filter(webhook_delivery::Column::WebhookId.eq(webhook_id))
+      .order_by_desc(webhook_delivery::Column::Id)
+      .limit(limit)
+      .all(self.db)
+      .await
+  }
+
+  /// 到了重试时间的待投递记录
+  pub async fn get_due_deliveries(
+    &self,
+    now: DateTimeUtc,
+  ) -> Result<Vec<webhook_delivery::Model>, DbErr> {
+    webhook_delivery::Entity::find()
+      .filter(webhook_delivery::Column::Status.eq(DeliveryStatus::Pending))
+      .filter(webhook_delivery::Column::NextAttemptAt.lte(now))
+      .order_by_asc(webhook_delivery::Column::Id)
+      .all(self.db)
+      .await
+  }
+
+  pub async fn delete_deliveries_by_webhook_id(&self, webhook_id: i32) -> Result<u64, DbErr> {
+    let res = webhook_delivery::Entity::delete_many()
+      .filter(webhook_delivery::Column::WebhookId.eq(webhook_id))
+      .exec(self.db)
+      .await?;
+    Ok(res.rows_affected)
+  }
+}
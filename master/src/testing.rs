@@ -0,0 +1,153 @@
+//! 服务层测试共用的应用状态和数据，数据库为内存中的 SQLite
+
+use std::{sync::Arc, time::Duration};
+
+use entity::{
+  agent::{self, AgentStatus},
+  deployment::{self, DeploymentStatus},
+  site::{self, Bandwidth, SiteStatus},
+  task::TaskStatus,
+  user::{self, UserStatus, UserType},
+};
+use helpers::{jwt, time::utc_now};
+use rpc::{CloudflareRpc, MockAgentApi, WebhookRpc};
+use sea_orm::{ActiveValue::Set, IntoActiveModel};
+
+use crate::{
+  app::AppState,
+  components::{deployment::stream::DeploymentStreams, task::service::TaskWorkers},
+  helper::TrustedProxies,
+  migration::migrate,
+  rate_limit::{LoginGuard, RateLimit, RateLimiter},
+  reconcile::OrphanSites,
+  repository::RepositoryManager,
+};
+
+pub async fn test_state(agent_rpc: MockAgentApi) -> AppState {
+  // 测试中不限流
+  let limit = RateLimit {
+    burst: 0,
+    period: 0,
+  };
+  AppState {
+    repo: RepositoryManager::new(migrate("sqlite::memory:").await.unwrap()),
+    login_token_key: "login_token_key".to_string(),
+    register_agent_key: "register_agent_key".to_string(),
+    register_agent_key_expire: 3600,
+    agent_rpc: Arc::new(agent_rpc),
+    cloudflare_rpc: CloudflareRpc::new(String::new(), String::new(), String::new())
+      .await
+      .unwrap(),
+    casual_site_ttl: 86400,
+    casual_site_expire_warning: 3600,
+    webhook_rpc: WebhookRpc::new(false).unwrap(),
+    webhook_max_attempts: 3,
+    webhook_retry_interval: 60,
+    deployment_streams: DeploymentStreams::default(),
+    task_max_attempts: 3,
+    task_retry_interval: 0,
+    task_retention: 86400,
+    task_workers: TaskWorkers::default(),
+    reconcile_revoke_orphans: false,
+    reconcile_orphan_grace: 0,
+    orphan_sites: OrphanSites::default(),
+    backup_dir: std::env::temp_dir().display().to_string(),
+    backup_interval: 0,
+    backup_retention: 1,
+    trust_proxy: false,
+    trusted_proxies: TrustedProxies::default(),
+    rate_limiter: RateLimiter::new(limit, Default::default()),
+    login_guard: LoginGuard::new(limit, 5, Duration::from_secs(60)),
+  }
+}
+
+pub async fn create_user(state: &AppState, user_id: &str, r#type: UserType) -> user::Model {
+  state
+    .repo
+    .user()
+    .create_user(user::ActiveModel {
+      user_id: Set(user_id.to_string()),
+      nickname: Set(user_id.to_string()),
+      password: Set("password".to_string()),
+      email: Set(format!("{}@example.com", user_id)),
+      r#type: Set(r#type),
+      status: Set(UserStatus::Active),
+      is_email_verified: Set(1),
+      is_phone_verified: Set(0),
+      created_at: Set(utc_now()),
+      ..Default::default()
+    })
+    .await
+    .unwrap()
+}
+
+pub async fn create_agent(state: &AppState, ip_address: &str) -> agent::Model {
+  state
+    .repo
+    .agent()
+    .create_agent(agent::ActiveModel {
+      hostname: Set(ip_address.to_string()),
+      ip_address: Set(ip_address.to_string()),
+      api_url: Set(format!("http://{}:5001", ip_address)),
+      storage_path: Set("/data".to_string()),
+      available_space: Set(1024),
+      status: Set(AgentStatus::Online),
+      token: Set(format!("token-{}", ip_address)),
+      created_at: Set(utc_now()),
+      ..Default::default()
+    })
+    .await
+    .unwrap()
+}
+
+/// 创建站点和它当前的部署
+pub async fn create_site(
+  state: &AppState,
+  site_id: &str,
+  user_id: &str,
+  agent_id: i32,
+  status: DeploymentStatus,
+) -> (site::Model, deployment::Model) {
+  let deployment = state
+    .repo
+    .deployment()
+    .create_deployment(deployment::ActiveModel {
+      site_id: Set(site_id.to_string()),
+      agent_id: Set(agent_id),
+      status: Set(status),
+      execution_time: Set(utc_now()),
+      created_at: Set(utc_now()),
+      ..Default::default()
+    })
+    .await
+    .unwrap();
+  let site = state
+    .repo
+    .site()
+    .create_site(site::ActiveModel {
+      site_id: Set(site_id.to_string()),
+      user_id: Set(user_id.to_string()),
+      deployment_id: Set(Some(deployment.id)),
+      name: Set(site_id.to_string()),
+      status: Set(SiteStatus::Active),
+      bandwidth: Set(Bandwidth::One),
+      created_at: Set(utc_now()),
+      ..Default::default()
+    })
+    .await
+    .unwrap();
+  (site, deployment)
+}
+
+/// 把任务直接标记为结束
+pub async fn finish(state: &AppState, task_id: i32, status: TaskStatus) {
+  let task = state.repo.task().get_task(task_id).await.unwrap().unwrap();
+  let mut active_task = task.into_active_model();
+  active_task.status = Set(status);
+  state.repo.task().update_task(active_task).await.unwrap();
+}
+
+/// 用户登录后得到的 token
+pub fn login_token(state: &AppState, user_id: &str) -> String {
+  jwt::sign(user_id.to_string(), &state.login_token_key, 86400).unwrap()
+}
@@ -1,32 +1,284 @@
-use entity::agent::AgentStatus;
+use std::time::{Duration, Instant};
+
+use entity::{
+  agent::{AgentStatus, join_public_ips},
+  audit_log::AuditAction,
+  webhook::WebhookEvent,
+};
 use helpers::time::utc_now;
+use rpc::AgentEndpoint;
 use sea_orm::{ActiveValue::Set, IntoActiveModel};
+use serde_json::json;
 
-use crate::{error::AppError, repository::RepositoryManager};
+use crate::{
+  app::AppState,
+  components::{
+    audit::service::{AuditEntry, record},
+    site, task, webhook,
+  },
+  error::AppError,
+};
 
-pub async fn scheduled_task(db: &RepositoryManager) -> Result<(), AppError> {
-  check_agents_status(&db).await?;
-  Ok(())
+/// 定时任务的执行间隔
+pub const SCHEDULED_TASK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// 任务调度器检查待执行任务的间隔
+pub const TASK_DISPATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 各项定时任务相互独立，一项失败只记录日志，不影响其他项的执行
+pub async fn scheduled_task(state: &AppState) {
+  let now = Instant::now();
+  state.rate_limiter.prune(now);
+  state.login_guard.prune(now);
+  let results = [
+    ("check agents status", check_agents_status(state).await),
+    ("warn expiring sites", warn_expiring_sites(state).await),
+    ("collect expired sites", collect_expired_sites(state).await),
+    (
+      "retry webhook deliveries",
+      webhook::service::retry_due_deliveries(state).await,
+    ),
+    (
+      "prune finished tasks",
+      task::service::prune_finished_tasks(state).await,
+    ),
+  ];
+  for (name, result) in results {
+    if let Err(err) = result {
+      tracing::error!("Scheduled task `{}` failed: {}", name, err);
+    }
+  }
 }
 
-async fn check_agents_status(db: &RepositoryManager) -> Result<(), AppError> {
+async fn check_agents_status(state: &AppState) -> Result<(), AppError> {
+  let db = &state.repo;
   let agents = db.agent().get_agents().await?;
-  let agent_rpc = rpc::AgentRpc::new()?;
 
   for agent in agents {
-    let new_status = if let Ok(_) = agent_rpc.get_agent_heartbeat(&agent.ip_address).await {
+    let heartbeat = state
+      .agent_rpc
+      .get_agent_heartbeat(&AgentEndpoint::from(&agent))
+      .await;
+    let new_status = if heartbeat.is_ok() {
       AgentStatus::Online
     } else {
       AgentStatus::Offline
     };
     tracing::debug!("Agent {} is {}", agent.ip_address, agent.status);
+    // 旧版本的 Agent 不上报地址，保留原来的记录
+    let public_ips = heartbeat
+      .ok()
+      .filter(|heartbeat| !heartbeat.public_ips.is_empty())
+      .map(|heartbeat| join_public_ips(&heartbeat.public_ips))
+      .filter(|public_ips| agent.public_ips.as_ref() != Some(public_ips));
 
-    if agent.status != new_status {
-      let mut active_agent = agent.into_active_model();
-      active_agent.status = Set(new_status);
-      active_agent.updated_at = Set(Some(utc_now()));
-      db.agent().update_agent(active_agent).await?;
+    if agent.status == new_status && public_ips.is_none() {
+      continue;
+    }
+    if agent.status != new_status && new_status == AgentStatus::Offline {
+      webhook::service::emit(
+        state,
+        WebhookEvent::AgentOffline,
+        None,
+        None,
+        json!({
+          "agent_id": agent.id,
+          "hostname": agent.hostname,
+          "ip_address": agent.ip_address,
+        }),
+      )
+      .await;
+    }
+    let mut active_agent = agent.into_active_model();
+    active_agent.status = Set(new_status);
+    if let Some(public_ips) = public_ips {
+      active_agent.public_ips = Set(Some(public_ips));
     }
+    active_agent.updated_at = Set(Some(utc_now()));
+    db.agent().update_agent(active_agent).await?;
   }
   Ok(())
 }
+
+/// 对即将过期的临时站点发出警告，提醒后记录在站点上，每个站点只会被提醒一次
+async fn warn_expiring_sites(state: &AppState) -> Result<(), AppError> {
+  let now = utc_now();
+  let to = now + chrono::Duration::seconds(state.casual_site_expire_warning);
+  let sites = state.repo.site().get_sites_to_warn(now, to).await?;
+  if sites.is_empty() {
+    return Ok(());
+  }
+  let ids = sites.iter().map(|site| site.id).collect();
+  state.repo.site().mark_sites_warned(ids, now).await?;
+  for site in sites {
+    tracing::warn!(
+      "Site {} of user {} will expire at {:?}",
+      site.site_id,
+      site.user_id,
+      site.expired_at
+    );
+  }
+  Ok(())
+}
+
+/// 回收已过期的站点：撤销 Agent 上的文件与 Nginx 配置，删除 DNS 记录和数据库记录
+async fn collect_expired_sites(state: &AppState) -> Result<(), AppError> {
+  let sites = state.repo.site().get_expired_sites(utc_now()).await?;
+  for site in sites {
+    // Agent 不可达时保留站点，等下一轮再尝试回收
+    let result = site::service::remove_site(state, &site).await;
+    if let Err(err) = &result {
+      tracing::error!("Failed to collect expired site {}: {}", site.site_id, err);
+      continue;
+    }
+    tracing::info!("Expired site {} has been collected", site.site_id);
+    let entry = AuditEntry::new("system", AuditAction::SiteDelete)
+      .target(format!("site:{}", site.site_id))
+      .detail("expired");
+    record(state, entry, &result).await;
+  }
+  Ok(())
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+  use entity::{deployment::DeploymentStatus, site, user::UserType};
+  use rpc::MockAgentApi;
+
+  use super::*;
+  use crate::testing::{create_agent, create_site, create_user, test_state};
+
+  /// 创建在 `seconds` 秒后过期的站点，负数表示已过期
+  async fn create_expiring_site(
+    state: &AppState,
+    site_id: &str,
+    agent_id: i32,
+    seconds: i64,
+  ) -> site::Model {
+    let (site, _) = create_site(
+      state,
+      site_id,
+      "owner",
+      agent_id,
+      DeploymentStatus::Published,
+    )
+    .await;
+    let mut active_site = site.into_active_model();
+    active_site.expired_at = Set(Some(utc_now() + chrono::Duration::seconds(seconds)));
+    state.repo.site().update_site(active_site).await.unwrap()
+  }
+
+  async fn get_site(state: &AppState, site_id: &str) -> Option<site::Model> {
+    state.repo.site().get_site_by_id(site_id).await.unwrap()
+  }
+
+  #[actix_web::test]
+  async fn test_warn_expiring_sites() {
+    let state = test_state(MockAgentApi::new()).await;
+    create_user(&state, "owner", UserType::Normal).await;
+    let agent = create_agent(&state, "10.0.0.1").await;
+    create_expiring_site(&state, "expiring", agent.id, 600).await;
+    create_expiring_site(&state, "later", agent.id, 7200).await;
+    create_expiring_site(&state, "expired", agent.id, -60).await;
+
+    let expired = state
+      .repo
+      .site()
+      .get_expired_sites(utc_now())
+      .await
+      .unwrap();
+    assert_eq!(
+      expired
+        .iter()
+        .map(|site| site.site_id.as_str())
+        .collect::<Vec<_>>(),
+      ["expired"]
+    );
+
+    warn_expiring_sites(&state).await.unwrap();
+    let warned_at = get_site(&state, "expiring").await.unwrap().expire_warned_at;
+    assert!(warned_at.is_some());
+    assert!(
+      get_site(&state, "later")
+        .await
+        .unwrap()
+        .expire_warned_at
+        .is_none()
+    );
+    assert!(
+      get_site(&state, "expired")
+        .await
+        .unwrap()
+        .expire_warned_at
+        .is_none()
+    );
+
+    // 第二轮不会再次提醒
+    warn_expiring_sites(&state).await.unwrap();
+    assert_eq!(
+      get_site(&state, "expiring").await.unwrap().expire_warned_at,
+      warned_at
+    );
+  }
+
+  #[actix_web::test]
+  async fn test_collect_expired_sites() {
+    use entity::audit_log::{AuditAction, AuditResult};
+
+    use crate::repository::AuditLogFilter;
+
+    let mut agent_rpc = MockAgentApi::new();
+    agent_rpc.expect_task_revoke().returning(|_, _| Ok(true));
+    let state = test_state(agent_rpc).await;
+    create_user(&state, "owner", UserType::Normal).await;
+    let agent = create_agent(&state, "10.0.0.1").await;
+    create_expiring_site(&state, "expired", agent.id, -60).await;
+    create_expiring_site(&state, "expiring", agent.id, 600).await;
+
+    collect_expired_sites(&state).await.unwrap();
+    assert!(get_site(&state, "expired").await.is_none());
+    assert!(get_site(&state, "expiring").await.is_some());
+    let (logs, total) = state
+      .repo
+      .audit_log()
+      .list_logs(AuditLogFilter::default(), 0, 10)
+      .await
+      .unwrap();
+    assert_eq!(total, 1);
+    assert_eq!(logs[0].actor, "system");
+    assert_eq!(logs[0].action, AuditAction::SiteDelete);
+    assert_eq!(logs[0].target.as_deref(), Some("site:expired"));
+    assert_eq!(logs[0].detail.as_deref(), Some("expired"));
+    assert_eq!(logs[0].result, AuditResult::Success);
+  }
+
+  #[actix_web::test]
+  async fn test_collect_expired_sites_failure() {
+    let mut agent_rpc = MockAgentApi::new();
+    agent_rpc
+      .expect_task_revoke()
+      .returning(|_, _| Err(rpc::error::Error::ConnectAgent("timeout".to_string())));
+    let state = test_state(agent_rpc).await;
+    create_user(&state, "owner", UserType::Normal).await;
+    let agent = create_agent(&state, "10.0.0.1").await;
+    create_expiring_site(&state, "expired", agent.id, -60).await;
+
+    // Agent 不可达时保留站点，等下一轮再回收，不写审计日志
+    collect_expired_sites(&state).await.unwrap();
+    assert!(get_site(&state, "expired").await.is_some());
+    let (_, total) = state
+      .repo
+      .audit_log()
+      .list_logs(Default::default(), 0, 10)
+      .await
+      .unwrap();
+    assert_eq!(total, 0);
+    let deployments = state
+      .repo
+      .deployment()
+      .get_deployments_by_site_id("expired")
+      .await
+      .unwrap();
+    assert_eq!(deployments.len(), 1);
+  }
+}
@@ -0,0 +1,99 @@
+use std::net::{IpAddr, SocketAddr};
+
+use sea_orm_migration::{prelude::*, schema::*};
+
+/// Agent 默认监听的端口
+const DEFAULT_AGENT_PORT: u16 = 5001;
+
+#[derive(DeriveIden)]
+enum Agent {
+  Table,
+  Id,
+  IpAddress,
+  ApiUrl,          // Agent API 地址
+  CaCert,          // 校验 Agent 证书的 CA 证书
+  CertFingerprint, // Agent 证书的 SHA-256 指纹
+}
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    // SQLite 每条 ALTER TABLE 只能修改一列
+    manager
+      .alter_table(
+        Table::alter()
+          .table(Agent::Table)
+          .add_column(string(Agent::ApiUrl).default("").comment("Agent API 地址"))
+          .to_owned(),
+      )
+      .await?;
+    manager
+      .alter_table(
+        Table::alter()
+          .table(Agent::Table)
+          .add_column(text_null(Agent::CaCert).comment("校验 Agent 证书的 CA 证书"))
+          .to_owned(),
+      )
+      .await?;
+    manager
+      .alter_table(
+        Table::alter()
+          .table(Agent::Table)
+          .add_column(string_null(Agent::CertFingerprint).comment("Agent 证书的 SHA-256 指纹"))
+          .to_owned(),
+      )
+      .await?;
+
+    // 已注册的 Agent 使用原来固定的地址
+    let db = manager.get_connection();
+    let builder = db.get_database_backend();
+    let agents = db
+      .query_all(
+        builder.build(
+          Query::select()
+            .columns([Agent::Id, Agent::IpAddress])
+            .from(Agent::Table),
+        ),
+      )
+      .await?;
+    for agent in agents {
+      let id: i32 = agent.try_get("", &Agent::Id.to_string())?;
+      let ip_address: String = agent.try_get("", &Agent::IpAddress.to_string())?;
+      db.execute(
+        builder.build(
+          Query::update()
+            .table(Agent::Table)
+            .value(Agent::ApiUrl, default_api_url(&ip_address))
+            .and_where(Expr::col(Agent::Id).eq(id)),
+        ),
+      )
+      .await?;
+    }
+    Ok(())
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    for column in [Agent::ApiUrl, Agent::CaCert, Agent::CertFingerprint] {
+      manager
+        .alter_table(
+          Table::alter()
+            .table(Agent::Table)
+            .drop_column(column)
+            .to_owned(),
+        )
+        .await?;
+    }
+    Ok(())
+  }
+}
+
+/// IPv6 地址需要放在方括号中
+fn default_api_url(ip_address: &str) -> String {
+  match ip_address.parse::<IpAddr>() {
+    Ok(ip) => format!("http://{}", SocketAddr::new(ip, DEFAULT_AGENT_PORT)),
+    Err(_) => format!("http://{}:{}", ip_address, DEFAULT_AGENT_PORT),
+  }
+}
@@ -0,0 +1,35 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveIden)]
+enum Agent {
+  Table,
+  PublicIps, // 公网地址，以逗号分隔
+}
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .alter_table(
+        Table::alter()
+          .table(Agent::Table)
+          .add_column(string_null(Agent::PublicIps).comment("公网地址，以逗号分隔"))
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .alter_table(
+        Table::alter()
+          .table(Agent::Table)
+          .drop_column(Agent::PublicIps)
+          .to_owned(),
+      )
+      .await
+  }
+}
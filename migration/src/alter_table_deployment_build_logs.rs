@@ -0,0 +1,48 @@
+use sea_orm_migration::{prelude::*, sea_orm::DbBackend};
+
+#[derive(DeriveIden)]
+enum Deployment {
+  Table,
+  BuildLogs, // 构建日志
+}
+
+/// 构建日志改为 text，MySQL 的 varchar(255) 放不下完整的日志
+///
+/// SQLite 不限制 varchar 的长度，也不支持修改列类型，保持不变
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    alter_build_logs(manager, ColumnType::Text).await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    alter_build_logs(manager, ColumnType::String(StringLen::None)).await
+  }
+}
+
+async fn alter_build_logs(
+  manager: &SchemaManager<'_>,
+  column_type: ColumnType,
+) -> Result<(), DbErr> {
+  let mut column = ColumnDef::new_with_type(Deployment::BuildLogs, column_type);
+  match manager.get_database_backend() {
+    DbBackend::Sqlite => return Ok(()),
+    // Postgres 只修改类型，可空性沿用原来的定义
+    DbBackend::Postgres => {}
+    // MySQL 的 MODIFY COLUMN 会覆盖整个列定义，需要带上可空性与注释
+    DbBackend::MySql => {
+      column.null().comment("构建日志");
+    }
+  }
+  manager
+    .alter_table(
+      Table::alter()
+        .table(Deployment::Table)
+        .modify_column(column)
+        .to_owned(),
+    )
+    .await
+}
@@ -0,0 +1,161 @@
+use sea_orm_migration::{prelude::*, sea_orm::DbBackend};
+
+/// 早期迁移中不带时区的时间列，实体按 `DateTimeUtc` 读写，Postgres 需要 timestamptz
+const TIMESTAMP_COLUMNS: &[(&str, &[&str])] = &[
+  ("user", &["created_at", "updated_at"]),
+  ("agent", &["created_at", "updated_at", "last_heartbeat"]),
+  ("site", &["created_at", "updated_at", "expired_at"]),
+  ("nginx", &["created_at", "updated_at"]),
+  ("deployment", &["execution_time", "created_at"]),
+  ("deployment_event", &["created_at"]),
+  ("webhook", &["created_at"]),
+  (
+    "webhook_delivery",
+    &["next_attempt_at", "created_at", "updated_at"],
+  ),
+  ("task", &["next_run_at", "created_at", "updated_at"]),
+];
+
+/// 早期迁移中的无符号整数与 tinyint 列，实体按 `i32` 与 `i16` 读写，MySQL 需要有符号的 int 与 smallint
+///
+/// 依次为表名、列名、是否可空、默认值与注释，MySQL 的 MODIFY COLUMN 会覆盖整个列定义
+const INTEGER_COLUMNS: &[(&str, &str, bool, Option<i32>, &str)] = &[
+  ("user", "is_email_verified", false, Some(0), "邮箱是否验证"),
+  ("user", "is_phone_verified", false, Some(0), "手机是否验证"),
+  ("agent", "available_space", false, None, "可用空间"),
+  ("site", "deployment_id", true, None, "部署 ID"),
+  ("site", "bandwidth", true, None, "带宽"),
+  ("nginx", "agent_id", false, None, "提供服务的 Agent ID"),
+  ("nginx", "deployment_id", false, None, "发布的部署 ID"),
+  ("deployment", "agent_id", true, None, "执行部署的 AgentID"),
+  (
+    "deployment_event",
+    "deployment_id",
+    false,
+    None,
+    "关联的部署 ID",
+  ),
+  (
+    "webhook_delivery",
+    "webhook_id",
+    false,
+    None,
+    "关联的 Webhook ID",
+  ),
+  ("webhook_delivery", "attempts", false, Some(0), "已投递次数"),
+  (
+    "webhook_delivery",
+    "response_status",
+    true,
+    None,
+    "最近一次的响应状态码",
+  ),
+  ("task", "deployment_id", true, None, "部署 ID"),
+  ("task", "attempts", false, Some(0), "已执行次数"),
+];
+
+/// 使用无符号自增主键创建的表
+const UNSIGNED_PK_TABLES: &[&str] = &[
+  "user",
+  "agent",
+  "site",
+  "nginx",
+  "deployment",
+  "deployment_event",
+  "webhook",
+  "webhook_delivery",
+  "task",
+];
+
+/// 早期的迁移按 SQLite 编写，SQLite 不区分这些类型，已有的库保持不变；
+/// Postgres 与 MySQL 的列类型在这里改为与实体一致
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    match manager.get_database_backend() {
+      DbBackend::Sqlite => Ok(()),
+      DbBackend::Postgres => alter_timestamps(manager, ColumnType::TimestampWithTimeZone).await,
+      DbBackend::MySql => alter_integers(manager, true).await,
+    }
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    match manager.get_database_backend() {
+      DbBackend::Sqlite => Ok(()),
+      DbBackend::Postgres => alter_timestamps(manager, ColumnType::Timestamp).await,
+      DbBackend::MySql => alter_integers(manager, false).await,
+    }
+  }
+}
+
+/// Postgres 的 ALTER COLUMN 只修改类型，可空性与默认值沿用原来的定义
+async fn alter_timestamps(
+  manager: &SchemaManager<'_>,
+  column_type: ColumnType,
+) -> Result<(), DbErr> {
+  for (table, columns) in TIMESTAMP_COLUMNS {
+    let mut alter = Table::alter().table(Alias::new(*table)).to_owned();
+    for column in *columns {
+      alter.modify_column(ColumnDef::new_with_type(
+        Alias::new(*column),
+        column_type.clone(),
+      ));
+    }
+    manager.alter_table(alter).await?;
+  }
+  Ok(())
+}
+
+/// `signed` 为 true 时改为有符号的 int 与 smallint，否则恢复为原来的 int unsigned 与 tinyint
+async fn alter_integers(manager: &SchemaManager<'_>, signed: bool) -> Result<(), DbErr> {
+  for table in UNSIGNED_PK_TABLES {
+    let mut id = ColumnDef::new(Alias::new("id"));
+    if signed {
+      id.integer();
+    } else {
+      id.unsigned();
+    }
+    id.not_null().auto_increment();
+    if *table != "user" && *table != "agent" && *table != "site" {
+      id.comment("主键 ID");
+    }
+    manager
+      .alter_table(
+        Table::alter()
+          .table(Alias::new(*table))
+          .modify_column(id)
+          .to_owned(),
+      )
+      .await?;
+  }
+  for (table, column, nullable, default, comment) in INTEGER_COLUMNS {
+    let mut def = ColumnDef::new(Alias::new(*column));
+    match (signed, *table == "user") {
+      (true, true) => def.small_integer(),
+      (false, true) => def.tiny_integer(),
+      (true, false) => def.integer(),
+      (false, false) => def.unsigned(),
+    };
+    if *nullable {
+      def.null();
+    } else {
+      def.not_null();
+    }
+    if let Some(default) = default {
+      def.default(*default);
+    }
+    def.comment(*comment);
+    manager
+      .alter_table(
+        Table::alter()
+          .table(Alias::new(*table))
+          .modify_column(def)
+          .to_owned(),
+      )
+      .await?;
+  }
+  Ok(())
+}
@@ -0,0 +1,35 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveIden)]
+enum Site {
+  Table,
+  ExpireWarnedAt, // 过期提醒时间，每个临时站点只提醒一次
+}
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .alter_table(
+        Table::alter()
+          .table(Site::Table)
+          .add_column(timestamp_with_time_zone_null(Site::ExpireWarnedAt).comment("过期提醒时间"))
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .alter_table(
+        Table::alter()
+          .table(Site::Table)
+          .drop_column(Site::ExpireWarnedAt)
+          .to_owned(),
+      )
+      .await
+  }
+}
@@ -0,0 +1,35 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveIden)]
+enum Site {
+  Table,
+  ExpiredAt, // 过期时间，临时站点到期后会被回收
+}
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .alter_table(
+        Table::alter()
+          .table(Site::Table)
+          .add_column(timestamp_null(Site::ExpiredAt).comment("过期时间"))
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .alter_table(
+        Table::alter()
+          .table(Site::Table)
+          .drop_column(Site::ExpiredAt)
+          .to_owned(),
+      )
+      .await
+  }
+}
@@ -1,5 +1,7 @@
 use sea_orm_migration::{prelude::*, schema::*};
 
+use crate::pk_auto_unsigned;
+
 #[derive(DeriveIden)]
 enum Agent {
   Table,
@@ -29,7 +31,7 @@ impl MigrationTrait for Migration {
         Table::create()
           .table(Agent::Table)
           .if_not_exists()
-          .col(pk_auto(Agent::Id).unsigned())
+          .col(pk_auto_unsigned(manager, Agent::Id))
           .col(string(Agent::Hostname).comment("主机名"))
           .col(string(Agent::IpAddress).comment("IP 地址"))
           .col(string(Agent::StoragePath).comment("存储根路径"))
@@ -0,0 +1,63 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveIden)]
+enum AuditLog {
+  Table,
+  Id,        // 主键 ID
+  Actor,     // 执行操作的一方
+  Action,    // 操作
+  Target,    // 操作对象
+  Ip,        // 客户端 IP
+  Result,    // 操作结果
+  Detail,    // 补充信息
+  CreatedAt, // 创建时间
+}
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .create_table(
+        Table::create()
+          .table(AuditLog::Table)
+          .if_not_exists()
+          .col(pk_auto(AuditLog::Id).comment("主键 ID"))
+          .col(string(AuditLog::Actor).comment("执行操作的一方"))
+          .col(string(AuditLog::Action).comment("操作"))
+          .col(string_null(AuditLog::Target).comment("操作对象"))
+          .col(string_null(AuditLog::Ip).comment("客户端 IP"))
+          .col(string(AuditLog::Result).comment("操作结果"))
+          .col(text_null(AuditLog::Detail).comment("补充信息"))
+          .col(timestamp_with_time_zone(AuditLog::CreatedAt).comment("创建时间"))
+          .to_owned(),
+      )
+      .await?;
+    manager
+      .create_index(
+        Index::create()
+          .name("idx_audit_log_actor")
+          .table(AuditLog::Table)
+          .col(AuditLog::Actor)
+          .to_owned(),
+      )
+      .await?;
+    manager
+      .create_index(
+        Index::create()
+          .name("idx_audit_log_target")
+          .table(AuditLog::Table)
+          .col(AuditLog::Target)
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .drop_table(Table::drop().table(AuditLog::Table).to_owned())
+      .await
+  }
+}
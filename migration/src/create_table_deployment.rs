@@ -1,5 +1,7 @@
 use sea_orm_migration::{prelude::*, schema::*};
 
+use crate::pk_auto_unsigned;
+
 #[derive(DeriveIden)]
 enum Deployment {
   Table,
@@ -26,7 +28,7 @@ impl MigrationTrait for Migration {
         Table::create()
           .table(Deployment::Table)
           .if_not_exists()
-          .col(pk_auto(Deployment::Id).unsigned().comment("主键 ID"))
+          .col(pk_auto_unsigned(manager, Deployment::Id).comment("主键 ID"))
           .col(string(Deployment::SiteId).comment("关联的站点 ID"))
           .col(unsigned_null(Deployment::AgentId).comment("执行部署的 AgentID"))
           .col(string(Deployment::Status).comment("部署状态: pending, building, published, failed"))
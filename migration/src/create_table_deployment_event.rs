@@ -0,0 +1,54 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+use crate::pk_auto_unsigned;
+
+#[derive(DeriveIden)]
+enum DeploymentEvent {
+  Table,
+  Id,           // 主键 ID
+  DeploymentId, // 关联的部署 ID
+  FromStatus,   // 变更前的状态
+  ToStatus,     // 变更后的状态
+  Actor,        // 触发变更的一方
+  Reason,       // 变更原因
+  CreatedAt,    // 创建时间
+}
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .create_table(
+        Table::create()
+          .table(DeploymentEvent::Table)
+          .if_not_exists()
+          .col(pk_auto_unsigned(manager, DeploymentEvent::Id).comment("主键 ID"))
+          .col(unsigned(DeploymentEvent::DeploymentId).comment("关联的部署 ID"))
+          .col(string_null(DeploymentEvent::FromStatus).comment("变更前的状态"))
+          .col(string(DeploymentEvent::ToStatus).comment("变更后的状态"))
+          .col(string(DeploymentEvent::Actor).comment("触发变更的一方"))
+          .col(string_null(DeploymentEvent::Reason).comment("变更原因"))
+          .col(timestamp(DeploymentEvent::CreatedAt).comment("创建时间"))
+          .to_owned(),
+      )
+      .await?;
+    manager
+      .create_index(
+        Index::create()
+          .name("idx_deployment_event_deployment_id")
+          .table(DeploymentEvent::Table)
+          .col(DeploymentEvent::DeploymentId)
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .drop_table(Table::drop().table(DeploymentEvent::Table).to_owned())
+      .await
+  }
+}
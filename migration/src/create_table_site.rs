@@ -1,6 +1,8 @@
 use sea_orm::EnumIter;
 use sea_orm_migration::{prelude::*, schema::*};
 
+use crate::pk_auto_unsigned;
+
 #[derive(DeriveIden)]
 enum Site {
   Table,
@@ -35,7 +37,7 @@ impl MigrationTrait for Migration {
         Table::create()
           .table(Site::Table)
           .if_not_exists()
-          .col(pk_auto(Site::Id).unsigned())
+          .col(pk_auto_unsigned(manager, Site::Id))
           .col(string(Site::SiteId).comment("站点ID"))
           .col(string(Site::UserId).comment("用户ID"))
           .col(
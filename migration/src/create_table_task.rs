@@ -0,0 +1,69 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+use crate::pk_auto_unsigned;
+
+#[derive(DeriveIden)]
+enum Task {
+  Table,
+  Id,             // 主键 ID
+  Type,           // publish, revoke, migrate
+  Status,         // pending, running, succeeded, failed
+  UserId,         // 提交任务的用户 ID
+  SiteId,         // 站点 ID
+  DeploymentId,   // 部署 ID
+  Payload,        // 任务参数
+  IdempotencyKey, // 幂等键
+  Attempts,       // 已执行次数
+  LastError,      // 最近一次的错误信息
+  Result,         // 执行结果
+  NextRunAt,      // 下一次执行时间
+  CreatedAt,      // 创建时间
+  UpdatedAt,      // 更新时间
+}
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .create_table(
+        Table::create()
+          .table(Task::Table)
+          .if_not_exists()
+          .col(pk_auto_unsigned(manager, Task::Id).comment("主键 ID"))
+          .col(string(Task::Type).comment("任务类型: publish, revoke, migrate"))
+          .col(string(Task::Status).comment("任务状态: pending, running, succeeded, failed"))
+          .col(string(Task::UserId).comment("提交任务的用户 ID"))
+          .col(string(Task::SiteId).comment("站点 ID"))
+          .col(unsigned_null(Task::DeploymentId).comment("部署 ID"))
+          .col(text(Task::Payload).comment("任务参数"))
+          .col(string_uniq(Task::IdempotencyKey).comment("幂等键"))
+          .col(unsigned(Task::Attempts).default(0).comment("已执行次数"))
+          .col(text_null(Task::LastError).comment("最近一次的错误信息"))
+          .col(text_null(Task::Result).comment("执行结果"))
+          .col(timestamp_null(Task::NextRunAt).comment("下一次执行时间"))
+          .col(timestamp(Task::CreatedAt).comment("创建时间"))
+          .col(timestamp_null(Task::UpdatedAt).comment("更新时间"))
+          .to_owned(),
+      )
+      .await?;
+    manager
+      .create_index(
+        Index::create()
+          .name("idx_task_status_next_run_at")
+          .table(Task::Table)
+          .col(Task::Status)
+          .col(Task::NextRunAt)
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .drop_table(Table::drop().table(Task::Table).to_owned())
+      .await
+  }
+}
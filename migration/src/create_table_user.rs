@@ -1,6 +1,8 @@
 use sea_orm::EnumIter;
 use sea_orm_migration::{prelude::*, schema::*};
 
+use crate::pk_auto_unsigned;
+
 #[derive(DeriveIden)]
 enum User {
   Table,           // 表名
@@ -46,7 +48,7 @@ impl MigrationTrait for Migration {
         Table::create()
           .table(User::Table)
           .if_not_exists()
-          .col(pk_auto(User::Id).unsigned())
+          .col(pk_auto_unsigned(manager, User::Id))
           .col(string(User::UserId).unique_key().comment("用户 UUID"))
           .col(string(User::Nickname).comment("昵称"))
           .col(string(User::Password).comment("密码"))
@@ -0,0 +1,96 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+use crate::pk_auto_unsigned;
+
+#[derive(DeriveIden)]
+enum Webhook {
+  Table,
+  Id,        // 主键 ID
+  UserId,    // 所属用户 ID
+  SiteId,    // 订阅的站点 ID，为空时订阅用户所有站点
+  Url,       // 接收地址
+  Secret,    // 签名密钥
+  Events,    // 订阅的事件
+  CreatedAt, // 创建时间
+}
+
+#[derive(DeriveIden)]
+enum WebhookDelivery {
+  Table,
+  Id,             // 主键 ID
+  WebhookId,      // 关联的 Webhook ID
+  Event,          // 事件名
+  Payload,        // 投递内容
+  Status,         // pending, succeeded, failed
+  Attempts,       // 已投递次数
+  ResponseStatus, // 最近一次的响应状态码
+  Error,          // 最近一次的错误信息
+  NextAttemptAt,  // 下一次重试时间
+  CreatedAt,      // 创建时间
+  UpdatedAt,      // 更新时间
+}
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .create_table(
+        Table::create()
+          .table(Webhook::Table)
+          .if_not_exists()
+          .col(pk_auto_unsigned(manager, Webhook::Id).comment("主键 ID"))
+          .col(string(Webhook::UserId).comment("所属用户 ID"))
+          .col(string_null(Webhook::SiteId).comment("订阅的站点 ID，为空时订阅用户所有站点"))
+          .col(string(Webhook::Url).comment("接收地址"))
+          .col(string(Webhook::Secret).comment("签名密钥"))
+          .col(string(Webhook::Events).comment("订阅的事件，逗号分隔"))
+          .col(timestamp(Webhook::CreatedAt).comment("创建时间"))
+          .to_owned(),
+      )
+      .await?;
+    manager
+      .create_table(
+        Table::create()
+          .table(WebhookDelivery::Table)
+          .if_not_exists()
+          .col(pk_auto_unsigned(manager, WebhookDelivery::Id).comment("主键 ID"))
+          .col(unsigned(WebhookDelivery::WebhookId).comment("关联的 Webhook ID"))
+          .col(string(WebhookDelivery::Event).comment("事件名"))
+          .col(text(WebhookDelivery::Payload).comment("投递内容"))
+          .col(string(WebhookDelivery::Status).comment("投递状态: pending, succeeded, failed"))
+          .col(
+            unsigned(WebhookDelivery::Attempts)
+              .default(0)
+              .comment("已投递次数"),
+          )
+          .col(unsigned_null(WebhookDelivery::ResponseStatus).comment("最近一次的响应状态码"))
+          .col(string_null(WebhookDelivery::Error).comment("最近一次的错误信息"))
+          .col(timestamp_null(WebhookDelivery::NextAttemptAt).comment("下一次重试时间"))
+          .col(timestamp(WebhookDelivery::CreatedAt).comment("创建时间"))
+          .col(timestamp_null(WebhookDelivery::UpdatedAt).comment("更新时间"))
+          .to_owned(),
+      )
+      .await?;
+    manager
+      .create_index(
+        Index::create()
+          .name("idx_webhook_delivery_webhook_id")
+          .table(WebhookDelivery::Table)
+          .col(WebhookDelivery::WebhookId)
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .drop_table(Table::drop().table(WebhookDelivery::Table).to_owned())
+      .await?;
+    manager
+      .drop_table(Table::drop().table(Webhook::Table).to_owned())
+      .await
+  }
+}
@@ -1,10 +1,22 @@
 pub use sea_orm_migration::prelude::*;
+use sea_orm_migration::{schema::pk_auto, sea_orm::DbBackend};
 
+mod alter_table_agent_api_url;
+mod alter_table_agent_public_ips;
+mod alter_table_deployment_build_logs;
+mod alter_table_portable_types;
+mod alter_table_site_expire_warned_at;
+mod alter_table_site_expired_at;
 mod create_table_agent;
+mod create_table_audit_log;
 mod create_table_deployment;
+mod create_table_deployment_event;
 mod create_table_nginx;
 mod create_table_site;
+mod create_table_task;
 mod create_table_user;
+mod create_table_webhook;
+mod recreate_table_nginx;
 
 pub struct Migrator;
 
@@ -17,6 +29,27 @@ impl MigratorTrait for Migrator {
       Box::new(create_table_site::Migration),
       Box::new(create_table_nginx::Migration),
       Box::new(create_table_deployment::Migration),
+      Box::new(alter_table_site_expired_at::Migration),
+      Box::new(create_table_deployment_event::Migration),
+      Box::new(create_table_webhook::Migration),
+      Box::new(create_table_task::Migration),
+      Box::new(recreate_table_nginx::Migration),
+      Box::new(create_table_audit_log::Migration),
+      Box::new(alter_table_agent_api_url::Migration),
+      Box::new(alter_table_agent_public_ips::Migration),
+      Box::new(alter_table_deployment_build_logs::Migration),
+      Box::new(alter_table_portable_types::Migration),
+      Box::new(alter_table_site_expire_warned_at::Migration),
     ]
   }
 }
+
+/// 早期迁移使用的无符号自增主键
+///
+/// Postgres 不支持无符号的自增列，改用普通的自增主键；SQLite 与 MySQL 生成的表结构与原迁移保持一致
+fn pk_auto_unsigned<T: IntoIden>(manager: &SchemaManager, name: T) -> ColumnDef {
+  match manager.get_database_backend() {
+    DbBackend::Postgres => pk_auto(name),
+    _ => pk_auto(name).unsigned().take(),
+  }
+}
@@ -0,0 +1,61 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+use crate::pk_auto_unsigned;
+
+/// 旧的 `nginx` 表从未写入过数据，且 `agent_id` 唯一导致一个 Agent 只能记录一个站点，
+/// 直接删除后按新的结构重建
+#[derive(DeriveIden)]
+enum Nginx {
+  Table,
+  Id,            // 主键 ID
+  SiteId,        // 关联的站点 ID
+  AgentId,       // 提供服务的 Agent ID
+  DeploymentId,  // 发布的部署 ID
+  ConfigHash,    // Nginx 配置的 SHA-256
+  ConfigContent, // Nginx 配置内容
+  CreatedAt,     // 创建时间
+  UpdatedAt,     // 更新时间
+}
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+  async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .drop_table(Table::drop().table(Nginx::Table).if_exists().to_owned())
+      .await?;
+    manager
+      .create_table(
+        Table::create()
+          .table(Nginx::Table)
+          .if_not_exists()
+          .col(pk_auto_unsigned(manager, Nginx::Id).comment("主键 ID"))
+          .col(string_uniq(Nginx::SiteId).comment("站点 ID"))
+          .col(unsigned(Nginx::AgentId).comment("提供服务的 Agent ID"))
+          .col(unsigned(Nginx::DeploymentId).comment("发布的部署 ID"))
+          .col(string_null(Nginx::ConfigHash).comment("Nginx 配置的 SHA-256"))
+          .col(text_null(Nginx::ConfigContent).comment("Nginx 配置内容"))
+          .col(timestamp(Nginx::CreatedAt).comment("创建时间"))
+          .col(timestamp_null(Nginx::UpdatedAt).comment("更新时间"))
+          .to_owned(),
+      )
+      .await?;
+    manager
+      .create_index(
+        Index::create()
+          .name("idx_nginx_agent_id")
+          .table(Nginx::Table)
+          .col(Nginx::AgentId)
+          .to_owned(),
+      )
+      .await
+  }
+
+  async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+    manager
+      .drop_table(Table::drop().table(Nginx::Table).to_owned())
+      .await
+  }
+}
@@ -0,0 +1,194 @@
+//! Agent 和 Master 接口的抽象，业务代码依赖这些 trait，测试时可以换成 mock 实现
+
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+use common::{
+  Paginated,
+  agent::{
+    ArtifactEncoding, HeartbeatResponse, InitUploadResponse, InventoryResponse, ManifestEntry,
+    TaskPublishResponse, UploadManifestResponse, UploadSessionResponse,
+  },
+  master::{CreateDeploymentResponse, DeploymentLogsResponse, TaskResponse},
+};
+use entity::site;
+
+use crate::{
+  AgentEndpoint, AgentUrl, CreateSiteData, DeploymentStatus, DeploymentStream, GetCasualTokenData,
+  LoginData, error::Error,
+};
+
+/// Agent 提供的接口，发布类接口由 Master 签名调用，上传类接口使用 upload token
+#[cfg_attr(feature = "mock", mockall::automock)]
+#[async_trait]
+pub trait AgentApi: Send + Sync {
+  async fn get_agent_heartbeat(&self, agent: &AgentEndpoint) -> Result<HeartbeatResponse, Error>;
+
+  async fn init_upload_session(
+    &self,
+    agent: &AgentEndpoint,
+    site_id: String,
+    deployment_id: i32,
+    sha256: Option<String>,
+    upload_id: Option<String>,
+  ) -> Result<InitUploadResponse, Error>;
+
+  #[allow(clippy::too_many_arguments)]
+  async fn task_publish(
+    &self,
+    site_id: String,
+    deployment_id: i32,
+    agent: &AgentEndpoint,
+    bandwidth: String,
+    bind_domain: Option<String>,
+    preview_domain: String,
+    idempotency_key: Option<String>,
+  ) -> Result<TaskPublishResponse, Error>;
+
+  async fn task_revoke(&self, site_id: String, agent: &AgentEndpoint) -> Result<bool, Error>;
+
+  /// 查询 Agent 上实际提供服务的站点
+  async fn get_inventory(&self, agent: &AgentEndpoint) -> Result<InventoryResponse, Error>;
+
+  /// 将站点目录从 Agent 导出为 tar.gz 并保存到 `dest`
+  async fn task_export(
+    &self,
+    site_id: String,
+    agent: &AgentEndpoint,
+    dest: &Path,
+  ) -> Result<(), Error>;
+
+  async fn upload_file(
+    &self,
+    agent: &AgentUrl,
+    upload_token: String,
+    path: PathBuf,
+  ) -> Result<(), Error>;
+
+  /// 开始或恢复分片上传，返回 Agent 已收到的字节数
+  async fn upload_session(
+    &self,
+    agent: &AgentUrl,
+    upload_token: &str,
+  ) -> Result<UploadSessionResponse, Error>;
+
+  async fn upload_chunk(
+    &self,
+    agent: &AgentUrl,
+    upload_token: &str,
+    offset: u64,
+    chunk: Vec<u8>,
+  ) -> Result<UploadSessionResponse, Error>;
+
+  async fn complete_upload(
+    &self,
+    agent: &AgentUrl,
+    upload_token: &str,
+    sha256: String,
+    encoding: ArtifactEncoding,
+  ) -> Result<(), Error>;
+
+  /// 提交文件清单，返回 Agent 缺少的文件摘要
+  async fn upload_manifest(
+    &self,
+    agent: &AgentUrl,
+    upload_token: &str,
+    files: Vec<ManifestEntry>,
+  ) -> Result<UploadManifestResponse, Error>;
+
+  async fn upload_blob(
+    &self,
+    agent: &AgentUrl,
+    upload_token: &str,
+    sha256: &str,
+    content: Vec<u8>,
+  ) -> Result<(), Error>;
+
+  async fn complete_manifest(&self, agent: &AgentUrl, upload_token: &str) -> Result<(), Error>;
+}
+
+impl std::fmt::Debug for dyn AgentApi {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str("AgentApi")
+  }
+}
+
+/// Master 提供给 CLI 的接口
+#[cfg_attr(feature = "mock", mockall::automock)]
+#[async_trait]
+pub trait MasterApi: Send + Sync {
+  async fn signup(&self, nickname: String, email: String, password: String) -> Result<bool, Error>;
+
+  async fn login(&self, email: String, password: String) -> Result<LoginData, Error>;
+
+  async fn get_casual_token(&self) -> Result<GetCasualTokenData, Error>;
+
+  async fn create_site(&self, token: &str) -> Result<CreateSiteData, Error>;
+
+  /// 获取一页站点，`page` 从 1 开始
+  async fn get_sites(
+    &self,
+    token: &str,
+    page: u64,
+    page_size: u64,
+  ) -> Result<Paginated<site::Model>, Error>;
+
+  async fn create_deployment(
+    &self,
+    site_id: String,
+    sha256: Option<String>,
+    token: &str,
+  ) -> Result<CreateDeploymentResponse, Error>;
+
+  /// 创建待上传的部署，返回部署 id，构建输出可以在构建时上报到该部署
+  async fn prepare_deployment(&self, site_id: String, token: &str) -> Result<i32, Error>;
+
+  /// 为 [`MasterApi::prepare_deployment`] 创建的部署开启上传
+  async fn init_deployment_upload(
+    &self,
+    deployment_id: i32,
+    sha256: Option<String>,
+    token: &str,
+  ) -> Result<CreateDeploymentResponse, Error>;
+
+  async fn update_deployment_status(
+    &self,
+    agent_token: String,
+    deployment_id: i32,
+    status: DeploymentStatus,
+  ) -> Result<(), Error>;
+
+  /// 提交发布任务，返回任务信息，通过 [`MasterApi::get_task`] 查询结果。
+  /// 相同的 `idempotency_key` 只会创建一个任务
+  async fn publish_site(
+    &self,
+    token: &str,
+    site_id: String,
+    deployment_id: i32,
+    bind_domain: Option<String>,
+    idempotency_key: Option<String>,
+  ) -> Result<TaskResponse, Error>;
+
+  async fn get_task(&self, token: &str, task_id: i32) -> Result<TaskResponse, Error>;
+
+  /// 上报部署的构建日志
+  async fn append_deployment_logs(
+    &self,
+    token: &str,
+    deployment_id: i32,
+    content: String,
+  ) -> Result<(), Error>;
+
+  /// 订阅部署进度，返回时服务端已开始推送
+  async fn stream_deployment(
+    &self,
+    token: &str,
+    deployment_id: i32,
+  ) -> Result<DeploymentStream, Error>;
+
+  async fn get_deployment_logs(
+    &self,
+    token: &str,
+    deployment_id: i32,
+  ) -> Result<DeploymentLogsResponse, Error>;
+}
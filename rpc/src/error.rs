@@ -1,3 +1,4 @@
+use common::error::ErrorCode;
 use thiserror::Error;
 use tracing::error;
 
@@ -8,7 +9,8 @@ pub enum Error {
     #[from]
     source: std::io::Error,
   },
-  #[error("Api error")]
+  /// HTTP 状态码、错误码和错误信息
+  #[error("Api error: {2}")]
   Api(u16, i32, String),
   #[error("Cloudflare Framework error")]
   CloudflareFramework {
@@ -26,10 +28,15 @@ pub enum Error {
   ConnectMaster,
   #[error("Build request error")]
   BuildRequest,
+  #[error("Invalid TLS config: {0}")]
+  InvalidTls(String),
   #[error("Invalid content type")]
   InvalidContentType,
   #[error("Decode error")]
   Decode,
+  /// Webhook 地址不是 http(s) 或指向内网
+  #[error("Forbidden webhook target: {0}")]
+  ForbiddenTarget(String),
   #[error("Rpc call error: {source}")]
   RpcCall {
     #[source]
@@ -37,6 +44,26 @@ pub enum Error {
   },
 }
 
+impl Error {
+  /// 服务端返回的错误码，不是接口错误或错误码未知时为 `None`
+  pub fn error_code(&self) -> Option<ErrorCode> {
+    match self {
+      Error::Api(_, code, _) => ErrorCode::from_code(*code),
+      _ => None,
+    }
+  }
+
+  /// 连接失败、超时和服务端错误可以重试，请求本身有误时重试也不会成功
+  pub fn is_retryable(&self) -> bool {
+    match self {
+      Error::Api(status, ..) => *status >= 500 || *status == 429,
+      Error::Internal { .. } | Error::ConnectAgent(_) | Error::ConnectMaster => true,
+      Error::RpcCall { source } => source.is_timeout() || source.is_request(),
+      _ => false,
+    }
+  }
+}
+
 impl From<reqwest::Error> for Error {
   fn from(err: reqwest::Error) -> Self {
     tracing::error!("{:#?}", err);
@@ -1,18 +1,38 @@
+pub mod api;
 pub mod error;
+pub mod retry;
+pub mod tls;
 
 use std::fmt::Debug;
-use std::{path::PathBuf, time::Duration};
+use std::{
+  collections::{HashMap, HashSet, VecDeque},
+  io::SeekFrom,
+  net::{IpAddr, SocketAddr},
+  path::{Path, PathBuf},
+  sync::{Arc, Mutex},
+  time::Duration,
+};
 
 use common::{
+  Paginated,
   agent::{
-    HeartbeatResponse, InitUploadRequest, InitUploadResponse, TaskPublishRequest, TaskRevokeRequest,
+    AgentTls, ArtifactEncoding, CompleteManifestRequest, CompleteUploadRequest, HeartbeatResponse,
+    InitUploadRequest, InitUploadResponse, InventoryResponse, ManifestEntry, TaskExportRequest,
+    TaskPublishRequest, TaskPublishResponse, TaskRevokeRequest, UploadManifestRequest,
+    UploadManifestResponse, UploadSessionRequest, UploadSessionResponse,
   },
+  digest::sha256_file,
   master::{
-    AssignTaskRequest, CreateDeploymentRequest, CreateDeploymentResponse, GetSitesResponse,
-    UserRegisterRequest,
+    AppendDeploymentLogsRequest, AssignTaskRequest, CreateDeploymentRequest,
+    CreateDeploymentResponse, DeploymentLogsResponse, DeploymentStreamEvent,
+    IDEMPOTENCY_KEY_HEADER, InitDeploymentUploadRequest, PrepareDeploymentRequest,
+    PrepareDeploymentResponse, TaskResponse, UserRegisterRequest,
   },
+  signature,
 };
+use entity::{agent, site};
 
+use async_trait::async_trait;
 use reqwest::Method;
 use reqwest::{
   header::{ACCEPT, CONTENT_TYPE, HeaderMap},
@@ -20,10 +40,24 @@ use reqwest::{
 };
 use serde::{Deserialize, Serialize, de::DeserializeOwned};
 use serde_json::{Value, json};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+
+pub use api::{AgentApi, MasterApi};
+#[cfg(feature = "mock")]
+pub use api::{MockAgentApi, MockMasterApi};
+pub use retry::{RetryPolicy, Timeouts};
 
 use crate::error::Error;
 
-fn build_base_client_builder() -> Result<reqwest::Client, Error> {
+/// 单个分片或文件上传失败后的重试策略，会重试所有错误
+const CHUNK_RETRY: RetryPolicy = RetryPolicy {
+  max_retries: 5,
+  base_delay: Duration::from_secs(2),
+  max_delay: Duration::from_secs(16),
+};
+
+/// 各请求通过 `RequestBuilder::timeout` 单独设置超时
+fn base_client_builder(connect_timeout: Duration) -> reqwest::ClientBuilder {
   reqwest::Client::builder()
     .default_headers({
       let mut headers = HeaderMap::new();
@@ -31,8 +65,12 @@ fn build_base_client_builder() -> Result<reqwest::Client, Error> {
       headers
     })
     .tcp_keepalive(Some(Duration::from_secs(60)))
-    .timeout(Duration::from_secs(3))
+    .connect_timeout(connect_timeout)
     .no_proxy()
+}
+
+fn build_base_client_builder(connect_timeout: Duration) -> Result<reqwest::Client, Error> {
+  base_client_builder(connect_timeout)
     .build()
     .map_err(|_| Error::BuildRequest)
 }
@@ -44,6 +82,30 @@ struct RpcResponse<T> {
   pub data: T,
 }
 
+/// 把失败的响应转换为 [`Error::Api`]
+async fn api_error(resp: reqwest::Response) -> Error {
+  let status_code = resp.status().as_u16();
+  match resp.json::<RpcResponse<Value>>().await {
+    Ok(data) => Error::Api(status_code, data.code, data.msg),
+    Err(err) => err.into(),
+  }
+}
+
+async fn parse_response<B: DeserializeOwned>(resp: reqwest::Response) -> Result<B, Error> {
+  if !resp.status().is_success() {
+    return Err(api_error(resp).await);
+  }
+  let is_json = resp
+    .headers()
+    .get(CONTENT_TYPE)
+    .and_then(|value| value.to_str().ok())
+    .is_some_and(|value| value.starts_with("application/json"));
+  if !is_json {
+    return Err(Error::InvalidContentType);
+  }
+  Ok(resp.json::<RpcResponse<B>>().await?.data)
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct InitUploadData {
   pub upload_token: String,
@@ -54,76 +116,297 @@ pub struct TaskPublishData {
   pub preview_url: String,
 }
 
+/// Agent API 的地址和校验证书的方式
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AgentUrl {
+  /// 如 `https://10.0.0.1:5001`，不以 `/` 结尾
+  pub base_url: String,
+  pub tls: AgentTls,
+}
+
+impl AgentUrl {
+  pub fn new(base_url: impl Into<String>, tls: AgentTls) -> Self {
+    let base_url: String = base_url.into();
+    Self {
+      base_url: base_url.trim_end_matches('/').to_string(),
+      tls,
+    }
+  }
+
+  fn join(&self, path: &str) -> String {
+    format!("{}{}", self.base_url, path)
+  }
+}
+
+impl From<&agent::Model> for AgentUrl {
+  fn from(agent: &agent::Model) -> Self {
+    Self::new(
+      agent.api_url.clone(),
+      AgentTls {
+        ca_cert: agent.ca_cert.clone(),
+        cert_fingerprint: agent.cert_fingerprint.clone(),
+      },
+    )
+  }
+}
+
+/// Agent 的访问地址和用于签名请求的 token
+#[derive(Debug, Clone)]
+pub struct AgentEndpoint {
+  pub url: AgentUrl,
+  pub token: String,
+}
+
+impl From<&agent::Model> for AgentEndpoint {
+  fn from(agent: &agent::Model) -> Self {
+    Self {
+      url: AgentUrl::from(agent),
+      token: agent.token.clone(),
+    }
+  }
+}
+
 #[derive(Debug, Clone)]
 pub struct AgentRpc {
   api_client: reqwest::Client,
+  /// 按证书校验方式缓存的客户端，复用连接
+  tls_clients: Arc<Mutex<HashMap<AgentTls, reqwest::Client>>>,
+  retry: RetryPolicy,
+  timeouts: Timeouts,
 }
 
 impl AgentRpc {
   pub fn new() -> Result<Self, Error> {
+    Self::with_options(RetryPolicy::default(), Timeouts::default())
+  }
+
+  pub fn with_options(retry: RetryPolicy, timeouts: Timeouts) -> Result<Self, Error> {
     Ok(Self {
-      api_client: build_base_client_builder()?,
+      api_client: build_base_client_builder(timeouts.connect)?,
+      tls_clients: Arc::default(),
+      retry,
+      timeouts,
     })
   }
 
-  pub async fn fetch<T: Serialize, B: DeserializeOwned>(
+  /// 返回按 `tls` 校验 Agent 证书的客户端
+  fn client(&self, tls: &AgentTls) -> Result<reqwest::Client, Error> {
+    if *tls == AgentTls::default() {
+      return Ok(self.api_client.clone());
+    }
+    let mut clients = self.tls_clients.lock().unwrap();
+    if let Some(client) = clients.get(tls) {
+      return Ok(client.clone());
+    }
+    let client = tls::build_agent_client(tls, self.timeouts.connect)?;
+    clients.insert(tls.clone(), client.clone());
+    Ok(client)
+  }
+
+  /// 构造使用 Agent 的 token 签名的请求，`path` 中的查询字符串一并签名
+  fn signed_request<T: Serialize>(
     &self,
-    agent_ip: &str,
+    agent: &AgentEndpoint,
     method: reqwest::Method,
     path: &str,
-    body: Option<T>,
-  ) -> Result<B, Error> {
-    let url = format!("http://{}:5001/api{}", agent_ip, path);
-    let client = if method == "post" {
-      self.api_client.post(url).json(&body)
+    body: Option<&T>,
+  ) -> Result<reqwest::RequestBuilder, Error> {
+    let path = format!("/api{}", path);
+    let url = agent.url.join(&path);
+    let body = if method == Method::POST {
+      serde_json::to_vec(&body).map_err(|_| Error::BuildRequest)?
     } else {
-      self.api_client.get(url)
+      Vec::new()
     };
-    let resp = client.send().await?;
-    let content_type = resp
-      .headers()
-      .get(CONTENT_TYPE)
-      .ok_or(Error::InvalidContentType)?;
-    if content_type != "application/json" {
-      return Err(Error::InvalidContentType);
+    let timestamp = signature::timestamp();
+    let nonce = format!("{:032x}", rand::random::<u128>());
+    let sign = signature::sign(
+      &agent.token,
+      method.as_str(),
+      &path,
+      timestamp,
+      &nonce,
+      &body,
+    );
+    let mut client = self
+      .client(&agent.url.tls)?
+      .request(method.clone(), url)
+      .header(signature::TIMESTAMP_HEADER, timestamp)
+      .header(signature::NONCE_HEADER, nonce)
+      .header(signature::SIGNATURE_HEADER, sign);
+    if method == Method::POST {
+      client = client.header(CONTENT_TYPE, "application/json").body(body);
     }
+    Ok(client)
+  }
 
-    if resp.status().is_success() {
-      let data = resp.json::<RpcResponse<B>>().await?;
-      Ok(data.data)
+  /// 向 Agent 发起签名请求，每次重试都会重新签名
+  async fn fetch_with<T: Serialize + Sync, B: DeserializeOwned>(
+    &self,
+    agent: &AgentEndpoint,
+    method: reqwest::Method,
+    path: &str,
+    body: Option<&T>,
+    timeout: Duration,
+    retry: RetryPolicy,
+  ) -> Result<B, Error> {
+    let method = &method;
+    retry
+      .run(|| async move {
+        let resp = self
+          .signed_request(agent, method.clone(), path, body)?
+          .timeout(timeout)
+          .send()
+          .await?;
+        parse_response(resp).await
+      })
+      .await
+  }
+
+  /// 向 Agent 发起请求，请求会使用 Agent 的 token 签名。只有 GET 请求按重试策略重试，
+  /// POST 请求会修改 Agent 上的状态，失败后由调用方（如任务队列）决定是否重新提交
+  pub async fn fetch<T: Serialize + Sync, B: DeserializeOwned>(
+    &self,
+    agent: &AgentEndpoint,
+    method: reqwest::Method,
+    path: &str,
+    body: Option<T>,
+  ) -> Result<B, Error> {
+    let retry = if method == Method::GET {
+      self.retry
     } else {
-      let status_code = resp.status().as_u16();
-      let data = resp.json::<RpcResponse<Value>>().await?;
-      Err(Error::Api(status_code, data.code, data.msg))
-    }
+      RetryPolicy::NONE
+    };
+    self
+      .fetch_with(
+        agent,
+        method,
+        path,
+        body.as_ref(),
+        self.timeouts.request,
+        retry,
+      )
+      .await
   }
+}
 
-  pub async fn get_agent_heartbeat(&self, agent_ip: &str) -> Result<HeartbeatResponse, Error> {
-    let body = self
-      .fetch::<_, HeartbeatResponse>(agent_ip, Method::GET, "/heartbeat", Some(()))
-      .await?;
-    Ok(body)
+#[async_trait]
+impl AgentApi for AgentRpc {
+  /// 心跳失败即视为离线，不重试
+  async fn get_agent_heartbeat(&self, agent: &AgentEndpoint) -> Result<HeartbeatResponse, Error> {
+    self
+      .fetch_with::<(), _>(
+        agent,
+        Method::GET,
+        "/heartbeat",
+        None,
+        self.timeouts.heartbeat,
+        RetryPolicy::NONE,
+      )
+      .await
   }
 
-  pub async fn init_upload_session(
+  async fn init_upload_session(
     &self,
-    agent_ip: &str,
+    agent: &AgentEndpoint,
     site_id: String,
+    deployment_id: i32,
+    sha256: Option<String>,
+    upload_id: Option<String>,
   ) -> Result<InitUploadResponse, Error> {
-    let body = self
-      .fetch::<_, InitUploadResponse>(
-        agent_ip,
+    self
+      .fetch(
+        agent,
         Method::POST,
         "/upload/init",
-        Some(InitUploadRequest { site_id }),
+        Some(InitUploadRequest {
+          site_id,
+          deployment_id,
+          sha256,
+          upload_id,
+        }),
+      )
+      .await
+  }
+
+  /// 不在这里重试，失败后由任务队列按退避时间重新执行，Agent 按 `idempotency_key` 去重
+  async fn task_publish(
+    &self,
+    site_id: String,
+    deployment_id: i32,
+    agent: &AgentEndpoint,
+    bandwidth: String,
+    bind_domain: Option<String>,
+    preview_domain: String,
+    idempotency_key: Option<String>,
+  ) -> Result<TaskPublishResponse, Error> {
+    self
+      .fetch_with(
+        agent,
+        Method::POST,
+        "/task/publish",
+        Some(&TaskPublishRequest {
+          site_id,
+          deployment_id,
+          bandwidth,
+          bind_domain,
+          preview_domain,
+          idempotency_key,
+        }),
+        self.timeouts.publish,
+        RetryPolicy::NONE,
+      )
+      .await
+  }
+
+  async fn task_revoke(&self, site_id: String, agent: &AgentEndpoint) -> Result<bool, Error> {
+    self
+      .fetch::<_, Value>(
+        agent,
+        Method::POST,
+        "/task/revoke",
+        Some(TaskRevokeRequest { site_id }),
       )
       .await?;
-    Ok(body)
+    Ok(true)
+  }
+
+  async fn get_inventory(&self, agent: &AgentEndpoint) -> Result<InventoryResponse, Error> {
+    self
+      .fetch::<(), _>(agent, Method::GET, "/inventory", None)
+      .await
   }
 
-  pub async fn upload_file(
+  async fn task_export(
     &self,
-    agent_ip: &str,
+    site_id: String,
+    agent: &AgentEndpoint,
+    dest: &Path,
+  ) -> Result<(), Error> {
+    let mut resp = self
+      .signed_request(
+        agent,
+        Method::POST,
+        "/task/export",
+        Some(&TaskExportRequest { site_id }),
+      )?
+      .timeout(self.timeouts.export)
+      .send()
+      .await?;
+    if !resp.status().is_success() {
+      return Err(api_error(resp).await);
+    }
+    let mut file = tokio::fs::File::create(dest).await?;
+    while let Some(chunk) = resp.chunk().await? {
+      file.write_all(&chunk).await?;
+    }
+    file.flush().await?;
+    Ok(())
+  }
+
+  async fn upload_file(
+    &self,
+    agent: &AgentUrl,
     upload_token: String,
     path: PathBuf,
   ) -> Result<(), Error> {
@@ -141,65 +424,232 @@ impl AgentRpc {
     form = form.part("dist", part);
     form = form.part("upload_token", Part::text(upload_token));
     let resp = self
-      .api_client
-      .post(format!("http://{}:5001/api/upload/file", agent_ip))
+      .client(&agent.tls)?
+      .post(agent.join("/api/upload/file"))
       .multipart(form)
-      .timeout(Duration::from_secs(60))
+      .timeout(self.timeouts.upload)
       .send()
       .await?;
-    println!("{:#?}", resp);
-    if resp.status().is_success() {
-      resp.json::<RpcResponse<()>>().await?;
-      Ok(())
-    } else {
-      let status_code = resp.status().as_u16();
-      let data = resp.json::<RpcResponse<Value>>().await?;
-      Err(Error::Api(status_code, data.code, data.msg))
-    }
+    parse_response::<Value>(resp).await?;
+    Ok(())
   }
 
-  pub async fn task_publish(
+  async fn upload_session(
     &self,
-    site_id: String,
-    deployment_id: u32,
-    ip_address: String,
-    bandwidth: String,
-    bind_domain: Option<String>,
-    preview_domain: String,
-  ) -> Result<bool, Error> {
-    self
-      .fetch::<_, Value>(
-        &ip_address,
-        Method::POST,
-        "/task/publish",
-        Some(TaskPublishRequest {
-          site_id,
-          deployment_id,
-          bandwidth,
-          bind_domain,
-          preview_domain,
-        }),
-      )
+    agent: &AgentUrl,
+    upload_token: &str,
+  ) -> Result<UploadSessionResponse, Error> {
+    let resp = self
+      .client(&agent.tls)?
+      .post(agent.join("/api/upload/session"))
+      .json(&UploadSessionRequest {
+        upload_token: upload_token.to_string(),
+      })
+      .timeout(self.timeouts.request)
+      .send()
       .await?;
-    Ok(true)
+    parse_response(resp).await
   }
 
-  pub async fn task_revoke(&self, site_id: String, ip_address: &str) -> Result<bool, Error> {
+  async fn upload_chunk(
+    &self,
+    agent: &AgentUrl,
+    upload_token: &str,
+    offset: u64,
+    chunk: Vec<u8>,
+  ) -> Result<UploadSessionResponse, Error> {
     let resp = self
-      .api_client
-      .post(format!("http://{}:5001/api/task/revoke", ip_address))
-      .json(&TaskRevokeRequest { site_id })
+      .client(&agent.tls)?
+      .put(agent.join("/api/upload/chunk"))
+      .bearer_auth(upload_token)
+      .query(&[("offset", offset)])
+      .header(CONTENT_TYPE, "application/octet-stream")
+      .body(chunk)
+      .timeout(self.timeouts.upload)
       .send()
       .await?;
-    if resp.status().is_success() {
-      resp.json::<RpcResponse<()>>().await?;
-      Ok(true)
-    } else {
-      let status_code = resp.status().as_u16();
-      let data = resp.json::<RpcResponse<Value>>().await?;
-      Err(Error::Api(status_code, data.code, data.msg))
+    parse_response(resp).await
+  }
+
+  async fn complete_upload(
+    &self,
+    agent: &AgentUrl,
+    upload_token: &str,
+    sha256: String,
+    encoding: ArtifactEncoding,
+  ) -> Result<(), Error> {
+    let resp = self
+      .client(&agent.tls)?
+      .post(agent.join("/api/upload/complete"))
+      .json(&CompleteUploadRequest {
+        upload_token: upload_token.to_string(),
+        sha256,
+        encoding,
+      })
+      .timeout(self.timeouts.upload)
+      .send()
+      .await?;
+    parse_response::<Value>(resp).await?;
+    Ok(())
+  }
+
+  async fn upload_manifest(
+    &self,
+    agent: &AgentUrl,
+    upload_token: &str,
+    files: Vec<ManifestEntry>,
+  ) -> Result<UploadManifestResponse, Error> {
+    let resp = self
+      .client(&agent.tls)?
+      .post(agent.join("/api/upload/manifest"))
+      .json(&UploadManifestRequest {
+        upload_token: upload_token.to_string(),
+        files,
+      })
+      .timeout(self.timeouts.request)
+      .send()
+      .await?;
+    parse_response(resp).await
+  }
+
+  async fn upload_blob(
+    &self,
+    agent: &AgentUrl,
+    upload_token: &str,
+    sha256: &str,
+    content: Vec<u8>,
+  ) -> Result<(), Error> {
+    let resp = self
+      .client(&agent.tls)?
+      .put(agent.join(&format!("/api/upload/blob/{}", sha256)))
+      .bearer_auth(upload_token)
+      .header(CONTENT_TYPE, "application/octet-stream")
+      .body(content)
+      .timeout(self.timeouts.upload)
+      .send()
+      .await?;
+    parse_response::<Value>(resp).await?;
+    Ok(())
+  }
+
+  async fn complete_manifest(&self, agent: &AgentUrl, upload_token: &str) -> Result<(), Error> {
+    let resp = self
+      .client(&agent.tls)?
+      .post(agent.join("/api/upload/manifest/complete"))
+      .json(&CompleteManifestRequest {
+        upload_token: upload_token.to_string(),
+      })
+      .timeout(self.timeouts.upload)
+      .send()
+      .await?;
+    parse_response::<Value>(resp).await?;
+    Ok(())
+  }
+}
+
+/// 分片上传文件，失败的分片会从 Agent 记录的位置重试，
+/// 每个分片完成后通过 `progress(已上传字节数, 总字节数)` 报告进度
+pub async fn upload_file_chunked<A: AgentApi + ?Sized>(
+  api: &A,
+  agent: &AgentUrl,
+  upload_token: String,
+  path: PathBuf,
+  encoding: ArtifactEncoding,
+  progress: impl Fn(u64, u64),
+) -> Result<(), Error> {
+  let sha256 = sha256_file(&path)?;
+  let mut file = tokio::fs::File::open(&path).await?;
+  let total = file.metadata().await?.len();
+  let session = api.upload_session(agent, &upload_token).await?;
+  let chunk_size = session.chunk_size.max(1);
+  let mut offset = session.offset;
+  let mut retries = 0;
+  progress(offset, total);
+
+  while offset < total {
+    let mut chunk = vec![0; chunk_size.min(total - offset) as usize];
+    file.seek(SeekFrom::Start(offset)).await?;
+    file.read_exact(&mut chunk).await?;
+    match api.upload_chunk(agent, &upload_token, offset, chunk).await {
+      Ok(session) => {
+        offset = session.offset;
+        retries = 0;
+        progress(offset, total);
+      }
+      Err(err) => {
+        retries += 1;
+        if retries > CHUNK_RETRY.max_retries {
+          return Err(err);
+        }
+        tracing::warn!(
+          "Upload chunk at {} failed, retry {}: {}",
+          offset,
+          retries,
+          err
+        );
+        tokio::time::sleep(CHUNK_RETRY.delay(retries)).await;
+        // 以 Agent 实际收到的字节数为准继续上传
+        if let Ok(session) = api.upload_session(agent, &upload_token).await {
+          offset = session.offset;
+        }
+      }
+    }
+  }
+  api
+    .complete_upload(agent, &upload_token, sha256, encoding)
+    .await
+}
+
+/// 增量上传站点目录：只上传 Agent 缺少的文件，失败的文件会重试，
+/// 通过 `progress(已上传字节数, 需上传字节数)` 报告进度
+pub async fn upload_directory<A: AgentApi + ?Sized>(
+  api: &A,
+  agent: &AgentUrl,
+  upload_token: String,
+  root: &Path,
+  files: Vec<ManifestEntry>,
+  progress: impl Fn(u64, u64),
+) -> Result<(), Error> {
+  let missing: HashSet<String> = api
+    .upload_manifest(agent, &upload_token, files.clone())
+    .await?
+    .missing
+    .into_iter()
+    .collect();
+  let mut pending = Vec::new();
+  let mut seen = HashSet::new();
+  for entry in files {
+    if missing.contains(&entry.sha256) && seen.insert(entry.sha256.clone()) {
+      pending.push(entry);
+    }
+  }
+  let total = pending.iter().map(|f| f.size).sum();
+  let mut uploaded = 0;
+  progress(uploaded, total);
+
+  for entry in pending {
+    let content = tokio::fs::read(root.join(&entry.path)).await?;
+    let mut retries = 0;
+    loop {
+      match api
+        .upload_blob(agent, &upload_token, &entry.sha256, content.clone())
+        .await
+      {
+        Ok(()) => break,
+        Err(err) => {
+          retries += 1;
+          if retries > CHUNK_RETRY.max_retries {
+            return Err(err);
+          }
+          tracing::warn!("Upload {} failed, retry {}: {}", entry.path, retries, err);
+          tokio::time::sleep(CHUNK_RETRY.delay(retries)).await;
+        }
+      }
     }
+    uploaded += entry.size;
+    progress(uploaded, total);
   }
+  api.complete_manifest(agent, &upload_token).await
 }
 
 #[derive(Debug, Serialize)]
@@ -232,10 +682,10 @@ pub struct CreateSiteData {
 #[derive(Debug, Deserialize, Clone)]
 pub struct CreateDeploymentData {
   pub site_id: String,
-  pub deployment_id: u32,
+  pub deployment_id: i32,
   pub deploy_url: String,
   pub deploy_token: String,
-  pub agent_id: u32,
+  pub agent_id: i32,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -243,235 +693,541 @@ pub struct DeploySiteData {
   pub domian: String,
 }
 
+/// 发布任务成功后的结果
 #[derive(Debug, Deserialize, Clone)]
 pub struct AssignTaskData {
   pub preview_url: String,
+  pub bind_url: Option<String>,
+  pub expired_at: Option<String>,
+}
+
+/// 部署进度的 SSE 连接
+#[derive(Debug)]
+pub struct DeploymentStream {
+  /// 为空时只返回 `pending` 中的事件
+  resp: Option<reqwest::Response>,
+  buf: Vec<u8>,
+  pending: VecDeque<DeploymentStreamEvent>,
+}
+
+impl DeploymentStream {
+  /// 依次返回给定事件的进度流，用于测试
+  pub fn from_events(events: impl IntoIterator<Item = DeploymentStreamEvent>) -> Self {
+    Self {
+      resp: None,
+      buf: Vec::new(),
+      pending: events.into_iter().collect(),
+    }
+  }
+
+  /// 读取下一个事件，服务端结束推送时返回 `None`
+  pub async fn next_event(&mut self) -> Result<Option<DeploymentStreamEvent>, Error> {
+    loop {
+      if let Some(event) = self.pending.pop_front() {
+        return Ok(Some(event));
+      }
+      let Some(resp) = self.resp.as_mut() else {
+        return Ok(None);
+      };
+      match resp.chunk().await? {
+        Some(chunk) => {
+          self.buf.extend_from_slice(&chunk);
+          self.pending.extend(drain_sse_events(&mut self.buf));
+        }
+        None => return Ok(None),
+      }
+    }
+  }
+}
+
+/// 取出缓冲区中完整的 SSE 事件，不完整的部分留到下次
+fn drain_sse_events(buf: &mut Vec<u8>) -> Vec<DeploymentStreamEvent> {
+  let mut events = Vec::new();
+  while let Some(pos) = buf.windows(2).position(|w| w == b"\n\n") {
+    let frame: Vec<u8> = buf.drain(..pos + 2).collect();
+    let frame = String::from_utf8_lossy(&frame);
+    let data = frame
+      .lines()
+      .filter_map(|line| line.strip_prefix("data:"))
+      .map(str::trim_start)
+      .collect::<Vec<_>>()
+      .join("\n");
+    if data.is_empty() {
+      continue;
+    }
+    match serde_json::from_str(&data) {
+      Ok(event) => events.push(event),
+      Err(err) => tracing::warn!("Invalid deployment stream event: {}", err),
+    }
+  }
+  events
 }
 
 #[derive(Debug, Clone)]
 pub struct MasterRpc {
   api_client: reqwest::Client,
   master_url: String,
+  retry: RetryPolicy,
+  timeouts: Timeouts,
 }
 
 impl MasterRpc {
   pub fn new(master_url: String) -> Result<Self, Error> {
+    Self::with_options(master_url, RetryPolicy::default(), Timeouts::default())
+  }
+
+  pub fn with_options(
+    master_url: String,
+    retry: RetryPolicy,
+    timeouts: Timeouts,
+  ) -> Result<Self, Error> {
     Ok(Self {
       master_url,
-      api_client: build_base_client_builder()?,
+      api_client: build_base_client_builder(timeouts.connect)?,
+      retry,
+      timeouts,
     })
   }
 
+  fn url(&self, path: &str) -> String {
+    format!("{}/api{}", self.master_url, path)
+  }
+
+  /// 发送 `build` 构造的请求并解析响应，`retry` 为 true 时按重试策略重试，
+  /// 只有重复执行没有副作用的请求才能重试
+  async fn send<B: DeserializeOwned>(
+    &self,
+    build: impl Fn() -> reqwest::RequestBuilder,
+    retry: bool,
+  ) -> Result<B, Error> {
+    let policy = if retry { self.retry } else { RetryPolicy::NONE };
+    let build = &build;
+    policy
+      .run(|| async move {
+        let resp = build().timeout(self.timeouts.request).send().await?;
+        parse_response(resp).await
+      })
+      .await
+  }
+
+  /// 请求 Master 的接口，GET 请求失败时会重试
   pub async fn fetch<T: Serialize, B: DeserializeOwned>(
     &self,
-    method: &str,
+    method: Method,
     path: &str,
     body: Option<T>,
   ) -> Result<B, Error> {
-    let url = format!("{}/api{}", self.master_url, path);
-    let client = if method == "post" {
-      self.api_client.post(url).json(&body)
-    } else {
-      self.api_client.get(url)
-    };
-    let resp = client.send().await?;
-    let content_type = resp
-      .headers()
-      .get(CONTENT_TYPE)
-      .ok_or(Error::InvalidContentType)?;
-    if content_type != "application/json" {
-      return Err(Error::InvalidContentType);
-    }
-
-    if resp.status().is_success() {
-      let data = resp.json::<RpcResponse<B>>().await?;
-      Ok(data.data)
-    } else {
-      let status_code = resp.status().as_u16();
-      let data = resp.json::<RpcResponse<Value>>().await?;
-      Err(Error::Api(status_code, data.code, data.msg))
-    }
+    let retry = method == Method::GET;
+    self
+      .send(
+        || {
+          let req = self.api_client.request(method.clone(), self.url(path));
+          if method == Method::GET {
+            req
+          } else {
+            req.json(&body)
+          }
+        },
+        retry,
+      )
+      .await
   }
+}
 
-  pub async fn signup(
-    &self,
-    nickname: String,
-    email: String,
-    password: String,
-  ) -> Result<bool, Error> {
-    let resp = self
-      .api_client
-      .post(format!("{}/api/user", self.master_url))
-      .json(&UserRegisterRequest {
-        nickname,
-        email,
-        password,
-      })
-      .send()
+#[async_trait]
+impl MasterApi for MasterRpc {
+  async fn signup(&self, nickname: String, email: String, password: String) -> Result<bool, Error> {
+    self
+      .fetch::<_, Value>(
+        Method::POST,
+        "/user",
+        Some(UserRegisterRequest {
+          nickname,
+          email,
+          password,
+        }),
+      )
       .await?;
-
-    if resp.status().is_success() {
-      resp.json::<RpcResponse<()>>().await?;
-      Ok(true)
-    } else {
-      let status_code = resp.status().as_u16();
-      let data = resp.json::<RpcResponse<Value>>().await?;
-      Err(Error::Api(status_code, data.code, data.msg))
-    }
+    Ok(true)
   }
 
-  pub async fn login(&self, email: String, password: String) -> Result<LoginData, Error> {
-    let body = self
-      .fetch::<_, LoginData>(
-        "post",
+  async fn login(&self, email: String, password: String) -> Result<LoginData, Error> {
+    self
+      .fetch(
+        Method::POST,
         "/user/token",
         Some(json!({
           "email": email,
           "password": password,
         })),
       )
-      .await?;
-    Ok(body)
+      .await
   }
 
-  pub async fn get_casual_token(&self) -> Result<GetCasualTokenData, Error> {
-    let resp = self
-      .api_client
-      .get(format!("{}/api/user/casual", self.master_url))
-      .send()
-      .await?;
-
-    if resp.status().is_success() {
-      let data = resp.json::<RpcResponse<GetCasualTokenData>>().await?;
-      Ok(data.data)
-    } else {
-      let status_code = resp.status().as_u16();
-      let data = resp.json::<RpcResponse<Value>>().await?;
-      Err(Error::Api(status_code, data.code, data.msg))
-    }
+  /// 每次调用都会创建一个临时用户，不重试
+  async fn get_casual_token(&self) -> Result<GetCasualTokenData, Error> {
+    self
+      .send(|| self.api_client.get(self.url("/user/casual")), false)
+      .await
   }
 
-  pub async fn create_site(&self, token: &str) -> Result<CreateSiteData, Error> {
-    let resp = self
-      .api_client
-      .post(format!("{}/api/site", self.master_url))
-      .bearer_auth(token)
-      .json(&json!({
-        "site_name": "casual_site"
-      }))
-      .send()
-      .await?;
-
-    if resp.status().is_success() {
-      let data = resp.json::<RpcResponse<CreateSiteData>>().await?;
-      Ok(data.data)
-    } else {
-      let status_code = resp.status().as_u16();
-      let data = resp.json::<RpcResponse<Value>>().await?;
-      Err(Error::Api(status_code, data.code, data.msg))
-    }
+  async fn create_site(&self, token: &str) -> Result<CreateSiteData, Error> {
+    self
+      .send(
+        || {
+          self
+            .api_client
+            .post(self.url("/site"))
+            .bearer_auth(token)
+            .json(&json!({
+              "site_name": "casual_site"
+            }))
+        },
+        false,
+      )
+      .await
   }
 
-  pub async fn get_sites(&self, token: &str) -> Result<GetSitesResponse, Error> {
-    let resp = self
-      .api_client
-      .get(format!("{}/api/sites", self.master_url))
-      .bearer_auth(token)
-      .send()
-      .await?;
-
-    if resp.status().is_success() {
-      let data = resp.json::<RpcResponse<GetSitesResponse>>().await?;
-      Ok(data.data)
-    } else {
-      let status_code = resp.status().as_u16();
-      let data = resp.json::<RpcResponse<Value>>().await?;
-      Err(Error::Api(status_code, data.code, data.msg))
-    }
+  async fn get_sites(
+    &self,
+    token: &str,
+    page: u64,
+    page_size: u64,
+  ) -> Result<Paginated<site::Model>, Error> {
+    self
+      .send(
+        || {
+          self
+            .api_client
+            .get(self.url("/sites"))
+            .query(&[("page", page), ("page_size", page_size)])
+            .bearer_auth(token)
+        },
+        true,
+      )
+      .await
   }
 
-  pub async fn create_deployment(
+  async fn create_deployment(
     &self,
     site_id: String,
+    sha256: Option<String>,
     token: &str,
   ) -> Result<CreateDeploymentResponse, Error> {
-    let resp = self
-      .api_client
-      .post(format!("{}/api/deployment", self.master_url))
-      .bearer_auth(token)
-      .json(&CreateDeploymentRequest { site_id })
-      .send()
+    let body = CreateDeploymentRequest { site_id, sha256 };
+    self
+      .send(
+        || {
+          self
+            .api_client
+            .post(self.url("/deployment"))
+            .bearer_auth(token)
+            .json(&body)
+        },
+        false,
+      )
+      .await
+  }
+
+  async fn prepare_deployment(&self, site_id: String, token: &str) -> Result<i32, Error> {
+    let body = PrepareDeploymentRequest { site_id };
+    let response: PrepareDeploymentResponse = self
+      .send(
+        || {
+          self
+            .api_client
+            .post(self.url("/deployment/prepare"))
+            .bearer_auth(token)
+            .json(&body)
+        },
+        false,
+      )
       .await?;
+    Ok(response.deployment_id)
+  }
 
-    if resp.status().is_success() {
-      let data = resp.json::<RpcResponse<CreateDeploymentResponse>>().await?;
-      Ok(data.data)
-    } else {
-      let status_code = resp.status().as_u16();
-      let data = resp.json::<RpcResponse<Value>>().await?;
-      Err(Error::Api(status_code, data.code, data.msg))
-    }
+  async fn init_deployment_upload(
+    &self,
+    deployment_id: i32,
+    sha256: Option<String>,
+    token: &str,
+  ) -> Result<CreateDeploymentResponse, Error> {
+    let body = InitDeploymentUploadRequest { sha256 };
+    self
+      .send(
+        || {
+          self
+            .api_client
+            .post(self.url(&format!("/deployment/{}/upload", deployment_id)))
+            .bearer_auth(token)
+            .json(&body)
+        },
+        false,
+      )
+      .await
   }
 
-  pub async fn update_deployment_status(
+  async fn update_deployment_status(
     &self,
     agent_token: String,
-    deployment_id: u32,
+    deployment_id: i32,
     status: DeploymentStatus,
   ) -> Result<(), Error> {
-    let resp = self
-      .api_client
-      .post(format!("{}/api/deployment/status", self.master_url))
-      .json(&json!({
-        "agent_token": agent_token,
-        "deployment_id": deployment_id,
-        "status": status
-      }))
-      .send()
+    self
+      .fetch::<_, Value>(
+        Method::POST,
+        "/deployment/status",
+        Some(json!({
+          "agent_token": agent_token,
+          "deployment_id": deployment_id,
+          "status": status
+        })),
+      )
       .await?;
-
-    if resp.status().is_success() {
-      let data = resp.json::<RpcResponse<()>>().await?;
-      Ok(data.data)
-    } else {
-      let status_code = resp.status().as_u16();
-      let data = resp.json::<RpcResponse<Value>>().await?;
-      Err(Error::Api(status_code, data.code, data.msg))
-    }
+    Ok(())
   }
 
-  pub async fn publish_site(
+  /// 未提供 `idempotency_key` 时 Master 同样只会为一个部署创建一个发布任务，因此可以重试
+  async fn publish_site(
     &self,
     token: &str,
     site_id: String,
-    deployment_id: u32,
+    deployment_id: i32,
     bind_domain: Option<String>,
-  ) -> Result<AssignTaskData, Error> {
+    idempotency_key: Option<String>,
+  ) -> Result<TaskResponse, Error> {
+    let body = AssignTaskRequest {
+      r#type: "publish".to_string(),
+      site_id,
+      deployment_id,
+      bind_domain,
+    };
+    self
+      .send(
+        || {
+          let mut req = self
+            .api_client
+            .post(self.url("/agent/task"))
+            .bearer_auth(token);
+          if let Some(key) = &idempotency_key {
+            req = req.header(IDEMPOTENCY_KEY_HEADER, key);
+          }
+          req.json(&body)
+        },
+        true,
+      )
+      .await
+  }
+
+  async fn get_task(&self, token: &str, task_id: i32) -> Result<TaskResponse, Error> {
+    self
+      .send(
+        || {
+          self
+            .api_client
+            .get(self.url(&format!("/tasks/{}", task_id)))
+            .bearer_auth(token)
+        },
+        true,
+      )
+      .await
+  }
+
+  async fn append_deployment_logs(
+    &self,
+    token: &str,
+    deployment_id: i32,
+    content: String,
+  ) -> Result<(), Error> {
+    let body = AppendDeploymentLogsRequest { content };
+    self
+      .send::<Value>(
+        || {
+          self
+            .api_client
+            .post(self.url(&format!("/deployment/{}/logs", deployment_id)))
+            .bearer_auth(token)
+            .json(&body)
+        },
+        false,
+      )
+      .await?;
+    Ok(())
+  }
+
+  async fn stream_deployment(
+    &self,
+    token: &str,
+    deployment_id: i32,
+  ) -> Result<DeploymentStream, Error> {
     let resp = self
       .api_client
-      .post(format!("{}/api/agent/task", self.master_url))
+      .get(self.url(&format!("/deployment/{}/stream", deployment_id)))
       .bearer_auth(token)
-      .json(&AssignTaskRequest {
-        r#type: "publish".to_string(),
-        site_id,
-        deployment_id,
-        bind_domain,
-      })
+      .header(ACCEPT, "text/event-stream")
+      .timeout(self.timeouts.stream)
       .send()
       .await?;
 
     if resp.status().is_success() {
-      let data = resp.json::<RpcResponse<AssignTaskData>>().await?;
-      Ok(data.data)
+      Ok(DeploymentStream {
+        resp: Some(resp),
+        buf: Vec::new(),
+        pending: VecDeque::new(),
+      })
     } else {
-      let status_code = resp.status().as_u16();
-      let data = resp.json::<RpcResponse<Value>>().await?;
-      Err(Error::Api(status_code, data.code, data.msg))
+      Err(api_error(resp).await)
     }
   }
+
+  async fn get_deployment_logs(
+    &self,
+    token: &str,
+    deployment_id: i32,
+  ) -> Result<DeploymentLogsResponse, Error> {
+    self
+      .send(
+        || {
+          self
+            .api_client
+            .get(self.url(&format!("/deployment/{}/logs", deployment_id)))
+            .bearer_auth(token)
+        },
+        true,
+      )
+      .await
+  }
 }
 
-use std::net::Ipv4Addr;
+/// 地址是否属于公网，回环、内网、链路本地（含 `169.254.169.254` 元数据服务）、
+/// 运营商级 NAT（部分云厂商的元数据服务在此网段）等地址都不是公网地址
+pub fn is_public_ip(ip: IpAddr) -> bool {
+  match ip {
+    IpAddr::V4(ip) => {
+      let [a, b, ..] = ip.octets();
+      !(ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        || a == 0
+        || (a == 100 && b & 0xc0 == 64))
+    }
+    IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+      Some(ip) => is_public_ip(IpAddr::V4(ip)),
+      None => {
+        !(ip.is_loopback()
+          || ip.is_unspecified()
+          || ip.is_multicast()
+          || ip.is_unique_local()
+          || ip.is_unicast_link_local())
+      }
+    },
+  }
+}
+
+/// 解析主机名，任一地址不是公网地址时拒绝
+async fn resolve_public(host: &str, port: u16) -> Result<Vec<SocketAddr>, Error> {
+  let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port)).await?.collect();
+  if addrs.is_empty() || addrs.iter().any(|addr| !is_public_ip(addr.ip())) {
+    return Err(Error::ForbiddenTarget(host.to_string()));
+  }
+  Ok(addrs)
+}
+
+/// 只解析到公网地址的 DNS 解析器，连接时再检查一次，避免创建后改变解析绕过检查
+#[derive(Debug)]
+struct PublicResolver;
+
+impl reqwest::dns::Resolve for PublicResolver {
+  fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+    Box::pin(async move {
+      let addrs = resolve_public(name.as_str(), 0)
+        .await
+        .map_err(|err| std::io::Error::other(err.to_string()))?;
+      Ok(Box::new(addrs.into_iter()) as reqwest::dns::Addrs)
+    })
+  }
+}
+
+/// 投递 Webhook 的客户端，接收方可能较慢，超时比内部调用更长
+#[derive(Debug, Clone)]
+pub struct WebhookRpc {
+  api_client: reqwest::Client,
+  /// 允许投递到内网地址，仅用于自建环境
+  allow_private_network: bool,
+}
+
+impl WebhookRpc {
+  pub fn new(allow_private_network: bool) -> Result<Self, Error> {
+    // 重定向的目标可能是内网的 IP 地址，不跟随重定向
+    let mut builder = reqwest::Client::builder()
+      .timeout(Duration::from_secs(10))
+      .no_proxy()
+      .redirect(reqwest::redirect::Policy::none());
+    if !allow_private_network {
+      builder = builder.dns_resolver(Arc::new(PublicResolver));
+    }
+    Ok(Self {
+      api_client: builder.build().map_err(|_| Error::BuildRequest)?,
+      allow_private_network,
+    })
+  }
+
+  /// 检查 Webhook 地址，只允许解析到公网地址的 http(s) 地址
+  pub async fn check_url(&self, url: &str) -> Result<(), Error> {
+    let forbidden = || Error::ForbiddenTarget(url.to_string());
+    let parsed = reqwest::Url::parse(url).map_err(|_| forbidden())?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+      return Err(forbidden());
+    }
+    if self.allow_private_network {
+      return Ok(());
+    }
+    let host = parsed.host_str().ok_or_else(forbidden)?;
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    if let Ok(ip) = host.parse::<IpAddr>() {
+      return if is_public_ip(ip) {
+        Ok(())
+      } else {
+        Err(forbidden())
+      };
+    }
+    resolve_public(host, parsed.port_or_known_default().unwrap_or(80))
+      .await
+      .map(|_| ())
+  }
+
+  /// 签名并投递一次 Webhook，返回接收方的 HTTP 状态码
+  pub async fn deliver(
+    &self,
+    url: &str,
+    secret: &str,
+    event: &str,
+    delivery_id: i32,
+    body: Vec<u8>,
+  ) -> Result<u16, Error> {
+    self.check_url(url).await?;
+    let timestamp = signature::timestamp();
+    let resp = self
+      .api_client
+      .post(url)
+      .header(CONTENT_TYPE, "application/json")
+      .header(signature::TIMESTAMP_HEADER, timestamp)
+      .header(
+        signature::WEBHOOK_SIGNATURE_HEADER,
+        signature::sign_webhook(secret, timestamp, &body),
+      )
+      .header(signature::WEBHOOK_EVENT_HEADER, event)
+      .header(signature::WEBHOOK_DELIVERY_HEADER, delivery_id)
+      .body(body)
+      .send()
+      .await?;
+    Ok(resp.status().as_u16())
+  }
+}
 
 use cloudflare::endpoints::dns::dns::{self, CreateDnsRecordParams};
 use cloudflare::framework::auth::Credentials;
@@ -515,7 +1271,9 @@ impl CloudflareRpc {
     Ok(response.result)
   }
 
-  pub async fn create_a_record(&self, name: &str, ip: Ipv4Addr) {
+  /// IPv4 地址创建 A 记录，IPv6 地址创建 AAAA 记录
+  pub async fn create_address_record(&self, name: &str, ip: IpAddr) -> Result<(), Error> {
+    let content = address_content(ip);
     let endpoint = dns::CreateDnsRecord {
       zone_identifier: &self.zone_identifier,
       params: CreateDnsRecordParams {
@@ -523,12 +1281,39 @@ impl CloudflareRpc {
         priority: None,
         proxied: None,
         name,
-        content: dns::DnsContent::A { content: ip },
+        content,
       },
     };
-    if let Ok(resp) = self.api_client.request(&endpoint).await {
-      println!("{:#?}", resp);
+    self.api_client.request(&endpoint).await?;
+    Ok(())
+  }
+
+  /// 为每个地址创建 A 或 AAAA 记录
+  pub async fn create_address_records(&self, name: &str, ips: &[IpAddr]) -> Result<(), Error> {
+    for ip in ips {
+      self.create_address_record(name, *ip).await?;
+    }
+    Ok(())
+  }
+
+  /// 删除指定名称的所有 DNS 记录，返回删除的数量
+  pub async fn delete_dns_records(&self, name: &str) -> Result<usize, Error> {
+    let endpoint = dns::ListDnsRecords {
+      zone_identifier: &self.zone_identifier,
+      params: dns::ListDnsRecordsParams {
+        name: Some(name.to_string()),
+        ..Default::default()
+      },
+    };
+    let records = self.api_client.request(&endpoint).await?.result;
+    for record in records.iter() {
+      let endpoint = dns::DeleteDnsRecord {
+        zone_identifier: &self.zone_identifier,
+        identifier: &record.id,
+      };
+      self.api_client.request(&endpoint).await?;
     }
+    Ok(records.len())
   }
 
   pub async fn create_cname_record(
@@ -551,11 +1336,165 @@ impl CloudflareRpc {
   }
 }
 
+/// 地址对应的 DNS 记录内容
+fn address_content(ip: IpAddr) -> dns::DnsContent {
+  match ip {
+    IpAddr::V4(content) => dns::DnsContent::A { content },
+    IpAddr::V6(content) => dns::DnsContent::AAAA { content },
+  }
+}
+
 #[cfg(test)]
 mod test {
 
   use super::*;
 
+  /// 在本地启动一个只处理一次请求的 HTTP 接收方，返回地址和收到的请求头与请求体
+  async fn local_receiver(
+    status: u16,
+  ) -> (
+    String,
+    tokio::task::JoinHandle<(Vec<(String, String)>, Vec<u8>)>,
+  ) {
+    use tokio::io::AsyncWriteExt;
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    let handle = tokio::spawn(async move {
+      let (mut socket, _) = listener.accept().await.unwrap();
+      let mut buf = Vec::new();
+      let mut chunk = [0u8; 1024];
+      let (header_end, content_length) = loop {
+        let n = socket.read(&mut chunk).await.unwrap();
+        buf.extend_from_slice(&chunk[..n]);
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+          let head = String::from_utf8_lossy(&buf[..pos]).to_lowercase();
+          let length = head
+            .lines()
+            .find_map(|l| l.strip_prefix("content-length:"))
+            .map(|v| v.trim().parse::<usize>().unwrap())
+            .unwrap_or(0);
+          break (pos + 4, length);
+        }
+      };
+      while buf.len() < header_end + content_length {
+        let n = socket.read(&mut chunk).await.unwrap();
+        buf.extend_from_slice(&chunk[..n]);
+      }
+      let headers = String::from_utf8_lossy(&buf[..header_end])
+        .lines()
+        .skip(1)
+        .filter_map(|l| l.split_once(':'))
+        .map(|(k, v)| (k.trim().to_lowercase(), v.trim().to_string()))
+        .collect();
+      socket
+        .write_all(format!("HTTP/1.1 {} OK\r\ncontent-length: 0\r\n\r\n", status).as_bytes())
+        .await
+        .unwrap();
+      (headers, buf[header_end..].to_vec())
+    });
+    (url, handle)
+  }
+
+  #[test]
+  fn test_drain_sse_events() {
+    let mut buf = b": keep-alive\n\nevent: log\ndata: {\"type\":\"log\",\"source\":\"publish\",\"line\":\"ok\"}\n\nevent: status\ndata: {\"type\"".to_vec();
+    let events = drain_sse_events(&mut buf);
+    assert_eq!(events.len(), 1);
+    assert!(matches!(
+      &events[0],
+      DeploymentStreamEvent::Log { source, line } if source == "publish" && line == "ok"
+    ));
+    buf.extend_from_slice(b":\"status\",\"status\":\"published\",\"reason\":null}\n\n");
+    let events = drain_sse_events(&mut buf);
+    assert!(events[0].is_terminal());
+    assert!(buf.is_empty());
+  }
+
+  #[tokio::test]
+  pub async fn test_deliver_webhook() {
+    let (url, receiver) = local_receiver(200).await;
+    let body = br#"{"event":"ping"}"#.to_vec();
+    let status = WebhookRpc::new(true)
+      .unwrap()
+      .deliver(&url, "secret", "ping", 7, body.clone())
+      .await
+      .unwrap();
+    assert_eq!(status, 200);
+
+    let (headers, received) = receiver.await.unwrap();
+    let header = |name: &str| {
+      headers
+        .iter()
+        .find(|(k, _)| k == name)
+        .map(|(_, v)| v.clone())
+        .unwrap()
+    };
+    assert_eq!(received, body);
+    assert_eq!(header(signature::WEBHOOK_EVENT_HEADER), "ping");
+    assert_eq!(header(signature::WEBHOOK_DELIVERY_HEADER), "7");
+    let timestamp = header(signature::TIMESTAMP_HEADER).parse().unwrap();
+    assert!(signature::verify_webhook(
+      "secret",
+      timestamp,
+      &received,
+      &header(signature::WEBHOOK_SIGNATURE_HEADER)
+    ));
+  }
+
+  #[tokio::test]
+  pub async fn test_check_webhook_url() {
+    let rpc = WebhookRpc::new(false).unwrap();
+    for url in [
+      "http://127.0.0.1:8080/hook",
+      "http://10.0.0.1/hook",
+      "http://192.168.1.1/hook",
+      "http://169.254.169.254/latest/meta-data",
+      "http://100.100.100.200/latest/meta-data",
+      "http://[::1]/hook",
+      "http://[::ffff:127.0.0.1]/hook",
+      "http://[fd00:ec2::254]/hook",
+      "http://localhost/hook",
+      "ftp://example.com/hook",
+    ] {
+      assert!(rpc.check_url(url).await.is_err(), "{}", url);
+    }
+    assert!(rpc.check_url("https://1.1.1.1/hook").await.is_ok());
+    assert!(
+      WebhookRpc::new(true)
+        .unwrap()
+        .check_url("http://127.0.0.1/hook")
+        .await
+        .is_ok()
+    );
+  }
+
+  #[tokio::test]
+  pub async fn test_deliver_webhook_error_status() {
+    let (url, receiver) = local_receiver(500).await;
+    let status = WebhookRpc::new(true)
+      .unwrap()
+      .deliver(&url, "secret", "ping", 1, b"{}".to_vec())
+      .await
+      .unwrap();
+    assert_eq!(status, 500);
+    receiver.await.unwrap();
+  }
+
+  #[test]
+  fn test_address_content() {
+    let v4: IpAddr = "203.0.113.1".parse().unwrap();
+    let v6: IpAddr = "2001:db8::1".parse().unwrap();
+    assert!(matches!(
+      address_content(v4),
+      dns::DnsContent::A { content } if IpAddr::V4(content) == v4
+    ));
+    assert!(matches!(
+      address_content(v6),
+      dns::DnsContent::AAAA { content } if IpAddr::V6(content) == v6
+    ));
+  }
+
   #[tokio::test]
   pub async fn test_dns() {
     let cloudflare_api_key = std::env::var("CLOUDFLARE_API_KEY").unwrap();
@@ -0,0 +1,134 @@
+//! 请求的重试策略与超时时间
+
+use std::{future::Future, time::Duration};
+
+use crate::error::Error;
+
+/// 指数退避的重试策略，只重试 [`Error::is_retryable`] 为 true 的错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+  /// 第一次失败后最多再重试的次数
+  pub max_retries: u32,
+  /// 第一次重试前的等待时间，之后每次翻倍
+  pub base_delay: Duration,
+  pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+  /// 不重试
+  pub const NONE: RetryPolicy = RetryPolicy {
+    max_retries: 0,
+    base_delay: Duration::ZERO,
+    max_delay: Duration::ZERO,
+  };
+
+  /// 第 `retry` 次重试（从 1 开始）前的等待时间
+  pub fn delay(&self, retry: u32) -> Duration {
+    self
+      .base_delay
+      .saturating_mul(1 << retry.saturating_sub(1).min(16))
+      .min(self.max_delay)
+  }
+
+  /// 执行 `call`，失败且可以重试时按退避时间重新调用
+  pub async fn run<T, F, Fut>(&self, mut call: F) -> Result<T, Error>
+  where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, Error>>,
+  {
+    let mut retries = 0;
+    loop {
+      match call().await {
+        Err(err) if retries < self.max_retries && err.is_retryable() => {
+          retries += 1;
+          tracing::warn!("Request failed, retry {}: {}", retries, err);
+          tokio::time::sleep(self.delay(retries)).await;
+        }
+        result => return result,
+      }
+    }
+  }
+}
+
+impl Default for RetryPolicy {
+  fn default() -> Self {
+    RetryPolicy {
+      max_retries: 2,
+      base_delay: Duration::from_millis(500),
+      max_delay: Duration::from_secs(5),
+    }
+  }
+}
+
+/// 各类请求的超时时间，发布和导出需要等待 Agent 处理文件，比普通请求长
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timeouts {
+  pub connect: Duration,
+  /// 没有单独设置的请求
+  pub request: Duration,
+  pub heartbeat: Duration,
+  /// 上传分片、文件以及完成上传
+  pub upload: Duration,
+  pub publish: Duration,
+  pub export: Duration,
+  /// 订阅部署进度的长连接
+  pub stream: Duration,
+}
+
+impl Default for Timeouts {
+  fn default() -> Self {
+    Timeouts {
+      connect: Duration::from_secs(3),
+      request: Duration::from_secs(10),
+      heartbeat: Duration::from_secs(3),
+      upload: Duration::from_secs(60),
+      publish: Duration::from_secs(120),
+      export: Duration::from_secs(300),
+      stream: Duration::from_secs(600),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::atomic::{AtomicU32, Ordering};
+
+  use super::*;
+
+  #[test]
+  fn test_delay() {
+    let policy = RetryPolicy::default();
+    assert_eq!(policy.delay(1), Duration::from_millis(500));
+    assert_eq!(policy.delay(3), Duration::from_secs(2));
+    assert_eq!(policy.delay(10), Duration::from_secs(5));
+  }
+
+  #[tokio::test]
+  async fn test_run() {
+    let policy = RetryPolicy {
+      max_retries: 2,
+      base_delay: Duration::from_millis(1),
+      max_delay: Duration::from_millis(1),
+    };
+    let calls = AtomicU32::new(0);
+    let result = policy
+      .run(|| async {
+        calls.fetch_add(1, Ordering::SeqCst);
+        Err::<(), _>(Error::Api(503, 1003, String::new()))
+      })
+      .await;
+    assert!(result.is_err());
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+    // 请求本身有误时不重试
+    calls.store(0, Ordering::SeqCst);
+    let result = policy
+      .run(|| async {
+        calls.fetch_add(1, Ordering::SeqCst);
+        Err::<(), _>(Error::Api(400, 2004, String::new()))
+      })
+      .await;
+    assert!(result.is_err());
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+  }
+}
@@ -0,0 +1,146 @@
+//! 校验 Agent 证书
+
+use std::{sync::Arc, time::Duration};
+
+use common::{agent::AgentTls, digest::sha256_bytes};
+use rustls::{
+  DigitallySignedStruct, SignatureScheme,
+  client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+  crypto::{CryptoProvider, ring, verify_tls12_signature, verify_tls13_signature},
+  pki_types::{CertificateDer, ServerName, UnixTime},
+};
+
+use crate::{base_client_builder, error::Error};
+
+/// 规范化证书指纹：去掉冒号和空白并转为小写，不是 SHA-256 时返回 `None`
+pub fn normalize_fingerprint(fingerprint: &str) -> Option<String> {
+  let fingerprint: String = fingerprint
+    .chars()
+    .filter(|c| *c != ':' && !c.is_whitespace())
+    .collect::<String>()
+    .to_lowercase();
+  (fingerprint.len() == 64 && fingerprint.chars().all(|c| c.is_ascii_hexdigit()))
+    .then_some(fingerprint)
+}
+
+/// PEM 中至少包含一个可以解析的证书
+pub fn is_valid_ca_cert(pem: &str) -> bool {
+  reqwest::Certificate::from_pem_bundle(pem.as_bytes()).is_ok_and(|certs| !certs.is_empty())
+}
+
+/// 只接受指定指纹的证书，不校验证书链和域名，适用于自签名证书
+#[derive(Debug)]
+struct FingerprintVerifier {
+  fingerprint: String,
+  provider: Arc<CryptoProvider>,
+}
+
+impl ServerCertVerifier for FingerprintVerifier {
+  fn verify_server_cert(
+    &self,
+    end_entity: &CertificateDer<'_>,
+    _intermediates: &[CertificateDer<'_>],
+    _server_name: &ServerName<'_>,
+    _ocsp_response: &[u8],
+    _now: UnixTime,
+  ) -> Result<ServerCertVerified, rustls::Error> {
+    if sha256_bytes(end_entity.as_ref()) == self.fingerprint {
+      Ok(ServerCertVerified::assertion())
+    } else {
+      Err(rustls::Error::General(
+        "Certificate fingerprint mismatch".to_string(),
+      ))
+    }
+  }
+
+  fn verify_tls12_signature(
+    &self,
+    message: &[u8],
+    cert: &CertificateDer<'_>,
+    dss: &DigitallySignedStruct,
+  ) -> Result<HandshakeSignatureValid, rustls::Error> {
+    verify_tls12_signature(
+      message,
+      cert,
+      dss,
+      &self.provider.signature_verification_algorithms,
+    )
+  }
+
+  fn verify_tls13_signature(
+    &self,
+    message: &[u8],
+    cert: &CertificateDer<'_>,
+    dss: &DigitallySignedStruct,
+  ) -> Result<HandshakeSignatureValid, rustls::Error> {
+    verify_tls13_signature(
+      message,
+      cert,
+      dss,
+      &self.provider.signature_verification_algorithms,
+    )
+  }
+
+  fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+    self
+      .provider
+      .signature_verification_algorithms
+      .supported_schemes()
+  }
+}
+
+/// 构造按 `tls` 校验 Agent 证书的客户端
+pub(crate) fn build_agent_client(
+  tls: &AgentTls,
+  connect_timeout: Duration,
+) -> Result<reqwest::Client, Error> {
+  let builder = base_client_builder(connect_timeout);
+  let builder = if let Some(fingerprint) = &tls.cert_fingerprint {
+    let fingerprint = normalize_fingerprint(fingerprint)
+      .ok_or_else(|| Error::InvalidTls("Invalid certificate fingerprint".to_string()))?;
+    let provider = Arc::new(ring::default_provider());
+    let config = rustls::ClientConfig::builder_with_provider(provider.clone())
+      .with_safe_default_protocol_versions()
+      .map_err(|err| Error::InvalidTls(err.to_string()))?
+      .dangerous()
+      .with_custom_certificate_verifier(Arc::new(FingerprintVerifier {
+        fingerprint,
+        provider,
+      }))
+      .with_no_client_auth();
+    builder.use_preconfigured_tls(config)
+  } else if let Some(ca_cert) = &tls.ca_cert {
+    let certs = reqwest::Certificate::from_pem_bundle(ca_cert.as_bytes())
+      .map_err(|err| Error::InvalidTls(err.to_string()))?;
+    certs
+      .into_iter()
+      .fold(builder.tls_built_in_root_certs(false), |builder, cert| {
+        builder.add_root_certificate(cert)
+      })
+  } else {
+    builder
+  };
+  builder.build().map_err(|_| Error::BuildRequest)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_normalize_fingerprint() {
+    let hex = "AB".repeat(32);
+    let colons = vec!["AB"; 32].join(":");
+    assert_eq!(normalize_fingerprint(&hex), Some("ab".repeat(32)));
+    assert_eq!(normalize_fingerprint(&colons), Some("ab".repeat(32)));
+    assert_eq!(normalize_fingerprint("abcd"), None);
+    assert_eq!(normalize_fingerprint(&"zz".repeat(32)), None);
+
+    let tls = AgentTls {
+      ca_cert: None,
+      cert_fingerprint: Some("abcd".to_string()),
+    };
+    assert!(build_agent_client(&tls, Duration::from_secs(1)).is_err());
+    assert!(!is_valid_ca_cert("not a certificate"));
+  }
+}